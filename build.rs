@@ -0,0 +1,24 @@
+use std::{path::Path, process::Command};
+
+fn main() {
+    let target = std::env::var("TARGET").unwrap_or_default();
+    println!("cargo:rustc-env=CXX_AUTO_XTASK_TARGET={target}");
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let git_dir = Path::new(&manifest_dir).join(".git");
+    if git_dir.exists() {
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        println!("cargo:rerun-if-changed={}", git_dir.join("refs").display());
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.current_dir(&manifest_dir);
+    cmd.args(["rev-parse", "--short", "HEAD"]);
+    if let Ok(output) = cmd.output() {
+        if output.status.success() {
+            if let Ok(commit) = String::from_utf8(output.stdout) {
+                println!("cargo:rustc-env=CXX_AUTO_XTASK_GIT_COMMIT={}", commit.trim());
+            }
+        }
+    }
+}
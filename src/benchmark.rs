@@ -0,0 +1,282 @@
+use crate::BoxResult;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::SystemTime};
+
+/// The estimated mean time of a benchmark, in nanoseconds, with its confidence bounds.
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct Estimate {
+    pub mean: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// Benchmark estimates, keyed by benchmark id.
+pub type Results = BTreeMap<String, Estimate>;
+
+/// A benchmark whose time increased by more than the threshold relative to the baseline.
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Regression {
+    pub id: String,
+    pub baseline: Estimate,
+    pub current: Estimate,
+}
+
+impl Regression {
+    /// The change of the mean time, as a fraction of the baseline mean.
+    #[must_use]
+    pub fn change(&self) -> f64 {
+        change(&self.baseline, &self.current)
+    }
+}
+
+fn change(baseline: &Estimate, current: &Estimate) -> f64 {
+    current.mean / baseline.mean - 1.0
+}
+
+/// Parses a regression threshold such as `5%` or `5` into a fraction (e.g. `0.05`).
+///
+/// # Errors
+///
+/// Will return `Err` if `threshold` is not a non-negative percentage.
+pub fn parse_threshold(threshold: &str) -> BoxResult<f64> {
+    let percent = threshold
+        .trim()
+        .trim_end_matches('%')
+        .parse::<f64>()
+        .ok()
+        .filter(|percent| percent.is_finite() && *percent >= 0.0)
+        .ok_or_else(|| format!("invalid regression threshold `{threshold}`: expected a percentage like `5%`"))?;
+    Ok(percent / 100.0)
+}
+
+/// The benchmarks in `current` that regressed by more than `threshold` relative to `baseline`.
+///
+/// A benchmark only counts as regressed if even the lower bound of its current estimate is slower than the baseline
+/// mean by more than `threshold`, so that measurement noise alone does not fail the comparison. Benchmarks missing from
+/// either side are ignored.
+#[must_use]
+pub fn regressions(baseline: &Results, current: &Results, threshold: f64) -> Vec<Regression> {
+    current
+        .iter()
+        .filter_map(|(id, current)| {
+            let baseline = baseline.get(id)?;
+            (current.lower > baseline.mean * (1.0 + threshold)).then(|| Regression {
+                id: id.clone(),
+                baseline: *baseline,
+                current: *current,
+            })
+        })
+        .collect()
+}
+
+/// Prints each benchmark in `current` next to its `baseline` estimate.
+pub fn print_comparison(baseline: &Results, current: &Results) {
+    for (id, current) in current {
+        match baseline.get(id) {
+            Some(baseline) => crate::handler::status(format_args!(
+                "{id}: {:.1} ns -> {:.1} ns ({:+.2}%)",
+                baseline.mean,
+                current.mean,
+                change(baseline, current) * 100.0
+            )),
+            None => crate::handler::status(format_args!("{id}: {:.1} ns (no baseline)", current.mean)),
+        }
+    }
+}
+
+/// The file the results for the commit `rev` are stored in.
+#[must_use]
+pub fn baseline_path(target_dir: &Utf8Path, rev: &str) -> Utf8PathBuf {
+    target_dir.join("xtask").join("bench").join(format!("{rev}.json"))
+}
+
+/// Loads the results stored for the commit `rev`.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - No results are stored for `rev`
+/// - The stored results are malformed
+pub fn load(target_dir: &Utf8Path, rev: &str) -> BoxResult<Results> {
+    let path = baseline_path(target_dir, rev);
+    let data = std::fs::read_to_string(&path).map_err(|err| {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            format!("no benchmark results stored for `{rev}`\nRun `xtask bench` with that commit checked out first")
+        } else {
+            format!("failed to read `{path}`: {err}")
+        }
+    })?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+/// Stores `results` for the commit `rev`, merged over any results already stored for it.
+///
+/// # Errors
+///
+/// Will return `Err` if the results file cannot be read, parsed, or written.
+pub fn save(target_dir: &Utf8Path, rev: &str, results: &Results) -> BoxResult<()> {
+    let path = baseline_path(target_dir, rev);
+    let mut stored = if path.exists() {
+        load(target_dir, rev)?
+    } else {
+        Results::new()
+    };
+    stored.extend(results.iter().map(|(id, estimate)| (id.clone(), *estimate)));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(&stored)?)?;
+    Ok(())
+}
+
+/// Collects the criterion estimates under `<target_dir>/criterion` written since `since`.
+///
+/// Criterion stores the estimates of the latest run of each benchmark in `<id>/new/estimates.json`.
+///
+/// # Errors
+///
+/// Will return `Err` if a directory cannot be read or an estimates file is malformed.
+pub fn collect_criterion(target_dir: &Utf8Path, since: SystemTime) -> BoxResult<Results> {
+    let root = target_dir.join("criterion");
+    let mut results = Results::new();
+    if root.is_dir() {
+        collect_criterion_dir(&root, &root, since, &mut results)?;
+    }
+    Ok(results)
+}
+
+fn collect_criterion_dir(root: &Utf8Path, dir: &Utf8Path, since: SystemTime, results: &mut Results) -> BoxResult<()> {
+    let estimates = dir.join("new").join("estimates.json");
+    if estimates.is_file() && std::fs::metadata(&estimates)?.modified()? >= since {
+        let json = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(&estimates)?)?;
+        let value = |pointer: &str| {
+            json.pointer(pointer)
+                .and_then(serde_json::Value::as_f64)
+                .ok_or_else(|| format!("`{pointer}` not found in `{estimates}`"))
+        };
+        let estimate = Estimate {
+            mean: value("/mean/point_estimate")?,
+            lower: value("/mean/confidence_interval/lower_bound")?,
+            upper: value("/mean/confidence_interval/upper_bound")?,
+        };
+        let id = dir.strip_prefix(root)?.as_str().replace('\\', "/");
+        results.insert(id, estimate);
+    }
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        let path = entry.path();
+        // NOTE: `report` holds criterion's HTML output and the other names hold the estimates of previous runs
+        let skip = ["report", "new", "base", "change"];
+        if path.is_dir() && !path.file_name().map_or(false, |name| skip.contains(&name)) {
+            collect_criterion_dir(root, path, since, results)?;
+        }
+    }
+    Ok(())
+}
+
+/// Collects the results from a Google Benchmark JSON file (`--benchmark_format=json`), if it was written since `since`.
+///
+/// The `mean` and `stddev` aggregates are used when the benchmarks were run with repetitions; otherwise the time of the
+/// single run is used as the estimate, without bounds.
+///
+/// # Errors
+///
+/// Will return `Err` if the file cannot be read or is malformed.
+#[allow(clippy::module_name_repetitions)]
+pub fn collect_google_benchmark(path: &Utf8Path, since: SystemTime) -> BoxResult<Results> {
+    let mut results = Results::new();
+    if !path.is_file() || std::fs::metadata(path)?.modified()? < since {
+        return Ok(results);
+    }
+    let json = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(path)?)?;
+    let benchmarks = json
+        .get("benchmarks")
+        .and_then(serde_json::Value::as_array)
+        .ok_or_else(|| format!("`benchmarks` not found in `{path}`"))?;
+    let mut stddevs = BTreeMap::new();
+    for benchmark in benchmarks {
+        let field = |key: &str| benchmark.get(key).and_then(serde_json::Value::as_str);
+        let (Some(name), Some(time)) = (
+            field("run_name").or_else(|| field("name")),
+            benchmark.get("real_time").and_then(serde_json::Value::as_f64),
+        ) else {
+            continue;
+        };
+        let time = time * nanoseconds_per(field("time_unit").unwrap_or("ns"))?;
+        let estimate = Estimate {
+            mean: time,
+            lower: time,
+            upper: time,
+        };
+        match (field("run_type"), field("aggregate_name")) {
+            (Some("aggregate"), Some("mean")) => {
+                results.insert(name.to_owned(), estimate);
+            },
+            (Some("aggregate"), Some("stddev")) => {
+                stddevs.insert(name.to_owned(), time);
+            },
+            (Some("aggregate"), _) => {},
+            _ => {
+                results.entry(name.to_owned()).or_insert(estimate);
+            },
+        }
+    }
+    for (name, stddev) in stddevs {
+        if let Some(estimate) = results.get_mut(&name) {
+            estimate.lower = estimate.mean - stddev;
+            estimate.upper = estimate.mean + stddev;
+        }
+    }
+    Ok(results)
+}
+
+fn nanoseconds_per(unit: &str) -> BoxResult<f64> {
+    match unit {
+        "ns" => Ok(1.0),
+        "us" => Ok(1e3),
+        "ms" => Ok(1e6),
+        "s" => Ok(1e9),
+        _ => Err(format!("unrecognized Google Benchmark time unit `{unit}`").into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Estimate, Results};
+
+    fn results(id: &str, mean: f64, lower: f64) -> Results {
+        let estimate = Estimate {
+            mean,
+            lower,
+            upper: mean + (mean - lower),
+        };
+        Results::from([(id.to_owned(), estimate)])
+    }
+
+    #[test]
+    fn thresholds_are_percentages() {
+        assert!((super::parse_threshold("5%").unwrap() - 0.05).abs() < f64::EPSILON);
+        assert!((super::parse_threshold(" 5 ").unwrap() - 0.05).abs() < f64::EPSILON);
+        for invalid in ["-5%", "five", "5%%%x", "inf", ""] {
+            assert!(super::parse_threshold(invalid).is_err(), "`{invalid}` was accepted");
+        }
+    }
+
+    #[test]
+    fn only_regressions_beyond_the_threshold_count() {
+        let baseline = results("parse", 100.0, 99.0);
+        let inside = results("parse", 106.0, 104.9);
+        assert!(super::regressions(&baseline, &inside, 0.05).is_empty());
+
+        let outside = results("parse", 106.0, 105.1);
+        let regressions = super::regressions(&baseline, &outside, 0.05);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].id, "parse");
+        assert!((regressions[0].change() - 0.06).abs() < 1e-9);
+
+        let other = results("render", 200.0, 190.0);
+        assert!(super::regressions(&baseline, &other, 0.05).is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::{
+    command::Context,
+    error::{Error, Kind},
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::process::Command;
+
+/// The environment variable holding the bearer token sent to an `http://` or `https://` cache, if set.
+pub const TOKEN_VAR: &str = "XTASK_CACHE_TOKEN";
+
+/// The initial value of [`fnv1a`].
+pub const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// A remote cache, named by the URL of `xtask.cache.url`.
+///
+/// The cloud buckets and directories are accessed through [`Context::runner`] (or the file system), like the other
+/// tools. HTTP servers are the exception: they are accessed in process with `ureq`, since no CLI can be assumed on
+/// every platform for them, so they cannot be stubbed with a [`crate::runner::MockRunner`].
+pub enum Remote {
+    /// An S3 bucket prefix (`s3://`), accessed with the `aws` CLI
+    S3(String),
+    /// A Google Cloud Storage bucket prefix (`gs://`), accessed with the `gsutil` CLI
+    Gcs(String),
+    /// An HTTP server (`http://` or `https://`) taking `GET`, `HEAD`, and `PUT` requests for the entries
+    Http(String),
+    /// A directory (`file://`), e.g. on a volume shared between CI runners
+    Dir(Utf8PathBuf),
+}
+
+impl Remote {
+    /// The remote cache named by `url`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `url` is not an `s3://`, `gs://`, `http://`, `https://`, or `file://` URL.
+    pub fn parse(url: &str) -> BoxResult<Self> {
+        let invalid = || {
+            let message =
+                format!("`xtask.cache.url` `{url}` is not an `s3://`, `gs://`, `http(s)://`, or `file://` URL");
+            Error::new(Kind::Validation, message)
+        };
+        let parsed = url::Url::parse(url).map_err(|_| invalid())?;
+        let base = url.trim_end_matches('/').to_owned();
+        match parsed.scheme() {
+            "s3" => Ok(Remote::S3(base)),
+            "gs" => Ok(Remote::Gcs(base)),
+            "http" | "https" => Ok(Remote::Http(base)),
+            "file" => {
+                let path = parsed.to_file_path().map_err(|()| invalid())?;
+                Ok(Remote::Dir(Utf8PathBuf::try_from(path)?))
+            },
+            _ => Err(invalid().into()),
+        }
+    }
+
+    /// The URL (or path) of the entry `name`.
+    #[must_use]
+    pub fn location(&self, name: &str) -> String {
+        match self {
+            Remote::S3(base) | Remote::Gcs(base) | Remote::Http(base) => format!("{base}/{name}"),
+            Remote::Dir(dir) => dir.join(name).into_string(),
+        }
+    }
+
+    /// Whether the remote cache has the entry `name`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the remote cache cannot be queried.
+    pub fn contains(&self, context: &Context<'_>, name: &str) -> BoxResult<bool> {
+        let location = self.location(name);
+        match self {
+            // NOTE: both CLIs fail for a missing object, so a failure is only an error if it printed one
+            Remote::S3(_) => probe(context, context.command("aws").args(["s3", "ls", &location])),
+            Remote::Gcs(_) => probe(context, context.command("gsutil").args(["-q", "stat", &location])),
+            Remote::Http(_) => match authorized(ureq::head(&location)).call() {
+                Ok(_) => Ok(true),
+                Err(ureq::Error::Status(404, _)) => Ok(false),
+                Err(err) => Err(format!("failed to query `{location}`: {err}").into()),
+            },
+            Remote::Dir(_) => Ok(Utf8Path::new(&location).is_file()),
+        }
+    }
+
+    /// Downloads the entry `name` to the file `path`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the entry cannot be downloaded.
+    pub fn download(&self, context: &Context<'_>, name: &str, path: &Utf8Path) -> BoxResult<()> {
+        let location = self.location(name);
+        match self {
+            Remote::S3(_) => copy(
+                context,
+                context.command("aws").args(["s3", "cp", &location, path.as_str()]),
+            ),
+            Remote::Gcs(_) => copy(
+                context,
+                context.command("gsutil").args(["cp", &location, path.as_str()]),
+            ),
+            Remote::Http(_) => {
+                let response = authorized(ureq::get(&location))
+                    .call()
+                    .map_err(|err| format!("failed to download `{location}`: {err}"))?;
+                let mut file =
+                    std::fs::File::create(path).map_err(|err| format!("failed to create `{path}`: {err}"))?;
+                std::io::copy(&mut response.into_reader(), &mut file)
+                    .map_err(|err| format!("failed to download `{location}`: {err}"))?;
+                Ok(())
+            },
+            Remote::Dir(_) => {
+                std::fs::copy(&location, path).map_err(|err| format!("failed to copy `{location}`: {err}"))?;
+                Ok(())
+            },
+        }
+    }
+
+    /// Uploads the file `path` as the entry `name`, replacing any existing one.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the entry cannot be uploaded.
+    pub fn upload(&self, context: &Context<'_>, name: &str, path: &Utf8Path) -> BoxResult<()> {
+        let location = self.location(name);
+        match self {
+            Remote::S3(_) => copy(
+                context,
+                context.command("aws").args(["s3", "cp", path.as_str(), &location]),
+            ),
+            Remote::Gcs(_) => copy(
+                context,
+                context.command("gsutil").args(["cp", path.as_str(), &location]),
+            ),
+            Remote::Http(_) => {
+                let file = std::fs::File::open(path).map_err(|err| format!("failed to open `{path}`: {err}"))?;
+                // NOTE: a body of unknown length would be sent chunked, which plain file servers often reject
+                let length = file.metadata()?.len().to_string();
+                authorized(ureq::put(&location))
+                    .set("Content-Type", "application/gzip")
+                    .set("Content-Length", &length)
+                    .send(file)
+                    .map_err(|err| format!("failed to upload `{location}`: {err}"))?;
+                Ok(())
+            },
+            Remote::Dir(dir) => {
+                std::fs::create_dir_all(dir).map_err(|err| format!("failed to create `{dir}`: {err}"))?;
+                // NOTE: copy under a temporary name first, so that a concurrent pull never sees a partial entry
+                let partial = dir.join(format!(".{name}.partial"));
+                std::fs::copy(path, &partial).map_err(|err| format!("failed to copy to `{partial}`: {err}"))?;
+                std::fs::rename(&partial, &location).map_err(|err| format!("failed to write `{location}`: {err}"))?;
+                Ok(())
+            },
+        }
+    }
+}
+
+// Adds the bearer token of `TOKEN_VAR` (if set) to `request`.
+fn authorized(request: ureq::Request) -> ureq::Request {
+    match std::env::var(TOKEN_VAR) {
+        Ok(token) if !token.is_empty() => request.set("Authorization", &format!("Bearer {token}")),
+        _ => request,
+    }
+}
+
+// Runs the existence check `cmd`, which succeeds for an existing object and fails silently for a missing one.
+fn probe(context: &Context<'_>, cmd: &mut Command) -> BoxResult<bool> {
+    if context.print_env {
+        crate::handler::print_env(cmd);
+    }
+    let output = context.runner.output(cmd).map_err(|err| {
+        let program = crate::timings::command_name(cmd);
+        Error::new(crate::error::kind(&err), format!("failed to run `{program}`: {err}"))
+    })?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() && !stderr.trim().is_empty() {
+        return Err(format!("`{}` failed:\n{}", crate::timings::command_name(cmd), stderr.trim_end()).into());
+    }
+    Ok(output.status.success())
+}
+
+// Runs the copy `cmd`, failing if it does.
+fn copy(context: &Context<'_>, cmd: &mut Command) -> BoxResult<()> {
+    if context.print_env {
+        crate::handler::print_env(cmd);
+    }
+    let status = context.runner.status(cmd).map_err(|err| {
+        let program = crate::timings::command_name(cmd);
+        Error::new(crate::error::kind(&err), format!("failed to run `{program}`: {err}"))
+    })?;
+    if !status.success() {
+        return Err(format!("`{}` failed", crate::timings::command_name(cmd)).into());
+    }
+    Ok(())
+}
+
+/// The 64-bit FNV-1a hash of `bytes`, continuing from `hash` (which starts at [`FNV_OFFSET`]).
+///
+/// Unlike the hashers of the standard library, this is stable across Rust releases and platforms, as cache keys must
+/// be.
+#[must_use]
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Remote;
+    use crate::error::Kind;
+
+    #[test]
+    fn remotes_are_parsed_by_scheme() {
+        let remote = Remote::parse("s3://bucket/prefix/").unwrap();
+        assert!(matches!(&remote, Remote::S3(base) if base == "s3://bucket/prefix"));
+        assert_eq!(remote.location("key.tar.gz"), "s3://bucket/prefix/key.tar.gz");
+
+        let remote = Remote::parse("gs://bucket").unwrap();
+        assert_eq!(remote.location("key.tar.gz"), "gs://bucket/key.tar.gz");
+
+        let remote = Remote::parse("https://cache.example.com/xtask").unwrap();
+        assert!(matches!(&remote, Remote::Http(_)));
+        assert_eq!(
+            remote.location("key.tar.gz"),
+            "https://cache.example.com/xtask/key.tar.gz"
+        );
+
+        let remote = Remote::parse("file:///mnt/cache").unwrap();
+        assert!(matches!(&remote, Remote::Dir(dir) if dir == "/mnt/cache"));
+        assert_eq!(remote.location("key.tar.gz"), "/mnt/cache/key.tar.gz");
+    }
+
+    #[test]
+    fn other_urls_are_rejected() {
+        for url in ["ftp://example.com/cache", "not a url", "/mnt/cache"] {
+            let Err(err) = Remote::parse(url) else {
+                panic!("`{url}` was accepted");
+            };
+            assert_eq!(crate::error::kind(&*err), Kind::Validation);
+        }
+    }
+}
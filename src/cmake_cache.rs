@@ -0,0 +1,139 @@
+use crate::BoxResult;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+
+// The file in the build directory recording the inputs of the last successful configure.
+const STATE_FILE: &str = "xtask-configure.json";
+
+/// The inputs of a `CMake` configure run which the `CMake` cache does not pick up changes to by itself.
+///
+/// A define dropped from the command line keeps its cached value, and the generator and toolchain file are only read
+/// when the cache is first created, so a build configured with different inputs needs a fresh cache.
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigureState {
+    pub generator: String,
+    /// The arguments following the generator, source, and build directory (e.g. `-DFOO=ON`)
+    pub args: Vec<String>,
+    /// The toolchain file given with `-DCMAKE_TOOLCHAIN_FILE` (or the `CMAKE_TOOLCHAIN_FILE` environment variable)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toolchain_file: Option<Utf8PathBuf>,
+    /// The contents of the toolchain file, if it exists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toolchain_contents: Option<String>,
+}
+
+impl ConfigureState {
+    /// The state of a configure run with `generator` and `args`, reading the toolchain file (relative to `source_dir`)
+    /// named by `args` or the environment.
+    #[must_use]
+    pub fn new(source_dir: &Utf8Path, generator: &str, args: Vec<String>) -> Self {
+        let toolchain_file = args
+            .iter()
+            .rev()
+            .find_map(|arg| {
+                let define = arg.strip_prefix("-DCMAKE_TOOLCHAIN_FILE")?;
+                let (_, path) = define.split_once('=')?;
+                Some(Utf8PathBuf::from(path))
+            })
+            .or_else(|| std::env::var("CMAKE_TOOLCHAIN_FILE").ok().map(Utf8PathBuf::from))
+            .map(|path| source_dir.join(path));
+        let toolchain_contents = toolchain_file
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok());
+        ConfigureState {
+            generator: generator.to_owned(),
+            args,
+            toolchain_file,
+            toolchain_contents,
+        }
+    }
+}
+
+/// The reasons the `CMake` cache of `build_dir` is stale for a configure run with `state`, which are none if there is
+/// no cache yet.
+///
+/// The generator is checked against `CMakeCache.txt`, and the rest against the state saved by [`save`], if any.
+///
+/// # Errors
+///
+/// Will return `Err` if `CMakeCache.txt` or the saved state exists but cannot be read.
+pub fn stale_reasons(build_dir: &Utf8Path, state: &ConfigureState) -> BoxResult<Vec<String>> {
+    let cache_path = build_dir.join("CMakeCache.txt");
+    if !cache_path.exists() {
+        return Ok(vec![]);
+    }
+    let cache = std::fs::read_to_string(&cache_path).map_err(|err| format!("failed to read `{cache_path}`: {err}"))?;
+    let mut reasons = vec![];
+    let cached_generator = cache
+        .lines()
+        .find_map(|line| line.strip_prefix("CMAKE_GENERATOR:INTERNAL="));
+    if let Some(generator) = cached_generator {
+        if generator != state.generator {
+            reasons.push(format!(
+                "the generator changed from `{generator}` to `{}`",
+                state.generator
+            ));
+        }
+    }
+
+    let state_path = build_dir.join(STATE_FILE);
+    if !state_path.exists() {
+        return Ok(reasons);
+    }
+    let data = std::fs::read_to_string(&state_path).map_err(|err| format!("failed to read `{state_path}`: {err}"))?;
+    // NOTE: a state file that cannot be parsed (e.g. from another xtask version) says nothing about the cache
+    let Ok(saved) = serde_json::from_str::<ConfigureState>(&data) else {
+        return Ok(reasons);
+    };
+    let removed = saved
+        .args
+        .iter()
+        .filter(|arg| !state.args.contains(arg))
+        .collect::<Vec<_>>();
+    if !removed.is_empty() {
+        let removed = removed.iter().map(|arg| format!("`{arg}`")).collect::<Vec<_>>();
+        reasons.push(format!("arguments were removed or changed: {}", removed.join(", ")));
+    }
+    if saved.toolchain_file != state.toolchain_file {
+        reasons.push("the toolchain file changed".into());
+    } else if let Some(path) = &state.toolchain_file {
+        if saved.toolchain_contents != state.toolchain_contents {
+            reasons.push(format!("the contents of the toolchain file `{path}` changed"));
+        }
+    }
+    Ok(reasons)
+}
+
+/// Records `state` as the inputs of the configured cache in `build_dir`, for later runs of [`stale_reasons`].
+///
+/// # Errors
+///
+/// Will return `Err` if the state file cannot be written.
+pub fn save(build_dir: &Utf8Path, state: &ConfigureState) -> BoxResult<()> {
+    let path = build_dir.join(STATE_FILE);
+    std::fs::write(&path, serde_json::to_string_pretty(state)? + "\n")
+        .map_err(|err| format!("failed to write `{path}`: {err}"))?;
+    Ok(())
+}
+
+/// Removes the `CMake` cache of `build_dir` (`CMakeCache.txt` and `CMakeFiles`), along with the saved state, so that
+/// the next configure run starts fresh. The build outputs are left in place.
+///
+/// # Errors
+///
+/// Will return `Err` if a file or directory exists but cannot be removed.
+pub fn invalidate(build_dir: &Utf8Path) -> BoxResult<()> {
+    for file in ["CMakeCache.txt", STATE_FILE] {
+        let path = build_dir.join(file);
+        if path.exists() {
+            std::fs::remove_file(&path).map_err(|err| format!("failed to remove `{path}`: {err}"))?;
+        }
+    }
+    let path = build_dir.join("CMakeFiles");
+    if path.exists() {
+        std::fs::remove_dir_all(&path).map_err(|err| format!("failed to remove `{path}`: {err}"))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use crate::{config::XtaskCmakeInstall, BoxResult};
+use camino::{Utf8Path, Utf8PathBuf};
+
+// The extensions of static libraries, shared libraries, and import libraries across platforms.
+const LIBRARY_EXTENSIONS: &[&str] = &["a", "so", "dylib", "lib", "dll"];
+
+/// The expected files of `expected` that are missing from the install tree at `prefix`, each described by its kind and
+/// name (e.g. ``header `foo/foo.h` ``).
+///
+/// Headers are expected under `include`. Libraries and pkg-config files are looked for in the library directories of
+/// the prefix, which are `lib`, `lib64`, `lib32`, the subdirectories of `lib` (e.g. the `lib/x86_64-linux-gnu` of
+/// Debian's multiarch layout), and `bin` (for Windows DLLs). A library `foo` is any of `libfoo.a`, `libfoo.so` (or a
+/// versioned `libfoo.so.1`), `libfoo.dylib` (or a versioned `libfoo.1.dylib`), `foo.lib`, `foo.dll`, and the like,
+/// whatever the host platform, since the install may be cross-compiled. A pkg-config package `foo` is a `foo.pc` in
+/// the `pkgconfig` directory of a library directory or of `share`.
+///
+/// # Errors
+///
+/// Will return `Err` if a directory of the install tree cannot be read.
+pub fn missing(prefix: &Utf8Path, expected: &XtaskCmakeInstall) -> BoxResult<Vec<String>> {
+    let mut missing = vec![];
+    for header in &expected.headers {
+        if !prefix.join("include").join(header).is_file() {
+            missing.push(format!("header `{header}`"));
+        }
+    }
+    let lib_dirs = library_dirs(prefix)?;
+    let files = lib_dirs
+        .iter()
+        .map(|dir| file_names(dir))
+        .collect::<BoxResult<Vec<_>>>()?
+        .concat();
+    for library in &expected.libraries {
+        if !files.iter().any(|file| is_library(file, library)) {
+            missing.push(format!("library `{library}`"));
+        }
+    }
+    let pkg_config_dirs = lib_dirs
+        .iter()
+        .filter(|dir| dir.file_name() != Some("bin"))
+        .map(|dir| dir.join("pkgconfig"))
+        .chain([prefix.join("share").join("pkgconfig")])
+        .collect::<Vec<_>>();
+    for package in &expected.pkg_config {
+        if !pkg_config_dirs
+            .iter()
+            .any(|dir| dir.join(format!("{package}.pc")).is_file())
+        {
+            missing.push(format!("pkg-config file `{package}.pc`"));
+        }
+    }
+    Ok(missing)
+}
+
+// The library directories of the install tree at `prefix` that exist (see `missing`).
+fn library_dirs(prefix: &Utf8Path) -> BoxResult<Vec<Utf8PathBuf>> {
+    let mut dirs = vec![];
+    let lib = prefix.join("lib");
+    if lib.is_dir() {
+        for entry in lib.read_dir_utf8()? {
+            let entry = entry?;
+            if entry.path().is_dir() && entry.file_name() != "pkgconfig" && entry.file_name() != "cmake" {
+                dirs.push(entry.path().to_owned());
+            }
+        }
+    }
+    dirs.extend(
+        ["lib", "lib64", "lib32", "bin"]
+            .into_iter()
+            .map(|dir| prefix.join(dir))
+            .filter(|dir| dir.is_dir()),
+    );
+    Ok(dirs)
+}
+
+// The names of the files in `dir`.
+fn file_names(dir: &Utf8Path) -> BoxResult<Vec<String>> {
+    let mut names = vec![];
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        if !entry.path().is_dir() {
+            names.push(entry.file_name().to_owned());
+        }
+    }
+    Ok(names)
+}
+
+// Whether `file` is a static, shared, or import library named `library` (see `missing`).
+fn is_library(file: &str, library: &str) -> bool {
+    [Some(file), file.strip_prefix("lib")]
+        .into_iter()
+        .flatten()
+        .any(|name| {
+            let Some(rest) = name.strip_prefix(library).and_then(|rest| rest.strip_prefix('.')) else {
+                return false;
+            };
+            // NOTE: shared libraries may be versioned, as in `libfoo.so.1.2` or `libfoo.1.dylib`
+            let mut extensions = rest
+                .split('.')
+                .filter(|part| !part.chars().all(|char| char.is_ascii_digit()));
+            extensions
+                .next()
+                .map_or(false, |extension| LIBRARY_EXTENSIONS.contains(&extension))
+                && extensions.next().is_none()
+        })
+}
@@ -1,51 +1,629 @@
+// NOTE: the subcommands take `Context` by value, for the single signature of `Subcommand::run`, even where they only
+// borrow it
+#![allow(clippy::needless_pass_by_value)]
+
+mod audit;
+mod bench;
+mod bridge;
 mod build;
+mod cache;
 mod check;
+mod check_all;
 pub mod clang;
+mod clangd;
 mod clippy;
 mod cmake;
+mod completions;
+mod config;
+mod devenv;
+mod dist;
 mod doc;
 mod fmt;
+mod foreach;
+mod headers;
+mod hooks;
+mod install_tools;
+mod licenses;
+mod lint;
 mod miri;
+mod outdated;
+mod registry;
+mod size;
 mod tarpaulin;
 mod test;
 mod udeps;
+mod usage;
 mod valgrind;
 
 pub use self::{
+    audit::audit,
+    bench::bench,
+    bridge::bridge,
     build::build,
+    cache::cache,
     check::check,
+    check_all::check_all,
     clang::clang,
+    clangd::clangd,
     clippy::clippy,
     cmake::cmake,
+    completions::completions,
+    config::config,
+    devenv::devenv,
+    dist::dist,
     doc::doc,
     fmt::fmt,
+    foreach::foreach,
+    headers::headers,
+    install_tools::install_tools,
+    licenses::licenses,
+    lint::lint,
     miri::miri,
+    outdated::outdated,
+    registry::{check_lockfile, help, invoke_step, subcommand, Invocation, Subcommand, SUBCOMMANDS},
+    size::size,
     tarpaulin::tarpaulin,
     test::test,
     udeps::udeps,
+    usage::{Entry, Usage},
     valgrind::valgrind,
 };
 
-use crate::config::Config;
+use crate::{
+    config::{Config, XtaskProfile},
+    runner::{ParallelOutput, Runner, SystemRunner},
+    target_preset::TargetPreset,
+    tool_cache::ToolCache,
+    validation::Validation,
+    BoxResult,
+};
 use camino::Utf8PathBuf;
-use std::ffi::OsString;
+use std::{
+    ffi::{OsStr, OsString},
+    io::IsTerminal,
+    process::Command,
+    str::FromStr,
+};
 
+/// The environment variables kept with `--hermetic`: those needed to find and run the tools and toolchains at all.
+pub const HERMETIC_ENV: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "TMPDIR",
+    "TERM",
+    "CARGO_HOME",
+    "RUSTUP_HOME",
+    "RUSTUP_TOOLCHAIN",
+    "CARGO_TARGET_DIR",
+    "SDKROOT",
+    "DEVELOPER_DIR",
+    // NOTE: Windows programs commonly fail to start without these
+    "SYSTEMROOT",
+    "SYSTEMDRIVE",
+    "WINDIR",
+    "COMSPEC",
+    "PATHEXT",
+    "TEMP",
+    "TMP",
+    "USERPROFILE",
+    "APPDATA",
+    "LOCALAPPDATA",
+    "PROGRAMDATA",
+    "PROGRAMFILES",
+    "PROGRAMFILES(X86)",
+];
+
+#[allow(clippy::struct_excessive_bools)]
 pub struct Context<'a> {
     pub config: &'a Config,
     pub args: &'a mut pico_args::Arguments,
     pub tool_args: Vec<OsString>,
     pub current_dir: Option<Utf8PathBuf>,
     pub subcommand: Option<String>,
+    /// Spawns the processes for the command
+    pub runner: &'a dyn Runner,
+    /// Pass `--locked` to cargo invocations, and check that `Cargo.lock` is committed and unchanged afterwards
+    pub locked: bool,
+    /// Pass `--frozen` to cargo invocations
+    pub frozen: bool,
+    /// Pass `--offline` to cargo invocations and refuse other network access
+    pub offline: bool,
+    /// Pass `--color <WHEN>` to cargo invocations
+    pub color: Option<Color>,
+    /// The number of parallel jobs for the spawned tools
+    pub jobs: Option<u32>,
+    /// The name of the selected `xtask.profiles` entry, if any
+    pub profile: Option<String>,
+    /// The name of the selected built-in target preset (see [`crate::target_preset`]), if any
+    pub target_preset: Option<String>,
+    /// Run tools with only the allowlisted environment variables of the xtask process
+    pub hermetic: bool,
+    /// How to fold the output of each step of aggregate commands
+    pub log_groups: LogGroups,
+    /// Print the main process invocation, including its environment, before running it
+    pub print_env: bool,
+    /// Pass the output of tools run in parallel through as is, rather than prefixing each line with the tool's label
+    pub no_capture: bool,
+    /// Silence the status lines of the xtask (see [`crate::handler::status`])
+    pub quiet: bool,
+    /// Pass `--verbose` to cargo invocations
+    pub verbose: bool,
+    /// Probe the tools for their versions even if they are unchanged since they were validated (see
+    /// [`Context::validation`])
+    pub no_cache: bool,
 }
 
 impl<'a> Context<'a> {
+    /// Consumes the `--locked`, `--frozen`, `--offline`, `--color <WHEN>`, `-j/--jobs <N>`, `--log-groups <KIND>`,
+    /// `--profile <NAME>`, `--target-preset <NAME>`, `--hermetic`, `--print-env`, `--env-format <FORMAT>`,
+    /// `--no-capture`, `-q/--quiet`, `-v/--verbose`, `--no-cache`, `--error-format <FORMAT>`, `--timings`, and
+    /// `--timings-trace <PATH>` flags from `args`. The error and env formats are selected for the whole process (see
+    /// [`crate::handler::set_error_format`] and [`crate::handler::set_env_format`]), and an env format implies
+    /// `--print-env`. Likewise, timings are recorded for the whole process (see [`crate::timings::enable`]), where a
+    /// trace path implies `--timings`. `--quiet`, however, only silences the status lines of the whole process once the
+    /// subcommand is invoked (see [`Subcommand::invoke`]), so that creating a context has no lasting effect on them.
+    ///
+    /// A `--color`, `--jobs`, `--log-groups`, `--env-format`, or `--error-format` flag with a missing or invalid value
+    /// is left in `args` to be reported by [`crate::handler::unused`]. Without `--jobs`, the configured `xtask.jobs` (if
+    /// any) is used. Without `--log-groups`, the kind is detected from the CI environment. Without `--profile`, the
+    /// profile named by `XTASK_PROFILE` (if set) is selected, and likewise for `--target-preset` and
+    /// `XTASK_TARGET_PRESET`. Setting `XTASK_OFFLINE=1` in the environment is equivalent to `--offline`, and setting
+    /// `XTASK_LOCKED=1` (or `xtask.locked`) is equivalent to `--locked`.
     pub fn new(config: &'a Config, args: &'a mut pico_args::Arguments, tool_args: Vec<OsString>) -> Context<'a> {
+        let locked = args.contains("--locked")
+            || config.xtask.locked
+            || std::env::var("XTASK_LOCKED").map_or(false, |val| val == "1");
+        let frozen = args.contains("--frozen");
+        let offline = args.contains("--offline") || std::env::var("XTASK_OFFLINE").map_or(false, |val| val == "1");
+        let color = args.opt_value_from_str("--color").ok().flatten();
+        let jobs = args
+            .opt_value_from_str(["-j", "--jobs"])
+            .ok()
+            .flatten()
+            .or(config.xtask.jobs);
+        let log_groups = args
+            .opt_value_from_str("--log-groups")
+            .ok()
+            .flatten()
+            .unwrap_or_else(LogGroups::detect);
+        let profile = args
+            .opt_value_from_str("--profile")
+            .ok()
+            .flatten()
+            .or_else(|| std::env::var("XTASK_PROFILE").ok().filter(|val| !val.is_empty()));
+        let target_preset = args
+            .opt_value_from_str("--target-preset")
+            .ok()
+            .flatten()
+            .or_else(|| std::env::var("XTASK_TARGET_PRESET").ok().filter(|val| !val.is_empty()));
+        let hermetic = args.contains("--hermetic");
+        let mut print_env = args.contains("--print-env");
+        if let Some(format) = args.opt_value_from_str("--env-format").ok().flatten() {
+            crate::handler::set_env_format(format);
+            print_env = true;
+        }
+        let no_capture = args.contains("--no-capture");
+        let quiet = args.contains(["-q", "--quiet"]);
+        let verbose = args.contains(["-v", "--verbose"]);
+        let no_cache = args.contains("--no-cache");
+        if let Some(format) = args.opt_value_from_str("--error-format").ok().flatten() {
+            crate::handler::set_error_format(format);
+        }
+        let timings_trace = args.opt_value_from_str("--timings-trace").ok().flatten();
+        if args.contains("--timings") || timings_trace.is_some() {
+            crate::timings::enable(timings_trace);
+        }
         Context {
             config,
             args,
             tool_args,
             current_dir: None,
             subcommand: None,
+            runner: &SystemRunner,
+            locked,
+            frozen,
+            offline,
+            color,
+            jobs,
+            profile,
+            target_preset,
+            hermetic,
+            log_groups,
+            print_env,
+            no_capture,
+            quiet,
+            verbose,
+            no_cache,
+        }
+    }
+
+    /// Creates a context for running another command on behalf of this one, keeping the runner and global flags.
+    pub fn inherit<'b>(&self, args: &'b mut pico_args::Arguments, tool_args: Vec<OsString>) -> Context<'b>
+    where
+        'a: 'b,
+    {
+        let mut context = Context::new(self.config, args, tool_args);
+        context.runner = self.runner;
+        context.locked |= self.locked;
+        context.frozen |= self.frozen;
+        context.offline |= self.offline;
+        context.color = context.color.or(self.color);
+        context.jobs = context.jobs.or(self.jobs);
+        context.profile = context.profile.or_else(|| self.profile.clone());
+        context.target_preset = context.target_preset.or_else(|| self.target_preset.clone());
+        context.hermetic |= self.hermetic;
+        context.log_groups = self.log_groups;
+        context.print_env |= self.print_env;
+        context.no_capture |= self.no_capture;
+        context.quiet |= self.quiet;
+        context.verbose |= self.verbose;
+        context.no_cache |= self.no_cache;
+        context
+    }
+
+    /// The settings of the selected profile, if any.
+    ///
+    /// This is `None` for an unknown profile name, which [`Subcommand::invoke`] reports before running the subcommand.
+    #[must_use]
+    pub fn selected_profile(&self) -> Option<&'a XtaskProfile> {
+        self.profile
+            .as_ref()
+            .and_then(|name| self.config.xtask.profiles.get(name))
+    }
+
+    /// The selected target preset, if any.
+    ///
+    /// This is `None` for an unknown preset name, which [`Subcommand::invoke`] reports before running the subcommand.
+    #[must_use]
+    pub fn selected_target_preset(&self) -> Option<&'static TargetPreset> {
+        self.target_preset
+            .as_deref()
+            .and_then(|name| crate::target_preset::find(name).ok())
+    }
+
+    /// The linker for the configured `xtask.link.linker`, if any, unless a target preset is selected.
+    ///
+    /// This is `None` for an unknown linker name, which [`Subcommand::invoke`] reports before running a subcommand
+    /// that links.
+    #[must_use]
+    pub fn selected_linker(&self) -> Option<crate::linker::Selection> {
+        if self.target_preset.is_some() {
+            return None;
+        }
+        crate::linker::selected(&self.config.xtask).ok().flatten()
+    }
+
+    /// The `--target <TRIPLE>` arguments for cargo of the selected target preset, if any.
+    #[must_use]
+    pub fn target_args(&self) -> Vec<&'static str> {
+        self.selected_target_preset()
+            .map(|preset| vec!["--target", preset.triple])
+            .unwrap_or_default()
+    }
+
+    /// How to show the output of tools run in parallel (see [`crate::runner::run_parallel`]): passed through with
+    /// `--no-capture`, or else prefixed, in color unless `--color never` is given, `NO_COLOR` is set, or stdout is not
+    /// a terminal (without `--color always`).
+    #[must_use]
+    pub fn parallel_output(&self) -> ParallelOutput {
+        if self.no_capture {
+            return ParallelOutput::Passthrough;
+        }
+        let color = match self.color {
+            Some(Color::Always) => true,
+            Some(Color::Never) => false,
+            Some(Color::Auto) | None => std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal(),
+        };
+        ParallelOutput::Prefixed { color }
+    }
+
+    /// The default arguments for the xtask subcommand `name`: the configured `xtask.commands.<name>.args`, followed by
+    /// those of the selected profile.
+    #[must_use]
+    pub fn default_args(&self, name: &str) -> Vec<&'a str> {
+        let mut args = crate::config::command_default_args(self.config, name)
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if let Some(command) = self.selected_profile().and_then(|profile| profile.commands.get(name)) {
+            args.extend(command.args.iter().map(String::as_str));
+        }
+        args
+    }
+
+    /// The nightly toolchain name: the first installed of [`Context::nightly_candidates`] (see
+    /// [`crate::validation::first_installed_toolchain`]), or the first of them if rustup is missing but the workspace
+    /// has a `rust-toolchain.toml`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if several toolchains are acceptable and none of them is installed (or rustup fails).
+    pub fn nightly_toolchain(&self) -> BoxResult<&'a str> {
+        crate::validation::first_installed_toolchain(
+            &self.config.xtask,
+            self.runner,
+            &self.nightly_candidates(),
+            self.config.rust_toolchain.is_some(),
+        )
+    }
+
+    /// Checks that the cargo subcommand `subcommand` of the rustup component `component` runs with `toolchain` (see
+    /// [`crate::validation::validate_cargo_component`]).
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the subcommand fails to run, suggesting how to add the component if it is missing.
+    pub fn validate_component(&self, toolchain: &str, component: &str, subcommand: &str) -> BoxResult<()> {
+        let mut probe = self.cargo(Some(toolchain));
+        probe.args([subcommand, "--version"]);
+        crate::validation::validate_cargo_component(&self.config.xtask, self.runner, component, toolchain, probe)
+    }
+
+    /// The acceptable nightly toolchain names, in order of preference: the selected profile's
+    /// `rust.toolchain.nightly`, if any, or else [`crate::config::rust::toolchain::nightly_candidates`].
+    #[must_use]
+    pub fn nightly_candidates(&self) -> Vec<&'a str> {
+        match self.selected_profile().map(|profile| &profile.rust.toolchain.nightly) {
+            Some(nightly) if !nightly.is_empty() => nightly.iter().map(String::as_str).collect(),
+            _ => crate::config::rust::toolchain::nightly_candidates(self.config),
+        }
+    }
+
+    /// The stable toolchain name: the selected profile's `rust.toolchain.stable`, if any, or else
+    /// [`crate::config::rust::toolchain::stable`].
+    #[must_use]
+    pub fn stable_toolchain(&self) -> &'a str {
+        self.selected_profile()
+            .and_then(|profile| profile.rust.toolchain.stable.as_deref())
+            .unwrap_or_else(|| crate::config::rust::toolchain::stable(self.config))
+    }
+
+    /// Creates a command for `program` with the configured `xtask.env` environment variables, followed by those of the
+    /// selected profile.
+    ///
+    /// A `program` configured in `xtask.tools.paths` is run from the configured path, and `cargo` and `rustup` from
+    /// `$CARGO_HOME/bin` (see [`crate::config::Xtask::tool_path`]).
+    ///
+    /// With `jobs` set, `CARGO_BUILD_JOBS` and `CMAKE_BUILD_PARALLEL_LEVEL` are set as well, so that cargo and
+    /// `cmake --build` (including when run by build scripts) are bounded the same way.
+    ///
+    /// On macOS, `SDKROOT` is set to the SDK selected with `xcode-select` unless it is already set (or an iOS target
+    /// preset is selected), so that non-Xcode clang tools (e.g. from Homebrew) find the system headers.
+    ///
+    /// With a target preset selected, the compilers and linker of its SDK are set for cargo and the `cc` crate (see
+    /// [`crate::target_preset::cargo_env`]).
+    ///
+    /// With `hermetic` set, the environment inherited from the xtask process is first reduced to the variables in
+    /// [`HERMETIC_ENV`] and `xtask.hermetic.allow`, so that stray settings (e.g. `CXXFLAGS`) cannot affect the tools.
+    #[must_use]
+    pub fn command(&self, program: impl AsRef<OsStr>) -> Command {
+        let program = program.as_ref();
+        let cmd = match program.to_str() {
+            Some(program) => Command::new(&*self.config.xtask.tool_path(program)),
+            None => Command::new(program),
+        };
+        self.with_environment(cmd)
+    }
+
+    // Sets up the environment of `cmd` as described for `command`.
+    fn with_environment(&self, mut cmd: Command) -> Command {
+        if self.hermetic {
+            let allow = &self.config.xtask.hermetic.allow;
+            // NOTE: remove rather than clear, so that `--print-env` shows what was removed
+            for (name, _) in std::env::vars_os() {
+                // NOTE: names are compared ignoring case, since they are case-insensitive on Windows (e.g. `Path`)
+                let allowed = name.to_str().map_or(false, |name| {
+                    let matches = |allowed: &str| allowed.eq_ignore_ascii_case(name);
+                    HERMETIC_ENV.iter().copied().any(matches) || allow.iter().map(String::as_str).any(matches)
+                });
+                if !allowed {
+                    cmd.env_remove(name);
+                }
+            }
+        }
+        let preset = self.selected_target_preset();
+        #[cfg(target_os = "macos")]
+        if std::env::var_os("SDKROOT").is_none()
+            && !preset.map_or(false, |preset| {
+                matches!(preset.platform, crate::target_preset::Platform::Ios { .. })
+            })
+        {
+            if let Some(sdk_root) = crate::detection::detect_macos_sdk_root() {
+                cmd.env("SDKROOT", sdk_root);
+            }
         }
+        // NOTE: an NDK that is not found is reported by `Subcommand::invoke` before the subcommand runs
+        if let Some(env) = preset.and_then(|preset| crate::target_preset::cargo_env(preset, &self.config.xtask).ok()) {
+            cmd.envs(env);
+        }
+        cmd.envs(&self.config.xtask.env);
+        if let Some(profile) = self.selected_profile() {
+            cmd.envs(&profile.env);
+        }
+        if let Some(jobs) = self.jobs {
+            cmd.env("CARGO_BUILD_JOBS", jobs.to_string());
+            cmd.env("CMAKE_BUILD_PARALLEL_LEVEL", jobs.to_string());
+        }
+        cmd
+    }
+
+    /// The tools of the config, probed for their versions in the environment of [`Context::command`] (see
+    /// [`Validation`]).
+    ///
+    /// Unless `--no-cache` is given, the versions are kept in the tool cache under the target directory (see
+    /// [`crate::tool_cache::ToolCache`]) for the next invocations. The cache is only used once the target directory
+    /// exists, so that validating the tools does not create it.
+    #[must_use]
+    pub fn validation(&self) -> Validation<'_> {
+        let validation = Validation::with_command(self.config, self.runner, |program| self.command(program));
+        let target_dir = crate::workspace::target_dir(self.config);
+        if self.no_cache || !target_dir.is_dir() {
+            return validation;
+        }
+        validation.with_cache(ToolCache::path(target_dir))
+    }
+
+    /// Creates a `cargo` command for the given toolchain (if any), followed by the context's global cargo flags.
+    ///
+    /// The program is the configured `xtask.rust.cargo` wrapper, if any, or else the cargo running the xtask (see
+    /// [`crate::config::Xtask::cargo_program`]). The environment is that of [`Context::command`], and the flags
+    /// include those for the linker of `xtask.link.linker` (see [`Context::selected_linker`]).
+    #[must_use]
+    pub fn cargo(&self, toolchain: Option<&str>) -> Command {
+        let mut cmd = self.with_environment(Command::new(&*self.config.xtask.cargo_program(toolchain)));
+        if let Some(toolchain) = toolchain {
+            cmd.arg(format!("+{toolchain}"));
+        }
+        for (enabled, flag) in [
+            (self.locked, "--locked"),
+            (self.frozen, "--frozen"),
+            (self.offline, "--offline"),
+            (self.verbose, "--verbose"),
+        ] {
+            if enabled {
+                cmd.arg(flag);
+            }
+        }
+        if let Some(color) = self.color {
+            cmd.args(["--color", color.as_str()]);
+        }
+        // NOTE: `RUSTFLAGS` takes precedence over `build.rustflags`, so the linker flags are added to it when it is set
+        if let Some(linker) = self.selected_linker() {
+            let flags = crate::linker::rustflags(&linker);
+            match std::env::var("RUSTFLAGS") {
+                Ok(rustflags) if !rustflags.trim().is_empty() => {
+                    cmd.env("RUSTFLAGS", format!("{rustflags} {}", flags.join(" ")));
+                },
+                _ => {
+                    let flags = flags.iter().map(|flag| format!("{flag:?}")).collect::<Vec<_>>();
+                    cmd.args(["--config", &format!("build.rustflags=[{}]", flags.join(", "))]);
+                },
+            }
+        }
+        cmd
+    }
+}
+
+/// When to use colored output.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+impl Color {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Color::Auto => "auto",
+            Color::Always => "always",
+            Color::Never => "never",
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "auto" => Ok(Color::Auto),
+            "always" => Ok(Color::Always),
+            "never" => Ok(Color::Never),
+            _ => Err(format!("expected `auto`, `always`, or `never`, found `{str}`")),
+        }
+    }
+}
+
+/// How to mark the output of each step of an aggregate command as a collapsible group in CI logs.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LogGroups {
+    /// `::group::` and `::endgroup::` workflow commands
+    GitHub,
+    /// `--- ` section headers
+    Buildkite,
+    None,
+}
+
+impl LogGroups {
+    /// The kind of groups supported by the CI service the xtask is running on, if any.
+    #[must_use]
+    pub fn detect() -> Self {
+        let is_set = |name: &str| std::env::var(name).map_or(false, |val| val == "true");
+        if is_set("GITHUB_ACTIONS") {
+            LogGroups::GitHub
+        } else if is_set("BUILDKITE") {
+            LogGroups::Buildkite
+        } else {
+            LogGroups::None
+        }
+    }
+
+    /// Prints the start of a group titled `title`.
+    pub fn start(self, title: &str) {
+        match self {
+            LogGroups::GitHub => println!("::group::{title}"),
+            LogGroups::Buildkite => println!("--- {title}"),
+            LogGroups::None => {},
+        }
+    }
+
+    /// Prints the end of the group started last.
+    pub fn end(self) {
+        // NOTE: a Buildkite section lasts until the next one starts
+        if self == LogGroups::GitHub {
+            println!("::endgroup::");
+        }
+    }
+}
+
+impl FromStr for LogGroups {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "github" => Ok(LogGroups::GitHub),
+            "buildkite" => Ok(LogGroups::Buildkite),
+            "none" => Ok(LogGroups::None),
+            _ => Err(format!("expected `github`, `buildkite`, or `none`, found `{str}`")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Context;
+    use crate::testing;
+
+    #[test]
+    fn verbose_is_passed_to_cargo() {
+        let config = testing::config(serde_json::json!({}));
+        let mut args = pico_args::Arguments::from_vec(vec!["-v".into()]);
+        let context = Context::new(&config, &mut args, vec![]);
+        assert!(context.cargo(None).get_args().any(|arg| arg == "--verbose"));
+    }
+
+    #[test]
+    fn color_is_passed_to_cargo() {
+        let config = testing::config(serde_json::json!({}));
+        let mut args = pico_args::Arguments::from_vec(vec!["--color".into(), "always".into()]);
+        let context = Context::new(&config, &mut args, vec![]);
+        let cmd = context.cargo(Some("nightly"));
+        assert!(testing::command_line(&cmd).ends_with("cargo +nightly --color always"));
+    }
+
+    #[test]
+    fn configured_env_is_set_for_spawned_tools() {
+        let config = testing::config(serde_json::json!({ "env": { "FOO": "bar" } }));
+        let mut args = pico_args::Arguments::from_vec(vec![]);
+        let context = Context::new(&config, &mut args, vec![]);
+        let cmd = context.command("cmake");
+        assert!(
+            cmd.get_envs()
+                .any(|(name, value)| name == "FOO" && value == Some("bar".as_ref()))
+        );
     }
 }
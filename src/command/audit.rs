@@ -0,0 +1,77 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    BoxResult,
+};
+use std::process::ExitStatus;
+
+/// The usage of `xtask audit`.
+pub const USAGE: Usage = Usage {
+    name: "audit",
+    summary: "Check the project's dependencies for security advisories with cargo-audit",
+    args: "",
+    description: &[],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        Entry {
+            name: "--offline",
+            about: &[
+                "Pass `--offline` to cargo and use the cached advisory database",
+                "(also enabled by `XTASK_OFFLINE=1`)",
+            ],
+        },
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::HERMETIC,
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - Tool validation fails (missing tools, incorrect versions, etc.)
+/// - The command process fails to start
+/// - The command invocation fails with non-zero exit status
+pub fn audit(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    crate::handler::unused(context.args)?;
+
+    let project_root = crate::workspace::project_root(context.config);
+
+    let mut cmd = context.cargo(None);
+    cmd.current_dir(project_root);
+    cmd.args(["audit", "--version"]);
+    if !context.runner.output(&mut cmd)?.status.success() {
+        return Err("`cargo audit` is not available\nInstall it with `cargo install cargo-audit`".into());
+    }
+
+    let mut cmd = context.cargo(None);
+    cmd.current_dir(project_root);
+    cmd.args(["audit"]);
+    if context.offline {
+        cmd.args(["--no-fetch"]);
+    }
+    cmd.args(context.default_args("audit"));
+    cmd.args(context.tool_args);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
+
+    Ok(Some(status))
+}
@@ -0,0 +1,208 @@
+use crate::{
+    benchmark::Results,
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    runner::Runner,
+    BoxResult,
+};
+use camino::Utf8Path;
+use std::{process::ExitStatus, time::SystemTime};
+
+/// The usage of `xtask bench`.
+pub const USAGE: Usage = Usage {
+    name: "bench",
+    summary: "Run the project's benchmarks",
+    args: "",
+    description: &[
+        "The criterion results (and any configured Google Benchmark JSON results) of each run are stored for the commit checked",
+        "out at the time, so that later runs can use it as a `--baseline`. The results of a work tree with uncommitted changes",
+        "are stored as `<commit>-dirty` instead, so that they never become the baseline of the commit.",
+        "",
+        "Without `-p/--package`, the benchmarks of every workspace member are run.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::HERMETIC,
+        usage::PACKAGE,
+        usage::MANIFEST_PATH,
+        Entry {
+            name: "--nightly",
+            about: &["Run the benchmarks with the nightly toolchain (needed for `#[bench]`)"],
+        },
+        Entry {
+            name: "--baseline <REV>",
+            about: &["Compare the results against those stored for the git commit REV"],
+        },
+        Entry {
+            name: "--fail-on-regress <PERCENT>",
+            about: &["Fail if a benchmark is slower than the baseline by more than PERCENT (default: 5%)"],
+        },
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - No results are stored for the `--baseline` commit
+/// - A benchmark regressed by more than the `--fail-on-regress` threshold
+/// - The command process fails to start
+/// - The command invocation fails with non-zero exit status
+pub fn bench(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let nightly = context.args.contains("--nightly");
+
+    let members = crate::workspace::members(context.config).collect::<Vec<_>>();
+    let packages = crate::workspace::packages(context.config, context.args, &members)?;
+    let manifest_path = context.args.opt_value_from_str::<_, String>("--manifest-path")?;
+    let baseline = context.args.opt_value_from_str::<_, String>("--baseline")?;
+    let fail_on_regress = context.args.opt_value_from_str::<_, String>("--fail-on-regress")?;
+
+    crate::handler::unused(context.args)?;
+
+    let root = &context.config.cargo_metadata.workspace_root;
+    let target_dir = crate::workspace::target_dir(context.config);
+
+    let threshold = match (&baseline, fail_on_regress) {
+        (None, Some(_)) => return Err("`--fail-on-regress` requires `--baseline`".into()),
+        (None, None) => None,
+        (Some(_), threshold) => {
+            let threshold = threshold
+                .or_else(|| context.config.xtask.bench.fail_on_regress.clone())
+                .unwrap_or_else(|| "5%".into());
+            Some(crate::benchmark::parse_threshold(&threshold)?)
+        },
+    };
+    let baseline = match &baseline {
+        Some(reference) => {
+            let rev = crate::git::rev_parse(context.runner, root, reference)?
+                .ok_or("`--baseline` requires the workspace to be a git checkout")?;
+            Some(crate::benchmark::load(target_dir, &rev)?)
+        },
+        None => None,
+    };
+
+    let toolchain = nightly.then(|| context.nightly_toolchain()).transpose()?;
+
+    let mut cmd = context.cargo(toolchain);
+    cmd.current_dir(crate::workspace::project_root(context.config));
+    cmd.args(["bench"]);
+    for package in &packages {
+        cmd.args(["--package", package]);
+    }
+    if let Some(manifest_path) = &manifest_path {
+        cmd.args(["--manifest-path", manifest_path]);
+    }
+    cmd.args(context.default_args("bench"));
+    cmd.args(context.tool_args);
+
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let started = SystemTime::now();
+    let status = context.runner.status(&mut cmd)?;
+    if !status.success() {
+        return Ok(Some(status));
+    }
+
+    let mut results = crate::benchmark::collect_criterion(target_dir, started)?;
+    for path in &context.config.xtask.bench.google_benchmark_results {
+        results.extend(crate::benchmark::collect_google_benchmark(&root.join(path), started)?);
+    }
+    if let Some(key) = results_key(context.runner, root)? {
+        if !results.is_empty() {
+            crate::benchmark::save(target_dir, &key, &results)?;
+            crate::handler::status(format_args!("saved {} benchmark results for `{key}`", results.len()));
+        }
+    }
+
+    if let (Some(baseline), Some(threshold)) = (baseline, threshold) {
+        compare(&baseline, &results, threshold)?;
+    }
+
+    Ok(Some(status))
+}
+
+// The key the results of this run are stored under: the commit checked out, with a `-dirty` suffix if the work tree
+// has uncommitted changes (ignoring untracked files), or `None` outside a git work tree.
+fn results_key(runner: &dyn Runner, root: &Utf8Path) -> BoxResult<Option<String>> {
+    let Some(rev) = crate::git::rev_parse(runner, root, "HEAD")? else {
+        return Ok(None);
+    };
+    let changes = crate::git::status(runner, root, root)?.unwrap_or_default();
+    if changes
+        .iter()
+        .any(|line| !line.starts_with("??") && !line.starts_with("!!"))
+    {
+        return Ok(Some(format!("{rev}-dirty")));
+    }
+    Ok(Some(rev))
+}
+
+// Print the comparison of `results` with `baseline`, failing if any benchmark regressed by more than `threshold`.
+fn compare(baseline: &Results, results: &Results, threshold: f64) -> BoxResult<()> {
+    crate::benchmark::print_comparison(baseline, results);
+    let regressions = crate::benchmark::regressions(baseline, results, threshold);
+    if regressions.is_empty() {
+        return Ok(());
+    }
+    let regressions = regressions
+        .iter()
+        .map(|regression| format!("{} ({:+.2}%)", regression.id, regression.change() * 100.0))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Err(format!(
+        "benchmarks regressed by more than {}%: {regressions}",
+        threshold * 100.0
+    )
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{runner::MockRunner, testing};
+    use camino::Utf8Path;
+
+    fn results_key(status: &'static str) -> Option<String> {
+        let runner = MockRunner::new(move |cmd| {
+            let command_line = testing::command_line(cmd);
+            let stdout = if command_line.starts_with("git rev-parse --verify") {
+                "0123abcd\n"
+            } else if command_line.starts_with("git status") {
+                status
+            } else {
+                ""
+            };
+            Ok(testing::output(0, stdout))
+        });
+        super::results_key(&runner, Utf8Path::new(testing::ROOT)).unwrap()
+    }
+
+    #[test]
+    fn the_results_of_a_clean_tree_are_stored_for_its_commit() {
+        assert_eq!(results_key("").as_deref(), Some("0123abcd"));
+        assert_eq!(results_key("?? notes.txt\n!! target/\n").as_deref(), Some("0123abcd"));
+    }
+
+    #[test]
+    fn the_results_of_a_dirty_tree_are_not_stored_for_its_commit() {
+        assert_eq!(results_key(" M src/lib.rs\n").as_deref(), Some("0123abcd-dirty"));
+    }
+}
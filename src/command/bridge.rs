@@ -0,0 +1,383 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{path::PathBuf, process::ExitStatus, time::SystemTime};
+
+// A `#[cxx::bridge]` source file of a workspace package.
+struct Bridge {
+    source: Utf8PathBuf,
+    // The path of the generated header relative to an include directory: `<package>/<path within package>.h`
+    header: Utf8PathBuf,
+}
+
+/// The usage of `xtask bridge`.
+pub const USAGE: Usage = Usage {
+    name: "bridge",
+    summary: "Check that the headers generated from `#[cxx::bridge]` sources are up to date",
+    args: "[SUBCOMMAND]",
+    description: &[],
+    flags: &[usage::HELP],
+    subcommands: &[
+        Entry {
+            name: "check",
+            about: &[
+                "Check that the generated headers of the `#[cxx::bridge]` sources are up to date",
+                "With `xtask.bridge.genDir` configured, the committed headers there are compared with the output",
+                "of the `cxxbridge` command; otherwise the headers generated by cxx-build in each `OUT_DIR` are",
+                "compared by modification time",
+            ],
+        },
+        Entry {
+            name: "cxx-version",
+            about: &[
+                "Check that the `cxx.h` and `cxx.cc` compiled on the C++ side match the `cxx` crate in",
+                "`Cargo.lock`: the copies in `xtask.bridge.cxxSources` are compared with those of the crate,",
+                "and the version fetched by a `FetchContent_Declare(cxx ...)` in the CMake files (and the",
+                "fetched copy in `build/_deps`, if any) with the crate version",
+            ],
+        },
+    ],
+    examples: &[],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - A bridge source or generated header cannot be read
+/// - The `cxxbridge` command process fails to start or fails with non-zero exit status
+/// - A generated header is missing or out of date
+/// - The `cxx` crate is not a dependency, or the C++ copy of its `cxx.h` or `cxx.cc` does not match its version
+pub fn bridge(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let Some(bridge_subcommand) = context.args.opt_free_from_str::<String>()? else {
+        println!("{help}\n");
+        return Ok(None);
+    };
+
+    crate::handler::unused(context.args)?;
+
+    match bridge_subcommand.as_str() {
+        "check" => {},
+        "cxx-version" => return cxx_version(&context),
+        _ => {
+            println!("{help}\n");
+            return Err(Error::new(
+                Kind::Validation,
+                format!("unrecognized `xtask bridge` subcommand `{bridge_subcommand}`"),
+            )
+            .into());
+        },
+    }
+
+    let bridges = bridges(&context)?;
+    let problems = match &context.config.xtask.bridge.gen_dir {
+        Some(gen_dir) => {
+            let gen_dir = context.config.cargo_metadata.workspace_root.join(gen_dir);
+            check_gen_dir(&context, &gen_dir, &bridges)?
+        },
+        None => check_out_dirs(context.config, &bridges)?,
+    };
+
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("{problem}");
+        }
+        return Err(format!(
+            "{} of {} `#[cxx::bridge]` sources need their headers regenerated",
+            problems.len(),
+            bridges.len()
+        )
+        .into());
+    }
+    crate::handler::status(format_args!(
+        "the generated headers of {} `#[cxx::bridge]` sources are up to date",
+        bridges.len()
+    ));
+
+    Ok(None)
+}
+
+// The `#[cxx::bridge]` sources of the workspace packages.
+fn bridges(context: &Context<'_>) -> BoxResult<Vec<Bridge>> {
+    let pattern = regex::Regex::new(r"#\[cxx::bridge\b")?;
+    let mut bridges = vec![];
+    for package in context.config.cargo_metadata.workspace_packages() {
+        let Some(package_dir) = package.manifest_path.parent() else {
+            continue;
+        };
+        let mut sources = vec![];
+        rust_sources(package_dir, &mut sources)?;
+        for source in sources {
+            if pattern.is_match(&std::fs::read_to_string(&source)?) {
+                let relative = source.strip_prefix(package_dir)?;
+                let header = Utf8Path::new(&package.name).join(format!("{relative}.h"));
+                bridges.push(Bridge { source, header });
+            }
+        }
+    }
+    Ok(bridges)
+}
+
+// The `.rs` files under `dir`, skipping hidden directories and `target`.
+fn rust_sources(dir: &Utf8Path, sources: &mut Vec<Utf8PathBuf>) -> BoxResult<()> {
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            if entry.file_name().starts_with('.') || entry.file_name() == "target" {
+                continue;
+            }
+            rust_sources(path, sources)?;
+        } else if path.extension() == Some("rs") {
+            sources.push(path.to_owned());
+        }
+    }
+    Ok(())
+}
+
+// Compare the committed headers in `gen_dir` with the output of `cxxbridge <source> --header`.
+//
+// NOTE: modification times of committed files reflect the checkout rather than the generation, so compare content
+fn check_gen_dir(context: &Context<'_>, gen_dir: &Utf8Path, bridges: &[Bridge]) -> BoxResult<Vec<String>> {
+    let mut problems = vec![];
+    for bridge in bridges {
+        let header = gen_dir.join(&bridge.header);
+        let Ok(committed) = std::fs::read(&header) else {
+            problems.push(format!("missing: `{header}` (for `{}`)", bridge.source));
+            continue;
+        };
+        let mut cmd = context.command("cxxbridge");
+        cmd.arg(&bridge.source);
+        cmd.arg("--header");
+        let output = context.runner.output(&mut cmd).map_err(|err| {
+            format!("failed to run `cxxbridge`: {err}\nInstall it with `cargo install cxxbridge-cmd`")
+        })?;
+        if !output.status.success() {
+            let err = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("`cxxbridge {} --header` failed: \"{err}\"", bridge.source).into());
+        }
+        if output.stdout != committed {
+            problems.push(format!(
+                "stale: `{header}` differs from the output for `{}`",
+                bridge.source
+            ));
+        }
+    }
+    Ok(problems)
+}
+
+// Compare the modification times of the headers generated by cxx-build with those of their sources.
+//
+// Each build of a package has its own `OUT_DIR`, so the newest header among them is used.
+fn check_out_dirs(config: &crate::config::Config, bridges: &[Bridge]) -> BoxResult<Vec<String>> {
+    let include_dirs = out_dir_include_dirs(crate::workspace::target_dir(config).as_std_path())?;
+    let mut problems = vec![];
+    for bridge in bridges {
+        let mut newest: Option<(SystemTime, PathBuf)> = None;
+        for include_dir in &include_dirs {
+            let header = include_dir.join(&bridge.header);
+            if let Ok(modified) = std::fs::metadata(&header).and_then(|metadata| metadata.modified()) {
+                if newest.as_ref().map_or(true, |(newest, _)| modified > *newest) {
+                    newest = Some((modified, header));
+                }
+            }
+        }
+        let Some((generated, header)) = newest else {
+            problems.push(format!(
+                "missing: no generated header for `{}` (build the package first)",
+                bridge.source
+            ));
+            continue;
+        };
+        if std::fs::metadata(&bridge.source)?.modified()? > generated {
+            problems.push(format!(
+                "stale: `{}` is newer than `{}`",
+                bridge.source,
+                header.display()
+            ));
+        }
+    }
+    Ok(problems)
+}
+
+// The `<profile>/build/<package>-<hash>/out/cxxbridge/include` directories under `target_dir`, including those under
+// `<target triple>/` subdirectories.
+fn out_dir_include_dirs(target_dir: &std::path::Path) -> BoxResult<Vec<PathBuf>> {
+    let mut include_dirs = vec![];
+    let Ok(entries) = std::fs::read_dir(target_dir) else {
+        return Ok(include_dirs);
+    };
+    let mut profile_dirs = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            if let Ok(entries) = std::fs::read_dir(&path) {
+                for entry in entries {
+                    profile_dirs.push(entry?.path());
+                }
+            }
+            profile_dirs.push(path);
+        }
+    }
+    for profile_dir in profile_dirs {
+        let Ok(entries) = std::fs::read_dir(profile_dir.join("build")) else {
+            continue;
+        };
+        for entry in entries {
+            let include_dir = entry?.path().join("out").join("cxxbridge").join("include");
+            if include_dir.is_dir() {
+                include_dirs.push(include_dir);
+            }
+        }
+    }
+    Ok(include_dirs)
+}
+
+// Compare the `cxx.h` and `cxx.cc` compiled on the C++ side with those of the `cxx` crate in `Cargo.lock`.
+//
+// NOTE: the C++ runtime and the generated bridge code share the `cxxbridge1` ABI, which changes within it between
+// releases, so a mismatched pair can build and link but misbehave at runtime
+fn cxx_version(context: &Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let cxx = context
+        .config
+        .cargo_metadata
+        .packages
+        .iter()
+        .find(|package| package.name == "cxx")
+        .ok_or("the `cxx` crate is not a dependency of the workspace")?;
+    let version = cxx.version.to_string();
+    let crate_dir = cxx.manifest_path.parent().ok_or("the `cxx` crate has no directory")?;
+
+    let mut problems = vec![];
+    let cxx_sources = &context.config.xtask.bridge.cxx_sources;
+    for copy in cxx_sources {
+        compare_cxx_source(&root.join(copy), crate_dir, &version, &mut problems)?;
+    }
+    let mut cmake_files = vec![];
+    cmake_sources(root, &mut cmake_files)?;
+    let declarations = fetch_content_declarations(&cmake_files)?;
+    for (file, name, fetched) in &declarations {
+        match fetched {
+            Some(fetched) if *fetched == version => {},
+            Some(fetched) => problems.push(format!(
+                "mismatch: `{file}` fetches cxx {fetched}, but `Cargo.lock` has cxx {version}"
+            )),
+            None => problems.push(format!(
+                "unknown: the version fetched by `FetchContent_Declare({name} ...)` in `{file}` could not be \
+                 determined (expected a `GIT_TAG` or `URL` with a version)"
+            )),
+        }
+        let src_dir = root
+            .join("build")
+            .join("_deps")
+            .join(format!("{}-src", name.to_lowercase()));
+        for copy in [
+            src_dir.join("include").join("cxx.h"),
+            src_dir.join("src").join("cxx.cc"),
+        ] {
+            if copy.exists() {
+                compare_cxx_source(&copy, crate_dir, &version, &mut problems)?;
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        for problem in &problems {
+            println!("{problem}");
+        }
+        return Err(format!(
+            "the C++ side of cxx is out of sync with cxx {version} in `Cargo.lock`\nUpdate the vendored or fetched \
+             copy to cxx {version} (or pin the crate to the version of the C++ copy)"
+        )
+        .into());
+    }
+    if cxx_sources.is_empty() && declarations.is_empty() {
+        crate::handler::status(format_args!(
+            "no C++ copy of `cxx.h` or `cxx.cc` found (configure `xtask.bridge.cxxSources` for vendored copies); \
+             the copies of cxx-build always match the crate"
+        ));
+    } else {
+        crate::handler::status(format_args!(
+            "the C++ side of cxx matches cxx {version} in `Cargo.lock`"
+        ));
+    }
+
+    Ok(None)
+}
+
+// Compare `copy` with the `cxx.h` or `cxx.cc` (by its file name) of the `cxx` crate `version` in `crate_dir`.
+fn compare_cxx_source(
+    copy: &Utf8Path,
+    crate_dir: &Utf8Path,
+    version: &str,
+    problems: &mut Vec<String>,
+) -> BoxResult<()> {
+    let name = copy.file_name().unwrap_or_default();
+    let original = match name {
+        "cxx.h" => crate_dir.join("include").join("cxx.h"),
+        "cxx.cc" => crate_dir.join("src").join("cxx.cc"),
+        _ => return Err(format!("`{copy}` is neither a `cxx.h` nor a `cxx.cc`").into()),
+    };
+    let original = std::fs::read(&original).map_err(|err| format!("failed to read `{original}`: {err}"))?;
+    let Ok(contents) = std::fs::read(copy) else {
+        problems.push(format!("missing: `{copy}`"));
+        return Ok(());
+    };
+    if contents != original {
+        problems.push(format!("mismatch: `{copy}` differs from the `{name}` of cxx {version}"));
+    }
+    Ok(())
+}
+
+// The `CMakeLists.txt` and `.cmake` files under `dir`, skipping hidden directories, `target`, and `build`.
+fn cmake_sources(dir: &Utf8Path, sources: &mut Vec<Utf8PathBuf>) -> BoxResult<()> {
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            if entry.file_name().starts_with('.') || ["target", "build"].contains(&entry.file_name()) {
+                continue;
+            }
+            cmake_sources(path, sources)?;
+        } else if entry.file_name() == "CMakeLists.txt" || path.extension() == Some("cmake") {
+            sources.push(path.to_owned());
+        }
+    }
+    Ok(())
+}
+
+// The `FetchContent_Declare` calls for cxx in `files`, each with its file, its content name, and the version of its
+// `GIT_TAG` or `URL` (if any).
+fn fetch_content_declarations(files: &[Utf8PathBuf]) -> BoxResult<Vec<(Utf8PathBuf, String, Option<String>)>> {
+    let declare = regex::Regex::new(r"(?is)FetchContent_Declare\s*\(\s*(\w+)([^)]*)\)")?;
+    let source = regex::Regex::new(r#"(?i)\b(?:GIT_TAG|URL)\s+"?([^\s")]+)"#)?;
+    let version = regex::Regex::new(r"(\d+\.\d+\.\d+)")?;
+    let mut declarations = vec![];
+    for file in files {
+        let contents = std::fs::read_to_string(file).map_err(|err| format!("failed to read `{file}`: {err}"))?;
+        for captures in declare.captures_iter(&contents) {
+            let name = &captures[1];
+            if !name.eq_ignore_ascii_case("cxx") {
+                continue;
+            }
+            let fetched = source
+                .captures_iter(&captures[2])
+                .find_map(|source| version.captures(&source[1]).map(|version| version[1].to_owned()));
+            declarations.push((file.clone(), name.to_owned(), fetched));
+        }
+    }
+    Ok(declarations)
+}
@@ -1,38 +1,97 @@
-use crate::{command::Context, BoxResult};
-use std::process::{Command, ExitStatus};
+use crate::{
+    command::{
+        usage::{self, Usage},
+        Context,
+    },
+    BoxResult,
+};
+use std::{ffi::OsString, process::ExitStatus};
+
+/// The usage of `xtask build`.
+pub const USAGE: Usage = Usage {
+    name: "build",
+    summary: "Build the project",
+    args: "",
+    description: &[
+        "The sizes of the build artifacts are recorded after each successful build, for `xtask size`.",
+        "",
+        "With `xtask.cmake.prerequisite` configured, the CMake project is built first (configuring it if needed), and",
+        "cargo is given `XTASK_CMAKE_BUILD_DIR` and the configured paths within the build directory (e.g. `CXXLIB_DIR`).",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::TARGET_PRESET,
+        usage::HERMETIC,
+        usage::PACKAGE,
+        usage::MANIFEST_PATH,
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[],
+};
 
 /// # Errors
 ///
 /// Will return `Err` under the following circumstances:
 /// - Argument processing fails (e.g. invalid arguments)
+/// - Building the `CMake` project first fails (see `xtask.cmake.prerequisite`)
 /// - The command process fails to start
 /// - The command invocation fails with non-zero exit status
 pub fn build(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = r#"
-xtask-build
-
-USAGE:
-xtask build
-
-FLAGS:
--h, --help          Prints help information
--- '...'            Extra arguments to pass to the cargo command
-"#
-    .trim();
+    let help = USAGE.render();
 
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
+    let packages = crate::workspace::packages(context.config, context.args, &["cxx-auto"])?;
+    let manifest_path = context.args.opt_value_from_str::<_, String>("--manifest-path")?;
+
     crate::handler::unused(context.args)?;
 
-    let mut cmd = Command::new("cargo");
-    cmd.current_dir(crate::workspace::project_root()?);
+    let cmake_env = crate::command::cmake::prerequisite(&context)?;
+
+    let mut cmd = context.cargo(None);
+    cmd.envs(cmake_env);
+    cmd.current_dir(crate::workspace::project_root(context.config));
     cmd.args(["build"]);
-    cmd.args(["--package", "cxx-auto"]);
+    for package in &packages {
+        cmd.args(["--package", package]);
+    }
+    if let Some(manifest_path) = &manifest_path {
+        cmd.args(["--manifest-path", manifest_path]);
+    }
+    cmd.args(context.target_args());
+    let default_args = context.default_args("build");
+    cmd.args(&default_args);
+    let mut build_args = default_args.iter().copied().map(OsString::from).collect::<Vec<_>>();
+    build_args.extend(context.tool_args.iter().cloned());
+    let mut profile = crate::size::profile_dir(&build_args);
+    // NOTE: cargo puts the artifacts of a `--target` build under a directory named for the target
+    if let Some(preset) = context.selected_target_preset() {
+        profile = format!("{}/{profile}", preset.triple);
+    }
     cmd.args(context.tool_args);
 
-    let status = cmd.status()?;
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
+
+    // NOTE: keep a history of the artifact sizes for `xtask size`
+    if status.success() {
+        let root = &context.config.cargo_metadata.workspace_root;
+        let target_dir = crate::workspace::target_dir(context.config).as_std_path();
+        let record = crate::size::record(context.runner, root, target_dir, &profile)?;
+        crate::size::append_history(target_dir, record)?;
+    }
 
     Ok(Some(status))
 }
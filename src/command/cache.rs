@@ -0,0 +1,250 @@
+use crate::{
+    cache::{Remote, FNV_OFFSET},
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{fmt::Write, process::ExitStatus};
+
+/// The usage of `xtask cache`.
+pub const USAGE: Usage = Usage {
+    name: "cache",
+    summary: "Share the C++ build directories through a remote cache",
+    args: "[SUBCOMMAND]",
+    description: &[
+        "Pushes the directories of `xtask.cache.paths` (by default the CMake build directory `build`, but also e.g. a",
+        "ccache or sccache directory) as a compressed tarball to the remote cache of `xtask.cache.url`, and pulls them",
+        "back, so that CI runners start from the artifacts of an earlier build of the same sources.",
+        "",
+        "The entries are keyed by the host platform, the selected profile and target preset, the cached paths, and",
+        "the contents of the git-tracked files matching `xtask.cache.keyFiles` (all of them by default). A pulled",
+        "entry is extracted over the existing directories.",
+        "",
+        "The `aws` and `gsutil` CLIs are used for `s3://` and `gs://` caches, with their own credentials. For an",
+        "`http(s)://` cache, the token in `XTASK_CACHE_TOKEN` (if set) is sent as a bearer token.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::PROFILE,
+        usage::TARGET_PRESET,
+        Entry {
+            name: "--force",
+            about: &["Push the entry even if the remote cache already has it (push)"],
+        },
+    ],
+    subcommands: &[
+        Entry {
+            name: "key",
+            about: &["Print the key of the cache entry for the current sources"],
+        },
+        Entry {
+            name: "push",
+            about: &["Upload the cached directories, unless the remote cache already has the entry"],
+        },
+        Entry {
+            name: "pull",
+            about: &["Download and extract the cached directories, if the remote cache has the entry"],
+        },
+    ],
+    examples: &[Entry {
+        name: "xtask cache pull && xtask cmake build && xtask cache push",
+        about: &["Reuse and then share the CMake build directory in CI"],
+    }],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - The workspace is not in a git work tree, or a key file cannot be read
+/// - `xtask.cache.url` is missing or invalid, or `--offline` is given (push, pull)
+/// - None of the cached directories exist (push)
+/// - The command process fails to start
+/// - The remote cache cannot be queried, or the entry cannot be uploaded or downloaded
+pub fn cache(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let Some(cache_subcommand) = context.args.opt_free_from_str::<String>()? else {
+        println!("{help}\n");
+        return Ok(None);
+    };
+
+    if !["key", "push", "pull"].contains(&cache_subcommand.as_str()) {
+        println!("{help}\n");
+        return Err(Error::new(
+            Kind::Validation,
+            format!("unrecognized `xtask cache` subcommand `{cache_subcommand}`"),
+        )
+        .into());
+    }
+
+    let force = context.args.contains("--force");
+
+    crate::handler::unused(context.args)?;
+
+    let key = key(&context)?;
+    if cache_subcommand == "key" {
+        println!("{key}");
+        return Ok(None);
+    }
+
+    if context.offline {
+        let message = format!("`xtask cache {cache_subcommand}` accesses the remote cache, so it cannot run offline");
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+    let Some(url) = &context.config.xtask.cache.url else {
+        let message = "no remote cache is configured\nConfigure its URL as `xtask.cache.url`";
+        return Err(Error::new(Kind::Validation, message).into());
+    };
+    let remote = Remote::parse(url)?;
+
+    let name = format!("{key}.tar.gz");
+    let staging = crate::workspace::target_dir(context.config).join("xtask").join("cache");
+    std::fs::create_dir_all(&staging).map_err(|err| format!("failed to create `{staging}`: {err}"))?;
+    let archive = staging.join(&name);
+
+    let status = if cache_subcommand == "push" {
+        push(&context, &remote, &name, &archive, force)?
+    } else {
+        pull(&context, &remote, &name, &archive)?
+    };
+    if archive.exists() {
+        std::fs::remove_file(&archive).map_err(|err| format!("failed to remove `{archive}`: {err}"))?;
+    }
+
+    Ok(status)
+}
+
+// The cached directories, relative to the workspace root.
+fn paths(context: &Context<'_>) -> Vec<Utf8PathBuf> {
+    let paths = &context.config.xtask.cache.paths;
+    if paths.is_empty() {
+        vec![Utf8PathBuf::from("build")]
+    } else {
+        paths.clone()
+    }
+}
+
+// The key of the cache entry for the current sources (see the description of `USAGE`), prefixed with the host
+// platform for readability.
+fn key(context: &Context<'_>) -> BoxResult<String> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let platform = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+    let files = crate::git::tracked_files(context.runner, root)?
+        .ok_or("`xtask cache` keys the entries by the git-tracked files, so it must run in a git work tree")?;
+    let globs = context
+        .config
+        .xtask
+        .cache
+        .key_files
+        .iter()
+        .map(|glob| crate::glob::regex(glob))
+        .collect::<BoxResult<Vec<_>>>()?;
+
+    let mut manifest = String::new();
+    writeln!(manifest, "platform {platform}")?;
+    writeln!(manifest, "profile {}", context.profile.as_deref().unwrap_or_default())?;
+    writeln!(
+        manifest,
+        "target-preset {}",
+        context.target_preset.as_deref().unwrap_or_default()
+    )?;
+    for path in paths(context) {
+        writeln!(manifest, "path {path}")?;
+    }
+    let mut files = files
+        .iter()
+        .filter(|file| file.is_file())
+        .filter_map(|file| Some((file, file.strip_prefix(root).ok()?.as_str().replace('\\', "/"))))
+        .filter(|(_, relative)| globs.is_empty() || globs.iter().any(|glob| glob.is_match(relative)))
+        .collect::<Vec<_>>();
+    files.sort_by(|(_, lhs), (_, rhs)| lhs.cmp(rhs));
+    for (file, relative) in files {
+        let data = std::fs::read(file).map_err(|err| format!("failed to read `{file}`: {err}"))?;
+        writeln!(
+            manifest,
+            "file {relative} {:016x}",
+            crate::cache::fnv1a(FNV_OFFSET, &data)
+        )?;
+    }
+
+    Ok(format!(
+        "{platform}-{:016x}",
+        crate::cache::fnv1a(FNV_OFFSET, manifest.as_bytes())
+    ))
+}
+
+// Archives the cached directories into `archive` and uploads it as the entry `name`.
+fn push(
+    context: &Context<'_>,
+    remote: &Remote,
+    name: &str,
+    archive: &Utf8Path,
+    force: bool,
+) -> BoxResult<Option<ExitStatus>> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let location = remote.location(name);
+    let paths = paths(context)
+        .into_iter()
+        .filter(|path| root.join(path).exists())
+        .collect::<Vec<_>>();
+    if paths.is_empty() {
+        return Err(
+            "none of the cached directories exist, so there is nothing to push\nBuild the project first".into(),
+        );
+    }
+    if !force && remote.contains(context, name)? {
+        crate::handler::status(format_args!("the remote cache already has `{location}`"));
+        return Ok(None);
+    }
+
+    let mut cmd = context.command("tar");
+    cmd.args(["-czf", archive.as_str(), "-C", root.as_str()]);
+    cmd.args(paths.iter().map(|path| path.as_str()));
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
+    if !status.success() {
+        return Ok(Some(status));
+    }
+    remote.upload(context, name, archive)?;
+    crate::handler::status(format_args!(
+        "pushed `{}` to `{location}`",
+        paths.iter().map(|path| path.as_str()).collect::<Vec<_>>().join("`, `")
+    ));
+    Ok(Some(status))
+}
+
+// Downloads the entry `name` into `archive` (if the remote cache has it) and extracts it into the workspace root.
+fn pull(context: &Context<'_>, remote: &Remote, name: &str, archive: &Utf8Path) -> BoxResult<Option<ExitStatus>> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let location = remote.location(name);
+    if !remote.contains(context, name)? {
+        crate::handler::status(format_args!(
+            "the remote cache has no `{location}` yet, so nothing was pulled"
+        ));
+        return Ok(None);
+    }
+    remote.download(context, name, archive)?;
+
+    let mut cmd = context.command("tar");
+    cmd.args(["-xzf", archive.as_str(), "-C", root.as_str()]);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
+    if status.success() {
+        crate::handler::status(format_args!("pulled `{location}`"));
+    }
+    Ok(Some(status))
+}
@@ -1,39 +1,82 @@
-use crate::{command::Context, BoxResult};
-use std::process::{Command, ExitStatus};
+use crate::{
+    command::{
+        usage::{self, Usage},
+        Context,
+    },
+    BoxResult,
+};
+use std::process::ExitStatus;
+
+/// The usage of `xtask check`.
+pub const USAGE: Usage = Usage {
+    name: "check",
+    summary: "Check the project for errors",
+    args: "",
+    description: &[
+        "With `xtask.cmake.prerequisite` configured, the CMake project is built first (configuring it if needed), and",
+        "cargo is given `XTASK_CMAKE_BUILD_DIR` and the configured paths within the build directory (e.g. `CXXLIB_DIR`),",
+        "so that build scripts checking for the C++ libraries they link against find them.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::TARGET_PRESET,
+        usage::HERMETIC,
+        usage::PACKAGE,
+        usage::MANIFEST_PATH,
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[],
+};
 
 /// # Errors
 ///
 /// Will return `Err` under the following circumstances:
 /// - Argument processing fails (e.g. invalid arguments)
+/// - Building the `CMake` project first fails (see `xtask.cmake.prerequisite`)
 /// - The command process fails to start
 /// - The command invocation fails with non-zero exit status
 pub fn check(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = r#"
-xtask-check
-
-USAGE:
-xtask check
-
-FLAGS:
--h, --help          Prints help information
--- '...'            Extra arguments to pass to the cargo command
-"#
-    .trim();
+    let help = USAGE.render();
 
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
+    let packages = crate::workspace::packages(context.config, context.args, &["xtask", "cxx-auto"])?;
+    let manifest_path = context.args.opt_value_from_str::<_, String>("--manifest-path")?;
+
     crate::handler::unused(context.args)?;
 
-    let mut cmd = Command::new("cargo");
-    cmd.current_dir(crate::workspace::project_root()?);
+    let cmake_env = crate::command::cmake::prerequisite(&context)?;
+
+    let mut cmd = context.cargo(None);
+    cmd.envs(cmake_env);
+    cmd.current_dir(crate::workspace::project_root(context.config));
     cmd.args(["check"]);
-    cmd.args(["--package", "xtask"]);
-    cmd.args(["--package", "cxx-auto"]);
+    for package in &packages {
+        cmd.args(["--package", package]);
+    }
+    if let Some(manifest_path) = &manifest_path {
+        cmd.args(["--manifest-path", manifest_path]);
+    }
+    cmd.args(context.target_args());
+    cmd.args(context.default_args("check"));
     cmd.args(context.tool_args);
 
-    let status = cmd.status()?;
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
+
+    crate::status::record(context.config, context.runner, "check", status.success(), None)?;
 
     Ok(Some(status))
 }
@@ -0,0 +1,169 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    BoxResult,
+};
+use std::{ffi::OsString, process::ExitStatus};
+
+/// The usage of `xtask check-all`.
+pub const USAGE: Usage = Usage {
+    name: "check-all",
+    summary: "Run the header check, clippy, and the tests over all targets and features",
+    args: "",
+    description: &[
+        "Runs `xtask headers` (if `xtask.headers.header` is configured), and then `xtask clippy` and `xtask test`, both",
+        "with `--all-targets --all-features`.",
+        "",
+        "The steps can instead be configured as `xtask.checkAll.steps`, each an xtask command line (as for an alias)",
+        "or a table with the command line as `run` and the platforms to run it on (`only`) or skip it on (`skip`), so",
+        "that one configuration runs a sensible subset on every platform. A platform is an OS (e.g. `macos`), an OS",
+        "family (`unix` or `windows`), or an OS and architecture (e.g. `macos-aarch64`):",
+        "",
+        "    [workspace.metadata.xtask.checkAll]",
+        "    steps = [",
+        "        \"clippy --all-targets -- --all-features\",",
+        "        { run = \"valgrind test\", only = [\"linux\"] },",
+        "        { run = \"tarpaulin\", skip = [\"windows\", \"macos-aarch64\"] },",
+        "    ]",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::HERMETIC,
+        Entry {
+            name: "--log-groups <KIND>",
+            about: &[
+                "Fold the output of each step in CI logs (github, buildkite, none)",
+                "[default: detected from the environment]",
+            ],
+        },
+        Entry {
+            name: "-- '...'",
+            about: &["Extra arguments to pass to the cargo commands (only for the default steps)"],
+        },
+    ],
+    subcommands: &[],
+    examples: &[],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments, or extra arguments with configured steps)
+/// - A configured step is empty or does not name a built-in `xtask` subcommand
+/// - The command process fails to start
+/// - The command invocation fails with non-zero exit status
+pub fn check_all(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    crate::handler::unused(context.args)?;
+
+    if !context.config.xtask.check_all.steps.is_empty() {
+        return configured_steps(&context);
+    }
+
+    let mut steps = vec![];
+    if context.config.xtask.headers.header.is_some() {
+        steps.push("headers");
+    }
+    steps.extend(["clippy", "test"]);
+
+    let mut status = None;
+    for name in steps {
+        let step = crate::command::SUBCOMMANDS
+            .iter()
+            .find(|subcommand| subcommand.usage.name == name)
+            .ok_or_else(|| Error::new(Kind::Internal, format!("missing `xtask` subcommand `{name}`")))?;
+        // NOTE: the cargo arguments are only for the cargo steps
+        let (mut args, tool_args) = if name == "headers" {
+            (pico_args::Arguments::from_vec(vec![]), vec![])
+        } else {
+            let mut tool_args = vec![OsString::from("--all-features")];
+            tool_args.extend(context.tool_args.iter().cloned());
+            (pico_args::Arguments::from_vec(vec!["--all-targets".into()]), tool_args)
+        };
+        context.log_groups.start(&format!("xtask {name}"));
+        let result = step.invoke(context.inherit(&mut args, tool_args));
+        context.log_groups.end();
+        status = result?;
+        if let Some(status) = status {
+            if !status.success() {
+                break;
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+// Runs the steps of `xtask.checkAll.steps` that run on the host platform.
+fn configured_steps(context: &Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    if !context.tool_args.is_empty() {
+        let message = "`xtask check-all` does not take extra arguments with `xtask.checkAll.steps` configured";
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+
+    let platform = format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH);
+    let mut status = None;
+    for step in &context.config.xtask.check_all.steps {
+        if !step.runs_on_host() {
+            crate::handler::status(format_args!("skipping `xtask {}` on {platform}", step.run));
+            continue;
+        }
+        status = crate::command::invoke_step(context, "`xtask.checkAll.steps`", &step.run)?;
+        if let Some(status) = status {
+            if !status.success() {
+                break;
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::Context, runner::MockRunner, testing};
+    use std::sync::Mutex;
+
+    #[test]
+    fn clippy_and_test_run_with_all_targets_and_all_features() {
+        let config = testing::config(serde_json::json!({}));
+        let mut args = pico_args::Arguments::from_vec(vec![]);
+        let mut context = Context::new(&config, &mut args, vec!["--features".into(), "extra".into()]);
+        let installed = format!("nightly-{}\n", crate::version::TARGET);
+        let commands = Mutex::new(vec![]);
+        let runner = MockRunner::new(|cmd| {
+            let line = testing::command_line(cmd);
+            if line.ends_with("rustup toolchain list") {
+                return Ok(testing::output(0, &installed));
+            }
+            if line.ends_with(" clippy --version") {
+                return Ok(testing::output(0, "clippy 0.1.73\n"));
+            }
+            commands.lock().unwrap().push(line);
+            Ok(testing::output(0, ""))
+        });
+        context.runner = &runner;
+        super::check_all(context).unwrap();
+        let commands = commands.into_inner().unwrap();
+        assert_eq!(commands.len(), 2);
+        assert!(commands[0].ends_with(
+            " clippy --package xtask --package cxx-auto --all-targets --all-features --features extra -- -D warnings"
+        ));
+        assert!(commands[1].ends_with(" test --package cxx-auto --all-targets --all-features --features extra"));
+    }
+}
@@ -1,66 +1,221 @@
-use crate::{command::Context, BoxResult};
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    cxx_sources::CxxSources,
+    error::{Error, Kind},
+    runner::Runner,
+    validation::Tool,
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
 use std::{
+    collections::BTreeSet,
     ffi::OsString,
+    io::Read,
     process::{Command, ExitStatus},
 };
 
-pub fn help() -> &'static str {
-    let help = r#"
-    xtask-clang
-
-    USAGE:
-    xtask clang [SUBCOMMAND]
-
-    FLAGS:
-    -h, --help          Prints help information
-    -- '...'            Extra arguments to pass to the clang subcommand
-
-    SUBCOMMANDS:
-        format          Run run-clang-format.py on the project's C++ code
-                        Use `-- --help` to see the usage for run-clang-format.py
-        tidy            Run run-clang-tidy      on the project's C++ code
-                        Use `-- --help` to see the usage for run-clang-tidy
-    "#
-    .trim();
-    help
-}
+/// The usage of `xtask clang`.
+pub const USAGE: Usage = Usage {
+    name: "clang",
+    summary: "Run clang-format or clang-tidy on the project's C++ code",
+    args: "[SUBCOMMAND] [PATH]...",
+    description: &[
+        "The files formatted, tidied, or analyzed can be scoped to the C/C++ files tracked by git under the given PATHs",
+        "(relative to the current directory) or matching the `--paths` globs (relative to the workspace root), which",
+        "are passed on to run-clang-format and run-clang-tidy in their own syntax. Scoping combines with `--since`.",
+        "",
+        "Only the C/C++ sources described by the `xtask.cxx.include` and `xtask.cxx.exclude` globs are considered. If",
+        "either is configured, the tools are given the matching git-tracked files even when no scope is given, instead",
+        "of finding the files by their own defaults.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        Entry {
+            name: "-j, --jobs <N>",
+            about: &["Number of parallel clang-format or clang-tidy processes"],
+        },
+        usage::PROFILE,
+        usage::HERMETIC,
+        usage::NO_CAPTURE,
+        Entry {
+            name: "--log-groups <KIND>",
+            about: &[
+                "Fold the output of each step of tidy in CI logs (github, buildkite, none)",
+                "[default: detected from the environment]",
+            ],
+        },
+        Entry {
+            name: "--since <REF>",
+            about: &["Only format, tidy, or analyze the C/C++ files changed since the git ref"],
+        },
+        Entry {
+            name: "--paths <GLOB>",
+            about: &[
+                "Only format, tidy, or analyze the C/C++ files matching GLOB (e.g. 'src/**/*.cc'), where `*`",
+                "matches within a directory and `**` across directories (can be given multiple times)",
+            ],
+        },
+        Entry {
+            name: "--staged",
+            about: &[
+                "Only format (or check) the C/C++ files staged in git, as staged rather than as in the work tree",
+                "(format), for pre-commit hooks. clang-format is run directly, with the applicable run-clang-format",
+                "options of `xtask.clang.extraArgs.format` and the extra arguments",
+            ],
+        },
+        Entry {
+            name: "--ignore-build-errors",
+            about: &["Run tidy even if the prerequisite `cargo check` fails"],
+        },
+        Entry {
+            name: "--format-diff-only",
+            about: &[
+                "Check formatting without rewriting files and print a summary (for CI)",
+                "Exits with 0 if no files need formatting, 5 if some do, and 1 on errors",
+            ],
+        },
+        Entry {
+            name: "--check",
+            about: &["Same as `--format-diff-only`, as for `xtask fmt --check`"],
+        },
+        Entry {
+            name: "--max-findings <N>",
+            about: &[
+                "Fail analyze if there are more than N new findings (those not in `xtask.clang.analyze.baseline`)",
+                "[default: `xtask.clang.analyze.maxFindings`, else 0]",
+            ],
+        },
+        Entry {
+            name: "--update-baseline",
+            about: &["Write the findings of analyze to `xtask.clang.analyze.baseline`, accepting them"],
+        },
+        Entry {
+            name: "-- '...'",
+            about: &["Extra arguments to pass to the clang subcommand"],
+        },
+    ],
+    subcommands: &[
+        Entry {
+            name: "format",
+            about: &[
+                "Run run-clang-format.py on the project's C++ code",
+                "Use `-- --help` to see the usage for run-clang-format.py",
+            ],
+        },
+        Entry {
+            name: "tidy",
+            about: &[
+                "Run run-clang-tidy      on the project's C++ code",
+                "Use `-- --help` to see the usage for run-clang-tidy",
+                "Without run-clang-tidy, clang-tidy is run in parallel on each file of",
+                "`build/compile_commands.json`, and extra arguments are passed to clang-tidy",
+            ],
+        },
+        Entry {
+            name: "analyze",
+            about: &[
+                "Run the clang static analyzer on each file of `build/compile_commands.json`",
+                "HTML reports are written to `xtask.clang.analyze.outputDir` [default: target/xtask/analyze]",
+                "Findings listed in `xtask.clang.analyze.baseline` are not counted as new",
+            ],
+        },
+    ],
+    examples: &[
+        Entry {
+            name: "xtask clang tidy src/parser",
+            about: &["Tidy the C/C++ files under `src/parser`"],
+        },
+        Entry {
+            name: "xtask clang format --check --paths 'include/**/*.h'",
+            about: &["Check the formatting of the public headers"],
+        },
+    ],
+};
 
 /// # Errors
 ///
 /// Will return `Err` under the following circumstances:
-/// - Argument processing fails (e.g. invalid arguments)
+/// - Argument processing fails (e.g. invalid arguments), or a glob of `xtask.cxx` is malformed
 /// - Tool validation fails (missing tools, incorrect versions, etc.)
 /// - The command process fails to start
 /// - The command invocation fails with non-zero exit status
 pub fn clang(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = help();
+    let help = USAGE.render();
 
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
-    let clang_subcommand: String = context.subcommand.ok_or("expected a subcommand for `xtask clang`")?;
+    // NOTE: the subcommand is taken from `args` unless the caller already did (e.g. `xtask clang format` in an alias)
+    let clang_subcommand = match context.subcommand.clone() {
+        Some(clang_subcommand) => clang_subcommand,
+        None => context
+            .args
+            .opt_free_from_str::<String>()?
+            .ok_or("expected a subcommand for `xtask clang`")?,
+    };
+
+    let since = context.args.opt_value_from_str::<_, String>("--since")?;
+    let staged = context.args.contains("--staged");
+    let ignore_build_errors = context.args.contains("--ignore-build-errors");
+    let check = context.args.contains("--check");
+    let format_diff_only = context.args.contains("--format-diff-only") || check;
+    let max_findings = context.args.opt_value_from_str::<_, usize>("--max-findings")?;
+    let update_baseline = context.args.contains("--update-baseline");
+    let globs = context.args.values_from_str::<_, String>("--paths")?;
+    let paths = free_paths(context.args)?;
 
     crate::handler::unused(context.args)?;
 
-    let status = match &*clang_subcommand {
-        "format" => {
-            let run_clang_format_tool = &context.config.cmake_context.bin_run_clang_format;
-            let mut cmd = Command::new(run_clang_format_tool);
-            if !context.tool_args.contains(&OsString::from("--clang-format-executable")) {
-                let clang_format_tool = context.config.cmake_context.bin_clang_format.as_str();
-                cmd.args(["--clang-format-executable", clang_format_tool]);
-            }
-            cmd.args(context.tool_args);
-            cmd.status()?
+    let sources = CxxSources::new(context.config)?;
+
+    if staged {
+        if clang_subcommand != "format" || since.is_some() || !paths.is_empty() || !globs.is_empty() {
+            let message = "`--staged` is only supported by `xtask clang format`, without `--since` or paths";
+            return Err(Error::new(Kind::Validation, message).into());
+        }
+        return format_staged(&context, &sources, format_diff_only).map(Some);
+    }
+
+    // NOTE: `None` means all files, either because neither `--since` nor paths were given or because this is not a git
+    // checkout
+    let changed_files = match &since {
+        Some(reference) => {
+            let root = &context.config.cargo_metadata.workspace_root;
+            crate::git::changed_files(context.runner, root, reference)?.map(|files| sources.filter(files))
         },
+        None => None,
+    };
+    if let (Some(reference), Some(files)) = (&since, &changed_files) {
+        if files.is_empty() {
+            crate::handler::status(format_args!("no C/C++ files changed since `{reference}`"));
+            return Ok(None);
+        }
+    }
+    let changed_files = if paths.is_empty() && globs.is_empty() {
+        changed_files
+    } else {
+        let files = scoped_files(&context, &sources, changed_files, &paths, &globs)?;
+        if files.is_empty() {
+            crate::handler::status(format_args!("no C/C++ files match the given paths"));
+            return Ok(None);
+        }
+        Some(files)
+    };
+    let changed_files = source_files(&context, &sources, changed_files)?;
+    if changed_files.as_ref().map_or(false, Vec::is_empty) {
+        crate::handler::status(format_args!("no C/C++ files match `xtask.cxx`"));
+        return Ok(None);
+    }
+
+    let status = match clang_subcommand.as_str() {
+        "format" => format(&context, changed_files.as_deref(), format_diff_only)?,
         "tidy" => {
-            {
-                let mut cmd = Command::new("cargo");
-                cmd.args(["check"]);
-                let status = cmd.status()?;
-                crate::handler::subcommand_result("cargo check", Ok(Some(status)));
-            }
+            cargo_check(&context, ignore_build_errors)?;
             // {
             //     let config = context.config;
             //     let mut args = pico_args::Arguments::from_vec(vec!["build".into()]);
@@ -69,20 +224,754 @@ pub fn clang(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
             //     let result = crate::command::cmake(context);
             //     crate::handler::subcommand_result("cmake", result);
             // }
-            let run_clang_tidy_tool = &context.config.cmake_context.bin_run_clang_tidy;
-            let mut cmd = Command::new(run_clang_tidy_tool);
+            let run_clang_tidy_tool = context.validation().path(Tool::RunClangTidy);
+            // NOTE: many minimal LLVM installs ship `clang-tidy` without the `run-clang-tidy` script
+            if !run_clang_tidy_tool.is_file() {
+                crate::handler::status(format_args!(
+                    "`run-clang-tidy` not found, so running `clang-tidy` over the compilation database directly"
+                ));
+                return tidy_without_script(&context, changed_files.as_deref()).map(Some);
+            }
+            let mut cmd = script_command(&context, &run_clang_tidy_tool)?;
             if !context.tool_args.contains(&OsString::from("-clang-tidy-binary")) {
-                let clang_tidy_tool = context.config.cmake_context.bin_clang_tidy.as_str();
-                cmd.args(["-clang-tidy-binary", clang_tidy_tool]);
+                let clang_tidy_tool = context.validation().path(Tool::ClangTidy);
+                cmd.args(["-clang-tidy-binary", clang_tidy_tool.as_str()]);
+            }
+            if let Some(jobs) = context.jobs {
+                cmd.args(["-j", &jobs.to_string()]);
             }
+            cmd.args(&context.config.xtask.clang.extra_args.tidy);
             cmd.args(context.tool_args);
-            cmd.status()?
+            if let Some(files) = &changed_files {
+                // NOTE: run-clang-tidy treats its positional arguments as regexes over the compilation database
+                cmd.args(files.iter().map(|file| regex::escape(file.as_str())));
+            }
+            context.log_groups.start("run-clang-tidy");
+            if context.print_env {
+                crate::handler::print_env(&cmd);
+            }
+            let status = context.runner.status(&mut cmd);
+            context.log_groups.end();
+            lint_status(status?)?
         },
+        "analyze" => analyze(&context, changed_files.as_deref(), max_findings, update_baseline)?,
         _ => {
             println!("{help}\n");
-            return Err(format!("unrecognized `xtask clang` subcommand `{clang_subcommand}`").into());
+            return Err(Error::new(
+                Kind::Validation,
+                format!("unrecognized `xtask clang` subcommand `{clang_subcommand}`"),
+            )
+            .into());
         },
     };
 
     Ok(Some(status))
 }
+
+// The arguments left in `args` after the flags, which are paths, unless a flag is among them (which is then left to be
+// reported as unrecognized).
+fn free_paths(args: &mut pico_args::Arguments) -> BoxResult<Vec<Utf8PathBuf>> {
+    let mut paths = vec![];
+    if args
+        .clone()
+        .finish()
+        .iter()
+        .all(|arg| !arg.to_string_lossy().starts_with('-'))
+    {
+        while let Some(path) = args.opt_free_from_str::<Utf8PathBuf>()? {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+// The files of `files`, or else the sources tracked by git if `xtask.cxx` describes them (so that the tools do not
+// find the files by their own defaults).
+fn source_files(
+    context: &Context<'_>,
+    sources: &CxxSources,
+    files: Option<Vec<Utf8PathBuf>>,
+) -> BoxResult<Option<Vec<Utf8PathBuf>>> {
+    match files {
+        None if sources.is_configured() => {
+            let files = sources
+                .tracked(context.runner)?
+                .ok_or("`xtask.cxx` selects git-tracked files, so `xtask clang` must run in a git work tree")?;
+            Ok(Some(files))
+        },
+        files => Ok(files),
+    }
+}
+
+// The C/C++ files of `files` (or else the sources tracked by git) that are under one of `paths` or match one of
+// `globs`.
+fn scoped_files(
+    context: &Context<'_>,
+    sources: &CxxSources,
+    files: Option<Vec<Utf8PathBuf>>,
+    paths: &[Utf8PathBuf],
+    globs: &[String],
+) -> BoxResult<Vec<Utf8PathBuf>> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let files = match files {
+        Some(files) => files,
+        None => sources
+            .tracked(context.runner)?
+            .ok_or("scoping `xtask clang` to paths requires the workspace to be in a git work tree")?,
+    };
+    let current_dir = Utf8PathBuf::try_from(std::env::current_dir()?)?;
+    let paths = paths
+        .iter()
+        .map(|path| {
+            current_dir.join(path).canonicalize_utf8().map_err(|err| {
+                let message = format!("cannot scope `xtask clang` to `{path}`: {err}");
+                Error::new(Kind::Validation, message).into()
+            })
+        })
+        .collect::<BoxResult<Vec<_>>>()?;
+    let globs = globs
+        .iter()
+        .map(|glob| crate::glob::regex(glob))
+        .collect::<BoxResult<Vec<_>>>()?;
+    let canonical_root = root.canonicalize_utf8().unwrap_or_else(|_| root.clone());
+    Ok(files
+        .into_iter()
+        .filter(|file| {
+            let file = file.canonicalize_utf8().unwrap_or_else(|_| file.clone());
+            let relative = file
+                .strip_prefix(&canonical_root)
+                .map(|relative| relative.as_str().replace('\\', "/"));
+            paths.iter().any(|path| file.starts_with(path))
+                || relative.map_or(false, |relative| globs.iter().any(|glob| glob.is_match(&relative)))
+        })
+        .collect())
+}
+
+// Run `cargo check` before tidy, since the C++ code includes the headers generated by the build scripts.
+fn cargo_check(context: &Context<'_>, ignore_build_errors: bool) -> BoxResult<()> {
+    let mut cmd = context.cargo(None);
+    cmd.args(["check"]);
+    context.log_groups.start("cargo check");
+    let status = context.runner.status(&mut cmd);
+    context.log_groups.end();
+    let status = status?;
+    if !status.success() && !ignore_build_errors {
+        return Err(
+            "prerequisite `cargo check` failed, so `clang-tidy` was not run\nPass `--ignore-build-errors` to run \
+                    it anyway"
+                .into(),
+        );
+    }
+    Ok(())
+}
+
+// Run `clang-tidy` on each translation unit of `build/compile_commands.json` (or the subset of them in `files`), as
+// run-clang-tidy would.
+fn tidy_without_script(context: &Context<'_>, files: Option<&[Utf8PathBuf]>) -> BoxResult<ExitStatus> {
+    let build_dir = context.config.cargo_metadata.workspace_root.join("build");
+    let mut entries = crate::compile_commands::load(&build_dir)?;
+    if let Some(files) = files {
+        crate::compile_commands::retain_files(&mut entries, files);
+    }
+    let units = crate::compile_commands::files(&entries);
+    if units.is_empty() {
+        crate::handler::status(format_args!("no files of the compilation database to tidy"));
+        return Ok(crate::runner::exit_status(0));
+    }
+
+    let clang_tidy_tool = context.validation().path(Tool::ClangTidy);
+    let commands = units
+        .iter()
+        .map(|unit| {
+            let mut cmd = context.command(&clang_tidy_tool);
+            cmd.args(["-p", build_dir.as_str()]);
+            cmd.args(&context.config.xtask.clang.extra_args.tidy);
+            cmd.args(&context.tool_args);
+            cmd.arg(unit);
+            (label(context, unit), cmd)
+        })
+        .collect::<Vec<_>>();
+
+    context.log_groups.start("clang-tidy");
+    let failures = crate::runner::run_parallel(
+        context.runner,
+        commands,
+        jobs(context),
+        context.parallel_output(),
+        context.print_env,
+    );
+    context.log_groups.end();
+    let failures = failures?;
+    crate::handler::status(format_args!("clang-tidy: {failures} of {} files failed", units.len()));
+    lint_status(crate::runner::exit_status(i32::from(failures > 0)))
+}
+
+// Report the failure of a clang-tidy run as lint failures (see `Kind::Lint`).
+fn lint_status(status: ExitStatus) -> BoxResult<ExitStatus> {
+    if status.success() {
+        Ok(status)
+    } else {
+        Err(Error::new(Kind::Lint, "clang-tidy reported lint failures").into())
+    }
+}
+
+// Run the clang static analyzer on each entry of `build/compile_commands.json` (or those for `files`), writing an HTML
+// report for each finding to the output directory, and gating on the findings missing from the baseline.
+//
+// NOTE: the reports of a previous run are removed first, so that the findings counted are those of this run; only the
+// `report-*.html` files are removed, since the output directory is configurable and may hold other files
+fn analyze(
+    context: &Context<'_>,
+    files: Option<&[Utf8PathBuf]>,
+    max_findings: Option<usize>,
+    update_baseline: bool,
+) -> BoxResult<ExitStatus> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let settings = &context.config.xtask.clang.analyze;
+    let max_findings = max_findings.or(settings.max_findings).unwrap_or(0);
+    let output_dir = match &settings.output_dir {
+        Some(output_dir) => root.join(output_dir),
+        None => crate::workspace::target_dir(context.config)
+            .to_owned()
+            .join("xtask")
+            .join("analyze"),
+    };
+    let baseline = settings.baseline.as_ref().map(|baseline| root.join(baseline));
+    if update_baseline && baseline.is_none() {
+        let message = "no baseline is configured for `--update-baseline`\nSet `xtask.clang.analyze.baseline` (e.g. to \
+                       `.clang-analyze-baseline`)";
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+    std::fs::create_dir_all(&output_dir).map_err(|err| format!("failed to create `{output_dir}`: {err}"))?;
+    for report in reports(&output_dir)? {
+        std::fs::remove_file(&report).map_err(|err| format!("failed to remove `{report}`: {err}"))?;
+    }
+
+    let mut entries = crate::compile_commands::load(&root.join("build"))?;
+    if let Some(files) = files {
+        crate::compile_commands::retain_files(&mut entries, files);
+    }
+    if entries.is_empty() {
+        crate::handler::status(format_args!("no files of the compilation database to analyze"));
+        return Ok(crate::runner::exit_status(0));
+    }
+
+    let analyzer_tool = context.validation().path(Tool::ClangDriver);
+    let commands = entries
+        .iter()
+        .map(|entry| {
+            let mut cmd = context.command(&analyzer_tool);
+            cmd.current_dir(&entry.directory);
+            cmd.args(analyzer_args(&entry.arguments));
+            cmd.args(["--analyze", "--analyzer-output", "html", "-o", output_dir.as_str()]);
+            cmd.args(&context.config.xtask.clang.extra_args.analyze);
+            cmd.args(&context.tool_args);
+            (label(context, &entry.directory.join(&entry.file)), cmd)
+        })
+        .collect::<Vec<_>>();
+
+    context.log_groups.start("clang --analyze");
+    let failures = crate::runner::run_parallel(
+        context.runner,
+        commands,
+        jobs(context),
+        context.parallel_output(),
+        context.print_env,
+    );
+    context.log_groups.end();
+    let failures = failures?;
+
+    let mut findings = BTreeSet::new();
+    for report in reports(&output_dir)? {
+        let contents = std::fs::read_to_string(&report).map_err(|err| format!("failed to read `{report}`: {err}"))?;
+        findings.insert(finding(root, &contents));
+    }
+    crate::handler::status(format_args!(
+        "clang analyzer: {} findings in {} files (reports in `{output_dir}`)",
+        findings.len(),
+        entries.len()
+    ));
+    if failures > 0 {
+        println!(
+            "clang analyzer: {failures} of {} files failed to compile",
+            entries.len()
+        );
+        return Ok(crate::runner::exit_status(1));
+    }
+
+    if let (true, Some(baseline)) = (update_baseline, &baseline) {
+        let contents = findings
+            .iter()
+            .map(|finding| finding.clone() + "\n")
+            .collect::<Vec<_>>()
+            .concat();
+        std::fs::write(baseline, contents).map_err(|err| format!("failed to write `{baseline}`: {err}"))?;
+        crate::handler::status(format_args!(
+            "clang analyzer: wrote {} findings to `{baseline}`",
+            findings.len()
+        ));
+        return Ok(crate::runner::exit_status(0));
+    }
+    let known = match &baseline {
+        Some(baseline) => read_baseline(baseline)?,
+        None => BTreeSet::new(),
+    };
+    let new = findings.difference(&known).collect::<Vec<_>>();
+    if baseline.is_some() {
+        for finding in &new {
+            println!("new finding: {finding}");
+        }
+        crate::handler::status(format_args!("clang analyzer: {} new findings", new.len()));
+    }
+    if new.len() > max_findings {
+        let message = format!("clang analyzer: more new findings than the {max_findings} allowed");
+        return Err(Error::new(Kind::Lint, message).into());
+    }
+    Ok(crate::runner::exit_status(0))
+}
+
+// The findings accepted in the baseline file `baseline` (see `finding`), one per line, or none if it does not exist.
+fn read_baseline(baseline: &Utf8Path) -> BoxResult<BTreeSet<String>> {
+    match std::fs::read_to_string(baseline) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_owned)
+            .collect()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeSet::new()),
+        Err(err) => Err(format!("failed to read `{baseline}`: {err}").into()),
+    }
+}
+
+// The HTML reports of the analyzer in `output_dir`.
+fn reports(output_dir: &Utf8Path) -> BoxResult<Vec<Utf8PathBuf>> {
+    Ok(output_dir
+        .read_dir_utf8()
+        .map_err(|err| format!("failed to read `{output_dir}`: {err}"))?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().starts_with("report-") && entry.path().extension() == Some("html"))
+        .map(|entry| entry.path().to_owned())
+        .collect())
+}
+
+// The finding of an analyzer report, identified by its file (relative to `root`), bug type, and issue hash, which
+// unlike its line number is stable across unrelated edits: `<file>: <bug type> [<issue hash>]`.
+//
+// The analyzer writes these as `<!-- BUGFILE ... -->` (and similar) comments at the top of each report.
+fn finding(root: &Utf8Path, report: &str) -> String {
+    let field = |name: &str| {
+        report
+            .lines()
+            .find_map(|line| {
+                line.trim()
+                    .strip_prefix("<!-- ")?
+                    .strip_prefix(name)?
+                    .strip_prefix(' ')?
+                    .strip_suffix("-->")
+            })
+            .map(str::trim)
+            .unwrap_or_default()
+    };
+    let file = Utf8Path::new(field("BUGFILE"));
+    let file = file.strip_prefix(root).unwrap_or(file);
+    let hash = match field("ISSUEHASHCONTENTOFLINEINCONTEXT") {
+        "" => field("BUGLINE"),
+        hash => hash,
+    };
+    format!("{file}: {} [{hash}]", field("BUGTYPE"))
+}
+
+// The arguments of a compilation database entry to pass on to the analyzer: those after the compiler, without the
+// ones producing the object and dependency files.
+fn analyzer_args(arguments: &[String]) -> Vec<&str> {
+    let mut args = vec![];
+    let mut arguments = arguments.iter().skip(1).map(String::as_str);
+    while let Some(arg) = arguments.next() {
+        match arg {
+            "-c" | "-MD" | "-MMD" | "-MP" => {},
+            "-o" | "-MF" | "-MT" | "-MQ" => {
+                arguments.next();
+            },
+            _ => args.push(arg),
+        }
+    }
+    args
+}
+
+// The label of the output of the process for `file`: its path relative to the workspace root, if it is within it.
+fn label(context: &Context<'_>, file: &Utf8Path) -> String {
+    let root = &context.config.cargo_metadata.workspace_root;
+    file.strip_prefix(root).unwrap_or(file).to_string()
+}
+
+// The number of parallel processes: `jobs`, or else the number of available cores.
+fn jobs(context: &Context<'_>) -> usize {
+    context
+        .jobs
+        .and_then(|jobs| usize::try_from(jobs).ok())
+        .or_else(|| std::thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1)
+}
+
+// Run run-clang-format.py in its default check mode, mapping "diffs present" to an error of `Kind::Format`.
+//
+// The script exits with 1 when there are diffs and 2 on trouble (e.g. a file failed to parse), and prints a
+// `--- <file>\t(original)` header for each file that needs formatting.
+fn format_diff_only_status(runner: &dyn Runner, cmd: &mut Command) -> BoxResult<ExitStatus> {
+    let output = runner.output(cmd)?;
+    std::io::Write::write_all(&mut std::io::stdout(), &output.stdout)?;
+    std::io::Write::write_all(&mut std::io::stderr(), &output.stderr)?;
+    let files = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| line.starts_with("--- ") && line.ends_with("(original)"))
+        .count();
+    match output.status.code() {
+        Some(0) => {
+            crate::handler::status(format_args!("clang-format: 0 files need formatting"));
+            Ok(output.status)
+        },
+        Some(1) => {
+            let message = format!("clang-format: {files} files need formatting");
+            Err(Error::new(Kind::Format, message).into())
+        },
+        _ => Err("run-clang-format failed, so formatting could not be checked".into()),
+    }
+}
+
+// Run run-clang-format.py on the project's C++ code (or the subset of it in `changed_files`).
+fn format(
+    context: &Context<'_>,
+    changed_files: Option<&[Utf8PathBuf]>,
+    format_diff_only: bool,
+) -> BoxResult<ExitStatus> {
+    let run_clang_format_tool = context.validation().path(Tool::RunClangFormat);
+    let mut cmd = script_command(context, &run_clang_format_tool)?;
+    if format_diff_only && context.tool_args.iter().any(|arg| arg == "-i" || arg == "--in-place") {
+        return Err("`--format-diff-only` cannot be combined with `-i`/`--in-place`".into());
+    }
+    if !context.tool_args.contains(&OsString::from("--clang-format-executable")) {
+        let clang_format_tool = context.validation().tool(Tool::ClangFormat)?.path;
+        cmd.args(["--clang-format-executable", clang_format_tool.as_str()]);
+    }
+    if let Some(jobs) = context.jobs {
+        cmd.args(["-j", &jobs.to_string()]);
+    }
+    cmd.args(&context.config.xtask.clang.extra_args.format);
+    cmd.args(&context.tool_args);
+    if let Some(files) = changed_files {
+        cmd.args(files);
+    }
+    if format_diff_only {
+        context.config.xtask.c_locale(&mut cmd);
+    }
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    if format_diff_only {
+        return format_diff_only_status(context.runner, &mut cmd);
+    }
+    Ok(context.runner.status(&mut cmd)?)
+}
+
+// The arguments of `xtask clang format --staged`, from the run-clang-format.py arguments of
+// `xtask.clang.extraArgs.format` followed by the extra arguments.
+//
+// NOTE: `--staged` runs clang-format directly, so the options of run-clang-format.py selecting the executable and the
+// files are applied here, `--style` is passed on as clang-format's own, and those only affecting how the script runs
+// are dropped; any other argument is passed on to clang-format
+#[derive(Default)]
+struct StagedArgs {
+    clang_format: Option<String>,
+    extensions: Option<Vec<String>>,
+    exclude: Vec<String>,
+    args: Vec<OsString>,
+}
+
+impl StagedArgs {
+    fn parse(args: impl IntoIterator<Item = OsString>) -> BoxResult<Self> {
+        let mut staged = StagedArgs::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let Some(text) = arg.to_str() else {
+                staged.args.push(arg);
+                continue;
+            };
+            let (name, inline) = match text.split_once('=') {
+                Some((name, value)) if name.starts_with('-') => (name, Some(value.to_owned())),
+                _ => (text, None),
+            };
+            let mut value = || -> BoxResult<String> {
+                match inline.clone() {
+                    Some(value) => Ok(value),
+                    None => args
+                        .next()
+                        .map(|value| value.to_string_lossy().into_owned())
+                        .ok_or_else(|| format!("`{name}` requires a value").into()),
+                }
+            };
+            match name {
+                "--clang-format-executable" => staged.clang_format = Some(value()?),
+                "--extensions" => {
+                    let extensions = value()?.split(',').map(|ext| ext.trim().to_owned()).collect();
+                    staged.extensions = Some(extensions);
+                },
+                "-e" | "--exclude" => staged.exclude.push(value()?),
+                "--style" => staged.args.push(format!("--style={}", value()?).into()),
+                "-j" | "--color" => {
+                    value()?;
+                },
+                "-r" | "--recursive" | "-d" | "--dry-run" | "-i" | "--in-place" | "-q" | "--quiet" => {},
+                _ => staged.args.push(arg),
+            }
+        }
+        Ok(staged)
+    }
+
+    // Whether `file` (relative to the workspace root) is selected by `--extensions` and `--exclude`.
+    fn selects(&self, file: &Utf8Path) -> BoxResult<bool> {
+        let extension = file.extension().unwrap_or_default();
+        if let Some(extensions) = &self.extensions {
+            if !extensions.iter().any(|ext| ext == extension) {
+                return Ok(false);
+            }
+        }
+        let relative = file.as_str().replace('\\', "/");
+        for glob in &self.exclude {
+            if crate::glob::regex(glob)?.is_match(&relative) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+// Format (or with `check`, check the formatting of) the C/C++ files staged in git, as staged.
+//
+// The staged contents are written to temporary files and passed to clang-format on stdin, with `--assume-filename`
+// naming the file so that its `.clang-format` applies. Formatting writes back to files whose work tree contents match
+// the staged ones, and stages them again; a file with unstaged changes is left for the user to format.
+fn format_staged(context: &Context<'_>, sources: &CxxSources, check: bool) -> BoxResult<ExitStatus> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let files = sources.filter(
+        crate::git::staged_files(context.runner, root)?
+            .ok_or("`--staged` requires the project to be in a git work tree")?,
+    );
+    let staged = StagedArgs::parse(
+        context
+            .config
+            .xtask
+            .clang
+            .extra_args
+            .format
+            .iter()
+            .map(OsString::from)
+            .chain(context.tool_args.iter().cloned()),
+    )?;
+    let mut selected = vec![];
+    for file in files {
+        if staged.selects(file.strip_prefix(root).unwrap_or(&file))? {
+            selected.push(file);
+        }
+    }
+    let files = selected;
+    if files.is_empty() {
+        crate::handler::status(format_args!("no C/C++ files are staged"));
+        return Ok(crate::runner::exit_status(0));
+    }
+    let clang_format_tool = match &staged.clang_format {
+        Some(clang_format) => Utf8PathBuf::from(clang_format),
+        None => context.validation().tool(Tool::ClangFormat)?.path,
+    };
+
+    let temp_dir = crate::workspace::target_dir(context.config)
+        .to_owned()
+        .join("xtask/staged");
+    std::fs::create_dir_all(&temp_dir).map_err(|err| format!("failed to create `{temp_dir}`: {err}"))?;
+    let mut unformatted = vec![];
+    let mut unstaged = vec![];
+    let mut formatted = vec![];
+    for (i, file) in files.iter().enumerate() {
+        let contents = crate::git::staged_contents(context.runner, root, file)?;
+        let temp = temp_dir.join(format!("{i}.{}", file.extension().unwrap_or_default()));
+        std::fs::write(&temp, &contents).map_err(|err| format!("failed to write `{temp}`: {err}"))?;
+        let mut cmd = context.command(clang_format_tool.as_str());
+        cmd.arg(format!("--assume-filename={file}"));
+        cmd.args(&staged.args);
+        cmd.stdin(std::fs::File::open(&temp)?);
+        if context.print_env {
+            crate::handler::print_env(&cmd);
+        }
+        let output = context.runner.output(&mut cmd)?;
+        if !output.status.success() {
+            std::io::Write::write_all(&mut std::io::stderr(), &output.stderr)?;
+            return Err(format!("clang-format failed on the staged `{file}`").into());
+        }
+        if output.stdout == contents {
+            continue;
+        }
+        let relative = file.strip_prefix(root).unwrap_or(file).to_owned();
+        if check {
+            unformatted.push(relative);
+        } else if std::fs::read(file).map_or(false, |current| current == contents) {
+            std::fs::write(file, &output.stdout).map_err(|err| format!("failed to write `{file}`: {err}"))?;
+            formatted.push(file.clone());
+        } else {
+            unstaged.push(relative);
+        }
+    }
+    std::fs::remove_dir_all(&temp_dir).ok();
+
+    if check {
+        for file in &unformatted {
+            println!("{file} needs formatting");
+        }
+        if !unformatted.is_empty() {
+            let message = format!("clang-format: {} files need formatting", unformatted.len());
+            return Err(Error::new(Kind::Format, message).into());
+        }
+        crate::handler::status("clang-format: 0 files need formatting");
+        return Ok(crate::runner::exit_status(0));
+    }
+    if !formatted.is_empty() {
+        crate::git::add(context.runner, root, &formatted)?;
+    }
+    crate::handler::status(format_args!(
+        "clang-format: formatted and restaged {} files",
+        formatted.len()
+    ));
+    if !unstaged.is_empty() {
+        let files = unstaged
+            .iter()
+            .map(|file| format!("`{file}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Err(format!(
+            "staged files need formatting but have unstaged changes: {files}\nStage or stash the changes, and run \
+             `xtask clang format --staged` again"
+        )
+        .into());
+    }
+    Ok(crate::runner::exit_status(0))
+}
+
+// Create a command running `script`, through the detected Python interpreter if it is a Python script.
+//
+// Depending on the distribution, the clang wrapper scripts may lack the execute bit, lack a `.py` extension, or have a
+// shebang naming an interpreter that does not exist (e.g., `python` rather than `python3`).
+fn script_command(context: &Context<'_>, script: &Utf8Path) -> BoxResult<Command> {
+    let is_python = script.extension() == Some("py") || {
+        let mut head = vec![];
+        let file = std::fs::File::open(script).map_err(|err| format!("failed to open `{script}`: {err}"))?;
+        file.take(128).read_to_end(&mut head)?;
+        let shebang = head.split(|&byte| byte == b'\n').next().unwrap_or_default();
+        shebang.starts_with(b"#!") && String::from_utf8_lossy(shebang).contains("python")
+    };
+    if is_python {
+        let (python, python_args) = crate::detection::detect_python(context)?;
+        let mut cmd = context.command(python);
+        cmd.args(python_args);
+        cmd.arg(script);
+        Ok(cmd)
+    } else {
+        Ok(context.command(script))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::Context, runner::MockRunner, testing};
+    use camino::Utf8Path;
+    use std::sync::Mutex;
+
+    #[test]
+    fn tidy_is_not_run_if_the_prerequisite_build_fails() {
+        let config = testing::config(serde_json::json!({}));
+        let mut args = pico_args::Arguments::from_vec(vec!["tidy".into()]);
+        let mut context = Context::new(&config, &mut args, vec![]);
+        let commands = Mutex::new(vec![]);
+        let runner = MockRunner::new(|cmd| {
+            commands.lock().unwrap().push(testing::command_line(cmd));
+            Ok(testing::output(101, ""))
+        });
+        context.runner = &runner;
+        let err = super::clang(context).unwrap_err();
+        assert!(err.to_string().starts_with("prerequisite `cargo check` failed"));
+        let commands = commands.into_inner().unwrap();
+        assert_eq!(commands.len(), 1);
+        assert!(commands[0].ends_with("cargo check"));
+    }
+
+    #[test]
+    fn configured_format_args_precede_the_extra_arguments() {
+        let script = camino::Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join("xtask-test-run-clang-format");
+        std::fs::write(&script, "#!/bin/sh\n").unwrap();
+        let mut config = testing::config(serde_json::json!({
+            "clang": { "extraArgs": { "format": ["--extensions=cc,h"] } }
+        }));
+        config.cmake_context.bin_run_clang_format = script.clone();
+        let mut args = pico_args::Arguments::from_vec(vec![]);
+        let mut context = Context::new(&config, &mut args, vec!["--style=file".into()]);
+        let command = Mutex::new(None);
+        let runner = MockRunner::new(|cmd| {
+            *command.lock().unwrap() = Some(testing::command_line(cmd));
+            Ok(testing::output(0, ""))
+        });
+        context.runner = &runner;
+        let result = super::format(&context, None, false);
+        std::fs::remove_file(&script).ok();
+        result.unwrap();
+        assert_eq!(
+            command.into_inner().unwrap().unwrap(),
+            format!("{script} --clang-format-executable clang-format --extensions=cc,h --style=file")
+        );
+    }
+
+    #[test]
+    fn finding_is_keyed_by_file_bug_type_and_issue_hash() {
+        let report = "<!-- BUGTYPE Dereference of null pointer -->\n<!-- BUGFILE /work/cxx/a.cc -->\n<!-- BUGLINE 12 \
+                      -->\n<!-- ISSUEHASHCONTENTOFLINEINCONTEXT 0123abcd -->\n<html></html>\n";
+        assert_eq!(
+            super::finding(Utf8Path::new("/work"), report),
+            "cxx/a.cc: Dereference of null pointer [0123abcd]"
+        );
+    }
+
+    #[test]
+    fn finding_falls_back_to_the_line_without_an_issue_hash() {
+        let report = "<!-- BUGTYPE Dead store -->\n<!-- BUGFILE /elsewhere/b.cc -->\n<!-- BUGLINE 7 -->\n";
+        assert_eq!(
+            super::finding(Utf8Path::new("/work"), report),
+            "/elsewhere/b.cc: Dead store [7]"
+        );
+    }
+
+    #[test]
+    fn staged_args_apply_the_run_clang_format_options() {
+        let args = [
+            "--extensions=cc,h",
+            "-r",
+            "--style",
+            "file",
+            "-j",
+            "4",
+            "--exclude",
+            "third_party/**",
+            "--clang-format-executable=/opt/clang-format",
+            "--sort-includes",
+        ];
+        let staged = super::StagedArgs::parse(args.into_iter().map(Into::into)).unwrap();
+        assert_eq!(staged.clang_format.as_deref(), Some("/opt/clang-format"));
+        assert_eq!(staged.args, ["--style=file", "--sort-includes"]);
+        assert!(staged.selects(Utf8Path::new("cxx/a.cc")).unwrap());
+        assert!(!staged.selects(Utf8Path::new("cxx/a.cpp")).unwrap());
+        assert!(!staged.selects(Utf8Path::new("third_party/b.h")).unwrap());
+    }
+
+    #[test]
+    fn staged_args_require_option_values() {
+        let args = ["--style"];
+        assert!(super::StagedArgs::parse(args.into_iter().map(Into::into)).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    validation::Tool,
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{collections::BTreeSet, fmt::Write, process::ExitStatus};
+
+/// The usage of `xtask clangd`.
+pub const USAGE: Usage = Usage {
+    name: "clangd",
+    summary: "Generate the clangd configuration matching the validated clang toolchain",
+    args: "[SUBCOMMAND]",
+    description: &[
+        "Generates the `.clangd` file of the workspace, so that editors using clangd see the code the way the xtask",
+        "builds it: with the compilation database of `build`, and the builtin headers (the resource directory) of the",
+        "clang validated against `xtask.clang.version`.",
+        "",
+        "For builds with other compilers (e.g. cross builds with a target preset), the compilers of the compilation",
+        "database other than that clang are listed as the `--query-driver` argument for clangd, which clangd only takes",
+        "on its command line (e.g. in the `clangd.arguments` setting of VS Code).",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        Entry {
+            name: "--force",
+            about: &["Overwrite an existing `.clangd` that was not generated by the xtask"],
+        },
+        Entry {
+            name: "--check",
+            about: &["Check that `.clangd` is up to date instead of writing it"],
+        },
+    ],
+    subcommands: &[Entry {
+        name: "setup",
+        about: &["Write `.clangd` to the workspace root"],
+    }],
+    examples: &[],
+};
+
+// The first line of the generated `.clangd`, which marks it as safe to overwrite.
+const HEADER: &str = "# Generated by `xtask clangd setup` from the xtask config; do not edit.";
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - Tool validation fails (missing tools, incorrect versions, etc.)
+/// - The resource directory of clang cannot be determined
+/// - The compilation database is malformed
+/// - `.clangd` was not generated by the xtask (without `--force`) or cannot be written
+/// - With `--check`, `.clangd` is missing or differs from the generated one
+pub fn clangd(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let Some(clangd_subcommand) = context.args.opt_free_from_str::<String>()? else {
+        println!("{help}\n");
+        return Ok(None);
+    };
+
+    if clangd_subcommand != "setup" {
+        println!("{help}\n");
+        return Err(Error::new(
+            Kind::Validation,
+            format!("unrecognized `xtask clangd` subcommand `{clangd_subcommand}`"),
+        )
+        .into());
+    }
+
+    let force = context.args.contains("--force");
+    let check = context.args.contains("--check");
+
+    crate::handler::unused(context.args)?;
+
+    let root = &context.config.cargo_metadata.workspace_root;
+    let driver = context.validation().tool(Tool::ClangDriver)?.path;
+    let resource_dir = resource_dir(&context, &driver)?;
+    let query_drivers = query_drivers(&root.join("build"), &driver)?;
+
+    let path = root.join(".clangd");
+    let contents = render(&resource_dir, &query_drivers)?;
+    let existing = std::fs::read_to_string(&path).ok();
+
+    if check {
+        if existing.as_ref() != Some(&contents) {
+            return Err(format!("`{path}` is missing or out of date\nRegenerate it with `xtask clangd setup`").into());
+        }
+        crate::handler::status(format_args!("`{path}` is up to date"));
+        return Ok(None);
+    }
+
+    if !force && existing.map_or(false, |existing| !existing.starts_with(HEADER)) {
+        return Err(format!("`{path}` was not generated by the xtask\nPass `--force` to overwrite it").into());
+    }
+    std::fs::write(&path, contents).map_err(|err| format!("failed to write `{path}`: {err}"))?;
+    crate::handler::status(format_args!("wrote `{path}`"));
+    if !query_drivers.is_empty() {
+        crate::handler::status(format_args!(
+            "Pass `{}` to clangd for the compilers of the build",
+            query_driver_arg(&query_drivers)
+        ));
+    }
+
+    Ok(None)
+}
+
+// The resource directory of the clang driver `driver`, which holds its builtin headers.
+fn resource_dir(context: &Context<'_>, driver: &Utf8Path) -> BoxResult<String> {
+    let mut cmd = context.command(driver);
+    cmd.arg("-print-resource-dir");
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let output = context.runner.output(&mut cmd).map_err(|err| {
+        Error::new(
+            crate::error::kind(&err),
+            format!("failed to run `{driver} -print-resource-dir`: {err}"),
+        )
+    })?;
+    let resource_dir = String::from_utf8(output.stdout)?.trim().to_owned();
+    if !output.status.success() || resource_dir.is_empty() {
+        return Err(format!("`{driver} -print-resource-dir` failed to print the resource directory of clang").into());
+    }
+    Ok(resource_dir)
+}
+
+// The compilers of the compilation database in `build_dir` (if it exists) other than the clang driver `driver`, for
+// clangd to query for their system headers.
+fn query_drivers(build_dir: &Utf8Path, driver: &Utf8Path) -> BoxResult<BTreeSet<Utf8PathBuf>> {
+    if !build_dir.join("compile_commands.json").is_file() {
+        return Ok(BTreeSet::new());
+    }
+    let driver_name = driver.file_name().unwrap_or(driver.as_str());
+    let compilers = crate::compile_commands::load(build_dir)?
+        .into_iter()
+        .filter_map(|entry| entry.arguments.into_iter().next())
+        .map(Utf8PathBuf::from)
+        .filter(|compiler| compiler.as_path() != driver && compiler.file_name() != Some(driver_name))
+        .collect();
+    Ok(compilers)
+}
+
+// The `--query-driver` argument for clangd allowing the compilers `query_drivers`.
+fn query_driver_arg(query_drivers: &BTreeSet<Utf8PathBuf>) -> String {
+    let globs = query_drivers.iter().map(|driver| driver.as_str()).collect::<Vec<_>>();
+    format!("--query-driver={}", globs.join(","))
+}
+
+// The contents of `.clangd`, where the paths are written as JSON strings, which are also valid YAML strings.
+fn render(resource_dir: &str, query_drivers: &BTreeSet<Utf8PathBuf>) -> BoxResult<String> {
+    let mut contents = String::new();
+    writeln!(contents, "{HEADER}")?;
+    if !query_drivers.is_empty() {
+        writeln!(contents, "#")?;
+        writeln!(
+            contents,
+            "# clangd takes the other compilers of the compilation database on its command line:"
+        )?;
+        writeln!(contents, "#     {}", query_driver_arg(query_drivers))?;
+    }
+    writeln!(contents, "CompileFlags:")?;
+    writeln!(contents, "  CompilationDatabase: build")?;
+    writeln!(contents, "  Add:")?;
+    let resource_dir = format!("-resource-dir={resource_dir}");
+    writeln!(contents, "    - {}", serde_json::to_string(&resource_dir)?)?;
+    Ok(contents)
+}
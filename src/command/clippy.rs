@@ -1,41 +1,463 @@
-use crate::{command::Context, BoxResult};
-use std::process::{Command, ExitStatus};
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    BoxResult,
+};
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    process::{Command, ExitStatus},
+};
+
+/// The usage of `xtask clippy`.
+pub const USAGE: Usage = Usage {
+    name: "clippy",
+    summary: "Lint the project with clippy",
+    args: "",
+    description: &[],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::TARGET_PRESET,
+        usage::HERMETIC,
+        usage::PACKAGE,
+        usage::MANIFEST_PATH,
+        Entry {
+            name: "--all-targets",
+            about: &["Check all targets (lib, bins, tests, benches, examples)"],
+        },
+        Entry {
+            name: "--no-deny-warnings",
+            about: &["Report warnings without failing (drops `warnings` from the denied lints)"],
+        },
+        Entry {
+            name: "--since <REF>",
+            about: &[
+                "Only lint the workspace packages owning files changed since the git ref (among those given",
+                "with `-p`, if any)",
+            ],
+        },
+        Entry {
+            name: "--baseline <REV>",
+            about: &[
+                "Fail only on diagnostics that are not also reported for the merge base of REV and `HEAD`, by",
+                "running clippy there too (in a git work tree under `target/xtask/clippy-baseline`, where its",
+                "diagnostics are cached); denied lints are reported as warnings, so that none stop the build",
+            ],
+        },
+        Entry {
+            name: "-- '...'",
+            about: &[
+                "Extra arguments to pass to the cargo command",
+                "(arguments after a further `--` are passed to clippy itself)",
+            ],
+        },
+    ],
+    subcommands: &[],
+    examples: &[],
+};
 
 /// # Errors
 ///
 /// Will return `Err` under the following circumstances:
 /// - Argument processing fails (e.g. invalid arguments)
+/// - The `clippy` component fails to run (e.g. it is not installed for the nightly toolchain)
 /// - The command process fails to start
 /// - The command invocation fails with non-zero exit status
+/// - With `--since`, `git diff` fails or the ref does not name a commit
+/// - With `--baseline`, the project is not in a git work tree, or clippy fails on the baseline without diagnostics
 pub fn clippy(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = r#"
-xtask-clippy
-
-USAGE:
-xtask clippy
+    let help = USAGE.render();
 
-FLAGS:
--h, --help          Prints help information
--- '...'            Extra arguments to pass to the cargo command
-"#
-    .trim();
-
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
+    let all_targets = context.args.contains("--all-targets");
+    let no_deny_warnings = context.args.contains("--no-deny-warnings");
+    let baseline = context.args.opt_value_from_str::<_, String>("--baseline")?;
+
+    let since = context.args.opt_value_from_str::<_, String>("--since")?;
+
+    let packages = crate::workspace::packages(context.config, context.args, &[])?;
+    let manifest_path = context.args.opt_value_from_str::<_, String>("--manifest-path")?;
+
     crate::handler::unused(context.args)?;
 
-    let toolchain = crate::config::rust::toolchain::nightly(context.config);
+    let packages = match &since {
+        Some(reference) => {
+            let Some(changed) = changed_packages(&context, reference, &packages)? else {
+                crate::handler::status(format_args!(
+                    "no files of the workspace packages changed since `{reference}`"
+                ));
+                return Ok(None);
+            };
+            changed
+        },
+        None if packages.is_empty() => vec!["xtask".into(), "cxx-auto".into()],
+        None => packages,
+    };
+
+    let mut cargo_args = context.tool_args.clone();
+    let separator = cargo_args
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(cargo_args.len());
+    let clippy_args = cargo_args.split_off(separator).into_iter().skip(1).collect();
+    let options = Options {
+        packages,
+        manifest_path,
+        all_targets,
+        no_deny_warnings,
+        baseline: baseline.is_some(),
+        cargo_args,
+        clippy_args,
+    };
+    let project_root = crate::workspace::project_root(context.config).to_owned();
+
+    context.validate_component(context.nightly_toolchain()?, "clippy", "clippy")?;
 
-    let mut cmd = Command::new("cargo");
-    cmd.current_dir(crate::workspace::project_root()?);
-    cmd.args([&format!("+{toolchain}"), "clippy"]);
-    cmd.args(["--package", "xtask"]);
-    cmd.args(["--package", "cxx-auto"]);
-    cmd.args(context.tool_args);
-    cmd.args(["--", "-D", "warnings"]);
-    let status = cmd.status()?;
+    if let Some(reference) = &baseline {
+        return clippy_baseline(&context, &options, &project_root, reference);
+    }
+
+    let mut cmd = command(&context, &options, &project_root)?;
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
+    // NOTE: cargo exits with 101 both for lints denied by `-D warnings` and for build errors, which are reported alike
+    if !status.success() {
+        let message = "`cargo clippy` reported lint failures (or failed to build)";
+        return Err(Error::new(Kind::Lint, message).into());
+    }
 
     Ok(Some(status))
 }
+
+// The packages (among `selected`, if any) owning the files changed since `reference`, or `None` if there are none.
+//
+// Outside a git checkout, this is `selected` (or every workspace package), as if every file had changed.
+fn changed_packages(context: &Context<'_>, reference: &str, selected: &[String]) -> BoxResult<Option<Vec<String>>> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let Some(files) = crate::git::changed_files(context.runner, root, reference)? else {
+        let all = crate::workspace::members(context.config)
+            .map(ToOwned::to_owned)
+            .collect();
+        return Ok(Some(if selected.is_empty() { all } else { selected.to_vec() }));
+    };
+    let mut packages = crate::workspace::owning_packages(context.config, &files)
+        .into_iter()
+        .map(|(package, _)| package.name.clone())
+        .filter(|name| selected.is_empty() || selected.contains(name))
+        .collect::<Vec<_>>();
+    packages.sort();
+    packages.dedup();
+    Ok((!packages.is_empty()).then_some(packages))
+}
+
+// The options of a clippy run, shared by the runs at `HEAD` and at the baseline.
+struct Options {
+    packages: Vec<String>,
+    manifest_path: Option<String>,
+    all_targets: bool,
+    no_deny_warnings: bool,
+    // Report JSON diagnostics, with the denied lints demoted to warnings
+    baseline: bool,
+    cargo_args: Vec<OsString>,
+    clippy_args: Vec<OsString>,
+}
+
+// A diagnostic reported by clippy, identified by its lint, file, and message rather than its line, so that it still
+// matches after the code around it moves.
+#[derive(Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+struct Diagnostic {
+    code: Option<String>,
+    file: Option<String>,
+    message: String,
+    #[serde(skip)]
+    rendered: String,
+}
+
+// The cached diagnostics of a baseline, along with the clippy arguments they were reported for.
+#[derive(Deserialize, Serialize)]
+struct Baseline {
+    args: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// The clippy command for the workspace at `project_root`.
+fn command(context: &Context<'_>, options: &Options, project_root: &Utf8Path) -> BoxResult<Command> {
+    let toolchain = context.nightly_toolchain()?;
+
+    let mut cmd = context.cargo(Some(toolchain));
+    cmd.current_dir(project_root);
+    cmd.args(["clippy"]);
+    for package in &options.packages {
+        cmd.args(["--package", package]);
+    }
+    if let Some(manifest_path) = &options.manifest_path {
+        cmd.args(["--manifest-path", manifest_path]);
+    }
+    if options.all_targets {
+        cmd.args(["--all-targets"]);
+    }
+    if options.baseline {
+        cmd.args(["--message-format", "json"]);
+    }
+    cmd.args(context.target_args());
+    cmd.args(context.default_args("clippy"));
+
+    cmd.args(&options.cargo_args);
+    cmd.args(["--"]);
+    let lints = context
+        .config
+        .xtask
+        .commands
+        .get("clippy")
+        .and_then(|command| command.lints.as_ref());
+    match lints {
+        Some(lints) => {
+            for (flag, names) in [("-D", &lints.deny), ("-W", &lints.warn), ("-A", &lints.allow)] {
+                for name in names {
+                    if flag == "-D" && name == "warnings" && (options.no_deny_warnings || options.baseline) {
+                        continue;
+                    }
+                    let flag = if flag == "-D" && options.baseline { "-W" } else { flag };
+                    cmd.args([flag, name]);
+                }
+            }
+        },
+        None => {
+            if !options.no_deny_warnings && !options.baseline {
+                cmd.args(["-D", "warnings"]);
+            }
+        },
+    }
+    cmd.args(&options.clippy_args);
+    Ok(cmd)
+}
+
+// Runs clippy against the baseline of the merge base of `reference` and `HEAD`, failing only on new diagnostics.
+fn clippy_baseline(
+    context: &Context<'_>,
+    options: &Options,
+    project_root: &Utf8Path,
+    reference: &str,
+) -> BoxResult<Option<ExitStatus>> {
+    let base = crate::git::merge_base(context.runner, project_root, reference)?
+        .ok_or("`--baseline` requires the project to be in a git work tree")?;
+    let baseline = baseline_diagnostics(context, options, project_root, &base)?;
+
+    let (status, diagnostics, stderr) = diagnostics(context, command(context, options, project_root)?)?;
+    if !status.success() && diagnostics.is_empty() {
+        eprint!("{stderr}");
+        return Ok(Some(status));
+    }
+
+    let mut known = BTreeMap::<&Diagnostic, usize>::new();
+    for diagnostic in &baseline {
+        *known.entry(diagnostic).or_default() += 1;
+    }
+    let mut new = vec![];
+    for diagnostic in &diagnostics {
+        match known.get_mut(diagnostic) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => new.push(diagnostic),
+        }
+    }
+
+    for diagnostic in &new {
+        print!("{}", diagnostic.rendered);
+    }
+    let summary = format!(
+        "{} diagnostics, of which {} are new since `{reference}` (merge base `{base}`)",
+        diagnostics.len(),
+        new.len()
+    );
+    if !new.is_empty() {
+        return Err(Error::new(Kind::Lint, summary).into());
+    }
+    crate::handler::status(summary);
+    Ok(Some(crate::runner::exit_status(0)))
+}
+
+// The diagnostics of the commit `base`, from the cache if they were reported for the same arguments, or else from a
+// clippy run in a temporary git work tree.
+fn baseline_diagnostics(
+    context: &Context<'_>,
+    options: &Options,
+    project_root: &Utf8Path,
+    base: &str,
+) -> BoxResult<Vec<Diagnostic>> {
+    let dir = crate::workspace::target_dir(context.config)
+        .to_owned()
+        .join("xtask/clippy-baseline");
+    let cache = dir.join(format!("{base}.json"));
+    let args = command(context, options, project_root)?
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    if let Ok(data) = std::fs::read_to_string(&cache) {
+        // NOTE: a cache that cannot be parsed (e.g. from another xtask version) is recomputed
+        if let Ok(baseline) = serde_json::from_str::<Baseline>(&data) {
+            if baseline.args == args {
+                return Ok(baseline.diagnostics);
+            }
+        }
+    }
+
+    std::fs::create_dir_all(&dir).map_err(|err| format!("failed to create `{dir}`: {err}"))?;
+    let worktree = dir.join("worktree");
+    crate::git::add_worktree(context.runner, project_root, &worktree, base)?;
+    let prefix = crate::git::prefix(context.runner, project_root)?;
+    let mut cmd = command(context, options, &worktree.join(prefix))?;
+    // NOTE: a separate target directory keeps the baseline build from invalidating the one for `HEAD`
+    cmd.env("CARGO_TARGET_DIR", dir.join("target"));
+    crate::handler::status(format_args!("running clippy on the baseline `{base}`"));
+    let result = diagnostics(context, cmd);
+    crate::git::remove_worktree(context.runner, project_root, &worktree)?;
+    let (status, diagnostics, stderr) = result?;
+    if !status.success() && diagnostics.is_empty() {
+        return Err(format!("clippy failed on the baseline `{base}`:\n{stderr}").into());
+    }
+
+    let baseline = Baseline { args, diagnostics };
+    std::fs::write(&cache, serde_json::to_string(&baseline)?)
+        .map_err(|err| format!("failed to write `{cache}`: {err}"))?;
+    Ok(baseline.diagnostics)
+}
+
+// Runs the clippy command `cmd` (with `--message-format json`), returning its status, diagnostics, and stderr.
+fn diagnostics(context: &Context<'_>, mut cmd: Command) -> BoxResult<(ExitStatus, Vec<Diagnostic>, String)> {
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let output = context.runner.output(&mut cmd)?;
+    let mut diagnostics = vec![];
+    for line in String::from_utf8(output.stdout)?.lines() {
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        if json["reason"] != "compiler-message" {
+            continue;
+        }
+        let message = &json["message"];
+        // NOTE: summaries such as "aborting due to previous error" have neither a code nor a location
+        let code = message["code"]["code"].as_str().map(String::from);
+        let file = message["spans"]
+            .as_array()
+            .and_then(|spans| spans.iter().find(|span| span["is_primary"] == true))
+            .and_then(|span| span["file_name"].as_str())
+            .map(String::from);
+        if code.is_none() && file.is_none() {
+            continue;
+        }
+        diagnostics.push(Diagnostic {
+            code,
+            file,
+            message: message["message"].as_str().unwrap_or_default().to_owned(),
+            rendered: message["rendered"].as_str().unwrap_or_default().to_owned(),
+        });
+    }
+    Ok((
+        output.status,
+        diagnostics,
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::Context, config::Config, runner::MockRunner, testing};
+    use std::sync::Mutex;
+
+    // The clippy command line run by `xtask clippy` with `args` and the extra arguments `tool_args`.
+    fn clippy_command_line(config: &Config, args: &[&str], tool_args: &[&str]) -> String {
+        let mut args = pico_args::Arguments::from_vec(args.iter().map(Into::into).collect());
+        let mut context = Context::new(config, &mut args, tool_args.iter().map(Into::into).collect());
+        let installed = format!("nightly-{}\n", crate::version::TARGET);
+        let clippy = Mutex::new(None);
+        let runner = MockRunner::new(|cmd| {
+            let line = testing::command_line(cmd);
+            if line.ends_with("rustup toolchain list") {
+                return Ok(testing::output(0, &installed));
+            }
+            *clippy.lock().unwrap() = Some(line);
+            Ok(testing::output(0, ""))
+        });
+        context.runner = &runner;
+        super::clippy(context).unwrap();
+        clippy.into_inner().unwrap().expect("clippy was not run")
+    }
+
+    #[test]
+    fn locked_is_passed_after_the_toolchain() {
+        let line = clippy_command_line(&testing::config(serde_json::json!({})), &["--locked"], &[]);
+        assert!(line.ends_with(" +nightly --locked clippy --package xtask --package cxx-auto -- -D warnings"));
+    }
+
+    #[test]
+    fn configured_args_precede_the_extra_arguments() {
+        let xtask = serde_json::json!({ "commands": { "clippy": { "args": ["--all-features"] } } });
+        let line = clippy_command_line(&testing::config(xtask), &[], &[
+            "--no-default-features",
+            "--",
+            "-W",
+            "clippy::nursery",
+        ]);
+        assert!(line.ends_with(
+            " clippy --package xtask --package cxx-auto --all-features --no-default-features -- -D warnings -W \
+             clippy::nursery"
+        ));
+    }
+
+    #[test]
+    fn all_targets_precedes_the_clippy_arguments() {
+        let line = clippy_command_line(&testing::config(serde_json::json!({})), &["--all-targets"], &[
+            "--",
+            "-W",
+            "clippy::nursery",
+        ]);
+        assert!(
+            line.ends_with(
+                " clippy --package xtask --package cxx-auto --all-targets -- -D warnings -W clippy::nursery"
+            )
+        );
+    }
+
+    #[test]
+    fn configured_lints_replace_deny_warnings() {
+        let xtask = serde_json::json!({
+            "commands": {
+                "clippy": { "lints": { "deny": ["clippy::all"], "warn": ["clippy::pedantic"], "allow": ["clippy::too_many_lines"] } }
+            }
+        });
+        let line = clippy_command_line(&testing::config(xtask), &[], &[]);
+        assert!(line.ends_with(" -- -D clippy::all -W clippy::pedantic -A clippy::too_many_lines"));
+    }
+
+    #[test]
+    fn packages_restrict_the_default_package_set() {
+        let mut config = testing::config(serde_json::json!({}));
+        testing::add_members(&mut config, &["foo", "bar"]);
+        let line = clippy_command_line(&config, &["-p", "foo"], &[]);
+        assert!(line.ends_with(" clippy --package foo -- -D warnings"));
+
+        let mut args = pico_args::Arguments::from_vec(vec!["-p".into(), "baz".into()]);
+        let context = Context::new(&config, &mut args, vec![]);
+        let err = super::clippy(context).unwrap_err();
+        assert_eq!(err.to_string(), "package `baz` is not a member of the workspace");
+    }
+}
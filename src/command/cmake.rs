@@ -1,5 +1,112 @@
-use crate::{command::Context, BoxResult};
-use std::process::{Command, ExitStatus};
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    validation::Tool,
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{ffi::OsString, process::ExitStatus};
+
+/// The usage of `xtask cmake`.
+pub const USAGE: Usage = Usage {
+    name: "cmake",
+    summary: "Build the project's C++ code with CMake",
+    args: "[SUBCOMMAND]",
+    description: &[
+        "Configuring applies `xtask.cmake.speed`: `pch = false` turns off the precompiled headers of the targets, and",
+        "`unity = N` turns on unity builds of up to N sources per batch (`0` turns them off).",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        Entry {
+            name: "-j, --jobs <N>",
+            about: &["Number of parallel jobs for `cmake --build` (via `CMAKE_BUILD_PARALLEL_LEVEL`) and ctest"],
+        },
+        Entry {
+            name: "--profile <NAME>",
+            about: &[
+                "Apply the settings of the `xtask.profiles` entry NAME (also selected by `XTASK_PROFILE`),",
+                "including its `cmakeDefines` when configuring (configure)",
+            ],
+        },
+        Entry {
+            name: "--target-preset <NAME>",
+            about: &[
+                "Configure with the toolchain of the Android or iOS target preset NAME (also selected by",
+                "`XTASK_TARGET_PRESET`), switching an existing build from scratch (configure)",
+            ],
+        },
+        Entry {
+            name: "--frozen",
+            about: &["Fail instead of reconfiguring from scratch when the cache of `build` is stale (configure)"],
+        },
+        Entry {
+            name: "--fresh",
+            about: &["Remove the cache of `build` before configuring, as for a first configure (configure)"],
+        },
+        usage::HERMETIC,
+        Entry {
+            name: "--timing",
+            about: &["Afterwards, report the build time of each target in the last ninja build of `build`"],
+        },
+        Entry {
+            name: "--prefix <DIR>",
+            about: &["Install into DIR rather than the staging prefix `target/xtask/install` (install)"],
+        },
+        Entry {
+            name: "--rerun-failed",
+            about: &["Run only the tests that failed in the previous run (test)"],
+        },
+        Entry {
+            name: "--label <LABEL>",
+            about: &["Run only the tests with a label matching the regex LABEL (test)"],
+        },
+        Entry {
+            name: "--regex <REGEX>",
+            about: &["Run only the tests with a name matching REGEX (test)"],
+        },
+        Entry {
+            name: "--junit <PATH>",
+            about: &["Also write the test results to PATH as a JUnit XML report (test)"],
+        },
+        Entry {
+            name: "-- '...'",
+            about: &["Extra arguments to pass to the cmake subcommand"],
+        },
+    ],
+    subcommands: &[
+        Entry {
+            name: "configure",
+            about: &[
+                "Configure the CMake build in `build`, starting from a fresh cache if the generator, the",
+                "toolchain file, or the arguments of the previous configure changed",
+            ],
+        },
+        Entry {
+            name: "build",
+            about: &["Same as `configure`"],
+        },
+        Entry {
+            name: "test",
+            about: &["Run the tests of the CMake build with ctest and summarize the results"],
+        },
+        Entry {
+            name: "install",
+            about: &[
+                "Install the CMake build with `cmake --install` into a fresh staging prefix, and check that the",
+                "headers, libraries, and pkg-config files of `xtask.cmake.install` were installed",
+            ],
+        },
+    ],
+    examples: &[Entry {
+        name: "xtask cmake install -- --component sdk",
+        about: &["Check the installed files of the `sdk` component"],
+    }],
+};
 
 /// # Errors
 ///
@@ -8,23 +115,12 @@ use std::process::{Command, ExitStatus};
 /// - Tool validation fails (missing tools, incorrect versions, etc.)
 /// - The command process fails to start
 /// - The command invocation fails with non-zero exit status
+/// - For `install`, an expected file of `xtask.cmake.install` is missing from the install tree
+/// - For `test` with `--junit`, the report cannot be written
 pub fn cmake(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = r#"
-xtask-cmake
-
-USAGE:
-xtask cmake [SUBCOMMAND]
-
-FLAGS:
--h, --help          Prints help information
--- '...'            Extra arguments to pass to the cmake subcommand
-
-SUBCOMMANDS:
-    build
-"#
-    .trim();
+    let help = USAGE.render();
 
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
@@ -33,20 +129,265 @@ SUBCOMMANDS:
         return Ok(None);
     };
 
+    let fresh = context.args.contains("--fresh");
+    let rerun_failed = context.args.contains("--rerun-failed");
+    let label = context.args.opt_value_from_str::<_, String>("--label")?;
+    let regex = context.args.opt_value_from_str::<_, String>("--regex")?;
+    let junit = context.args.opt_value_from_str::<_, Utf8PathBuf>("--junit")?;
+    let timing = context.args.contains("--timing");
+    let prefix = context.args.opt_value_from_str::<_, Utf8PathBuf>("--prefix")?;
+
     crate::handler::unused(context.args)?;
 
-    let status = if cmake_subcommand == "build" {
-        let mut cmd = Command::new("cmake");
-        cmd.args(["-G", "Ninja"]);
-        cmd.args(["-S", "."]);
-        cmd.args(["-B", "build"]);
+    // NOTE: check the versions up front rather than have configuration fail midway on a missing feature
+    validate_tools(&context, &cmake_subcommand)?;
+
+    let status = if cmake_subcommand == "build" || cmake_subcommand == "configure" {
+        configure(&context, fresh, &context.tool_args)?
+    } else if cmake_subcommand == "test" {
+        let build_dir = context.config.cargo_metadata.workspace_root.join("build");
+        let log = build_dir.join("xtask-ctest.log");
+        // NOTE: a log left by a previous run must not be summarized as this run's
+        if log.exists() {
+            std::fs::remove_file(&log)?;
+        }
+        let mut cmd = context.command("ctest");
+        context.config.xtask.c_locale(&mut cmd);
+        cmd.args(["--output-on-failure"]);
+        cmd.arg("--output-log");
+        cmd.arg(&log);
+        if rerun_failed {
+            cmd.args(["--rerun-failed"]);
+        }
+        if let Some(label) = &label {
+            cmd.args(["--label-regex", label]);
+        }
+        if let Some(regex) = &regex {
+            cmd.args(["--tests-regex", regex]);
+        }
+        if let Some(jobs) = context.jobs {
+            cmd.args(["--parallel", &jobs.to_string()]);
+        }
+        cmd.args(context.default_args("ctest"));
         cmd.args(context.tool_args);
-        cmd.current_dir(&context.config.cargo_metadata.workspace_root);
-        cmd.status()?
+        cmd.current_dir(&build_dir);
+        if context.print_env {
+            crate::handler::print_env(&cmd);
+        }
+        let status = context.runner.status(&mut cmd)?;
+        // NOTE: ctest writes no log if it fails before running tests (e.g. without a configured build)
+        let results = match std::fs::read_to_string(&log) {
+            Ok(output) => Some(crate::ctest::parse_results(&output)?),
+            Err(_) => None,
+        };
+        if let (Some(results), false) = (&results, context.quiet) {
+            println!();
+            crate::ctest::print_summary(results);
+        }
+        if let Some(junit) = &junit {
+            crate::ctest::write_junit(results.as_deref().unwrap_or_default(), junit)?;
+        }
+        status
+    } else if cmake_subcommand == "install" {
+        install(&context, prefix)?
     } else {
         println!("{help}\n");
-        return Err(format!("unrecognized `xtask cmake` subcommand `{cmake_subcommand}`").into());
+        return Err(Error::new(
+            Kind::Validation,
+            format!("unrecognized `xtask cmake` subcommand `{cmake_subcommand}`"),
+        )
+        .into());
     };
 
+    if timing {
+        print_timing(&context.config.cargo_metadata.workspace_root.join("build"))?;
+    }
+
     Ok(Some(status))
 }
+
+/// Builds the `CMake` project in `build` before cargo runs, if `xtask.cmake.prerequisite` is configured, returning the
+/// environment variables for cargo: `XTASK_CMAKE_BUILD_DIR` naming the build directory, and the configured `env`.
+///
+/// The project is configured first if `build` has no `CMake` cache yet, and otherwise left to `cmake --build`, which
+/// reconfigures it as needed while keeping the arguments of the last `xtask cmake configure`.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Tool validation fails (missing tools, incorrect versions, etc.)
+/// - The command process fails to start
+/// - Configuring or building the `CMake` project fails
+pub fn prerequisite(context: &Context<'_>) -> BoxResult<Vec<(String, String)>> {
+    let Some(prerequisite) = &context.config.xtask.cmake.prerequisite else {
+        return Ok(vec![]);
+    };
+    validate_tools(context, "build")?;
+    let root = &context.config.cargo_metadata.workspace_root;
+    let build_dir = root.join("build");
+
+    if !build_dir.join("CMakeCache.txt").is_file() {
+        context.log_groups.start("cmake configure");
+        let status = configure(context, false, &[]);
+        context.log_groups.end();
+        if !status?.success() {
+            return Err("prerequisite CMake configure failed, so cargo was not run".into());
+        }
+    }
+
+    let mut cmd = context.command("cmake");
+    cmd.args(["--build", "build"]);
+    for target in &prerequisite.targets {
+        cmd.args(["--target", target]);
+    }
+    cmd.current_dir(root);
+    context.log_groups.start("cmake --build");
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd);
+    context.log_groups.end();
+    if !status?.success() {
+        return Err("prerequisite CMake build failed, so cargo was not run".into());
+    }
+
+    let mut env = vec![("XTASK_CMAKE_BUILD_DIR".to_owned(), build_dir.to_string())];
+    for (name, path) in &prerequisite.env {
+        env.push((name.clone(), build_dir.join(path).to_string()));
+    }
+    Ok(env)
+}
+
+// Checks the configured minimum versions of cmake and, when configuring, ninja.
+fn validate_tools(context: &Context<'_>, cmake_subcommand: &str) -> BoxResult<()> {
+    let tools: &[Tool] = if cmake_subcommand == "test" {
+        &[Tool::Cmake]
+    } else {
+        &[Tool::Cmake, Tool::Ninja]
+    };
+    let (_, errors) = crate::validation::validate_tools(&context.validation(), tools);
+    crate::validation::tool_errors(errors)
+}
+
+// Print the per-target build times of the last ninja build in `build_dir`, from its `.ninja_log`.
+fn print_timing(build_dir: &Utf8Path) -> BoxResult<()> {
+    let path = build_dir.join(".ninja_log");
+    let log = std::fs::read_to_string(&path)
+        .map_err(|err| format!("failed to read `{path}`: {err}\nBuild the project with ninja before `--timing`"))?;
+    let (times, wall) = crate::ninja_log::parse_target_times(&log)?;
+    println!();
+    crate::ninja_log::print_summary(&times, wall);
+    Ok(())
+}
+
+// Configures the CMake build in `build` (with the extra arguments `tool_args`), first removing its cache if `fresh` is
+// set or the cache is stale.
+fn configure(context: &Context<'_>, fresh: bool, tool_args: &[OsString]) -> BoxResult<ExitStatus> {
+    let source_dir = &context.config.cargo_metadata.workspace_root;
+    let build_dir = source_dir.join("build");
+    let generator = "Ninja";
+
+    let mut args = vec![];
+    if let Some(ninja) = context.config.xtask.tools.paths.get("ninja") {
+        args.push(format!("-DCMAKE_MAKE_PROGRAM={ninja}"));
+    }
+    if let Some(preset) = context.selected_target_preset() {
+        args.extend(crate::target_preset::cmake_defines(preset, &context.config.xtask)?);
+    }
+    if let Some(linker) = context.selected_linker() {
+        args.extend(crate::linker::cmake_defines(&linker));
+    }
+    args.extend(context.config.xtask.cmake.speed.cmake_defines());
+    if let Some(profile) = context.selected_profile() {
+        for (name, value) in &profile.cmake_defines {
+            args.push(format!("-D{name}={value}"));
+        }
+    }
+    args.extend(context.default_args("cmake").into_iter().map(String::from));
+    args.extend(tool_args.iter().map(|arg| arg.to_string_lossy().into_owned()));
+    let state = crate::cmake_cache::ConfigureState::new(source_dir, generator, args);
+
+    if fresh {
+        crate::cmake_cache::invalidate(&build_dir)?;
+    } else {
+        let reasons = crate::cmake_cache::stale_reasons(&build_dir, &state)?;
+        if !reasons.is_empty() {
+            let reasons = reasons.join("\n    ");
+            if context.frozen {
+                return Err(format!(
+                    "the CMake cache in `{build_dir}` is stale:\n    {reasons}\nReconfigure with `xtask cmake configure \
+                     --fresh` (or without `--frozen`)"
+                )
+                .into());
+            }
+            crate::handler::status(format_args!(
+                "reconfiguring `{build_dir}` from scratch, since its CMake cache is stale:\n    {reasons}"
+            ));
+            crate::cmake_cache::invalidate(&build_dir)?;
+        }
+    }
+
+    let mut cmd = context.command("cmake");
+    cmd.args(["-G", generator]);
+    cmd.args(["-S", "."]);
+    cmd.args(["-B", "build"]);
+    cmd.args(&state.args);
+    cmd.current_dir(source_dir);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
+    if status.success() {
+        crate::cmake_cache::save(&build_dir, &state)?;
+    }
+    Ok(status)
+}
+
+// Installs the CMake build in `build` into `prefix` (or a fresh staging prefix), and checks the installed files.
+fn install(context: &Context<'_>, prefix: Option<Utf8PathBuf>) -> BoxResult<ExitStatus> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let prefix = if let Some(prefix) = prefix {
+        Utf8PathBuf::try_from(std::env::current_dir()?)?.join(prefix)
+    } else {
+        // NOTE: only the staging prefix is cleared, so that files left by a previous install cannot hide a missing
+        // one
+        let prefix = root.join("target").join("xtask").join("install");
+        if prefix.exists() {
+            std::fs::remove_dir_all(&prefix).map_err(|err| format!("failed to remove `{prefix}`: {err}"))?;
+        }
+        prefix
+    };
+
+    let mut cmd = context.command("cmake");
+    cmd.args(["--install", "build", "--prefix", prefix.as_str()]);
+    cmd.args(context.tool_args.iter());
+    cmd.current_dir(root);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
+    if !status.success() {
+        return Ok(status);
+    }
+
+    let expected = &context.config.xtask.cmake.install;
+    if expected.is_empty() {
+        crate::handler::status(format_args!(
+            "installed into `{prefix}` (configure `xtask.cmake.install` to check the installed files)"
+        ));
+        return Ok(status);
+    }
+    let missing = crate::cmake_install::missing(&prefix, expected)?;
+    if !missing.is_empty() {
+        return Err(format!(
+            "the install into `{prefix}` is missing:\n    {}\nCheck the `install` rules of the CMake project",
+            missing.join("\n    ")
+        )
+        .into());
+    }
+    let count = expected.headers.len() + expected.libraries.len() + expected.pkg_config.len();
+    crate::handler::status(format_args!(
+        "installed into `{prefix}`, with all {count} expected files"
+    ));
+    Ok(status)
+}
@@ -0,0 +1,188 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    BoxResult,
+};
+use std::{fmt::Write, process::ExitStatus};
+
+/// The usage of `xtask completions`.
+pub const USAGE: Usage = Usage {
+    name: "completions",
+    summary: "Print a shell completion script for the subcommands and their flags",
+    args: "<SHELL>",
+    description: &[
+        "Prints a completion script for SHELL (bash, zsh, or fish) for the `xtask` command, covering the subcommands, the",
+        "aliases configured in `xtask.alias`, and the flags and nested subcommands of each subcommand.",
+    ],
+    flags: &[usage::HELP],
+    subcommands: &[],
+    examples: &[
+        Entry {
+            name: "xtask completions bash > ~/.local/share/bash-completion/completions/xtask",
+            about: &["Install the completions for bash"],
+        },
+        Entry {
+            name: "xtask completions fish > ~/.config/fish/completions/xtask.fish",
+            about: &["Install the completions for fish"],
+        },
+    ],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - The shell is missing or not supported
+pub fn completions(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let Some(shell) = context.args.opt_free_from_str::<String>()? else {
+        println!("{help}\n");
+        return Err("expected a shell (bash, zsh, or fish)".into());
+    };
+
+    crate::handler::unused(context.args)?;
+
+    let mut commands = crate::command::SUBCOMMANDS
+        .iter()
+        .map(|subcommand| Command {
+            name: subcommand.usage.name,
+            summary: subcommand.usage.summary.to_owned(),
+            entries: subcommand
+                .usage
+                .subcommands
+                .iter()
+                .chain(subcommand.usage.flags)
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    for (name, steps) in crate::command::registry::aliases(context.config) {
+        commands.push(Command {
+            name,
+            summary: format!("Runs {}", steps.join(", then ")),
+            entries: vec![&usage::HELP],
+        });
+    }
+
+    let script = match shell.as_str() {
+        "bash" => bash(&commands)?,
+        "zsh" => zsh(&commands)?,
+        "fish" => fish(&commands)?,
+        _ => return Err(format!("unsupported shell `{shell}`: expected bash, zsh, or fish").into()),
+    };
+    print!("{script}");
+
+    Ok(None)
+}
+
+// A subcommand or alias to complete, with its nested subcommands and flags.
+struct Command<'a> {
+    name: &'a str,
+    summary: String,
+    entries: Vec<&'a Entry>,
+}
+
+impl Command<'_> {
+    fn words(&self) -> Vec<&'static str> {
+        self.entries.iter().flat_map(|entry| entry.words()).collect()
+    }
+}
+
+fn bash(commands: &[Command<'_>]) -> BoxResult<String> {
+    let names = commands.iter().map(|command| command.name).collect::<Vec<_>>();
+    let mut script = String::new();
+    writeln!(script, "_xtask() {{")?;
+    writeln!(script, "    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+    writeln!(script, "    if [[ $COMP_CWORD -eq 1 ]]; then")?;
+    writeln!(
+        script,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        names.join(" ")
+    )?;
+    writeln!(script, "        return")?;
+    writeln!(script, "    fi")?;
+    writeln!(script, "    case \"${{COMP_WORDS[1]}}\" in")?;
+    for command in commands {
+        writeln!(
+            script,
+            "        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;",
+            command.name,
+            command.words().join(" ")
+        )?;
+    }
+    writeln!(script, "    esac")?;
+    writeln!(script, "}}")?;
+    writeln!(script, "complete -F _xtask xtask")?;
+    Ok(script)
+}
+
+fn zsh(commands: &[Command<'_>]) -> BoxResult<String> {
+    // NOTE: zsh single-quoted strings cannot contain escapes, so a quote ends the string and is escaped outside it
+    let quote = |text: &str| format!("'{}'", text.replace('\'', r"'\''"));
+    let mut script = String::new();
+    writeln!(script, "#compdef xtask\n")?;
+    writeln!(script, "_xtask() {{")?;
+    writeln!(script, "    if (( CURRENT == 2 )); then")?;
+    writeln!(script, "        local -a subcommands=(")?;
+    for command in commands {
+        writeln!(
+            script,
+            "            {}",
+            quote(&format!("{}:{}", command.name, command.summary))
+        )?;
+    }
+    writeln!(script, "        )")?;
+    writeln!(script, "        _describe 'subcommand' subcommands")?;
+    writeln!(script, "        return")?;
+    writeln!(script, "    fi")?;
+    writeln!(script, "    case $words[2] in")?;
+    for command in commands {
+        writeln!(
+            script,
+            "        {}) compadd -- {} ;;",
+            command.name,
+            command.words().join(" ")
+        )?;
+    }
+    writeln!(script, "    esac")?;
+    writeln!(script, "}}\n")?;
+    writeln!(script, "_xtask \"$@\"")?;
+    Ok(script)
+}
+
+fn fish(commands: &[Command<'_>]) -> BoxResult<String> {
+    let quote = |text: &str| format!("'{}'", text.replace('\\', r"\\").replace('\'', r"\'"));
+    let mut script = String::new();
+    writeln!(script, "complete -c xtask -f")?;
+    for command in commands {
+        writeln!(
+            script,
+            "complete -c xtask -n __fish_use_subcommand -a {} -d {}",
+            command.name,
+            quote(&command.summary)
+        )?;
+    }
+    for command in commands {
+        let condition = quote(&format!("__fish_seen_subcommand_from {}", command.name));
+        for entry in &command.entries {
+            let about = quote(entry.about.first().copied().unwrap_or_default());
+            for word in entry.words() {
+                let option = if let Some(long) = word.strip_prefix("--") {
+                    format!("-l {long}")
+                } else if let Some(short) = word.strip_prefix('-') {
+                    format!("-s {short}")
+                } else {
+                    format!("-a {word}")
+                };
+                writeln!(script, "complete -c xtask -n {condition} {option} -d {about}")?;
+            }
+        }
+    }
+    Ok(script)
+}
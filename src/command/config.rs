@@ -0,0 +1,146 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    config::XTASK_FILES,
+    error::{Error, Kind},
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::process::ExitStatus;
+
+/// The usage of `xtask config`.
+pub const USAGE: Usage = Usage {
+    name: "config",
+    summary: "Convert the xtask config between TOML, JSON, and YAML, or migrate it to the current schema",
+    args: "[SUBCOMMAND]",
+    description: &[
+        "The xtask config is read from one of `xtask.toml`, `xtask.json`, or `xtask.yaml` in the workspace root, or else from",
+        "the `[workspace.metadata.xtask]` table of the workspace `Cargo.toml`.",
+        "",
+        "The config records the version of its schema in `schemaVersion`. A config of an older version is upgraded in",
+        "memory whenever it is loaded, and `migrate` upgrades the config file itself.",
+    ],
+    flags: &[
+        usage::HELP,
+        Entry {
+            name: "--force",
+            about: &["Overwrite the output file of `convert` if it exists"],
+        },
+        Entry {
+            name: "--dry-run",
+            about: &["Report the changes of `migrate` without rewriting the config file"],
+        },
+    ],
+    subcommands: &[
+        Entry {
+            name: "convert <PATH>",
+            about: &[
+                "Write the current xtask config to PATH, in the format given by its extension",
+                "(toml, json, yaml, or yml)",
+            ],
+        },
+        Entry {
+            name: "migrate",
+            about: &[
+                "Upgrade the xtask config file in place to the current schema version (dropping its",
+                "comments), reporting the renamed and removed keys",
+            ],
+        },
+    ],
+    examples: &[],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - There is no xtask config to convert or migrate
+/// - The output file already exists (without `--force`)
+/// - The config cannot be represented in or written to the output format
+/// - The config is of a newer schema version than this xtask supports
+pub fn config(mut context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let Some(config_subcommand) = context.args.opt_free_from_str::<String>()? else {
+        println!("{help}\n");
+        return Ok(None);
+    };
+
+    match config_subcommand.as_str() {
+        "convert" => convert(&mut context)?,
+        "migrate" => migrate(&mut context)?,
+        _ => {
+            println!("{help}\n");
+            return Err(Error::new(
+                Kind::Validation,
+                format!("unrecognized `xtask config` subcommand `{config_subcommand}`"),
+            )
+            .into());
+        },
+    }
+
+    Ok(None)
+}
+
+// Write the current xtask config to the path given in the arguments.
+fn convert(context: &mut Context<'_>) -> BoxResult<()> {
+    let force = context.args.contains("--force");
+    let path = context
+        .args
+        .opt_free_from_str::<Utf8PathBuf>()?
+        .ok_or("expected an output path for `xtask config convert`")?;
+
+    crate::handler::unused(context.args)?;
+
+    let (source, value) =
+        crate::config::xtask_value(&context.config.cargo_metadata)?.ok_or("there is no xtask config to convert")?;
+    if path.exists() && !force {
+        return Err(format!("`{path}` already exists\nPass `--force` to overwrite it").into());
+    }
+    crate::config::write_xtask_file(&path, &value)?;
+    println!("converted the xtask config from `{source}` to `{path}`");
+    println!("remove `{source}` so that the config is given in only one place");
+    Ok(())
+}
+
+// Upgrade the xtask config file to the current schema version, reporting the changes.
+fn migrate(context: &mut Context<'_>) -> BoxResult<()> {
+    let dry_run = context.args.contains("--dry-run");
+
+    crate::handler::unused(context.args)?;
+
+    let (source, mut value) =
+        crate::config::xtask_value(&context.config.cargo_metadata)?.ok_or("there is no xtask config to migrate")?;
+    let version = crate::migration::schema_version(&value)?;
+    let latest = crate::migration::SCHEMA_VERSION;
+    if version == latest {
+        println!("the xtask config in `{source}` is already at schema version {latest}");
+        return Ok(());
+    }
+    let changes = crate::migration::migrate(&mut value)?;
+    println!("migrating the xtask config in `{source}` from schema version {version} to {latest}:");
+    for change in &changes {
+        println!("    {change}");
+    }
+    println!("    set `schemaVersion` to {latest}");
+    if dry_run {
+        return Ok(());
+    }
+    // NOTE: `Cargo.toml` is not rewritten, since that would drop its formatting and comments along with the table's
+    if !XTASK_FILES.iter().any(|file| source.ends_with(file)) {
+        println!(
+            "\nReplace `{source}` in `Cargo.toml` with:\n\n{}",
+            toml::to_string_pretty(&value)?
+        );
+        return Ok(());
+    }
+    crate::config::write_xtask_file(Utf8Path::new(&source), &value)?;
+    println!("wrote `{source}`");
+    Ok(())
+}
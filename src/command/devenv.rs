@@ -0,0 +1,267 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    BoxResult,
+};
+use camino::Utf8PathBuf;
+use std::{fmt::Write, process::ExitStatus};
+
+const DEFAULT_BASE_IMAGE: &str = "ubuntu:22.04";
+
+/// The usage of `xtask devenv`.
+pub const USAGE: Usage = Usage {
+    name: "devenv",
+    summary: "Generate a development container installing the configured tool versions",
+    args: "[SUBCOMMAND]",
+    description: &[
+        "Generates a development container from the tool requirements of the xtask config, so that the container installs the",
+        "same versions that the xtask commands validate: `xtask.clang.version`, `xtask.cmake.version`, the `version` of",
+        "`xtask.tools.cmake` and `xtask.tools.ninja`, `xtask.python.version`, and the stable and nightly Rust toolchains (with",
+        "the components and targets of `rust-toolchain.toml`).",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PROFILE,
+        Entry {
+            name: "--output-dir <DIR>",
+            about: &["The directory to write the files to, relative to the workspace root [default: .devcontainer]"],
+        },
+        Entry {
+            name: "--base-image <IMAGE>",
+            about: &["The Debian or Ubuntu image to build on [default: ubuntu:22.04]"],
+        },
+        Entry {
+            name: "--force",
+            about: &["Overwrite existing files"],
+        },
+        Entry {
+            name: "--check",
+            about: &["Check that the files are up to date instead of writing them"],
+        },
+    ],
+    subcommands: &[Entry {
+        name: "generate",
+        about: &["Write `Dockerfile` and `devcontainer.json` to the output directory"],
+    }],
+    examples: &[],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - A configured tool version is malformed (e.g. a clang version without a major version)
+/// - An output file already exists (without `--force`) or cannot be written
+/// - With `--check`, an output file is missing or differs from the generated one
+pub fn devenv(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let Some(devenv_subcommand) = context.args.opt_free_from_str::<String>()? else {
+        println!("{help}\n");
+        return Ok(None);
+    };
+
+    if devenv_subcommand != "generate" {
+        println!("{help}\n");
+        return Err(Error::new(
+            Kind::Validation,
+            format!("unrecognized `xtask devenv` subcommand `{devenv_subcommand}`"),
+        )
+        .into());
+    }
+
+    let output_dir = context
+        .args
+        .opt_value_from_str::<_, Utf8PathBuf>("--output-dir")?
+        .unwrap_or_else(|| Utf8PathBuf::from(".devcontainer"));
+    let base_image = context
+        .args
+        .opt_value_from_str::<_, String>("--base-image")?
+        .unwrap_or_else(|| DEFAULT_BASE_IMAGE.to_owned());
+    let force = context.args.contains("--force");
+    let check = context.args.contains("--check");
+
+    crate::handler::unused(context.args)?;
+
+    let output_dir = context.config.cargo_metadata.workspace_root.join(output_dir);
+    let files = [
+        (output_dir.join("Dockerfile"), dockerfile(&context, &base_image)?),
+        (output_dir.join("devcontainer.json"), devcontainer_json(&context)?),
+    ];
+
+    if check {
+        let stale = files
+            .iter()
+            .filter(|(path, contents)| std::fs::read_to_string(path).ok().as_ref() != Some(contents))
+            .map(|(path, _)| path)
+            .collect::<Vec<_>>();
+        if !stale.is_empty() {
+            for path in &stale {
+                println!("{path} is missing or out of date");
+            }
+            return Err("the development container is out of date\nRegenerate it with `xtask devenv generate`".into());
+        }
+        crate::handler::status(format_args!(
+            "the development container in `{output_dir}` is up to date"
+        ));
+        return Ok(None);
+    }
+
+    if !force {
+        if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+            return Err(format!("`{path}` already exists\nPass `--force` to overwrite it").into());
+        }
+    }
+    std::fs::create_dir_all(&output_dir).map_err(|err| format!("failed to create `{output_dir}`: {err}"))?;
+    for (path, contents) in &files {
+        std::fs::write(path, contents).map_err(|err| format!("failed to write `{path}`: {err}"))?;
+        crate::handler::status(format_args!("wrote `{path}`"));
+    }
+
+    Ok(None)
+}
+
+// The Dockerfile installing the configured tool requirements on `base_image`.
+fn dockerfile(context: &Context<'_>, base_image: &str) -> BoxResult<String> {
+    let xtask = &context.config.xtask;
+    let tool_version = |tool: &str| xtask.tools.get(tool).and_then(|config| config.version.as_deref());
+
+    let mut packages = vec![
+        "build-essential",
+        "ca-certificates",
+        "curl",
+        "git",
+        "gnupg",
+        "lsb-release",
+    ];
+    packages.extend(["python3", "python3-pip", "software-properties-common", "wget"]);
+    let mut pip = vec![];
+    match xtask.cmake.version.as_deref().or_else(|| tool_version("cmake")) {
+        Some(version) => pip.push(format!("cmake>={version}")),
+        None => packages.push("cmake"),
+    }
+    match tool_version("ninja") {
+        Some(version) => pip.push(format!("ninja>={version}")),
+        None => packages.push("ninja-build"),
+    }
+
+    let mut dockerfile = String::new();
+    writeln!(
+        dockerfile,
+        "# Generated by `xtask devenv generate` from the xtask config; do not edit."
+    )?;
+    writeln!(dockerfile, "FROM {base_image}\n")?;
+    writeln!(dockerfile, "ARG DEBIAN_FRONTEND=noninteractive")?;
+    writeln!(
+        dockerfile,
+        "RUN apt-get update \\\n    && apt-get install -y --no-install-recommends {} \\\n    && rm -rf /var/lib/apt/lists/*",
+        packages.join(" ")
+    )?;
+
+    if let Some(version) = &xtask.python.version {
+        let minor = version.split('.').take(2).collect::<Vec<_>>().join(".");
+        writeln!(dockerfile, "\n# xtask.python.version")?;
+        writeln!(
+            dockerfile,
+            "RUN add-apt-repository -y ppa:deadsnakes/ppa \\\n    && apt-get update \\\n    && apt-get install -y \
+             --no-install-recommends python{minor} python{minor}-venv \\\n    && rm -rf /var/lib/apt/lists/* \\\n    \
+             && python{minor} -m venv /opt/venv"
+        )?;
+        // NOTE: `detection::detect_python` prefers the interpreter of the active virtualenv
+        writeln!(dockerfile, "ENV VIRTUAL_ENV=/opt/venv PATH=/opt/venv/bin:$PATH")?;
+    }
+
+    if !pip.is_empty() {
+        writeln!(dockerfile, "\n# xtask.cmake.version, xtask.tools")?;
+        writeln!(
+            dockerfile,
+            "RUN python3 -m pip install --no-cache-dir '{}'",
+            pip.join("' '")
+        )?;
+    }
+
+    if !xtask.clang.version.is_empty() {
+        let major = xtask
+            .clang
+            .version
+            .split('.')
+            .next()
+            .filter(|major| major.parse::<u32>().is_ok())
+            .ok_or_else(|| format!("malformed `xtask.clang.version` `{}`", xtask.clang.version))?;
+        writeln!(dockerfile, "\n# xtask.clang.version")?;
+        writeln!(
+            dockerfile,
+            "RUN wget -qO /tmp/llvm.sh https://apt.llvm.org/llvm.sh \\\n    && bash /tmp/llvm.sh {major} \\\n    && \
+             apt-get install -y --no-install-recommends clang-format-{major} clang-tidy-{major} clang-tools-{major} \
+             \\\n    && rm -rf /tmp/llvm.sh /var/lib/apt/lists/*"
+        )?;
+        writeln!(dockerfile, "ENV PATH=/usr/lib/llvm-{major}/bin:$PATH")?;
+    }
+
+    writeln!(dockerfile, "\n# xtask.rust.toolchain, rust-toolchain.toml")?;
+    writeln!(
+        dockerfile,
+        "ENV RUSTUP_HOME=/usr/local/rustup CARGO_HOME=/usr/local/cargo"
+    )?;
+    writeln!(dockerfile, "ENV PATH=/usr/local/cargo/bin:$PATH")?;
+    writeln!(dockerfile, "{}", rustup_commands(context).join(" \\\n    && "))?;
+    Ok(dockerfile)
+}
+
+// The commands installing rustup with the stable and nightly toolchains.
+fn rustup_commands(context: &Context<'_>) -> Vec<String> {
+    let stable = context.stable_toolchain();
+    // NOTE: the preferred nightly rather than the first installed one, since the toolchains are installed in the image
+    let nightly = context.nightly_candidates()[0];
+    let toolchain = context.config.rust_toolchain.as_ref().map(|config| &config.toolchain);
+    let mut components = vec!["clippy".to_owned(), "rustfmt".to_owned()];
+    components.extend(toolchain.iter().flat_map(|config| config.components.iter().cloned()));
+    components.sort();
+    components.dedup();
+    let targets = toolchain.map(|config| config.targets.as_slice()).unwrap_or_default();
+
+    let mut commands = vec![format!(
+        "RUN curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | sh -s -- -y --profile minimal \
+         --default-toolchain {stable}"
+    )];
+    for toolchain in [stable, nightly] {
+        let mut command = format!("rustup toolchain install {toolchain} --profile minimal");
+        for component in &components {
+            write!(command, " --component {component}").ok();
+        }
+        for target in targets {
+            write!(command, " --target {target}").ok();
+        }
+        commands.push(command);
+    }
+    commands.push(format!("rustup component add miri rust-src --toolchain {nightly}"));
+    commands
+}
+
+// The `devcontainer.json` building the generated Dockerfile.
+fn devcontainer_json(context: &Context<'_>) -> BoxResult<String> {
+    let name = context
+        .config
+        .cargo_metadata
+        .workspace_root
+        .file_name()
+        .unwrap_or("workspace");
+    let json = serde_json::json!({
+        "name": name,
+        "build": { "dockerfile": "Dockerfile" },
+        "postCreateCommand": "cargo xtask install-tools",
+        "customizations": {
+            "vscode": {
+                "extensions": ["rust-lang.rust-analyzer", "llvm-vs-code-extensions.vscode-clangd"],
+            },
+        },
+    });
+    Ok(serde_json::to_string_pretty(&json)? + "\n")
+}
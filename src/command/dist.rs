@@ -0,0 +1,380 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{ffi::OsString, process::ExitStatus};
+
+/// The usage of `xtask dist`.
+pub const USAGE: Usage = Usage {
+    name: "dist",
+    summary: "Package release builds of the binaries and libraries into distribution archives",
+    args: "",
+    description: &[
+        "Builds the binaries, static libraries, and dynamic libraries of the selected packages with `cargo build --release`",
+        "and, if the workspace has a `CMakeLists.txt`, the CMake project with `xtask cmake configure`, `cmake --build`, and",
+        "`cmake --install`. These are staged in `<output dir>/<name>-<version>-<target>`, along with the license files of",
+        "the workspace root, a `THIRD-PARTY-LICENSES.md` from `xtask licenses`, the files of `xtask.dist.include`, and a",
+        "`manifest.json` listing the staged files, which is then archived as a `.tar.gz` (or a `.zip` for Windows targets).",
+        "",
+        "Each run packages one target: the host, the one given with `--target`, or that of the target preset.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::TARGET_PRESET,
+        usage::HERMETIC,
+        usage::PACKAGE,
+        Entry {
+            name: "--target <TRIPLE>",
+            about: &["The Rust target to package for [default: the host, or the target of the target preset]"],
+        },
+        Entry {
+            name: "--format <FORMAT>",
+            about: &["The archive format (tar.gz, zip) [default: zip for Windows targets, otherwise tar.gz]"],
+        },
+        Entry {
+            name: "--output-dir <DIR>",
+            about: &["The directory to write the archives to [default: <target dir>/dist]"],
+        },
+        Entry {
+            name: "--no-cmake",
+            about: &["Leave out the CMake install tree"],
+        },
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[
+        Entry {
+            name: "xtask dist -p my-tool",
+            about: &["Package the release build of `my-tool` for the host"],
+        },
+        Entry {
+            name: "xtask dist --target-preset android-arm64 --no-cmake",
+            about: &["Package the Rust libraries for 64-bit ARM Android"],
+        },
+    ],
+};
+
+// The names of the files of the workspace root shipped as the project's own licenses.
+const LICENSE_PREFIXES: &[&str] = &["LICENSE", "LICENCE", "COPYING", "NOTICE"];
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - `--target` differs from the target of the selected target preset, or the `CMake` project cannot be built for it
+/// - A command process fails to start
+/// - A build or the archiver fails with non-zero exit status
+/// - The staging directory or an archive cannot be written
+pub fn dist(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let packages = crate::workspace::packages(context.config, context.args, &[])?;
+    let target = context.args.opt_value_from_str::<_, String>("--target")?;
+    let format = context.args.opt_value_from_str::<_, String>("--format")?;
+    let output_dir = context.args.opt_value_from_str::<_, Utf8PathBuf>("--output-dir")?;
+    let no_cmake = context.args.contains("--no-cmake");
+
+    crate::handler::unused(context.args)?;
+
+    let root = &context.config.cargo_metadata.workspace_root;
+    let host = host_triple(&context)?;
+    let preset = context.selected_target_preset();
+    let triple = match (target, preset) {
+        (Some(target), Some(preset)) if target != preset.triple => {
+            return Err(format!(
+                "`--target {target}` differs from the target `{}` of target preset `{}`",
+                preset.triple, preset.name
+            )
+            .into());
+        },
+        (Some(target), _) => target,
+        (None, Some(preset)) => preset.triple.to_owned(),
+        (None, None) => host.clone(),
+    };
+    let cmake = !no_cmake && root.join("CMakeLists.txt").exists();
+    if cmake && triple != host && preset.is_none() {
+        return Err(format!(
+            "the CMake project cannot be built for `{triple}` without a toolchain\nSelect a target preset with \
+             `--target-preset`, or leave out the CMake install tree with `--no-cmake`"
+        )
+        .into());
+    }
+    let format = format.unwrap_or_else(|| if triple.contains("windows") { "zip" } else { "tar.gz" }.to_owned());
+    if !["tar.gz", "zip"].contains(&format.as_str()) {
+        return Err(Error::new(
+            Kind::Validation,
+            format!("unrecognized archive format `{format}`: expected `tar.gz` or `zip`"),
+        )
+        .into());
+    }
+
+    let selected = context
+        .config
+        .cargo_metadata
+        .workspace_packages()
+        .into_iter()
+        .filter(|package| packages.is_empty() || packages.contains(&package.name))
+        .collect::<Vec<_>>();
+    let primary = selected.first().ok_or("the workspace has no packages to package")?;
+    let name = context.config.xtask.dist.name.as_deref().unwrap_or(&primary.name);
+    let version = primary.version.to_string();
+
+    let target_dir = crate::workspace::target_dir(context.config).to_owned();
+    let output_dir = output_dir.map_or_else(|| target_dir.join("dist"), |dir| root.join(dir));
+    let stage_name = format!("{name}-{version}-{triple}");
+    let stage = output_dir.join(&stage_name);
+    if stage.exists() {
+        std::fs::remove_dir_all(&stage).map_err(|err| format!("failed to remove `{stage}`: {err}"))?;
+    }
+    std::fs::create_dir_all(&stage).map_err(|err| format!("failed to create `{stage}`: {err}"))?;
+
+    let status = cargo_build(&context, &packages, &triple)?;
+    if !status.success() {
+        return Ok(Some(status));
+    }
+    let release_dir = target_dir.join(&triple).join("release");
+    for package in &selected {
+        for target in &package.targets {
+            for kind in &target.kind {
+                if let Some((dir, file)) = artifact(kind, &target.name, &triple) {
+                    copy_file(&release_dir.join(&file), &stage.join(dir).join(&file))?;
+                }
+            }
+        }
+    }
+
+    if cmake {
+        let status = cmake_install(&context, &stage)?;
+        if !status.success() {
+            return Ok(Some(status));
+        }
+    }
+
+    stage_licenses(&context, &stage)?;
+    let revision = crate::git::rev_parse(context.runner, root, "HEAD").ok().flatten();
+    write_manifest(&stage, name, &version, &triple, revision.as_deref())?;
+
+    let archive = output_dir.join(format!("{stage_name}.{format}"));
+    let status = archive_stage(&context, &output_dir, &stage_name, &archive, &format)?;
+    if status.success() {
+        crate::handler::status(format_args!("wrote `{archive}`"));
+    }
+
+    Ok(Some(status))
+}
+
+// The host target triple of the Rust toolchain, from `rustc -vV`.
+fn host_triple(context: &Context<'_>) -> BoxResult<String> {
+    let mut cmd = context.command("rustc");
+    cmd.arg("-vV");
+    let output = context.runner.output(&mut cmd)?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`rustc -vV` failed: \"{err}\"").into());
+    }
+    String::from_utf8(output.stdout)?
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| "`rustc -vV` printed no host target".into())
+}
+
+// Build the selected packages (or the whole workspace) in release mode for `triple`.
+fn cargo_build(context: &Context<'_>, packages: &[String], triple: &str) -> BoxResult<ExitStatus> {
+    let mut cmd = context.cargo(None);
+    cmd.current_dir(&context.config.cargo_metadata.workspace_root);
+    cmd.args(["build", "--release", "--target", triple]);
+    if packages.is_empty() {
+        cmd.arg("--workspace");
+    }
+    for package in packages {
+        cmd.args(["--package", package]);
+    }
+    cmd.args(context.default_args("dist"));
+    cmd.args(&context.tool_args);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    Ok(context.runner.status(&mut cmd)?)
+}
+
+// The staging subdirectory and file name of the artifact of a cargo target of `kind` named `name`, if it is shipped.
+fn artifact(kind: &str, name: &str, triple: &str) -> Option<(&'static str, String)> {
+    let windows = triple.contains("windows");
+    let apple = triple.contains("apple");
+    // NOTE: library artifacts are named after the crate, which has underscores in place of dashes
+    let lib = name.replace('-', "_");
+    match kind {
+        "bin" if windows => Some(("bin", format!("{name}.exe"))),
+        "bin" => Some(("bin", name.to_owned())),
+        "staticlib" if windows && triple.contains("msvc") => Some(("lib", format!("{lib}.lib"))),
+        "staticlib" => Some(("lib", format!("lib{lib}.a"))),
+        "cdylib" if windows => Some(("bin", format!("{lib}.dll"))),
+        "cdylib" if apple => Some(("lib", format!("lib{lib}.dylib"))),
+        "cdylib" => Some(("lib", format!("lib{lib}.so"))),
+        _ => None,
+    }
+}
+
+// Configure (with `xtask cmake configure`), build, and install the CMake project in release mode into `stage`.
+fn cmake_install(context: &Context<'_>, stage: &Utf8Path) -> BoxResult<ExitStatus> {
+    let configure = crate::command::SUBCOMMANDS
+        .iter()
+        .find(|subcommand| subcommand.usage.name == "cmake")
+        .ok_or_else(|| Error::new(Kind::Internal, "missing `xtask` subcommand `cmake`"))?;
+    let mut args = pico_args::Arguments::from_vec(vec!["configure".into()]);
+    let tool_args = vec![OsString::from("-DCMAKE_BUILD_TYPE=Release")];
+    if let Some(status) = configure.invoke(context.inherit(&mut args, tool_args))? {
+        if !status.success() {
+            return Ok(status);
+        }
+    }
+
+    let root = &context.config.cargo_metadata.workspace_root;
+    let mut status = crate::runner::exit_status(0);
+    for step in [vec!["--build", "build", "--config", "Release"], vec![
+        "--install",
+        "build",
+        "--config",
+        "Release",
+        "--prefix",
+        stage.as_str(),
+    ]] {
+        let mut cmd = context.command("cmake");
+        cmd.current_dir(root);
+        cmd.args(step);
+        if context.print_env {
+            crate::handler::print_env(&cmd);
+        }
+        status = context.runner.status(&mut cmd)?;
+        if !status.success() {
+            break;
+        }
+    }
+    Ok(status)
+}
+
+// Copy the license files of the workspace root and the files of `xtask.dist.include` into `stage`, and write the
+// report of `xtask licenses` to `THIRD-PARTY-LICENSES.md` there.
+fn stage_licenses(context: &Context<'_>, stage: &Utf8Path) -> BoxResult<()> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    for entry in root.read_dir_utf8()? {
+        let entry = entry?;
+        let upper = entry.file_name().to_uppercase();
+        if entry.path().is_file() && LICENSE_PREFIXES.iter().any(|prefix| upper.starts_with(prefix)) {
+            copy_file(entry.path(), &stage.join(entry.file_name()))?;
+        }
+    }
+    for file in &context.config.xtask.dist.include {
+        let name = file
+            .file_name()
+            .ok_or_else(|| format!("`{file}` in `xtask.dist.include` has no file name"))?;
+        copy_file(&root.join(file), &stage.join(name))?;
+    }
+
+    let licenses = crate::command::SUBCOMMANDS
+        .iter()
+        .find(|subcommand| subcommand.usage.name == "licenses")
+        .ok_or_else(|| Error::new(Kind::Internal, "missing `xtask` subcommand `licenses`"))?;
+    let report = stage.join("THIRD-PARTY-LICENSES.md");
+    let mut args = pico_args::Arguments::from_vec(vec!["--output".into(), report.into_string().into()]);
+    licenses.invoke(context.inherit(&mut args, vec![]))?;
+    Ok(())
+}
+
+// Write `manifest.json` to `stage`, listing the staged files with their sizes.
+fn write_manifest(stage: &Utf8Path, name: &str, version: &str, triple: &str, revision: Option<&str>) -> BoxResult<()> {
+    let mut files = vec![];
+    staged_files(stage, stage, &mut files)?;
+    files.sort();
+    let files = files
+        .iter()
+        .map(|(path, size)| serde_json::json!({ "path": path, "size": size }))
+        .collect::<Vec<_>>();
+    let manifest = serde_json::json!({
+        "name": name,
+        "version": version,
+        "target": triple,
+        "revision": revision,
+        "files": files,
+    });
+    let path = stage.join("manifest.json");
+    std::fs::write(&path, serde_json::to_string_pretty(&manifest)? + "\n")
+        .map_err(|err| format!("failed to write `{path}`: {err}"))?;
+    Ok(())
+}
+
+// The files under `dir`, with their paths relative to `stage` (with `/` separators) and sizes.
+fn staged_files(stage: &Utf8Path, dir: &Utf8Path, files: &mut Vec<(String, u64)>) -> BoxResult<()> {
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            staged_files(stage, path, files)?;
+        } else {
+            let relative = path
+                .strip_prefix(stage)?
+                .components()
+                .map(|component| component.as_str());
+            files.push((relative.collect::<Vec<_>>().join("/"), entry.metadata()?.len()));
+        }
+    }
+    Ok(())
+}
+
+// Archive the directory `stage_name` of `output_dir` as `archive`.
+fn archive_stage(
+    context: &Context<'_>,
+    output_dir: &Utf8Path,
+    stage_name: &str,
+    archive: &Utf8Path,
+    format: &str,
+) -> BoxResult<ExitStatus> {
+    // NOTE: `zip` adds to an existing archive rather than replacing it
+    if archive.exists() {
+        std::fs::remove_file(archive).map_err(|err| format!("failed to remove `{archive}`: {err}"))?;
+    }
+    let mut cmd = if format == "tar.gz" {
+        let mut cmd = context.command("tar");
+        cmd.args(["-czf", archive.as_str(), stage_name]);
+        cmd
+    } else if cfg!(windows) {
+        // NOTE: the `tar` of Windows is bsdtar, which picks the format from the archive's extension with `-a`
+        let mut cmd = context.command("tar");
+        cmd.args(["-a", "-cf", archive.as_str(), stage_name]);
+        cmd
+    } else {
+        let mut cmd = context.command("zip");
+        cmd.args(["-qr", archive.as_str(), stage_name]);
+        cmd
+    };
+    cmd.current_dir(output_dir);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    Ok(context.runner.status(&mut cmd)?)
+}
+
+fn copy_file(from: &Utf8Path, to: &Utf8Path) -> BoxResult<()> {
+    if let Some(dir) = to.parent() {
+        std::fs::create_dir_all(dir).map_err(|err| format!("failed to create `{dir}`: {err}"))?;
+    }
+    std::fs::copy(from, to).map_err(|err| format!("failed to copy `{from}` to `{to}`: {err}"))?;
+    Ok(())
+}
@@ -1,5 +1,98 @@
-use crate::{command::Context, BoxResult};
-use std::process::{Command, ExitStatus};
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    cxx_sources::CxxSources,
+    error::{Error, Kind},
+    BoxResult,
+};
+use camino::Utf8PathBuf;
+use std::{
+    collections::BTreeSet,
+    ffi::OsString,
+    process::{Command, ExitStatus},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+/// The usage of `xtask doc`.
+pub const USAGE: Usage = Usage {
+    name: "doc",
+    summary: "Build the project's documentation",
+    args: "",
+    description: &[
+        "Builds the rustdoc documentation of the workspace with the nightly toolchain, and then (if",
+        "`xtask.doc.doxygen` is configured) the documentation of the C++ code with doxygen.",
+        "",
+        "With `--serve`, the documentation is then served on a local HTTP server, with an index page linking to the",
+        "documented crates and the C++ API. The workspace is watched for changes, on which the documentation is",
+        "rebuilt and the open pages are reloaded.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::TARGET_PRESET,
+        usage::HERMETIC,
+        Entry {
+            name: "--no-deps",
+            about: &["Document only the workspace crates, not their dependencies (also set by `xtask.doc.noDeps`)"],
+        },
+        Entry {
+            name: "--document-private-items",
+            about: &["Document private items as well (also set by `xtask.doc.documentPrivateItems`)"],
+        },
+        Entry {
+            name: "--deny-warnings",
+            about: &[
+                "Fail on rustdoc warnings (e.g. broken intra-doc links) by adding `-Dwarnings` to `RUSTDOCFLAGS`",
+                "(also set by `xtask.doc.denyWarnings`)",
+            ],
+        },
+        Entry {
+            name: "--check-links",
+            about: &[
+                "Check the links of the generated documentation afterwards (also set by `xtask.doc.checkLinks`),",
+                "with the checker configured in `xtask.doc.linkChecker`",
+            ],
+        },
+        Entry {
+            name: "--link-checker <NAME>",
+            about: &[
+                "Check the links with NAME, implying `--check-links`: `builtin` checks the links between the",
+                "generated pages, and `lychee` also checks external links",
+            ],
+        },
+        Entry {
+            name: "--open",
+            about: &["Open the documentation in a browser afterwards (the served documentation with `--serve`)"],
+        },
+        Entry {
+            name: "--serve [PORT]",
+            about: &["Serve the documentation on `127.0.0.1:PORT`, rebuilding it on changes [default: 8000]"],
+        },
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[
+        Entry {
+            name: "xtask doc --no-deps --deny-warnings --check-links",
+            about: &[
+                "Build the documentation of the workspace crates as CI would, failing on warnings and broken links",
+            ],
+        },
+        Entry {
+            name: "xtask doc --no-deps --serve --open",
+            about: &["Iterate on the documentation of the bridge API in a browser"],
+        },
+    ],
+};
 
 /// # Errors
 ///
@@ -8,32 +101,297 @@ use std::process::{Command, ExitStatus};
 /// - Tool validation fails (missing tools, incorrect versions, etc.)
 /// - The command process fails to start
 /// - The command invocation fails with non-zero exit status
+/// - With `--check-links`, the link checker is unknown, fails to start, or the generated pages cannot be read
+/// - With `--serve`, the port cannot be bound or the workspace cannot be watched
 pub fn doc(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = r#"
-xtask-doc
-
-USAGE:
-xtask doc
+    let help = USAGE.render();
 
-FLAGS:
--h, --help          Prints help information
--- '...'            Extra arguments to pass to the cargo command
-"#
-    .trim();
-
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
-    crate::handler::unused(context.args)?;
+    let config = &context.config.xtask.doc;
+    let no_deps = context.args.contains("--no-deps") || config.no_deps;
+    let document_private_items = context.args.contains("--document-private-items") || config.document_private_items;
+    let deny_warnings = context.args.contains("--deny-warnings") || config.deny_warnings;
+    let link_checker = context.args.opt_value_from_str::<_, String>("--link-checker")?;
+    let check_links = context.args.contains("--check-links") || config.check_links || link_checker.is_some();
+    let link_checker = link_checker
+        .or_else(|| config.link_checker.clone())
+        .unwrap_or_else(|| "builtin".into());
+    if !["builtin", "lychee"].contains(&link_checker.as_str()) {
+        let message = format!("unrecognized link checker `{link_checker}`: expected `builtin` or `lychee`");
+        return Err(Error::new(Kind::Validation, message).into());
+    }
 
-    let toolchain = crate::config::rust::toolchain::nightly(context.config);
+    let open = context.args.contains("--open");
+    // NOTE: the port is optional, so a `--serve` without one is taken as a plain flag
+    let serve = match context.args.opt_value_from_str::<_, u16>("--serve") {
+        Ok(port) => port,
+        Err(_) => context.args.contains("--serve").then_some(DEFAULT_PORT),
+    };
 
-    let mut cmd = Command::new("cargo");
-    cmd.current_dir(crate::workspace::project_root()?);
-    cmd.args([&format!("+{toolchain}"), "doc"]);
-    cmd.args(context.tool_args);
-    let status = cmd.status()?;
+    crate::handler::unused(context.args)?;
 
+    let options = Options {
+        no_deps,
+        document_private_items,
+        deny_warnings,
+        open: open && serve.is_none(),
+    };
+    let status = build(&context, options)?;
+    if !status.success() {
+        return Ok(Some(status));
+    }
+    if check_links {
+        let status = check_doc_links(&context, &link_checker)?;
+        if !status.success() {
+            return Ok(Some(status));
+        }
+    }
+    if let Some(port) = serve {
+        serve_docs(&context, Options { open: false, ..options }, port, open)?;
+    }
     Ok(Some(status))
 }
+
+// The default port of `--serve`.
+const DEFAULT_PORT: u16 = 8000;
+
+// The options of the documentation build.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Copy)]
+struct Options {
+    no_deps: bool,
+    document_private_items: bool,
+    deny_warnings: bool,
+    open: bool,
+}
+
+// Builds the rustdoc documentation and then, if configured, the doxygen documentation.
+fn build(context: &Context<'_>, options: Options) -> BoxResult<ExitStatus> {
+    let toolchain = context.nightly_toolchain()?;
+
+    let mut cmd = context.cargo(Some(toolchain));
+    cmd.current_dir(crate::workspace::project_root(context.config));
+    cmd.args(["doc"]);
+    cmd.args(context.target_args());
+    if options.no_deps {
+        cmd.arg("--no-deps");
+    }
+    if options.document_private_items {
+        cmd.arg("--document-private-items");
+    }
+    if options.open {
+        cmd.arg("--open");
+    }
+    if options.deny_warnings {
+        cmd.env("RUSTDOCFLAGS", deny_warnings_flags(&cmd));
+    }
+    cmd.args(context.default_args("doc"));
+    cmd.args(&context.tool_args);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
+    let Some(doxygen) = &context.config.xtask.doc.doxygen else {
+        return Ok(status);
+    };
+    if !status.success() {
+        return Ok(status);
+    }
+
+    let mut cmd = context.command("doxygen");
+    cmd.arg(&doxygen.doxyfile);
+    cmd.current_dir(&context.config.cargo_metadata.workspace_root);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    Ok(context.runner.status(&mut cmd)?)
+}
+
+// Serves the documentation on `port` (see `crate::doc_server`), opening it in a browser if `open` is set, and rebuilds
+// it whenever the workspace changes, until interrupted.
+fn serve_docs(context: &Context<'_>, options: Options, port: u16, open: bool) -> BoxResult<()> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let rustdoc_dir = doc_dir(context);
+    let doxygen_dir = context
+        .config
+        .xtask
+        .doc
+        .doxygen
+        .as_ref()
+        .map(|doxygen| root.join(&doxygen.html_dir));
+    let crates = crate::doc_links::crate_dirs(&rustdoc_dir, documented_crates(context))
+        .iter()
+        .filter_map(|dir| dir.file_name().map(ToOwned::to_owned))
+        .collect();
+    // NOTE: the generated documentation must not count as a change of the workspace
+    let mut excluded = vec![crate::workspace::target_dir(context.config).to_owned()];
+    excluded.extend(doxygen_dir.clone());
+    let site = crate::doc_server::Site {
+        rustdoc_dir,
+        crates,
+        doxygen_dir,
+    };
+    let generation = crate::doc_server::start(site, port)?;
+
+    let url = format!("http://127.0.0.1:{port}/");
+    crate::handler::status(format_args!(
+        "serving the documentation at {url}, rebuilding it on changes (press Ctrl-C to stop)"
+    ));
+    if open {
+        open_browser(context, &url);
+    }
+    let sources = watched_sources(context)?;
+    let mut stamp = crate::doc_server::sources_stamp(&sources, &excluded)?;
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+        if crate::doc_server::sources_stamp(&sources, &excluded)? == stamp {
+            continue;
+        }
+        crate::handler::status(format_args!("rebuilding the documentation"));
+        if build(context, options)?.success() {
+            generation.fetch_add(1, Ordering::SeqCst);
+        } else {
+            println!("the documentation failed to build, so the previous build is still served");
+        }
+        // NOTE: the build may itself change the workspace (e.g. `Cargo.lock`), which is not a change to rebuild for
+        stamp = crate::doc_server::sources_stamp(&sources, &excluded)?;
+    }
+}
+
+// The sources the documentation is generated from, which `xtask doc --serve` watches for changes: the manifests of the
+// workspace packages and the directories of their targets (e.g. `src` and `tests`), and, with doxygen, the `Doxyfile`
+// and its inputs (or else the directories of the C/C++ sources).
+//
+// NOTE: the rest of the workspace (e.g. the CMake build tree) can be large, so it is not walked every second
+fn watched_sources(context: &Context<'_>) -> BoxResult<Vec<Utf8PathBuf>> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let mut sources = BTreeSet::new();
+    for package in context.config.cargo_metadata.workspace_packages() {
+        sources.insert(package.manifest_path.clone());
+        for target in &package.targets {
+            // NOTE: a target at the root of its package (e.g. `build.rs`) is watched alone, not the whole package
+            match target.src_path.parent() {
+                Some(dir) if package.manifest_path.parent() != Some(dir) => sources.insert(dir.to_owned()),
+                _ => sources.insert(target.src_path.clone()),
+            };
+        }
+    }
+    if let Some(doxygen) = &context.config.xtask.doc.doxygen {
+        let doxyfile = root.join(&doxygen.doxyfile);
+        let contents =
+            std::fs::read_to_string(&doxyfile).map_err(|err| format!("failed to read `{doxyfile}`: {err}"))?;
+        let inputs = crate::doc_server::doxygen_inputs(&contents);
+        if inputs.is_empty() {
+            let files = CxxSources::new(context.config)?.tracked(context.runner)?;
+            sources.extend(
+                files
+                    .iter()
+                    .flatten()
+                    .filter_map(|file| file.parent().map(ToOwned::to_owned)),
+            );
+        } else {
+            sources.extend(inputs.iter().map(|input| root.join(input)));
+        }
+        sources.insert(doxyfile);
+    }
+    // NOTE: the directories within others are walked with them
+    let dirs = sources.clone();
+    Ok(sources
+        .into_iter()
+        .filter(|source| !dirs.iter().any(|dir| source != dir && source.starts_with(dir)))
+        .collect())
+}
+
+// Opens `url` in the default browser, or tells the user to if that fails.
+fn open_browser(context: &Context<'_>, url: &str) {
+    let mut cmd = if cfg!(target_os = "macos") {
+        context.command("open")
+    } else if cfg!(windows) {
+        let mut cmd = context.command("cmd");
+        cmd.args(["/C", "start", ""]);
+        cmd
+    } else {
+        context.command("xdg-open")
+    };
+    cmd.arg(url);
+    if !context.runner.status(&mut cmd).map_or(false, |status| status.success()) {
+        println!("failed to open a browser, so open {url} yourself");
+    }
+}
+
+// The names of the workspace crates that are documented.
+fn documented_crates<'a>(context: &Context<'a>) -> impl Iterator<Item = &'a str> {
+    context
+        .config
+        .cargo_metadata
+        .workspace_packages()
+        .into_iter()
+        .flat_map(|package| &package.targets)
+        .filter(|target| target.kind.iter().any(|kind| kind == "lib" || kind == "bin"))
+        .map(|target| target.name.as_str())
+}
+
+// Checks the links of the generated documentation with `link_checker`.
+fn check_doc_links(context: &Context<'_>, link_checker: &str) -> BoxResult<ExitStatus> {
+    let doc_dir = doc_dir(context);
+    let crates = documented_crates(context);
+    let dirs = crate::doc_links::crate_dirs(&doc_dir, crates);
+    if link_checker == "lychee" {
+        let mut cmd = context.command("lychee");
+        cmd.arg("--no-progress");
+        // NOTE: offline, `lychee` checks only the local links, like the builtin checker
+        if context.offline {
+            cmd.arg("--offline");
+        }
+        cmd.args(context.default_args("lychee"));
+        cmd.args(&dirs);
+        if context.print_env {
+            crate::handler::print_env(&cmd);
+        }
+        let status = context.runner.status(&mut cmd)?;
+        if !status.success() {
+            return Err(Error::new(Kind::Lint, "`lychee` found broken links").into());
+        }
+        return Ok(status);
+    }
+
+    let broken = crate::doc_links::broken_links(&dirs)?;
+    for link in &broken {
+        let file = link.file.strip_prefix(&doc_dir).unwrap_or(&link.file);
+        println!("{file}: broken link `{}` ({})", link.href, link.reason);
+    }
+    if !broken.is_empty() {
+        return Err(Error::new(Kind::Lint, format!("doc: {} broken links", broken.len())).into());
+    }
+    crate::handler::status("doc: 0 broken links");
+    Ok(crate::runner::exit_status(0))
+}
+
+// The `RUSTDOCFLAGS` for `cmd` with `-Dwarnings` added to those it would otherwise run with.
+//
+// NOTE: `RUSTDOCFLAGS` takes precedence over `build.rustdocflags` of `.cargo/config.toml`, which is then ignored
+fn deny_warnings_flags(cmd: &Command) -> OsString {
+    let flags = match cmd.get_envs().find(|(key, _)| *key == "RUSTDOCFLAGS") {
+        Some((_, val)) => val.map(ToOwned::to_owned),
+        None => std::env::var_os("RUSTDOCFLAGS"),
+    };
+    let mut flags = flags.unwrap_or_default();
+    if !flags.is_empty() {
+        flags.push(" ");
+    }
+    flags.push("-Dwarnings");
+    flags
+}
+
+// The directory cargo generates the documentation in: `doc` of the target directory, or of its directory for the
+// triple of the selected target preset.
+fn doc_dir(context: &Context<'_>) -> Utf8PathBuf {
+    let mut dir = crate::workspace::target_dir(context.config).to_owned();
+    if let Some(preset) = context.selected_target_preset() {
+        dir.push(preset.triple);
+    }
+    dir.join("doc")
+}
@@ -1,39 +1,291 @@
-use crate::{command::Context, BoxResult};
-use std::process::{Command, ExitStatus};
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    runner::Runner,
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    process::{Command, ExitStatus},
+};
+
+// The rustfmt options that are only available with a nightly toolchain (`unstable_features`).
+const NIGHTLY_OPTIONS: &[&str] = &[
+    "binop_separator",
+    "blank_lines_lower_bound",
+    "blank_lines_upper_bound",
+    "brace_style",
+    "combine_control_expr",
+    "comment_width",
+    "condense_wildcard_suffixes",
+    "control_brace_style",
+    "doc_comment_code_block_width",
+    "empty_item_single_line",
+    "enum_discrim_align_threshold",
+    "error_on_line_overflow",
+    "error_on_unformatted",
+    "fn_single_line",
+    "force_multiline_blocks",
+    "format_code_in_doc_comments",
+    "format_generated_files",
+    "format_macro_bodies",
+    "format_macro_matchers",
+    "format_strings",
+    "group_imports",
+    "hex_literal_case",
+    "hide_parse_errors",
+    "ignore",
+    "imports_granularity",
+    "imports_indent",
+    "imports_layout",
+    "indent_style",
+    "inline_attribute_width",
+    "normalize_comments",
+    "normalize_doc_attributes",
+    "overflow_delimited_expr",
+    "reorder_impl_items",
+    "required_version",
+    "skip_children",
+    "space_after_colon",
+    "space_before_colon",
+    "spaces_around_ranges",
+    "struct_field_align_threshold",
+    "struct_lit_single_line",
+    "trailing_comma",
+    "trailing_semicolon",
+    "type_punctuation_density",
+    "unstable_features",
+    "version",
+    "where_single_line",
+    "wrap_comments",
+];
+
+/// The usage of `xtask fmt`.
+pub const USAGE: Usage = Usage {
+    name: "fmt",
+    summary: "Format the project's Rust code",
+    args: "",
+    description: &[
+        "The toolchain is chosen by the options in `rustfmt.toml` (or `.rustfmt.toml`): nightly if it uses options only",
+        "available on nightly (e.g. `imports_granularity`), and stable otherwise.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::HERMETIC,
+        Entry {
+            name: "--check",
+            about: &[
+                "Check formatting without rewriting files",
+                "Exits with 0 if no files need formatting, 5 if some do, and 1 on errors",
+            ],
+        },
+        Entry {
+            name: "--stable",
+            about: &["Format with the stable toolchain, failing if the rustfmt config needs nightly"],
+        },
+        Entry {
+            name: "--since <REF>",
+            about: &[
+                "Only format the Rust files of the workspace packages changed since the git ref, with rustfmt",
+                "itself (which also formats the out-of-line modules they declare)",
+            ],
+        },
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[],
+};
 
 /// # Errors
 ///
 /// Will return `Err` under the following circumstances:
 /// - Argument processing fails (e.g. invalid arguments)
 /// - Tool validation fails (missing tools, incorrect versions, etc.)
+/// - The rustfmt config is malformed, or uses nightly-only options with `--stable`
 /// - The command process fails to start
 /// - The command invocation fails with non-zero exit status
+/// - With `--since`, `git diff` fails or the ref does not name a commit
 pub fn fmt(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = r#"
-xtask-format
-
-USAGE:
-xtask format
-
-FLAGS:
--h, --help          Prints help information
--- '...'            Extra arguments to pass to the cargo command
-"#
-    .trim();
+    let help = USAGE.render();
 
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
+    let check = context.args.contains("--check");
+    let stable = context.args.contains("--stable");
+    let since = context.args.opt_value_from_str::<_, String>("--since")?;
+
     crate::handler::unused(context.args)?;
 
-    let toolchain = crate::config::rust::toolchain::nightly(context.config);
+    let nightly_options = nightly_options(&context.config.cargo_metadata.workspace_root)?;
+    let toolchain = match (nightly_options.is_empty(), stable) {
+        (true, _) => context.stable_toolchain(),
+        (false, false) => context.nightly_toolchain()?,
+        (false, true) => {
+            return Err(format!(
+                "the rustfmt config uses options only available on nightly: {}\nRun without `--stable` to format \
+                 with the nightly toolchain",
+                nightly_options.join(", ")
+            )
+            .into());
+        },
+    };
 
-    let mut cmd = Command::new("cargo");
-    cmd.current_dir(crate::workspace::project_root()?);
-    cmd.args([&format!("+{toolchain}"), "fmt", "--all"]);
-    cmd.args(context.tool_args);
-    let status = cmd.status()?;
+    context.validate_component(toolchain, "rustfmt", "fmt")?;
+
+    // NOTE: `None` means all files, either because `--since` was not given or because this is not a git checkout
+    let changed_files = match &since {
+        Some(reference) => {
+            let root = &context.config.cargo_metadata.workspace_root;
+            crate::git::changed_files(context.runner, root, reference)?
+        },
+        None => None,
+    };
+    let mut cmds = match &changed_files {
+        Some(files) => rustfmt_commands(&context, toolchain, files, check),
+        None => {
+            let mut cmd = context.cargo(Some(toolchain));
+            cmd.current_dir(crate::workspace::project_root(context.config));
+            cmd.args(["fmt", "--all"]);
+            if check {
+                cmd.args(["--check"]);
+            }
+            cmd.args(context.default_args("fmt"));
+            cmd.args(&context.tool_args);
+            vec![cmd]
+        },
+    };
+    if let (Some(reference), true) = (&since, cmds.is_empty()) {
+        crate::handler::status(format_args!(
+            "no Rust files of the workspace packages changed since `{reference}`"
+        ));
+        return Ok(None);
+    }
+    if check {
+        for cmd in &mut cmds {
+            context.config.xtask.c_locale(cmd);
+        }
+    }
+    if context.print_env {
+        for cmd in &cmds {
+            crate::handler::print_env(cmd);
+        }
+    }
+    if check {
+        return check_status(context.runner, &mut cmds).map(Some);
+    }
+    let mut status = crate::runner::exit_status(0);
+    for cmd in &mut cmds {
+        status = context.runner.status(cmd)?;
+        if !status.success() {
+            break;
+        }
+    }
 
     Ok(Some(status))
 }
+
+// The rustfmt commands formatting the changed `.rs` files among `files` which belong to workspace packages, one for
+// each edition of the packages.
+fn rustfmt_commands(context: &Context<'_>, toolchain: &str, files: &[Utf8PathBuf], check: bool) -> Vec<Command> {
+    let rust_files = files
+        .iter()
+        .filter(|file| file.extension() == Some("rs"))
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut editions = BTreeMap::<_, Vec<_>>::new();
+    for (package, file) in crate::workspace::owning_packages(context.config, &rust_files) {
+        editions.entry(package.edition.as_str()).or_default().push(file);
+    }
+    editions
+        .into_iter()
+        .map(|(edition, files)| {
+            let mut cmd = context.command("rustfmt");
+            cmd.current_dir(&context.config.cargo_metadata.workspace_root);
+            cmd.arg(format!("+{toolchain}"));
+            cmd.args(["--edition", edition]);
+            if check {
+                cmd.args(["--check"]);
+            }
+            cmd.args(context.default_args("fmt"));
+            cmd.args(&context.tool_args);
+            cmd.args(files);
+            cmd
+        })
+        .collect()
+}
+
+// Run `cargo fmt --check` (or `rustfmt --check`) commands, mapping "diffs present" to an error of `Kind::Format`.
+//
+// rustfmt exits with 1 both when there are diffs and on errors (e.g. a file failed to parse), but only prints a
+// `Diff in <file> at line <n>:` (or `Diff in <file>:<n>:`) header for each diff.
+fn check_status(runner: &dyn Runner, cmds: &mut [Command]) -> BoxResult<ExitStatus> {
+    let mut files = BTreeSet::new();
+    for cmd in cmds {
+        let output = runner.output(cmd)?;
+        std::io::Write::write_all(&mut std::io::stdout(), &output.stdout)?;
+        std::io::Write::write_all(&mut std::io::stderr(), &output.stderr)?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let diffs = stdout
+            .lines()
+            .filter_map(|line| line.strip_prefix("Diff in "))
+            .map(|line| diff_file(line).to_owned())
+            .collect::<BTreeSet<_>>();
+        match output.status.code() {
+            Some(0) => {},
+            Some(1) if !diffs.is_empty() => files.extend(diffs),
+            _ => return Err("rustfmt failed, so formatting could not be checked".into()),
+        }
+    }
+    if !files.is_empty() {
+        let message = format!("rustfmt: {} files need formatting", files.len());
+        return Err(Error::new(Kind::Format, message).into());
+    }
+    crate::handler::status("rustfmt: 0 files need formatting");
+    Ok(crate::runner::exit_status(0))
+}
+
+// The file of a `Diff in ` header: `<file> at line <n>:`, or `<file>:<n>:` with newer rustfmt versions.
+fn diff_file(header: &str) -> &str {
+    let header = header.strip_suffix(':').unwrap_or(header);
+    if let Some((file, _)) = header.rsplit_once(" at line ") {
+        return file;
+    }
+    match header.rsplit_once(':') {
+        Some((file, line)) if line.parse::<u32>().is_ok() => file,
+        _ => header,
+    }
+}
+
+// The nightly-only options set in the workspace `rustfmt.toml` or `.rustfmt.toml`, if any.
+fn nightly_options(root: &Utf8Path) -> BoxResult<Vec<String>> {
+    for name in ["rustfmt.toml", ".rustfmt.toml"] {
+        let path = root.join(name);
+        let data = match std::fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(format!("failed to read `{path}`: {err}").into()),
+        };
+        let table = data
+            .parse::<toml::Table>()
+            .map_err(|err| format!("malformed `{path}`: {err}"))?;
+        return Ok(table
+            .keys()
+            .filter(|key| NIGHTLY_OPTIONS.contains(&key.as_str()))
+            .cloned()
+            .collect());
+    }
+    Ok(vec![])
+}
@@ -0,0 +1,132 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    BoxResult,
+};
+use std::{ffi::OsString, process::ExitStatus, time::Instant};
+
+/// The usage of `xtask foreach`.
+pub const USAGE: Usage = Usage {
+    name: "foreach",
+    summary: "Run a cargo command for each workspace member and tabulate the results",
+    args: "-- <CARGO COMMAND> [ARGS]...",
+    description: &[
+        "Runs the cargo command once for each workspace member, as `cargo <CARGO COMMAND> --package <NAME> [ARGS]...`, and",
+        "prints a table of the results (e.g. `xtask foreach -- clippy -- -D warnings` to isolate the packages with warnings).",
+        "A leading `+<TOOLCHAIN>` argument selects the toolchain, as with cargo.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::HERMETIC,
+        Entry {
+            name: "--log-groups <KIND>",
+            about: &[
+                "Fold the output for each package in CI logs (github, buildkite, none)",
+                "[default: detected from the environment]",
+            ],
+        },
+        Entry {
+            name: "-p, --package <NAME>",
+            about: &["Operate on the given package instead of every member (may be repeated)"],
+        },
+        Entry {
+            name: "--keep-going",
+            about: &["Run the command for the remaining packages after one fails"],
+        },
+        Entry {
+            name: "-- '...'",
+            about: &["The cargo command and its arguments"],
+        },
+    ],
+    subcommands: &[],
+    examples: &[],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - No cargo command is given
+/// - A command process fails to start
+pub fn foreach(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let keep_going = context.args.contains("--keep-going");
+
+    let members = crate::workspace::members(context.config).collect::<Vec<_>>();
+    let packages = crate::workspace::packages(context.config, context.args, &members)?;
+
+    crate::handler::unused(context.args)?;
+
+    let mut cargo_args = context.tool_args.clone();
+    let toolchain = match cargo_args.first().and_then(|arg| arg.to_str()) {
+        Some(arg) if arg.starts_with('+') => {
+            let toolchain = arg[1 ..].to_owned();
+            cargo_args.remove(0);
+            Some(toolchain)
+        },
+        _ => None,
+    };
+    if cargo_args.is_empty() {
+        println!("{help}\n");
+        return Err("expected a cargo command after `--` (e.g. `xtask foreach -- check`)".into());
+    }
+    let cargo_subcommand: OsString = cargo_args.remove(0);
+
+    let project_root = crate::workspace::project_root(context.config);
+    let mut results = vec![];
+    let mut status = None;
+    for package in &packages {
+        let mut cmd = context.cargo(toolchain.as_deref());
+        cmd.current_dir(project_root);
+        cmd.arg(&cargo_subcommand);
+        cmd.args(["--package", package]);
+        cmd.args(&cargo_args);
+        context
+            .log_groups
+            .start(&format!("{package}: cargo {}", cargo_subcommand.to_string_lossy()));
+        if context.print_env {
+            crate::handler::print_env(&cmd);
+        }
+        let start = Instant::now();
+        let result = context.runner.status(&mut cmd);
+        context.log_groups.end();
+        let package_status = result?;
+        results.push((package.as_str(), package_status.success(), start.elapsed()));
+        // NOTE: report the first failure, rather than whichever package happened to run last
+        if status.map_or(true, |status: ExitStatus| status.success()) {
+            status = Some(package_status);
+        }
+        if !package_status.success() && !keep_going {
+            break;
+        }
+    }
+
+    let width = packages.iter().map(String::len).max().unwrap_or(0).max("PACKAGE".len());
+    crate::handler::status(format_args!("\n{:<width$}  {:<7}  TIME", "PACKAGE", "RESULT"));
+    for (package, success, elapsed) in &results {
+        let result = if *success { "ok" } else { "FAILED" };
+        crate::handler::status(format_args!(
+            "{package:<width$}  {result:<7}  {:.1}s",
+            elapsed.as_secs_f64()
+        ));
+    }
+    for package in packages.iter().skip(results.len()) {
+        crate::handler::status(format_args!("{package:<width$}  {:<7}  -", "skipped"));
+    }
+
+    Ok(status)
+}
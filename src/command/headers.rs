@@ -0,0 +1,166 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    config::XtaskHeadersStyle,
+    cxx_sources::CxxSources,
+    error::{Error, Kind},
+    BoxResult,
+};
+use std::{collections::BTreeMap, process::ExitStatus};
+
+/// The usage of `xtask headers`.
+pub const USAGE: Usage = Usage {
+    name: "headers",
+    summary: "Check that the Rust and C++ sources start with the configured license header",
+    args: "",
+    description: &[
+        "Checks that the source files tracked by git start with the license header configured in `xtask.headers.header`",
+        "(after a `#!` line, if any), commented in the style of their extension: `//` line comments for `.rs` and the C",
+        "and C++ extensions, unless configured otherwise in `xtask.headers.styles`. Files matching the globs of",
+        "`xtask.headers.exclude` are skipped, as are the C/C++ files outside the sources described by `xtask.cxx`.",
+        "",
+        "Exits with code 4 (as for lint failures) if any file is missing the header. With `--fix`, the header is inserted",
+        "instead.",
+    ],
+    flags: &[usage::HELP, Entry {
+        name: "--fix",
+        about: &["Insert the header into the files missing it"],
+    }],
+    subcommands: &[],
+    examples: &[Entry {
+        name: "xtask headers --fix",
+        about: &["Add the license header to new files"],
+    }],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - No header is configured in `xtask.headers.header`, or an exclude glob (or a glob of `xtask.cxx`) is invalid
+/// - The workspace is not in a git work tree, or listing its files fails
+/// - A source file cannot be read, or written with `--fix`
+pub fn headers(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let fix = context.args.contains("--fix");
+
+    crate::handler::unused(context.args)?;
+
+    let config = &context.config.xtask.headers;
+    let Some(header) = config.header.as_deref() else {
+        let message = "no license header is configured\nSet `xtask.headers.header` (e.g. to \
+                       `SPDX-License-Identifier: MIT`)";
+        return Err(Error::new(Kind::Validation, message).into());
+    };
+    let exclude = config
+        .exclude
+        .iter()
+        .map(|glob| crate::glob::regex(glob))
+        .collect::<BoxResult<Vec<_>>>()?;
+
+    let sources = CxxSources::new(context.config)?;
+
+    let root = &context.config.cargo_metadata.workspace_root;
+    let files = crate::git::tracked_files(context.runner, root)?
+        .ok_or("`xtask headers` requires the workspace to be in a git work tree")?;
+
+    let mut missing = vec![];
+    for file in files {
+        let Some(style) = file.extension().and_then(|extension| style(&config.styles, extension)) else {
+            continue;
+        };
+        // NOTE: the C/C++ files are those of `xtask.cxx`, so that the header check covers the same sources as the
+        // clang tools
+        let is_cxx = file
+            .extension()
+            .map_or(false, |ext| crate::git::CXX_EXTENSIONS.contains(&ext));
+        if is_cxx && !sources.contains(&file) {
+            continue;
+        }
+        let relative = file.strip_prefix(root).unwrap_or(&file);
+        if exclude
+            .iter()
+            .any(|glob| glob.is_match(&relative.as_str().replace('\\', "/")))
+        {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&file).map_err(|err| format!("failed to read `{file}`: {err}"))?;
+        let (shebang, rest) = split_shebang(&contents);
+        let rendered = render(header, &style);
+        if rest.starts_with(&rendered) {
+            continue;
+        }
+        if fix {
+            let separator = if rest.starts_with('\n') || rest.is_empty() {
+                ""
+            } else {
+                "\n"
+            };
+            let fixed = format!("{shebang}{rendered}{separator}{rest}");
+            std::fs::write(&file, fixed).map_err(|err| format!("failed to write `{file}`: {err}"))?;
+        }
+        missing.push(relative.to_owned());
+    }
+
+    if missing.is_empty() {
+        crate::handler::status(format_args!("all source files have the license header"));
+        return Ok(None);
+    }
+    if fix {
+        for file in &missing {
+            crate::handler::status(format_args!("inserted the license header into `{file}`"));
+        }
+        crate::handler::status(format_args!("inserted the license header into {} files", missing.len()));
+        return Ok(None);
+    }
+    for file in &missing {
+        println!("`{file}` is missing the license header");
+    }
+    let message = format!(
+        "{} files are missing the license header\nRun `xtask headers --fix` to insert it",
+        missing.len()
+    );
+    Err(Error::new(Kind::Lint, message).into())
+}
+
+// The comment style of files with `extension`: the configured one, if any, or else `//` line comments for Rust, C,
+// and C++ files.
+fn style(styles: &BTreeMap<String, XtaskHeadersStyle>, extension: &str) -> Option<XtaskHeadersStyle> {
+    if let Some(style) = styles.get(extension) {
+        return Some(style.clone());
+    }
+    (extension == "rs" || crate::git::CXX_EXTENSIONS.contains(&extension)).then(|| XtaskHeadersStyle {
+        start: None,
+        line: "//".into(),
+        end: None,
+    })
+}
+
+// The header comment for `header` in `style`, ending with a newline.
+fn render(header: &str, style: &XtaskHeadersStyle) -> String {
+    let mut lines = vec![];
+    lines.extend(style.start.clone());
+    for line in header.trim_end().lines() {
+        lines.push(format!("{} {line}", style.line).trim_end().to_owned());
+    }
+    lines.extend(style.end.clone());
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+// Splits off the `#!` line at the start of `contents` (but not a Rust inner attribute like `#![deny(...)]`), which
+// must stay first.
+fn split_shebang(contents: &str) -> (&str, &str) {
+    if contents.starts_with("#!") && !contents.starts_with("#![") {
+        let end = contents.find('\n').map_or(contents.len(), |end| end + 1);
+        return contents.split_at(end);
+    }
+    ("", contents)
+}
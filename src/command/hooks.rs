@@ -0,0 +1,77 @@
+use crate::{
+    command::{Context, LogGroups},
+    runner::Runner,
+    timings::Category,
+    BoxResult,
+};
+use std::process::Command;
+
+// Create the commands for the configured `hooks` of a subcommand, each given as a program followed by its arguments.
+//
+// NOTE: the commands are created up front since the subcommand consumes the context
+pub fn commands(context: &Context<'_>, hooks: &[Vec<String>]) -> BoxResult<Vec<Command>> {
+    let mut commands = vec![];
+    for hook in hooks {
+        let Some((program, args)) = hook.split_first() else {
+            return Err("`xtask.hooks` entries must not be empty".into());
+        };
+        let mut cmd = context.command(program);
+        cmd.args(args);
+        cmd.current_dir(&context.config.cargo_metadata.workspace_root);
+        commands.push(cmd);
+    }
+    Ok(commands)
+}
+
+// Run the `stage` (`pre` or `post`) hooks of `subcommand` in order, stopping at the first failure.
+//
+// Each hook gets its own log group, and its run is recorded in the timings.
+pub fn run(
+    runner: &dyn Runner,
+    log_groups: LogGroups,
+    subcommand: &str,
+    stage: &str,
+    commands: Vec<Command>,
+    print_env: bool,
+) -> BoxResult<()> {
+    for mut cmd in commands {
+        let program = cmd.get_program().to_string_lossy().into_owned();
+        let title = format!("{subcommand} {stage} hook {program}");
+        log_groups.start(&title);
+        crate::handler::status(format_args!("running `{subcommand}` {stage} hook `{program}`"));
+        if print_env {
+            crate::handler::print_env(&cmd);
+        }
+        let status = crate::timings::record(Category::Hook, || title, || runner.status(&mut cmd));
+        log_groups.end();
+        let status = status?;
+        if !status.success() {
+            return Err(format!("`{subcommand}` {stage} hook `{program}` failed with non-zero exit code").into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::LogGroups, runner::MockRunner, testing};
+    use std::{process::Command, sync::Mutex};
+
+    #[test]
+    fn hooks_stop_at_the_first_failure() {
+        let ran = Mutex::new(vec![]);
+        let runner = MockRunner::new(|cmd| {
+            let line = testing::command_line(cmd);
+            let code = i32::from(line == "false");
+            ran.lock().unwrap().push(line);
+            Ok(testing::output(code, ""))
+        });
+        let commands = ["true", "false", "never"].into_iter().map(Command::new).collect();
+        let err = super::run(&runner, LogGroups::None, "build", "pre", commands, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`build` pre hook `false` failed with non-zero exit code"
+        );
+        assert_eq!(ran.into_inner().unwrap(), ["true", "false"]);
+    }
+}
@@ -0,0 +1,295 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::Kind,
+    BoxError,
+    BoxResult,
+};
+use std::process::{Command, ExitStatus};
+
+// How a missing tool gets installed.
+enum Install {
+    // `cargo install <crate>`
+    Cargo(&'static str),
+    // `rustup component add <component> --toolchain <nightly>`
+    Component(&'static str),
+    // Only by the user, through the system package manager
+    System,
+}
+
+// A tool needed by one of the xtask commands.
+struct Tool {
+    name: &'static str,
+    // `cargo` subcommand (run with the nightly toolchain) or program probed for its version
+    probe: Probe,
+    install: Install,
+}
+
+enum Probe {
+    CargoSubcommand(&'static str),
+    Program(&'static str),
+    // The interpreter chosen by `detection::detect_python`
+    Python,
+    // The clang tool as resolved for the other commands (see `Validation::path`)
+    Clang(crate::validation::Tool),
+}
+
+const TOOLS: &[Tool] = &[
+    Tool {
+        name: "clippy",
+        probe: Probe::CargoSubcommand("clippy"),
+        install: Install::Component("clippy"),
+    },
+    Tool {
+        name: "rustfmt",
+        probe: Probe::CargoSubcommand("fmt"),
+        install: Install::Component("rustfmt"),
+    },
+    Tool {
+        name: "miri",
+        probe: Probe::CargoSubcommand("miri"),
+        install: Install::Component("miri"),
+    },
+    Tool {
+        name: "cargo-audit",
+        probe: Probe::CargoSubcommand("audit"),
+        install: Install::Cargo("cargo-audit"),
+    },
+    Tool {
+        name: "cargo-machete",
+        probe: Probe::CargoSubcommand("machete"),
+        install: Install::Cargo("cargo-machete"),
+    },
+    Tool {
+        name: "cargo-outdated",
+        probe: Probe::CargoSubcommand("outdated"),
+        install: Install::Cargo("cargo-outdated"),
+    },
+    Tool {
+        name: "cargo-tarpaulin",
+        probe: Probe::CargoSubcommand("tarpaulin"),
+        install: Install::Cargo("cargo-tarpaulin"),
+    },
+    Tool {
+        name: "cargo-udeps",
+        probe: Probe::CargoSubcommand("udeps"),
+        install: Install::Cargo("cargo-udeps"),
+    },
+    Tool {
+        name: "cargo-valgrind",
+        probe: Probe::CargoSubcommand("valgrind"),
+        install: Install::Cargo("cargo-valgrind"),
+    },
+    Tool {
+        name: "cmake",
+        probe: Probe::Program("cmake"),
+        install: Install::System,
+    },
+    Tool {
+        name: "ninja",
+        probe: Probe::Program("ninja"),
+        install: Install::System,
+    },
+    Tool {
+        name: "python3",
+        probe: Probe::Python,
+        install: Install::System,
+    },
+    Tool {
+        name: "clang",
+        probe: Probe::Clang(crate::validation::Tool::ClangDriver),
+        install: Install::System,
+    },
+    Tool {
+        name: "clang-format",
+        probe: Probe::Clang(crate::validation::Tool::ClangFormat),
+        install: Install::System,
+    },
+    Tool {
+        name: "clang-tidy",
+        probe: Probe::Clang(crate::validation::Tool::ClangTidy),
+        install: Install::System,
+    },
+];
+
+/// The usage of `xtask install-tools`.
+pub const USAGE: Usage = Usage {
+    name: "install-tools",
+    summary: "Install the tools needed by the other subcommands",
+    args: "",
+    description: &[
+        "Installs the cargo tools and rust components needed by the xtask commands, and lists the system tools which must be",
+        "installed manually.",
+    ],
+    flags: &[
+        usage::HELP,
+        Entry {
+            name: "--dry-run",
+            about: &["List what would be installed without installing it"],
+        },
+        Entry {
+            name: "--offline",
+            about: &["Fail instead of installing anything (also enabled by `XTASK_OFFLINE=1`)"],
+        },
+        usage::PRINT_ENV,
+    ],
+    subcommands: &[],
+    examples: &[],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - A rust component is installed but fails to run
+/// - Tools which cannot be installed automatically are missing
+/// - The command process fails to start
+/// - The command invocation fails with non-zero exit status
+pub fn install_tools(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let dry_run = context.args.contains("--dry-run");
+
+    crate::handler::unused(context.args)?;
+
+    let toolchain = context.nightly_toolchain()?;
+
+    let mut manual = vec![];
+    for tool in TOOLS {
+        // NOTE: a tool that fails to start is as missing as one that fails to run
+        let succeeds = |mut cmd: Command| {
+            context
+                .runner
+                .output(&mut cmd)
+                .map_or(false, |output| output.status.success())
+        };
+        let found = match (&tool.probe, &tool.install) {
+            // NOTE: a component which is installed but fails to run would still fail after installing it
+            (Probe::CargoSubcommand(subcommand), Install::Component(component)) => {
+                match context.validate_component(toolchain, component, subcommand) {
+                    Ok(()) => true,
+                    Err(err) if crate::error::kind(&*err) == Kind::ToolMissing => false,
+                    Err(err) => return Err(err),
+                }
+            },
+            (Probe::CargoSubcommand(subcommand), _) => {
+                let mut cmd = context.cargo(Some(toolchain));
+                cmd.args([subcommand, "--version"]);
+                succeeds(cmd)
+            },
+            (Probe::Program(program), _) => {
+                let mut cmd = context.command(program);
+                cmd.args([crate::config::tool_probe(context.config, program)]);
+                succeeds(cmd)
+            },
+            // NOTE: detection fails if the interpreter is older than the configured `xtask.python.version`
+            (Probe::Python, _) => crate::detection::detect_python(&context).map_or(false, |(python, python_args)| {
+                let mut cmd = context.command(python);
+                cmd.args(python_args);
+                cmd.arg("--version");
+                succeeds(cmd)
+            }),
+            (Probe::Clang(clang), _) => {
+                let mut cmd = context.command(context.validation().path(*clang).as_str());
+                cmd.arg("--version");
+                succeeds(cmd)
+            },
+        };
+        if found {
+            crate::handler::status(format_args!("found: {}", tool.name));
+            continue;
+        }
+        let mut cmd = match tool.install {
+            Install::Cargo(krate) => {
+                let mut cmd = context.cargo(None);
+                cmd.args(["install", krate]);
+                cmd
+            },
+            Install::Component(component) => {
+                let mut cmd = context.command("rustup");
+                cmd.args(["component", "add", component, "--toolchain", toolchain]);
+                cmd
+            },
+            Install::System => {
+                println!("missing: {} (install it with your system package manager)", tool.name);
+                manual.push(tool.name);
+                continue;
+            },
+        };
+        let command_line = crate::timings::command_name(&cmd);
+        println!("missing: {} (installing with `{command_line}`)", tool.name);
+        if dry_run {
+            continue;
+        }
+        if context.offline {
+            return Err(format!("cannot install `{}` in offline mode", tool.name).into());
+        }
+        if context.print_env {
+            crate::handler::print_env(&cmd);
+        }
+        let status = context.runner.status(&mut cmd).map_err(|err| match tool.install {
+            Install::Component(_) => crate::validation::rustup_error(&command_line, &err).into(),
+            _ => BoxError::from(err),
+        })?;
+        if !status.success() {
+            return Ok(Some(status));
+        }
+    }
+
+    if !manual.is_empty() {
+        let guidance = system_install(&manual);
+        return Err(format!("some tools must be installed manually, e.g. with `{guidance}`").into());
+    }
+
+    Ok(None)
+}
+
+// The command installing the tools `names` with the system package manager.
+fn system_install(names: &[&str]) -> String {
+    let mut packages = vec![];
+    for package in names.iter().map(|name| system_package(name)) {
+        if !packages.contains(&package) {
+            packages.push(package);
+        }
+    }
+    let packages = packages.join(" ");
+    if cfg!(target_os = "macos") {
+        format!("brew install {packages}")
+    } else if cfg!(target_os = "windows") {
+        format!("winget install {packages}")
+    } else {
+        format!("sudo apt install {packages}")
+    }
+}
+
+// The package of the system package manager providing the tool `name`: on macOS and Windows, the clang tools all come
+// with `llvm`.
+fn system_package(name: &str) -> &str {
+    match name {
+        "python3" if cfg!(any(target_os = "macos", target_os = "windows")) => "python",
+        "clang" | "clang-format" | "clang-tidy" if cfg!(any(target_os = "macos", target_os = "windows")) => "llvm",
+        "ninja" if cfg!(not(any(target_os = "macos", target_os = "windows"))) => "ninja-build",
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn system_tools_are_installed_by_package() {
+        let guidance = super::system_install(&["ninja", "clang", "clang-format", "clang-tidy"]);
+        if cfg!(target_os = "macos") {
+            assert_eq!(guidance, "brew install ninja llvm");
+        } else if cfg!(target_os = "windows") {
+            assert_eq!(guidance, "winget install ninja llvm");
+        } else {
+            assert_eq!(guidance, "sudo apt install ninja-build clang clang-format clang-tidy");
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    config::Config,
+    error::{Error, Kind},
+    BoxResult,
+};
+use camino::Utf8Path;
+use std::{fmt::Write, process::ExitStatus};
+
+// A third-party dependency, as listed in the report.
+struct Dependency {
+    name: String,
+    version: String,
+    license: String,
+    source: String,
+}
+
+/// The usage of `xtask licenses`.
+pub const USAGE: Usage = Usage {
+    name: "licenses",
+    summary: "Report the licenses of the project's Rust and C++ dependencies",
+    args: "",
+    description: &[
+        "Reports the licenses of the project's third-party dependencies: the Rust dependencies from `cargo metadata`, and the",
+        "C++ dependencies from the manifests (`vcpkg.json`, `conanfile.txt`) and vendored dependencies configured in",
+        "`xtask.licenses`.",
+    ],
+    flags: &[
+        usage::HELP,
+        Entry {
+            name: "--format <FORMAT>",
+            about: &["The report format (markdown, html) [default: markdown]"],
+        },
+        Entry {
+            name: "--output <PATH>",
+            about: &["Write the report to PATH instead of stdout"],
+        },
+    ],
+    subcommands: &[],
+    examples: &[],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - A configured C++ manifest cannot be read or is malformed
+/// - The report cannot be written
+pub fn licenses(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let format = context
+        .args
+        .opt_value_from_str::<_, String>("--format")?
+        .unwrap_or_else(|| "markdown".into());
+    let output = context.args.opt_value_from_str::<_, String>("--output")?;
+
+    crate::handler::unused(context.args)?;
+
+    let sections = [
+        ("Rust", rust_dependencies(context.config)),
+        ("C++", cxx_dependencies(context.config)?),
+    ];
+    let report = match format.as_str() {
+        "markdown" => markdown(&sections)?,
+        "html" => html(&sections)?,
+        _ => {
+            return Err(Error::new(
+                Kind::Validation,
+                format!("unrecognized report format `{format}`: expected `markdown` or `html`"),
+            )
+            .into());
+        },
+    };
+
+    match output {
+        Some(path) => std::fs::write(&path, report).map_err(|err| format!("failed to write `{path}`: {err}"))?,
+        None => print!("{report}"),
+    }
+
+    Ok(None)
+}
+
+// The non-workspace packages in the dependency graph.
+fn rust_dependencies(config: &Config) -> Vec<Dependency> {
+    let metadata = &config.cargo_metadata;
+    let mut dependencies = metadata
+        .packages
+        .iter()
+        .filter(|package| !metadata.workspace_members.contains(&package.id))
+        .map(|package| Dependency {
+            name: package.name.clone(),
+            version: package.version.to_string(),
+            license: package
+                .license
+                .clone()
+                .or_else(|| package.license_file.as_ref().map(|file| format!("see {file}")))
+                .unwrap_or_else(|| "UNKNOWN".into()),
+            source: package.repository.clone().unwrap_or_default(),
+        })
+        .collect::<Vec<_>>();
+    dependencies.sort_by(|lhs, rhs| (&lhs.name, &lhs.version).cmp(&(&rhs.name, &rhs.version)));
+    dependencies
+}
+
+// The configured vendored dependencies, followed by those listed in the configured manifests.
+//
+// Manifests do not record licenses, so a manifest dependency takes the license of the vendored entry with the same
+// name, if there is one.
+fn cxx_dependencies(config: &Config) -> BoxResult<Vec<Dependency>> {
+    let licenses = &config.xtask.licenses;
+    let mut dependencies = licenses
+        .vendored
+        .iter()
+        .map(|vendored| Dependency {
+            name: vendored.name.clone(),
+            version: vendored.version.clone().unwrap_or_default(),
+            license: vendored.license.clone(),
+            source: vendored.repository.clone().unwrap_or_default(),
+        })
+        .collect::<Vec<_>>();
+    for manifest in &licenses.manifests {
+        let path = config.cargo_metadata.workspace_root.join(manifest);
+        for (name, version) in manifest_dependencies(&path)? {
+            if dependencies.iter().any(|dependency| dependency.name == name) {
+                continue;
+            }
+            dependencies.push(Dependency {
+                name,
+                version,
+                license: "UNKNOWN".into(),
+                source: manifest.to_string(),
+            });
+        }
+    }
+    Ok(dependencies)
+}
+
+// The names and versions (if any) of the dependencies in a `vcpkg.json` or `conanfile.txt` manifest.
+fn manifest_dependencies(path: &Utf8Path) -> BoxResult<Vec<(String, String)>> {
+    let data = std::fs::read_to_string(path).map_err(|err| format!("failed to read `{path}`: {err}"))?;
+    match path.file_name() {
+        Some("vcpkg.json") => {
+            let json = serde_json::from_str::<serde_json::Value>(&data)?;
+            let dependencies = json.get("dependencies").and_then(serde_json::Value::as_array);
+            Ok(dependencies
+                .into_iter()
+                .flatten()
+                .filter_map(|dependency| {
+                    let name = dependency
+                        .as_str()
+                        .or_else(|| dependency.get("name").and_then(serde_json::Value::as_str))?;
+                    let version = dependency
+                        .get("version>=")
+                        .and_then(serde_json::Value::as_str)
+                        .unwrap_or_default();
+                    Some((name.to_owned(), version.to_owned()))
+                })
+                .collect())
+        },
+        Some("conanfile.txt") => {
+            let mut section = "";
+            let mut dependencies = vec![];
+            for line in data.lines().map(str::trim) {
+                if line.starts_with('[') && line.ends_with(']') {
+                    section = line;
+                } else if section == "[requires]" && !line.is_empty() && !line.starts_with('#') {
+                    // NOTE: a requirement is a reference like `fmt/10.1.0@user/channel#revision`
+                    let reference = line.split(['@', '#']).next().unwrap_or(line);
+                    let (name, version) = reference.split_once('/').unwrap_or((reference, ""));
+                    dependencies.push((name.to_owned(), version.to_owned()));
+                }
+            }
+            Ok(dependencies)
+        },
+        _ => Err(format!("unsupported C++ manifest `{path}`: expected `vcpkg.json` or `conanfile.txt`").into()),
+    }
+}
+
+fn markdown(sections: &[(&str, Vec<Dependency>)]) -> BoxResult<String> {
+    let mut report = String::from("# Third-party licenses\n");
+    for (title, dependencies) in sections {
+        write!(report, "\n## {title}\n\n")?;
+        if dependencies.is_empty() {
+            writeln!(report, "No dependencies.")?;
+            continue;
+        }
+        writeln!(report, "| Name | Version | License | Source |")?;
+        writeln!(report, "| --- | --- | --- | --- |")?;
+        for dependency in dependencies {
+            let cell = |text: &str| text.replace('|', "\\|");
+            writeln!(
+                report,
+                "| {} | {} | {} | {} |",
+                cell(&dependency.name),
+                cell(&dependency.version),
+                cell(&dependency.license),
+                cell(&dependency.source)
+            )?;
+        }
+    }
+    Ok(report)
+}
+
+fn html(sections: &[(&str, Vec<Dependency>)]) -> BoxResult<String> {
+    let mut report = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Third-party licenses</title>\n</head>\n<body>\n",
+    );
+    writeln!(report, "<h1>Third-party licenses</h1>")?;
+    for (title, dependencies) in sections {
+        writeln!(report, "<h2>{}</h2>", escape(title))?;
+        if dependencies.is_empty() {
+            writeln!(report, "<p>No dependencies.</p>")?;
+            continue;
+        }
+        writeln!(
+            report,
+            "<table>\n<tr><th>Name</th><th>Version</th><th>License</th><th>Source</th></tr>"
+        )?;
+        for dependency in dependencies {
+            writeln!(
+                report,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape(&dependency.name),
+                escape(&dependency.version),
+                escape(&dependency.license),
+                escape(&dependency.source)
+            )?;
+        }
+        writeln!(report, "</table>")?;
+    }
+    writeln!(report, "</body>\n</html>")?;
+    Ok(report)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
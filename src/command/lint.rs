@@ -0,0 +1,257 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    validation::Tool,
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::process::ExitStatus;
+
+/// The usage of `xtask lint`.
+pub const USAGE: Usage = Usage {
+    name: "lint",
+    summary: "Check the formatting and style of the project's non-Rust sources",
+    args: "[SUBCOMMAND]",
+    description: &[
+        "Runs the formatter of `xtask.lint.cmake.backend` over the git-tracked `CMakeLists.txt` and `*.cmake` files",
+        "(except those matching the globs of `xtask.lint.cmake.exclude`): `cmake-format` (the default), followed by",
+        "the `cmake-lint` linter, or `gersemi`, which only formats. The tools are validated like the other tools, so",
+        "their versions can be pinned with `xtask.tools.<name>.version`.",
+        "",
+        "Exits with 0 if the files are formatted and lint clean, 5 if some need formatting, 4 if `cmake-lint` reports",
+        "issues, and 1 on errors.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        Entry {
+            name: "--fix",
+            about: &["Format the files in place instead of checking them"],
+        },
+        Entry {
+            name: "--backend <NAME>",
+            about: &["The formatter to run (`cmake-format` or `gersemi`), overriding `xtask.lint.cmake.backend`"],
+        },
+        Entry {
+            name: "-- '...'",
+            about: &["Extra arguments to pass to the formatter"],
+        },
+    ],
+    subcommands: &[Entry {
+        name: "cmake",
+        about: &["Check (or fix) the formatting of the CMake files, and lint them with `cmake-lint`"],
+    }],
+    examples: &[Entry {
+        name: "xtask lint cmake --fix",
+        about: &["Format the CMake files in place, then lint them"],
+    }],
+};
+
+// The formatters of `xtask lint cmake`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    CmakeFormat,
+    Gersemi,
+}
+
+impl Backend {
+    // The backend named `name`.
+    fn parse(name: &str) -> BoxResult<Self> {
+        match name {
+            "cmake-format" => Ok(Backend::CmakeFormat),
+            "gersemi" => Ok(Backend::Gersemi),
+            _ => {
+                let message = format!("unknown `xtask lint cmake` backend `{name}`\nUse `cmake-format` or `gersemi`");
+                Err(Error::new(Kind::Validation, message).into())
+            },
+        }
+    }
+
+    // The tool formatting the files.
+    fn formatter(self) -> Tool {
+        match self {
+            Backend::CmakeFormat => Tool::CmakeFormat,
+            Backend::Gersemi => Tool::Gersemi,
+        }
+    }
+}
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments, or an unknown backend)
+/// - Tool validation fails (missing tools, incorrect versions, etc.)
+/// - The workspace is not in a git work tree, or a glob of `xtask.lint.cmake.exclude` is malformed
+/// - The command process fails to start
+/// - The formatter fails (e.g. a file failed to parse)
+pub fn lint(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let Some(lint_subcommand) = context.args.opt_free_from_str::<String>()? else {
+        println!("{help}\n");
+        return Ok(None);
+    };
+
+    if lint_subcommand != "cmake" {
+        println!("{help}\n");
+        return Err(Error::new(
+            Kind::Validation,
+            format!("unrecognized `xtask lint` subcommand `{lint_subcommand}`"),
+        )
+        .into());
+    }
+
+    let fix = context.args.contains("--fix");
+    let backend = context.args.opt_value_from_str::<_, String>("--backend")?;
+
+    crate::handler::unused(context.args)?;
+
+    let config = &context.config.xtask.lint.cmake;
+    let backend = Backend::parse(
+        backend
+            .as_deref()
+            .or(config.backend.as_deref())
+            .unwrap_or("cmake-format"),
+    )?;
+    let mut tools = vec![backend.formatter()];
+    if backend == Backend::CmakeFormat {
+        tools.push(Tool::CmakeLint);
+    }
+    let (resolved, errors) = crate::validation::validate_tools(&context.validation(), &tools);
+    crate::validation::tool_errors(errors)?;
+    let mut resolved = resolved.into_iter().map(|tool| tool.path);
+    let formatter = resolved.next().ok_or("the formatter was not resolved")?;
+    let linter = resolved.next();
+
+    let files = cmake_files(&context)?;
+    if files.is_empty() {
+        crate::handler::status(format_args!("no CMake files to lint"));
+        return Ok(None);
+    }
+
+    let status = if fix {
+        format_files(&context, &formatter, &files)?
+    } else {
+        check_files(&context, &formatter, &files)?
+    };
+    if !status.success() {
+        return Ok(Some(status));
+    }
+
+    match linter {
+        Some(linter) => lint_files(&context, &linter, &files).map(Some),
+        None => Ok(Some(status)),
+    }
+}
+
+// The git-tracked CMake files of the workspace, except those matching `xtask.lint.cmake.exclude`, relative to the
+// workspace root.
+fn cmake_files(context: &Context<'_>) -> BoxResult<Vec<Utf8PathBuf>> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let files = crate::git::tracked_files(context.runner, root)?
+        .ok_or("`xtask lint cmake` lints the git-tracked CMake files, so it must run in a git work tree")?;
+    let excluded = context
+        .config
+        .xtask
+        .lint
+        .cmake
+        .exclude
+        .iter()
+        .map(|glob| crate::glob::regex(glob))
+        .collect::<BoxResult<Vec<_>>>()?;
+    let mut files = files
+        .iter()
+        .filter(|file| file.file_name() == Some("CMakeLists.txt") || file.extension() == Some("cmake"))
+        .filter(|file| file.is_file())
+        .filter_map(|file| file.strip_prefix(root).ok())
+        .filter(|file| {
+            let relative = file.as_str().replace('\\', "/");
+            !excluded.iter().any(|glob| glob.is_match(&relative))
+        })
+        .map(Utf8Path::to_owned)
+        .collect::<Vec<_>>();
+    files.sort();
+    Ok(files)
+}
+
+// Formats `files` in place with `formatter`.
+fn format_files(context: &Context<'_>, formatter: &Utf8Path, files: &[Utf8PathBuf]) -> BoxResult<ExitStatus> {
+    let mut cmd = context.command(formatter);
+    cmd.current_dir(&context.config.cargo_metadata.workspace_root);
+    cmd.arg("-i");
+    cmd.args(&context.tool_args);
+    cmd.args(files);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    context
+        .runner
+        .status(&mut cmd)
+        .map_err(|err| Error::new(crate::error::kind(&err), format!("failed to run `{formatter}`: {err}")).into())
+}
+
+// Checks the formatting of `files` by comparing each with the output of `formatter`, mapping "diffs present" to an
+// error of `Kind::Format`.
+//
+// NOTE: both formatters print the formatted file to stdout by default, but their `--check` outputs differ, so the
+// files needing formatting are found by comparison instead
+fn check_files(context: &Context<'_>, formatter: &Utf8Path, files: &[Utf8PathBuf]) -> BoxResult<ExitStatus> {
+    let root = &context.config.cargo_metadata.workspace_root;
+    let mut unformatted = vec![];
+    for file in files {
+        let mut cmd = context.command(formatter);
+        cmd.current_dir(root);
+        cmd.args(&context.tool_args);
+        cmd.arg(file);
+        if context.print_env {
+            crate::handler::print_env(&cmd);
+        }
+        let output = context
+            .runner
+            .output(&mut cmd)
+            .map_err(|err| Error::new(crate::error::kind(&err), format!("failed to run `{formatter}`: {err}")))?;
+        if !output.status.success() {
+            std::io::Write::write_all(&mut std::io::stderr(), &output.stderr)?;
+            return Err(format!("`{formatter}` failed on `{file}`, so formatting could not be checked").into());
+        }
+        let path = root.join(file);
+        let contents = std::fs::read(&path).map_err(|err| format!("failed to read `{path}`: {err}"))?;
+        if output.stdout != contents {
+            println!("Diff in {file}");
+            unformatted.push(file);
+        }
+    }
+    let name = formatter.file_name().unwrap_or(formatter.as_str());
+    if !unformatted.is_empty() {
+        let message = format!("{name}: {} files need formatting", unformatted.len());
+        return Err(Error::new(Kind::Format, message).into());
+    }
+    crate::handler::status(format_args!("{name}: 0 files need formatting"));
+    Ok(crate::runner::exit_status(0))
+}
+
+// Lints `files` with `linter` (`cmake-lint`), mapping reported issues to an error of `Kind::Lint`.
+fn lint_files(context: &Context<'_>, linter: &Utf8Path, files: &[Utf8PathBuf]) -> BoxResult<ExitStatus> {
+    let mut cmd = context.command(linter);
+    cmd.current_dir(&context.config.cargo_metadata.workspace_root);
+    cmd.args(files);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context
+        .runner
+        .status(&mut cmd)
+        .map_err(|err| Error::new(crate::error::kind(&err), format!("failed to run `{linter}`: {err}")))?;
+    if status.success() {
+        Ok(status)
+    } else {
+        Err(Error::new(Kind::Lint, "`cmake-lint` reported lint failures").into())
+    }
+}
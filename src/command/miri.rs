@@ -1,5 +1,37 @@
-use crate::{command::Context, BoxResult};
-use std::process::{Command, ExitStatus};
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    BoxResult,
+};
+use std::process::ExitStatus;
+
+/// The usage of `xtask miri`.
+pub const USAGE: Usage = Usage {
+    name: "miri",
+    summary: "Run the project's tests with cargo-miri",
+    args: "[SUBCOMMAND]",
+    description: &[],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::HERMETIC,
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[Entry {
+        name: "test",
+        about: &["Run the project's tests with cargo-miri"],
+    }],
+    examples: &[],
+};
 
 /// # Errors
 ///
@@ -8,23 +40,12 @@ use std::process::{Command, ExitStatus};
 /// - Tool validation fails (missing tools, incorrect versions, etc.)
 /// - The command process fails to start
 /// - The command invocation fails with non-zero exit status
+// NOTE: matches on the subcommand so that more can be added alongside `test`
+#[allow(clippy::single_match_else)]
 pub fn miri(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = r#"
-xtask-miri
-
-USAGE:
-xtask miri [SUBCOMMAND]
-
-FLAGS:
--h, --help          Prints help information
--- '...'            Extra arguments to pass to the cargo command
-
-SUBCOMMANDS:
-    test            Run the project's tests  with cargo-miri
-"#
-    .trim();
+    let help = USAGE.render();
 
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
@@ -35,20 +56,29 @@ SUBCOMMANDS:
 
     crate::handler::unused(context.args)?;
 
-    let toolchain = crate::config::rust::toolchain::nightly(context.config);
+    let toolchain = context.nightly_toolchain()?;
+    context.validate_component(toolchain, "miri", "miri")?;
 
     let status = match &*miri_subcommand {
         "test" => {
-            let mut cmd = Command::new("cargo");
-            cmd.current_dir(crate::workspace::project_root()?);
-            cmd.args([&format!("+{toolchain}"), "miri"]);
+            let mut cmd = context.cargo(Some(toolchain));
+            cmd.current_dir(crate::workspace::project_root(context.config));
+            cmd.args(["miri"]);
             cmd.args([miri_subcommand]);
+            cmd.args(context.default_args("miri"));
             cmd.args(context.tool_args);
-            cmd.status()?
+            if context.print_env {
+                crate::handler::print_env(&cmd);
+            }
+            context.runner.status(&mut cmd)?
         },
         _ => {
             println!("{help}\n");
-            return Err(format!("unrecognized `xtask miri` subcommand `{miri_subcommand}`").into());
+            return Err(Error::new(
+                Kind::Validation,
+                format!("unrecognized `xtask miri` subcommand `{miri_subcommand}`"),
+            )
+            .into());
         },
     };
 
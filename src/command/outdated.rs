@@ -0,0 +1,310 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    cxx_pins::{Pin, PinKind},
+    error::{Error, Kind},
+    runner::Runner,
+    BoxResult,
+};
+use std::{collections::BTreeMap, io::Write, process::ExitStatus};
+
+// An outdated Rust dependency, as reported by cargo-outdated.
+struct RustDependency {
+    name: String,
+    project: String,
+    compat: String,
+    latest: String,
+    kind: String,
+    // The workspace members depending on it
+    members: Vec<String>,
+}
+
+// How a pinned C++ dependency compares to its upstream releases.
+enum Freshness {
+    UpToDate,
+    Outdated { latest: String },
+    Unknown { reason: String },
+}
+
+/// The usage of `xtask outdated`.
+pub const USAGE: Usage = Usage {
+    name: "outdated",
+    summary: "Report the project's outdated Rust and C++ dependencies",
+    args: "",
+    description: &[
+        "Reports the direct Rust dependencies of the workspace members with newer versions on crates.io, found with",
+        "cargo-outdated. With `--cxx`, also compares the pinned C++ dependencies with their upstream releases:",
+        "    vcpkg           the `builtin-baseline` (or default registry baseline) against the latest vcpkg commit",
+        "    conan           the requirements of `conan.lock` against the release tags of the repositories configured",
+        "                    in `xtask.outdated.repositories`",
+        "    FetchContent    the `GIT_TAG` of each `FetchContent_Declare` against the release tags of its repository",
+        "",
+        "Upstream releases are the tags of the repositories (found with `git ls-remote`) that name versions, such as",
+        "`v1.2.3` or `release-1.2.3`, leaving out pre-releases.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::COLOR,
+        usage::PROFILE,
+        usage::HERMETIC,
+        Entry {
+            name: "--cxx",
+            about: &["Also check the pinned C++ dependencies (vcpkg baseline, conan lock, FetchContent tags)"],
+        },
+        Entry {
+            name: "--exit-code",
+            about: &["Exit with code 4 (as for lint failures) if any dependency is outdated"],
+        },
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[Entry {
+        name: "xtask outdated --cxx --exit-code",
+        about: &["Fail CI when a Rust or C++ dependency has a newer release"],
+    }],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - `--offline` is given, since the registry and upstream repositories must be queried
+/// - Tool validation fails (missing tools, incorrect versions, etc.)
+/// - The command process fails to start
+/// - The output of cargo-outdated is malformed
+/// - With `--cxx`, a C++ manifest, lock, or `CMake` file cannot be read or is malformed
+pub fn outdated(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let cxx = context.args.contains("--cxx");
+    let exit_code = context.args.contains("--exit-code");
+
+    crate::handler::unused(context.args)?;
+
+    if context.offline {
+        let message = "`xtask outdated` queries crates.io and the upstream repositories, so it cannot run offline";
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+
+    let project_root = crate::workspace::project_root(context.config);
+
+    let mut probe = context.cargo(None);
+    probe.args(["outdated", "--version"]);
+    if !context
+        .runner
+        .output(&mut probe)
+        .map_or(false, |output| output.status.success())
+    {
+        let message = "`cargo-outdated` is not installed\nInstall it with `cargo install cargo-outdated` or `xtask \
+                       install-tools`";
+        return Err(Error::new(Kind::ToolMissing, message).into());
+    }
+
+    let mut cmd = context.cargo(None);
+    cmd.current_dir(project_root);
+    cmd.args(["outdated", "--workspace", "--root-deps-only", "--format", "json"]);
+    cmd.args(context.default_args("outdated"));
+    cmd.args(context.tool_args);
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let output = context.runner.output(&mut cmd)?;
+    if !output.status.success() {
+        std::io::stderr().write_all(&output.stderr)?;
+        return Ok(Some(output.status));
+    }
+    let rust = rust_dependencies(&output.stdout)?;
+    print_rust(&rust);
+    let mut count = rust.len();
+
+    if cxx {
+        let root = &context.config.cargo_metadata.workspace_root;
+        let pins = crate::cxx_pins::find(root, &context.config.xtask.outdated.repositories)?;
+        let freshness = pins
+            .iter()
+            .map(|pin| freshness(context.runner, pin))
+            .collect::<Vec<_>>();
+        println!();
+        print_cxx(&pins, &freshness);
+        count += freshness
+            .iter()
+            .filter(|freshness| matches!(freshness, Freshness::Outdated { .. }))
+            .count();
+    }
+
+    println!("\nfound {count} outdated dependencies");
+    if exit_code && count > 0 {
+        return Err(Error::new(Kind::Lint, format!("{count} dependencies are outdated")).into());
+    }
+    Ok(Some(output.status))
+}
+
+// The outdated dependencies in the `--format json` output of cargo-outdated, which is a JSON object per workspace
+// member, like `{"crate_name":"foo","dependencies":[{"name":"regex","project":"1.9.0","compat":"1.9.6",
+// "latest":"1.10.2","kind":"Normal","platform":null}]}`.
+fn rust_dependencies(stdout: &[u8]) -> BoxResult<Vec<RustDependency>> {
+    let field = |json: &serde_json::Value, name: &str| {
+        json.get(name)
+            .and_then(serde_json::Value::as_str)
+            .unwrap_or("---")
+            .to_owned()
+    };
+    let mut dependencies = BTreeMap::<[String; 5], Vec<String>>::new();
+    for line in String::from_utf8_lossy(stdout)
+        .lines()
+        .filter(|line| line.starts_with('{'))
+    {
+        let json = serde_json::from_str::<serde_json::Value>(line)
+            .map_err(|err| format!("failed to parse the output of cargo-outdated: {err}"))?;
+        let member = field(&json, "crate_name");
+        let outdated = json.get("dependencies").and_then(serde_json::Value::as_array);
+        for dependency in outdated.into_iter().flatten() {
+            let key = ["name", "project", "compat", "latest", "kind"].map(|name| field(dependency, name));
+            dependencies.entry(key).or_default().push(member.clone());
+        }
+    }
+    Ok(dependencies
+        .into_iter()
+        .map(|([name, project, compat, latest, kind], members)| RustDependency {
+            name,
+            project,
+            compat,
+            latest,
+            kind,
+            members,
+        })
+        .collect())
+}
+
+// How the pinned C++ dependency `pin` compares to its upstream releases. Failing to query the upstream repository
+// leaves its freshness unknown rather than failing the report.
+fn freshness(runner: &dyn Runner, pin: &Pin) -> Freshness {
+    let Some(repository) = &pin.repository else {
+        return Freshness::Unknown {
+            reason: format!(
+                "no upstream repository (set `xtask.outdated.repositories.{}`)",
+                pin.name
+            ),
+        };
+    };
+    if pin.kind == PinKind::VcpkgBaseline {
+        return match crate::git::remote_head(runner, repository) {
+            Ok(head) if head.starts_with(&pin.pinned) => Freshness::UpToDate,
+            Ok(head) => Freshness::Outdated {
+                latest: head.chars().take(12).collect(),
+            },
+            Err(err) => Freshness::Unknown {
+                reason: err.to_string(),
+            },
+        };
+    }
+    let tags = match crate::git::remote_tags(runner, repository) {
+        Ok(tags) => tags,
+        Err(err) => {
+            return Freshness::Unknown {
+                reason: err.to_string(),
+            };
+        },
+    };
+    let Some((latest_tag, latest)) = crate::cxx_pins::latest_release(&tags) else {
+        return Freshness::Unknown {
+            reason: "no release tags upstream".into(),
+        };
+    };
+    // NOTE: a `GIT_TAG` may also be the commit of a release tag
+    let is_commit = pin.pinned.len() >= 7 && pin.pinned.chars().all(|char| char.is_ascii_hexdigit());
+    let pinned = crate::cxx_pins::tag_version(&pin.pinned).or_else(|| {
+        let (tag, _) = tags
+            .iter()
+            .find(|(_, commit)| is_commit && commit.starts_with(&pin.pinned))?;
+        crate::cxx_pins::tag_version(tag)
+    });
+    match pinned {
+        Some(pinned) if crate::detection::version_at_least(&latest, &pinned) => Freshness::UpToDate,
+        Some(_) => Freshness::Outdated {
+            latest: latest_tag.to_owned(),
+        },
+        None => Freshness::Unknown {
+            reason: format!("`{}` is not a release (latest is `{latest_tag}`)", pin.pinned),
+        },
+    }
+}
+
+fn print_rust(dependencies: &[RustDependency]) {
+    println!("Rust dependencies:");
+    if dependencies.is_empty() {
+        println!("    all dependencies are up to date");
+        return;
+    }
+    let rows = dependencies
+        .iter()
+        .map(|dependency| {
+            vec![
+                dependency.name.clone(),
+                dependency.project.clone(),
+                dependency.compat.clone(),
+                dependency.latest.clone(),
+                dependency.kind.clone(),
+                dependency.members.join(", "),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(&["Name", "Project", "Compat", "Latest", "Kind", "Members"], &rows);
+}
+
+fn print_cxx(pins: &[Pin], freshness: &[Freshness]) {
+    println!("C++ dependencies:");
+    if pins.is_empty() {
+        println!("    no pinned dependencies found");
+        return;
+    }
+    let rows = pins
+        .iter()
+        .zip(freshness)
+        .map(|(pin, freshness)| {
+            let (latest, status) = match freshness {
+                Freshness::UpToDate => (String::new(), "up to date".to_owned()),
+                Freshness::Outdated { latest } => (latest.clone(), "outdated".to_owned()),
+                Freshness::Unknown { reason } => (String::new(), format!("unknown: {reason}")),
+            };
+            vec![
+                pin.name.clone(),
+                pin.kind.as_str().to_owned(),
+                pin.pinned.clone(),
+                latest,
+                pin.source.to_string(),
+                status,
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(&["Name", "Kind", "Pinned", "Latest", "Pinned in", "Status"], &rows);
+}
+
+// Prints `rows` in columns under `headers`, indented like the rest of the report.
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths = headers.iter().map(|header| header.len()).collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let cells = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>();
+        println!("    {}", cells.join("  ").trim_end());
+    };
+    line(headers.to_vec());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
@@ -0,0 +1,558 @@
+use crate::{
+    command::{
+        usage::{self, Entry},
+        Context,
+        Usage,
+    },
+    config::Config,
+    error::{Error, Kind},
+    runner::Runner,
+    timings::Category,
+    BoxResult,
+};
+use std::{ffi::OsString, process::ExitStatus};
+
+/// An `xtask` subcommand.
+pub struct Subcommand {
+    pub usage: &'static Usage,
+    pub run: fn(Context<'_>) -> BoxResult<Option<ExitStatus>>,
+}
+
+impl Subcommand {
+    /// Runs the subcommand, surrounded by the hooks configured for it in `xtask.hooks`.
+    ///
+    /// With `--quiet`, this silences the status lines for the rest of the process (see
+    /// [`crate::handler::set_quiet`]).
+    ///
+    /// The `pre` hooks run first and abort the subcommand if one fails. The `post` hooks run only if the subcommand
+    /// succeeds.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the following circumstances:
+    /// - Both `--quiet` and `--verbose` are given
+    /// - The selected profile is not configured in `xtask.profiles`
+    /// - The selected target preset is unknown or its SDK is not found (see [`crate::target_preset::validate`])
+    /// - The configured `xtask.link.linker` is unknown or not found (see [`crate::linker::validate`])
+    /// - A hook process fails to start or fails with non-zero exit status
+    /// - The subcommand fails
+    /// - With `--locked` or `--frozen`, `Cargo.lock` is not committed or was changed (see [`check_lockfile`])
+    pub fn invoke(&self, context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+        if context.quiet && context.verbose {
+            let message = "`--quiet` and `--verbose` cannot be used together";
+            return Err(Error::new(Kind::Validation, message).into());
+        }
+        if context.quiet {
+            crate::handler::set_quiet();
+        }
+        if let Some(name) = &context.profile {
+            let profiles = &context.config.xtask.profiles;
+            if profiles.is_empty() {
+                let message = format!("unknown profile `{name}`: no profiles are configured in `xtask.profiles`");
+                return Err(Error::new(Kind::Validation, message).into());
+            }
+            if !profiles.contains_key(name) {
+                let available = profiles.keys().map(String::as_str).collect::<Vec<_>>().join(", ");
+                let message = format!("unknown profile `{name}`: expected one of {available}");
+                return Err(Error::new(Kind::Validation, message).into());
+            }
+        }
+        if let Some(name) = &context.target_preset {
+            let preset = crate::target_preset::find(name)?;
+            crate::target_preset::validate(preset, &context.config.xtask, context.runner)?;
+        } else if crate::linker::LINKING_SUBCOMMANDS.contains(&self.usage.name) {
+            crate::linker::validate(&context.config.xtask, context.runner)?;
+        }
+        let runner = context.runner;
+        let (pre, post) = match context.config.xtask.hooks.get(self.usage.name) {
+            Some(hooks) => (
+                crate::command::hooks::commands(&context, &hooks.pre)?,
+                crate::command::hooks::commands(&context, &hooks.post)?,
+            ),
+            None => (vec![], vec![]),
+        };
+        let print_env = context.print_env;
+        let log_groups = context.log_groups;
+        let config = context.config;
+        let locked = context.locked || context.frozen;
+        crate::command::hooks::run(runner, log_groups, self.usage.name, "pre", pre, print_env)?;
+        let name = || format!("xtask {}", self.usage.name);
+        let status = crate::timings::record(Category::Subcommand, name, || (self.run)(context))?;
+        if locked {
+            check_lockfile(config, runner)?;
+        }
+        if status.map_or(true, |status| status.success()) {
+            crate::command::hooks::run(runner, log_groups, self.usage.name, "post", post, print_env)?;
+        }
+        Ok(status)
+    }
+}
+
+/// Checks that the workspace `Cargo.lock` is committed and unchanged, as `--locked` runs expect. Nothing is checked
+/// outside a git work tree.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Running `git status` fails
+/// - `Cargo.lock` is not committed or differs from the committed version
+pub fn check_lockfile(config: &Config, runner: &dyn Runner) -> BoxResult<()> {
+    let root = &config.cargo_metadata.workspace_root;
+    let lockfile = root.join("Cargo.lock");
+    let Some(status) = crate::git::status(runner, root, &lockfile)? else {
+        return Ok(());
+    };
+    if !lockfile.exists()
+        || status
+            .iter()
+            .any(|line| line.starts_with("??") || line.starts_with("!!"))
+    {
+        let message = "`Cargo.lock` is not committed\nCommit it so that `--locked` runs resolve the same dependencies";
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+    if !status.is_empty() {
+        let message = "`Cargo.lock` differs from the committed version\nCommit or revert the changes to it";
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+    Ok(())
+}
+
+/// A subcommand given on the command line: a built-in one, or an alias configured in `xtask.alias`.
+pub enum Invocation<'a> {
+    Builtin(&'static Subcommand),
+    Alias { name: &'a str, steps: &'a [String] },
+}
+
+impl Invocation<'_> {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Invocation::Builtin(subcommand) => subcommand.usage.name,
+            Invocation::Alias { name, .. } => name,
+        }
+    }
+
+    /// Runs the built-in subcommand (see [`Subcommand::invoke`]), or each step of the alias in order.
+    ///
+    /// Each step of an alias is a command line of a built-in subcommand, where the words after a `--` are its extra
+    /// arguments (e.g. `clang format -- --dry-run`). The steps inherit the global flags given for the alias, and stop
+    /// at the first one that fails.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the following circumstances:
+    /// - Argument processing fails (e.g. invalid arguments)
+    /// - A step does not name a built-in subcommand
+    /// - A step fails
+    pub fn invoke(&self, context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+        match *self {
+            Invocation::Builtin(subcommand) => subcommand.invoke(context),
+            Invocation::Alias { name, steps } => invoke_alias(context, name, steps),
+        }
+    }
+}
+
+fn invoke_alias(context: Context<'_>, name: &str, steps: &[String]) -> BoxResult<Option<ExitStatus>> {
+    let help = alias_help(name, steps);
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    crate::handler::unused(context.args)?;
+    if !context.tool_args.is_empty() {
+        return Err(Error::new(
+            Kind::Validation,
+            format!("alias `{name}` does not take extra arguments"),
+        )
+        .into());
+    }
+
+    let mut status = None;
+    for step in steps {
+        status = invoke_step(&context, &format!("alias `{name}`"), step)?;
+        if let Some(status) = status {
+            if !status.success() {
+                break;
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+/// Runs the xtask command line `step` (e.g. `clippy --all-targets -- --all-features`) of `owner` (e.g. an alias), as
+/// a log group of `context`.
+///
+/// The words of `step` after `--` are passed as the extra tool arguments of the subcommand.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - `step` is empty or does not name a built-in `xtask` subcommand
+/// - The subcommand fails
+pub fn invoke_step(context: &Context<'_>, owner: &str, step: &str) -> BoxResult<Option<ExitStatus>> {
+    let mut words = step.split_whitespace().map(OsString::from).collect::<Vec<_>>();
+    let tool_args = match words.iter().position(|word| word == "--") {
+        Some(separator) => words.split_off(separator).into_iter().skip(1).collect(),
+        None => vec![],
+    };
+    let mut words = words.into_iter();
+    let step_name = words
+        .next()
+        .ok_or_else(|| Error::new(Kind::Validation, format!("{owner} has an empty step")))?;
+    let subcommand = SUBCOMMANDS
+        .iter()
+        .find(|subcommand| step_name == subcommand.usage.name)
+        .ok_or_else(|| {
+            let message = format!("{owner} step `{step}` does not name a built-in `xtask` subcommand");
+            Error::new(Kind::Validation, message)
+        })?;
+    let mut args = pico_args::Arguments::from_vec(words.collect());
+    context.log_groups.start(&format!("xtask {step}"));
+    let result = subcommand.invoke(context.inherit(&mut args, tool_args));
+    context.log_groups.end();
+    result
+}
+
+// The `--help` text of an alias.
+fn alias_help(name: &str, steps: &[String]) -> String {
+    let mut help = format!("xtask-{name}\n\nUSAGE:\nxtask {name}\n\nAlias (from `xtask.alias`) running, in order:\n");
+    for step in steps {
+        help.push_str(&format!("    xtask {step}\n"));
+    }
+    help.push_str("\nFLAGS:\n-h, --help          Prints help information");
+    help
+}
+
+/// The usage of `xtask help`.
+pub const HELP_USAGE: Usage = Usage {
+    name: "help",
+    summary: "Print the usage of a subcommand, or the reference of every subcommand",
+    args: "[SUBCOMMAND]",
+    description: &[],
+    flags: &[usage::HELP, Entry {
+        name: "--markdown",
+        about: &["Print the usage of every subcommand as a Markdown CLI reference"],
+    }],
+    subcommands: &[],
+    examples: &[
+        Entry {
+            name: "xtask help cmake",
+            about: &["Print the usage of `xtask cmake` (the same as `xtask cmake --help`)"],
+        },
+        Entry {
+            name: "xtask help --markdown > docs/xtask.md",
+            about: &["Regenerate the CLI reference"],
+        },
+    ],
+};
+
+fn help_subcommand(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = HELP_USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let markdown = context.args.contains("--markdown");
+    let name = context.args.opt_free_from_str::<String>()?;
+
+    crate::handler::unused(context.args)?;
+
+    if markdown {
+        let sections = SUBCOMMANDS
+            .iter()
+            .map(|subcommand| subcommand.usage.markdown())
+            .collect::<Vec<_>>();
+        println!("# xtask\n\n{}", sections.join("\n"));
+        return Ok(None);
+    }
+    let Some(name) = name else {
+        println!("{}", crate::command::help(context.config));
+        return Ok(None);
+    };
+    if let Some(subcommand) = SUBCOMMANDS.iter().find(|subcommand| subcommand.usage.name == name) {
+        println!("{}", subcommand.usage.render());
+        return Ok(None);
+    }
+    if let Some((name, steps)) = aliases(context.config).find(|(alias, _)| *alias == name) {
+        println!("{}", alias_help(name, steps));
+        return Ok(None);
+    }
+    let mut message = format!("unrecognized `xtask` subcommand `{name}`");
+    let names = SUBCOMMANDS
+        .iter()
+        .map(|subcommand| subcommand.usage.name)
+        .chain(aliases(context.config).map(|(alias, _)| alias));
+    if let Some(suggestion) = suggest(&name, names) {
+        message.push_str(&format!("\nDid you mean `{suggestion}`?"));
+    }
+    Err(Error::new(Kind::Validation, message).into())
+}
+
+/// All `xtask` subcommands, in the order they are listed in the top-level help.
+pub const SUBCOMMANDS: &[Subcommand] = &[
+    Subcommand {
+        usage: &crate::command::audit::USAGE,
+        run: crate::command::audit,
+    },
+    Subcommand {
+        usage: &crate::command::bench::USAGE,
+        run: crate::command::bench,
+    },
+    Subcommand {
+        usage: &crate::command::bridge::USAGE,
+        run: crate::command::bridge,
+    },
+    Subcommand {
+        usage: &crate::command::build::USAGE,
+        run: crate::command::build,
+    },
+    Subcommand {
+        usage: &crate::command::cache::USAGE,
+        run: crate::command::cache,
+    },
+    Subcommand {
+        usage: &crate::command::check::USAGE,
+        run: crate::command::check,
+    },
+    Subcommand {
+        usage: &crate::command::check_all::USAGE,
+        run: crate::command::check_all,
+    },
+    Subcommand {
+        usage: &crate::command::clang::USAGE,
+        run: crate::command::clang,
+    },
+    Subcommand {
+        usage: &crate::command::clangd::USAGE,
+        run: crate::command::clangd,
+    },
+    Subcommand {
+        usage: &crate::command::clippy::USAGE,
+        run: crate::command::clippy,
+    },
+    Subcommand {
+        usage: &crate::command::completions::USAGE,
+        run: crate::command::completions,
+    },
+    Subcommand {
+        usage: &crate::command::cmake::USAGE,
+        run: crate::command::cmake,
+    },
+    Subcommand {
+        usage: &crate::command::config::USAGE,
+        run: crate::command::config,
+    },
+    Subcommand {
+        usage: &crate::command::devenv::USAGE,
+        run: crate::command::devenv,
+    },
+    Subcommand {
+        usage: &crate::command::dist::USAGE,
+        run: crate::command::dist,
+    },
+    Subcommand {
+        usage: &crate::command::doc::USAGE,
+        run: crate::command::doc,
+    },
+    Subcommand {
+        usage: &crate::command::fmt::USAGE,
+        run: crate::command::fmt,
+    },
+    Subcommand {
+        usage: &crate::command::foreach::USAGE,
+        run: crate::command::foreach,
+    },
+    Subcommand {
+        usage: &crate::command::headers::USAGE,
+        run: crate::command::headers,
+    },
+    Subcommand {
+        usage: &HELP_USAGE,
+        run: help_subcommand,
+    },
+    Subcommand {
+        usage: &crate::command::install_tools::USAGE,
+        run: crate::command::install_tools,
+    },
+    Subcommand {
+        usage: &crate::command::licenses::USAGE,
+        run: crate::command::licenses,
+    },
+    Subcommand {
+        usage: &crate::command::lint::USAGE,
+        run: crate::command::lint,
+    },
+    Subcommand {
+        usage: &crate::command::miri::USAGE,
+        run: crate::command::miri,
+    },
+    Subcommand {
+        usage: &crate::command::outdated::USAGE,
+        run: crate::command::outdated,
+    },
+    Subcommand {
+        usage: &crate::command::size::USAGE,
+        run: crate::command::size,
+    },
+    Subcommand {
+        usage: &crate::command::tarpaulin::USAGE,
+        run: crate::command::tarpaulin,
+    },
+    Subcommand {
+        usage: &crate::command::test::USAGE,
+        run: crate::command::test,
+    },
+    Subcommand {
+        usage: &crate::command::udeps::USAGE,
+        run: crate::command::udeps,
+    },
+    Subcommand {
+        usage: &crate::command::valgrind::USAGE,
+        run: crate::command::valgrind,
+    },
+];
+
+/// The top-level `xtask` help, listing every subcommand and the aliases configured in `xtask.alias`.
+#[must_use]
+pub fn help(config: &Config) -> String {
+    let mut help = String::from(
+        r#"
+xtask
+
+USAGE:
+xtask [SUBCOMMAND]
+
+FLAGS:
+-h, --help          Prints help information
+-V, --version       Prints version information (the crate version, git commit, and target), as JSON with `--json`
+
+Every subcommand also takes `--error-format <human|short>` (or `XTASK_ERROR_FORMAT`), where `short` prints errors on
+a single line as `error[<kind>]: <message>`, for scripting, and `--timings` (or `--timings-trace <PATH>`), which prints
+how long each subcommand, hook, tool probe, and external command took (and writes them as a Chrome trace to `PATH`).
+They also take `-q, --quiet`, which silences the status lines of the xtask (but not the output of the tools it runs, or what
+failed), or else `-v, --verbose`, which passes `--verbose` to cargo, and `--no-cache`, which probes the tools for
+their versions again rather than trusting those validated by previous runs (kept in `target/xtask/tools.json`).
+
+EXIT CODES:
+    0               Success
+    1               A subcommand or a tool it ran failed
+    2               Validation failed (invalid arguments or configuration, or unsupported tool versions)
+    3               A required tool was not found
+    4               A linter reported failures
+    5               A formatter found files needing formatting
+    101             Internal error
+
+SUBCOMMANDS:
+"#
+        .trim_start(),
+    );
+    for subcommand in SUBCOMMANDS {
+        help.push_str(&format!(
+            "    {:<16}{}\n",
+            subcommand.usage.name, subcommand.usage.summary
+        ));
+    }
+    let aliases = aliases(config).collect::<Vec<_>>();
+    if !aliases.is_empty() {
+        help.push_str("\nALIASES:\n");
+        for (name, steps) in aliases {
+            let steps = steps.iter().map(|step| format!("`{step}`")).collect::<Vec<_>>();
+            help.push_str(&format!("    {:<16}Runs {}\n", name, steps.join(", then ")));
+        }
+    }
+    help.push_str("\nUse `xtask help <SUBCOMMAND>` or `xtask <SUBCOMMAND> --help` to see the usage for a subcommand");
+    help
+}
+
+/// Takes the subcommand from `args` and looks it up in [`SUBCOMMANDS`], and then in the aliases configured in
+/// `xtask.alias`.
+///
+/// Prints the top-level help and returns `None` when no subcommand is given or `-h`/`--help` is passed instead, and
+/// likewise prints the version information for `-V`/`--version` (see [`crate::version::version`]).
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - The subcommand is not recognized (the top-level help is printed first)
+pub fn subcommand<'a>(config: &'a Config, args: &mut pico_args::Arguments) -> BoxResult<Option<Invocation<'a>>> {
+    let help = help(config);
+    let Some(name) = args.subcommand()? else {
+        if crate::version::version(args)? {
+            return Ok(None);
+        }
+        if !crate::handler::help(args, &help)? {
+            println!("{help}");
+        }
+        return Ok(None);
+    };
+    if let Some(subcommand) = SUBCOMMANDS.iter().find(|subcommand| subcommand.usage.name == name) {
+        return Ok(Some(Invocation::Builtin(subcommand)));
+    }
+    if let Some((name, steps)) = aliases(config).find(|(alias, _)| *alias == name) {
+        return Ok(Some(Invocation::Alias { name, steps }));
+    }
+    println!("{help}\n");
+    let mut message = format!("unrecognized `xtask` subcommand `{name}`");
+    let names = SUBCOMMANDS
+        .iter()
+        .map(|subcommand| subcommand.usage.name)
+        .chain(aliases(config).map(|(alias, _)| alias));
+    if let Some(suggestion) = suggest(&name, names) {
+        message.push_str(&format!("\nDid you mean `{suggestion}`?"));
+    }
+    Err(Error::new(Kind::Validation, message).into())
+}
+
+/// The aliases configured in `xtask.alias`, except those named like a built-in subcommand.
+pub fn aliases(config: &Config) -> impl Iterator<Item = (&str, &[String])> {
+    config
+        .xtask
+        .alias
+        .iter()
+        .filter(|(name, _)| SUBCOMMANDS.iter().all(|subcommand| subcommand.usage.name != *name))
+        .map(|(name, steps)| (name.as_str(), steps.as_slice()))
+}
+
+// The closest of `names` to `name`, if any is within a small edit distance.
+fn suggest<'a>(name: &str, names: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    names
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= 2)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, name)| name)
+}
+
+// Levenshtein distance between `lhs` and `rhs`.
+fn edit_distance(lhs: &str, rhs: &str) -> usize {
+    let rhs = rhs.chars().collect::<Vec<_>>();
+    let mut prev = (0 ..= rhs.len()).collect::<Vec<_>>();
+    for (i, lhs_char) in lhs.chars().enumerate() {
+        let mut next = vec![i + 1];
+        for (j, &rhs_char) in rhs.iter().enumerate() {
+            let substitution = prev[j] + usize::from(lhs_char != rhs_char);
+            next.push(substitution.min(prev[j + 1] + 1).min(next[j] + 1));
+        }
+        prev = next;
+    }
+    prev[rhs.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::Context, error::Kind, runner::MockRunner, testing};
+
+    #[test]
+    fn quiet_and_verbose_are_exclusive() {
+        let config = testing::config(serde_json::json!({}));
+        let mut args = pico_args::Arguments::from_vec(vec!["--quiet".into(), "--verbose".into()]);
+        let mut context = Context::new(&config, &mut args, vec![]);
+        let runner = MockRunner::new(|cmd| panic!("unexpected command `{}`", testing::command_line(cmd)));
+        context.runner = &runner;
+        let err = super::SUBCOMMANDS[0].invoke(context).unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::Validation);
+    }
+}
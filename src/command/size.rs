@@ -0,0 +1,91 @@
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    BoxResult,
+};
+use std::process::ExitStatus;
+
+/// The usage of `xtask size`.
+pub const USAGE: Usage = Usage {
+    name: "size",
+    summary: "Report the sizes of the build artifacts and their changes over time",
+    args: "",
+    description: &[
+        "Reports the sizes of the build artifacts (rlibs, libraries, and executables, including those of the CMake build) and",
+        "their changes since the previous recorded build, or since a named baseline. `xtask build` records the sizes after each",
+        "successful build.",
+    ],
+    flags: &[
+        usage::HELP,
+        Entry {
+            name: "--cargo-profile <NAME>",
+            about: &["The cargo profile directory to measure [default: debug]"],
+        },
+        Entry {
+            name: "--baseline <NAME>",
+            about: &["Compare against the named baseline instead of the previous recorded build"],
+        },
+        Entry {
+            name: "--save-baseline <NAME>",
+            about: &["Save the current sizes as the named baseline"],
+        },
+        Entry {
+            name: "--record",
+            about: &["Add the current sizes to the history"],
+        },
+    ],
+    subcommands: &[],
+    examples: &[],
+};
+
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - The named `--baseline` does not exist
+/// - The size history or a baseline cannot be read or written
+pub fn size(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
+    let help = USAGE.render();
+
+    if crate::handler::help(context.args, &help)? {
+        return Ok(None);
+    }
+
+    let profile = context
+        .args
+        .opt_value_from_str::<_, String>("--cargo-profile")?
+        .unwrap_or_else(|| "debug".into());
+    let baseline = context.args.opt_value_from_str::<_, String>("--baseline")?;
+    let save_baseline = context.args.opt_value_from_str::<_, String>("--save-baseline")?;
+    let record = context.args.contains("--record");
+
+    crate::handler::unused(context.args)?;
+
+    let root = &context.config.cargo_metadata.workspace_root;
+    let target_dir = crate::workspace::target_dir(context.config).as_std_path();
+
+    let current = crate::size::record(context.runner, root, target_dir, &profile)?;
+    let reference = match &baseline {
+        Some(name) => Some(crate::size::load_baseline(target_dir, name)?),
+        None => crate::size::load_history(target_dir)?
+            .into_iter()
+            .rev()
+            .find(|record| record.profile == profile),
+    };
+    if current.sizes.is_empty() {
+        crate::handler::status(format_args!("no `{profile}` build artifacts found"));
+    }
+    crate::size::print_report(&current.sizes, reference.as_ref().map(|reference| &reference.sizes));
+
+    if let Some(name) = &save_baseline {
+        crate::size::save_baseline(target_dir, name, &current)?;
+        crate::handler::status(format_args!("saved size baseline `{name}`"));
+    }
+    if record {
+        crate::size::append_history(target_dir, current)?;
+    }
+
+    Ok(None)
+}
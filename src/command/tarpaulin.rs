@@ -1,5 +1,38 @@
-use crate::{command::Context, BoxResult};
-use std::process::{Command, ExitStatus};
+use crate::{
+    command::{
+        usage::{self, Usage},
+        Context,
+    },
+    BoxResult,
+};
+use std::process::ExitStatus;
+
+/// The usage of `xtask tarpaulin`.
+pub const USAGE: Usage = Usage {
+    name: "tarpaulin",
+    summary: "Measure the project's test coverage with cargo-tarpaulin",
+    args: "",
+    description: &[
+        "The files matching the globs of `xtask.cxx.exclude` (e.g. vendored or generated sources) are left out of the",
+        "coverage.",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::HERMETIC,
+        usage::PACKAGE,
+        usage::MANIFEST_PATH,
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[],
+};
 
 /// # Errors
 ///
@@ -9,34 +42,58 @@ use std::process::{Command, ExitStatus};
 /// - The command process fails to start
 /// - The command invocation fails with non-zero exit status
 pub fn tarpaulin(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = r#"
-xtask-tarpaulin
-
-USAGE:
-xtask tarpaulin
-
-FLAGS:
--h, --help          Prints help information
--- '...'            Extra arguments to pass to the cargo command
-"#
-    .trim();
+    let help = USAGE.render();
 
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
+    let packages = crate::workspace::packages(context.config, context.args, &["cxx-auto"])?;
+    let manifest_path = context.args.opt_value_from_str::<_, String>("--manifest-path")?;
+
     crate::handler::unused(context.args)?;
 
-    let toolchain = crate::config::rust::toolchain::nightly(context.config);
+    let project_root = crate::workspace::project_root(context.config);
+    let toolchain = context.nightly_toolchain()?;
 
-    let mut cmd = Command::new("cargo");
-    cmd.current_dir(crate::workspace::project_root()?);
-    cmd.args([&format!("+{toolchain}"), "tarpaulin"]);
-    cmd.args(["--packages", "cxx-auto"]);
+    let mut cmd = context.cargo(Some(toolchain));
+    cmd.current_dir(project_root);
+    cmd.args(["tarpaulin"]);
+    for package in &packages {
+        cmd.args(["--packages", package]);
+    }
+    if let Some(manifest_path) = &manifest_path {
+        cmd.args(["--manifest-path", manifest_path]);
+    }
     cmd.args(["--timeout", "120"]);
     cmd.args(["--out", "Xml"]);
+    // NOTE: the `=` form keeps the globs from taking the following arguments as more values of the flag
+    for glob in context
+        .config
+        .xtask
+        .cxx
+        .exclude
+        .iter()
+        .flat_map(|glob| crate::glob::expand(glob))
+    {
+        cmd.arg(format!("--exclude-files={glob}"));
+    }
+    cmd.args(context.default_args("tarpaulin"));
     cmd.args(context.tool_args);
-    let status = cmd.status()?;
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
+
+    // NOTE: the coverage is read from the report of `--out Xml`, which is only written if the tests pass
+    let coverage = if status.success() {
+        std::fs::read_to_string(project_root.join("cobertura.xml"))
+            .ok()
+            .and_then(|xml| crate::status::cobertura_coverage(&xml))
+    } else {
+        None
+    };
+    crate::status::record(context.config, context.runner, "tarpaulin", status.success(), coverage)?;
 
     Ok(Some(status))
 }
@@ -1,5 +1,39 @@
-use crate::{command::Context, BoxResult};
-use std::process::{Command, ExitStatus};
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    BoxResult,
+};
+use std::process::ExitStatus;
+
+/// The usage of `xtask test`.
+pub const USAGE: Usage = Usage {
+    name: "test",
+    summary: "Run the project's tests",
+    args: "",
+    description: &[],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::HERMETIC,
+        usage::PACKAGE,
+        usage::MANIFEST_PATH,
+        Entry {
+            name: "--all-targets",
+            about: &["Test all targets (lib, bins, tests, benches, examples)"],
+        },
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[],
+};
 
 /// # Errors
 ///
@@ -8,31 +42,38 @@ use std::process::{Command, ExitStatus};
 /// - The command process fails to start
 /// - The command invocation fails with non-zero exit status
 pub fn test(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = r#"
-xtask-test
-
-USAGE:
-xtask test
-
-FLAGS:
--h, --help          Prints help information
--- '...'            Extra arguments to pass to the cargo command
-"#
-    .trim();
+    let help = USAGE.render();
 
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
+    let all_targets = context.args.contains("--all-targets");
+
+    let packages = crate::workspace::packages(context.config, context.args, &["cxx-auto"])?;
+    let manifest_path = context.args.opt_value_from_str::<_, String>("--manifest-path")?;
+
     crate::handler::unused(context.args)?;
 
-    let mut cmd = Command::new("cargo");
-    cmd.current_dir(crate::workspace::project_root()?);
+    let mut cmd = context.cargo(None);
+    cmd.current_dir(crate::workspace::project_root(context.config));
     cmd.args(["test"]);
-    cmd.args(["--package", "cxx-auto"]);
+    for package in &packages {
+        cmd.args(["--package", package]);
+    }
+    if let Some(manifest_path) = &manifest_path {
+        cmd.args(["--manifest-path", manifest_path]);
+    }
+    if all_targets {
+        cmd.args(["--all-targets"]);
+    }
+    cmd.args(context.default_args("test"));
     cmd.args(context.tool_args);
 
-    let status = cmd.status()?;
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let status = context.runner.status(&mut cmd)?;
 
     Ok(Some(status))
 }
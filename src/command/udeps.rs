@@ -1,5 +1,58 @@
-use crate::{command::Context, BoxResult};
-use std::process::{Command, ExitStatus};
+use crate::{
+    command::{
+        usage::{self, Entry, Usage},
+        Context,
+    },
+    error::{Error, Kind},
+    BoxResult,
+};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    process::{Command, ExitStatus, Output},
+};
+
+// The unused dependencies of each package with any, keyed by package name.
+type Report = BTreeMap<String, Vec<String>>;
+
+/// The usage of `xtask udeps`.
+pub const USAGE: Usage = Usage {
+    name: "udeps",
+    summary: "Check the project for unused dependencies with cargo-udeps",
+    args: "",
+    description: &[
+        "Reports the unused dependencies of the workspace packages, found with either of the backends:",
+        "    udeps           cargo-udeps, which builds the packages with the nightly toolchain (slow, but precise)",
+        "    machete         cargo-machete, which searches the sources for uses (fast, may miss uses through macros)",
+    ],
+    flags: &[
+        usage::HELP,
+        usage::PRINT_ENV,
+        usage::LOCKED,
+        usage::FROZEN,
+        usage::OFFLINE,
+        usage::COLOR,
+        usage::JOBS,
+        usage::PROFILE,
+        usage::HERMETIC,
+        Entry {
+            name: "--backend <NAME>",
+            about: &["The backend to find unused dependencies with (udeps, machete) [default: udeps]"],
+        },
+        Entry {
+            name: "--workspace",
+            about: &["Check the whole workspace instead of each member package"],
+        },
+        Entry {
+            name: "-p, --package <NAME>",
+            about: &["Operate on the given package instead of every member (may be repeated)"],
+        },
+        usage::MANIFEST_PATH,
+        usage::CARGO_ARGS,
+    ],
+    subcommands: &[],
+    examples: &[],
+};
 
 /// # Errors
 ///
@@ -7,35 +60,208 @@ use std::process::{Command, ExitStatus};
 /// - Argument processing fails (e.g. invalid arguments)
 /// - Tool validation fails (missing tools, incorrect versions, etc.)
 /// - The command process fails to start
-/// - The command invocation fails with non-zero exit status
+/// - The command output cannot be written
 pub fn udeps(context: Context<'_>) -> BoxResult<Option<ExitStatus>> {
-    let help = r#"
-xtask-udep
-
-USAGE:
-xtask udeps
-
-FLAGS:
--h, --help          Prints help information
--- '...'            Extra arguments to pass to the cargo command
-"#
-    .trim();
+    let help = USAGE.render();
 
-    if crate::handler::help(context.args, help)? {
+    if crate::handler::help(context.args, &help)? {
         return Ok(None);
     }
 
-    crate::handler::unused(context.args)?;
+    let backend = context
+        .args
+        .opt_value_from_str::<_, String>("--backend")?
+        .unwrap_or_else(|| "udeps".into());
+    let workspace = context.args.contains("--workspace");
+
+    let members = crate::workspace::members(context.config).collect::<Vec<_>>();
+    let packages = crate::workspace::packages(context.config, context.args, &members)?;
+    let manifest_path = context.args.opt_value_from_str::<_, String>("--manifest-path")?;
 
-    let toolchain = crate::config::rust::toolchain::nightly(context.config);
+    crate::handler::unused(context.args)?;
 
-    let mut cmd = Command::new("cargo");
-    cmd.current_dir(crate::workspace::project_root()?);
-    cmd.args([&format!("+{toolchain}"), "udeps"]);
-    cmd.args(["--package", "xtask"]);
-    cmd.args(["--package", "cxx-auto"]);
+    let mut cmd = match backend.as_str() {
+        "udeps" => {
+            let toolchain = context.nightly_toolchain()?;
+            let mut cmd = context.cargo(Some(toolchain));
+            cmd.current_dir(crate::workspace::project_root(context.config));
+            cmd.args(["udeps"]);
+            if workspace {
+                cmd.args(["--workspace"]);
+            } else {
+                for package in &packages {
+                    cmd.args(["--package", package]);
+                }
+            }
+            if let Some(manifest_path) = &manifest_path {
+                cmd.args(["--manifest-path", manifest_path]);
+            }
+            cmd.args(["--all-targets", "--output", "json"]);
+            cmd.args(context.default_args("udeps"));
+            cmd
+        },
+        "machete" => machete_command(&context, workspace, &packages, manifest_path.as_deref())?,
+        _ => {
+            return Err(Error::new(
+                Kind::Validation,
+                format!("unrecognized backend `{backend}`: expected `udeps` or `machete`"),
+            )
+            .into());
+        },
+    };
     cmd.args(context.tool_args);
-    let status = cmd.status()?;
+    if context.print_env {
+        crate::handler::print_env(&cmd);
+    }
+    let output = context.runner.output(&mut cmd)?;
 
-    Ok(Some(status))
+    // NOTE: the progress of the build (udeps) and any errors are on stderr
+    std::io::stderr().write_all(&output.stderr)?;
+    let report = if backend == "udeps" {
+        udeps_report(&output)
+    } else {
+        machete_report(&output)
+    };
+    match report {
+        Some(report) => print_report(&report),
+        // NOTE: the backend failed without a report, so show whatever it printed instead
+        None => std::io::stdout().write_all(&output.stdout)?,
+    }
+
+    Ok(Some(output.status))
+}
+
+// The cargo-machete command searching the directories of the selected packages.
+fn machete_command(
+    context: &Context<'_>,
+    workspace: bool,
+    packages: &[String],
+    manifest_path: Option<&str>,
+) -> BoxResult<Command> {
+    let mut probe = context.cargo(None);
+    probe.args(["machete", "--version"]);
+    if !context
+        .runner
+        .output(&mut probe)
+        .map_or(false, |output| output.status.success())
+    {
+        return Err(
+            "`cargo-machete` is not installed\nInstall it with `cargo install cargo-machete` or `xtask install-tools`"
+                .into(),
+        );
+    }
+    let mut cmd = context.cargo(None);
+    cmd.current_dir(crate::workspace::project_root(context.config));
+    cmd.args(["machete"]);
+    cmd.args(context.default_args("machete"));
+    // NOTE: cargo-machete selects packages by the directories to search rather than by name
+    let metadata = &context.config.cargo_metadata;
+    if let Some(manifest_path) = manifest_path {
+        let dir = camino::Utf8Path::new(manifest_path)
+            .parent()
+            .filter(|dir| !dir.as_str().is_empty())
+            .unwrap_or(".".into());
+        cmd.arg(dir);
+    } else if workspace {
+        cmd.arg(&metadata.workspace_root);
+    } else {
+        for package in metadata.workspace_packages() {
+            if packages.contains(&package.name) {
+                cmd.arg(package.manifest_path.parent().unwrap_or(&metadata.workspace_root));
+            }
+        }
+    }
+    Ok(cmd)
+}
+
+// The report in the `--output json` output of cargo-udeps, which lists the unused dependencies by kind for each
+// package, keyed like `foo 0.1.0 (path+file:///...)`.
+fn udeps_report(output: &Output) -> Option<Report> {
+    let json = serde_json::from_slice::<serde_json::Value>(&output.stdout).ok()?;
+    let mut report = Report::new();
+    for (package, kinds) in json.get("unused_deps")?.as_object()? {
+        let name = package.split_whitespace().next().unwrap_or(package);
+        let mut unused = vec![];
+        for kind in ["normal", "development", "build"] {
+            let names = kinds
+                .get(kind)
+                .and_then(serde_json::Value::as_array)
+                .into_iter()
+                .flatten();
+            unused.extend(names.filter_map(serde_json::Value::as_str).map(String::from));
+        }
+        if !unused.is_empty() {
+            unused.sort();
+            unused.dedup();
+            report.entry(name.to_owned()).or_default().extend(unused);
+        }
+    }
+    Some(report)
+}
+
+// The report in the output of cargo-machete, which lists the unused dependencies of each package under a
+// `<package> -- <manifest path>:` line, indented by a tab.
+//
+// cargo-machete exits with 1 when it finds unused dependencies, and with 2 on errors.
+fn machete_report(output: &Output) -> Option<Report> {
+    if !matches!(output.status.code(), Some(0 | 1)) {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut report = Report::new();
+    let mut package: Option<String> = None;
+    for line in stdout.lines() {
+        if let Some(name) = line.strip_prefix('\t') {
+            if let Some(package) = &package {
+                report.entry(package.clone()).or_default().push(name.trim().to_owned());
+            }
+        } else {
+            package = line.split_once(" -- ").map(|(name, _)| name.trim().to_owned());
+        }
+    }
+    Some(report)
+}
+
+fn print_report(report: &Report) {
+    if report.is_empty() {
+        crate::handler::status(format_args!("no unused dependencies found"));
+        return;
+    }
+    for (package, unused) in report {
+        let unused = unused.iter().map(|name| format!("`{name}`")).collect::<Vec<_>>();
+        println!("`{package}`: unused {}", unused.join(", "));
+    }
+    let count = report.values().map(Vec::len).sum::<usize>();
+    crate::handler::status(format_args!(
+        "found {count} unused dependencies in {} packages",
+        report.len()
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::Context, runner::MockRunner, testing};
+    use std::sync::Mutex;
+
+    #[test]
+    fn all_targets_of_the_workspace_members_are_checked() {
+        let mut config = testing::config(serde_json::json!({}));
+        testing::add_members(&mut config, &["core", "bindings"]);
+        let mut args = pico_args::Arguments::from_vec(vec![]);
+        let mut context = Context::new(&config, &mut args, vec![]);
+        let installed = format!("nightly-{}\n", crate::version::TARGET);
+        let udeps = Mutex::new(None);
+        let runner = MockRunner::new(|cmd| {
+            let line = testing::command_line(cmd);
+            if line.ends_with("rustup toolchain list") {
+                return Ok(testing::output(0, &installed));
+            }
+            *udeps.lock().unwrap() = Some(line);
+            Ok(testing::output(0, r#"{"success":true,"unused_deps":{}}"#))
+        });
+        context.runner = &runner;
+        super::udeps(context).unwrap();
+        let line = udeps.into_inner().unwrap().unwrap();
+        assert!(line.ends_with(" +nightly udeps --package core --package bindings --all-targets --output json"));
+    }
 }
@@ -0,0 +1,182 @@
+use std::fmt::Write;
+
+/// The usage of an `xtask` subcommand, from which its `--help` text, the `xtask help --markdown` reference, and the
+/// shell completions of `xtask completions` are rendered.
+pub struct Usage {
+    pub name: &'static str,
+    /// One-line description shown in the top-level help
+    pub summary: &'static str,
+    /// The arguments following `xtask <name>` in the usage line (e.g. `[SUBCOMMAND]`)
+    pub args: &'static str,
+    /// Paragraphs describing the subcommand, one entry per line (with empty entries between paragraphs)
+    pub description: &'static [&'static str],
+    pub flags: &'static [Entry],
+    pub subcommands: &'static [Entry],
+    /// Example command lines, each with a description
+    pub examples: &'static [Entry],
+}
+
+/// A flag, nested subcommand, or example in a [`Usage`].
+pub struct Entry {
+    /// The flag with its aliases and value (e.g. `-p, --package <NAME>`), or the subcommand with its arguments
+    pub name: &'static str,
+    /// The description, one entry per line
+    pub about: &'static [&'static str],
+}
+
+impl Entry {
+    /// The words to complete for the entry: each form of a flag (e.g. `-p` and `--package`), or the subcommand name.
+    ///
+    /// This is empty for the `-- '...'` entry, which stands for arbitrary arguments.
+    pub fn words(&self) -> impl Iterator<Item = &'static str> {
+        self.name
+            .split(", ")
+            .filter_map(|form| form.split_whitespace().next())
+            .filter(|word| *word != "--")
+    }
+}
+
+impl Usage {
+    /// The `--help` text of the subcommand.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut help = format!("xtask-{}\n\nUSAGE:\n{}\n\n", self.name, self.usage_line());
+        for line in self.description {
+            help.push_str(line);
+            help.push('\n');
+        }
+        if !self.description.is_empty() {
+            help.push('\n');
+        }
+        help.push_str("FLAGS:\n");
+        for flag in self.flags {
+            // NOTE: a name too long for the column of descriptions goes on a line of its own
+            let (first, rest) = match flag.about.split_first() {
+                Some((first, rest)) if flag.name.len() < 20 => (Some(*first), rest),
+                _ => (None, flag.about),
+            };
+            match first {
+                Some(first) => help.push_str(&format!("{:<20}{first}\n", flag.name)),
+                None => help.push_str(&format!("{}\n", flag.name)),
+            }
+            for line in rest {
+                help.push_str(&format!("{:20}{line}\n", ""));
+            }
+        }
+        if !self.subcommands.is_empty() {
+            help.push_str("\nSUBCOMMANDS:\n");
+            for subcommand in self.subcommands {
+                for (i, line) in subcommand.about.iter().enumerate() {
+                    let name = if i == 0 { subcommand.name } else { "" };
+                    help.push_str(&format!("    {name:<16}{line}\n"));
+                }
+            }
+        }
+        if !self.examples.is_empty() {
+            help.push_str("\nEXAMPLES:\n");
+            for example in self.examples {
+                help.push_str(&format!("    {}\n", example.name));
+                for line in example.about {
+                    help.push_str(&format!("{:8}{line}\n", ""));
+                }
+            }
+        }
+        help.trim_end().to_owned()
+    }
+
+    /// The section of the Markdown CLI reference for the subcommand.
+    #[must_use]
+    pub fn markdown(&self) -> String {
+        let mut markdown = String::new();
+        writeln!(markdown, "## `xtask {}`\n\n{}\n", self.name, self.summary).ok();
+        writeln!(markdown, "```text\n{}\n```", self.render()).ok();
+        markdown
+    }
+
+    fn usage_line(&self) -> String {
+        if self.args.is_empty() {
+            format!("xtask {}", self.name)
+        } else {
+            format!("xtask {} {}", self.name, self.args)
+        }
+    }
+}
+
+pub const HELP: Entry = Entry {
+    name: "-h, --help",
+    about: &["Prints help information"],
+};
+
+pub const PRINT_ENV: Entry = Entry {
+    name: "--print-env",
+    about: &[
+        "Print the command line and environment before running it",
+        "(`--env-format json` prints it as JSON instead, listing the environment variables set for it)",
+    ],
+};
+
+pub const NO_CAPTURE: Entry = Entry {
+    name: "--no-capture",
+    about: &["Pass the output of tools run in parallel through as is, instead of prefixing each line with its tool"],
+};
+
+pub const LOCKED: Entry = Entry {
+    name: "--locked",
+    about: &[
+        "Pass `--locked` to cargo, and check that `Cargo.lock` is committed and unchanged afterwards",
+        "(also enabled by `XTASK_LOCKED=1` or `xtask.locked`)",
+    ],
+};
+
+pub const FROZEN: Entry = Entry {
+    name: "--frozen",
+    about: &["Pass `--frozen` to cargo, and check that `Cargo.lock` is committed and unchanged afterwards"],
+};
+
+pub const OFFLINE: Entry = Entry {
+    name: "--offline",
+    about: &["Pass `--offline` to cargo (also enabled by `XTASK_OFFLINE=1`)"],
+};
+
+pub const COLOR: Entry = Entry {
+    name: "--color <WHEN>",
+    about: &["Pass `--color <WHEN>` to cargo (auto, always, never)"],
+};
+
+pub const JOBS: Entry = Entry {
+    name: "-j, --jobs <N>",
+    about: &["Number of parallel jobs for cargo and the tools it runs"],
+};
+
+pub const PROFILE: Entry = Entry {
+    name: "--profile <NAME>",
+    about: &["Apply the settings of the `xtask.profiles` entry NAME (also selected by `XTASK_PROFILE`)"],
+};
+
+pub const TARGET_PRESET: Entry = Entry {
+    name: "--target-preset <NAME>",
+    about: &[
+        "Cross compile for the Android or iOS target preset NAME (also selected by `XTASK_TARGET_PRESET`):",
+        "android-arm64, android-armv7, android-x86, android-x86_64, ios, ios-sim, ios-sim-x86_64",
+    ],
+};
+
+pub const HERMETIC: Entry = Entry {
+    name: "--hermetic",
+    about: &["Run tools with only the allowlisted environment variables (see `xtask.hermetic`)"],
+};
+
+pub const PACKAGE: Entry = Entry {
+    name: "-p, --package <NAME>",
+    about: &["Operate on the given package instead of the defaults (may be repeated)"],
+};
+
+pub const MANIFEST_PATH: Entry = Entry {
+    name: "--manifest-path <PATH>",
+    about: &["Path to the `Cargo.toml` to pass to cargo"],
+};
+
+pub const CARGO_ARGS: Entry = Entry {
+    name: "-- '...'",
+    about: &["Extra arguments to pass to the cargo command"],
+};
@@ -0,0 +1,98 @@
+use crate::BoxResult;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+
+/// An entry of a compilation database (`compile_commands.json`).
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Entry {
+    /// The working directory of the compilation
+    pub directory: Utf8PathBuf,
+    /// The source file, as an absolute path
+    pub file: Utf8PathBuf,
+    /// The compiler invocation, starting with the compiler
+    pub arguments: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct RawEntry {
+    directory: Utf8PathBuf,
+    file: Utf8PathBuf,
+    #[serde(default)]
+    arguments: Option<Vec<String>>,
+    #[serde(default)]
+    command: Option<String>,
+}
+
+/// Loads the compilation database `compile_commands.json` of the `CMake` build directory `build_dir`.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The file cannot be read (e.g. the build has not been configured)
+/// - The file is malformed
+pub fn load(build_dir: &Utf8Path) -> BoxResult<Vec<Entry>> {
+    let path = build_dir.join("compile_commands.json");
+    let data = std::fs::read_to_string(&path).map_err(|err| {
+        format!(
+            "failed to read `{path}`: {err}\nConfigure the CMake build with `xtask cmake build` (with \
+             `CMAKE_EXPORT_COMPILE_COMMANDS` enabled)"
+        )
+    })?;
+    let entries = serde_json::from_str::<Vec<RawEntry>>(&data).map_err(|err| format!("malformed `{path}`: {err}"))?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| Entry {
+            file: entry.directory.join(entry.file),
+            arguments: entry
+                .arguments
+                .unwrap_or_else(|| split_command(entry.command.as_deref().unwrap_or_default())),
+            directory: entry.directory,
+        })
+        .collect())
+}
+
+/// The source files of `entries`, without duplicates.
+#[must_use]
+pub fn files(entries: &[Entry]) -> Vec<Utf8PathBuf> {
+    let files = entries.iter().map(|entry| entry.file.clone()).collect::<BTreeSet<_>>();
+    files.into_iter().collect()
+}
+
+/// Keeps only the entries whose source file is one of `files` (compared after resolving symlinks where possible).
+pub fn retain_files(entries: &mut Vec<Entry>, files: &[Utf8PathBuf]) {
+    let canonical = |path: &Utf8Path| path.canonicalize_utf8().unwrap_or_else(|_| path.to_owned());
+    let files = files.iter().map(|file| canonical(file)).collect::<BTreeSet<_>>();
+    entries.retain(|entry| files.contains(&canonical(&entry.file)));
+}
+
+// Split a `command` string into arguments the way a POSIX shell would, for the quoting CMake produces: words are
+// separated by whitespace, and may contain single-quoted, double-quoted, and backslash-escaped parts.
+fn split_command(command: &str) -> Vec<String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                word.extend(chars.by_ref().take_while(|&char| char != '\''));
+            },
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                while let Some(char) = chars.next() {
+                    match char {
+                        '"' => break,
+                        '\\' => word.extend(chars.next()),
+                        _ => word.push(char),
+                    }
+                }
+            },
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            _ if char.is_whitespace() => words.extend(word.take()),
+            _ => word.get_or_insert_with(String::new).push(char),
+        }
+    }
+    words.extend(word);
+    words
+}
@@ -1,6 +1,7 @@
 use crate::{BoxError, BoxResult};
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use serde::Deserialize;
+use std::{borrow::Cow, collections::BTreeMap, process::Command};
 
 #[cfg_attr(feature = "debug", derive(Debug))]
 #[derive(Deserialize)]
@@ -12,6 +13,39 @@ pub struct CMakeContext {
     pub bin_run_clang_tidy: Utf8PathBuf,
 }
 
+impl CMakeContext {
+    /// Replaces the clang tools found by `CMake` with those configured in `xtask.tools.paths` (under the names
+    /// `clang-format`, `clang-tidy`, `run-clang-format`, and `run-clang-tidy`).
+    pub fn apply_tool_paths(&mut self, xtask: &Xtask) {
+        for (name, bin) in [
+            ("clang-format", &mut self.bin_clang_format),
+            ("clang-tidy", &mut self.bin_clang_tidy),
+            ("run-clang-format", &mut self.bin_run_clang_format),
+            ("run-clang-tidy", &mut self.bin_run_clang_tidy),
+        ] {
+            if let Some(path) = xtask.tools.paths.get(name) {
+                *bin = path.clone();
+            }
+        }
+    }
+
+    /// Replaces the clang tools found by `CMake` with those in the first of `dirs` (in priority order) containing them,
+    /// e.g. the directories of `xtask.clang.platform.macos.searchPaths` (see
+    /// [`crate::detection::detect_macos_clang_paths`]).
+    pub fn apply_search_paths(&mut self, dirs: &[Utf8PathBuf]) {
+        for (name, bin) in [
+            ("clang-format", &mut self.bin_clang_format),
+            ("clang-tidy", &mut self.bin_clang_tidy),
+            ("run-clang-format", &mut self.bin_run_clang_format),
+            ("run-clang-tidy", &mut self.bin_run_clang_tidy),
+        ] {
+            if let Some(path) = dirs.iter().map(|dir| dir.join(name)).find(|path| path.is_file()) {
+                *bin = path;
+            }
+        }
+    }
+}
+
 #[cfg_attr(feature = "debug", derive(Debug))]
 #[derive(Deserialize)]
 pub struct RustToolchain {
@@ -33,21 +67,905 @@ pub struct RustToolchainToolchain {
     pub targets: Vec<String>,
 }
 
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Xtask {
+    /// The version of the config schema (see [`crate::migration::SCHEMA_VERSION`]), or `None` for a config predating
+    /// versioning
+    #[serde(default)]
+    pub schema_version: Option<u32>,
+    /// Composite commands, keyed by name, each running xtask command lines in order (e.g.
+    /// `precommit = ["fmt --check", "clippy"]`)
+    ///
+    /// An alias named like a built-in subcommand is ignored.
+    #[serde(default)]
+    pub alias: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub android: XtaskAndroid,
+    #[serde(default)]
+    pub bench: XtaskBench,
+    #[serde(default)]
+    pub bridge: XtaskBridge,
+    #[serde(default)]
+    pub cache: XtaskCache,
+    #[serde(default)]
+    pub check_all: XtaskCheckAll,
+    #[serde(default)]
+    pub clang: XtaskClang,
+    #[serde(default)]
+    pub cmake: XtaskCmake,
+    #[serde(default)]
+    pub cxx: XtaskCxx,
+    #[serde(default)]
+    pub dist: XtaskDist,
+    #[serde(default)]
+    pub doc: XtaskDoc,
+    #[serde(default)]
+    pub headers: XtaskHeaders,
+    #[serde(default)]
+    pub hermetic: XtaskHermetic,
+    #[serde(default)]
+    pub ios: XtaskIos,
+    #[serde(default)]
+    pub licenses: XtaskLicenses,
+    #[serde(default)]
+    pub link: XtaskLink,
+    #[serde(default)]
+    pub lint: XtaskLint,
+    #[serde(default)]
+    pub outdated: XtaskOutdated,
+    #[serde(default)]
+    pub python: XtaskPython,
+    #[serde(default)]
+    pub rust: XtaskRust,
+    #[serde(default)]
+    pub status: XtaskStatus,
+    /// Environment variables set for every tool the xtask spawns
+    ///
+    /// These are added on top of the environment inherited from the xtask process, overriding inherited variables of
+    /// the same name.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// A `.env` file of environment variables, relative to the workspace root, merged over `xtask.env` when the config
+    /// is loaded (e.g. `.env`)
+    ///
+    /// For secrets and machine-specific settings (e.g. `VCPKG_ROOT`) kept out of the committed config. The variant of
+    /// each profile (e.g. `.env.asan` for the profile `asan`) is likewise merged over the `env` of the profile. Files
+    /// that do not exist are skipped (see [`crate::env_file`]).
+    #[serde(default)]
+    pub env_file: Option<Utf8PathBuf>,
+    /// Pass `--locked` to every cargo invocation (as with `--locked`), and check that `Cargo.lock` is committed and
+    /// unchanged after each subcommand
+    #[serde(default)]
+    pub locked: bool,
+    /// Keep the inherited locale for the tools whose output the xtask parses (e.g. version probes), instead of running
+    /// them with `LC_ALL=C` and `LANG=C`
+    #[serde(default)]
+    pub keep_locale: bool,
+    /// The default number of parallel jobs, overridden by `-j/--jobs`
+    #[serde(default)]
+    pub jobs: Option<u32>,
+    /// Per-command settings, keyed by xtask subcommand name (e.g. `clippy`)
+    #[serde(default)]
+    pub commands: BTreeMap<String, XtaskCommand>,
+    /// Commands run before and after xtask subcommands, keyed by subcommand name (e.g. `cmake`)
+    #[serde(default)]
+    pub hooks: BTreeMap<String, XtaskHooks>,
+    /// Named bundles of settings selected with `--profile <NAME>` (e.g. `ci` or `asan`)
+    #[serde(default)]
+    pub profiles: BTreeMap<String, XtaskProfile>,
+    /// Per-tool settings, keyed by program name (e.g. `clang-format`)
+    #[serde(default)]
+    pub tools: XtaskTools,
+}
+
+impl Xtask {
+    /// Loads the xtask config of the workspace described by `cargo_metadata`, or the default config if there is none.
+    ///
+    /// Unlike [`Config::load`], this does not need the `CMake` context of the workspace, so it can be used from a build
+    /// script with the metadata of the package being built.
+    ///
+    /// A config of an older schema version is upgraded in memory (see [`crate::migration::migrate`]), leaving the file
+    /// to `xtask config migrate`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the following circumstances:
+    /// - More than one xtask config is present (see [`xtask_value`])
+    /// - The xtask config cannot be read or is malformed
+    /// - The xtask config is of a newer schema version than this xtask supports
+    /// - `xtask.clang.version` is `"auto"` but reading the `.clang-version` file as text fails
+    /// - A path in `xtask.tools.paths` is not absolute
+    /// - The `xtask.envFile` (or a profile's variant of it) cannot be read or is malformed (see
+    ///   [`crate::env_file::load`])
+    pub fn load(cargo_metadata: &cargo_metadata::Metadata) -> BoxResult<Self> {
+        let mut xtask = match xtask_value(cargo_metadata)? {
+            Some((_, mut value)) => {
+                crate::migration::migrate(&mut value)?;
+                serde_json::from_value(value)?
+            },
+            None => Xtask::default(),
+        };
+        if xtask.clang.version == "auto" {
+            let path = cargo_metadata.workspace_root.join(".clang-version");
+            let data = std::fs::read_to_string(&path).map_err(|err| {
+                if err.kind() == std::io::ErrorKind::NotFound {
+                    format!(
+                        "Path not found: {} (required by `xtask.clang.version = \"auto\"`)",
+                        path.as_std_path().display()
+                    )
+                    .into()
+                } else {
+                    BoxError::from(err)
+                }
+            })?;
+            xtask.clang.version = data.trim().into();
+        }
+        if let Some((name, path)) = xtask.tools.paths.iter().find(|(_, path)| !path.is_absolute()) {
+            return Err(format!("`xtask.tools.paths` entry `{name}` must be an absolute path, found `{path}`").into());
+        }
+        if let Some(env_file) = &xtask.env_file {
+            let path = cargo_metadata.workspace_root.join(env_file);
+            xtask.env.extend(crate::env_file::load(&path)?);
+            for (name, profile) in &mut xtask.profiles {
+                let variant = Utf8PathBuf::from(format!("{path}.{name}"));
+                profile.env.extend(crate::env_file::load(&variant)?);
+            }
+        }
+        Ok(xtask)
+    }
+
+    /// Sets `LC_ALL=C` and `LANG=C` for `cmd`, whose output the xtask parses, so that localized tools report in
+    /// English with the same number formats everywhere. This is skipped if `xtask.keepLocale` is set.
+    pub fn c_locale(&self, cmd: &mut Command) {
+        if !self.keep_locale {
+            cmd.env("LC_ALL", "C");
+            cmd.env("LANG", "C");
+        }
+    }
+
+    /// The argument used to probe `tool` (a program name or path) for its version.
+    ///
+    /// This is the configured `xtask.tools.<name>.probe`, where `<name>` is the file name of `tool`, or `--version`.
+    #[must_use]
+    pub fn tool_probe(&self, tool: &str) -> &str {
+        let name = camino::Utf8Path::new(tool).file_name().unwrap_or(tool);
+        self.tools
+            .get(name)
+            .and_then(|tool| tool.probe.as_deref())
+            .unwrap_or("--version")
+    }
+
+    /// The program to run for `tool`: the path configured for it in `xtask.tools.paths`, if any, or else that of
+    /// [`rust_program`] for `cargo` and `rustup`, or else `tool` itself (to be searched for in `PATH`).
+    #[must_use]
+    pub fn tool_path<'a>(&'a self, tool: &'a str) -> Cow<'a, str> {
+        if let Some(path) = self.tools.paths.get(tool) {
+            return Cow::Borrowed(path.as_str());
+        }
+        rust_program(tool).map_or(Cow::Borrowed(tool), |path| Cow::Owned(path.into_string()))
+    }
+
+    /// The program to run for `cargo` with `toolchain` (if any): the configured `xtask.rust.cargo` wrapper, if any, or
+    /// else `cargo` resolved with [`Xtask::tool_path`].
+    ///
+    /// Without a `toolchain`, `$CARGO` takes the place of the rustup proxy, since cargo sets it to its own path when it
+    /// runs the xtask. (That cargo belongs to a single toolchain, so it cannot run `+<toolchain>` commands.)
+    #[must_use]
+    pub fn cargo_program(&self, toolchain: Option<&str>) -> Cow<'_, str> {
+        if let Some(wrapper) = &self.rust.cargo {
+            return self.tool_path(wrapper);
+        }
+        if toolchain.is_none() && !self.tools.paths.contains_key("cargo") {
+            if let Some(cargo) = running_cargo() {
+                return Cow::Owned(cargo);
+            }
+        }
+        self.tool_path("cargo")
+    }
+}
+
+/// The cargo running the xtask: `$CARGO`, which cargo sets to its own path for the processes it runs.
+#[must_use]
+pub fn running_cargo() -> Option<String> {
+    std::env::var("CARGO").ok().filter(|cargo| !cargo.is_empty())
+}
+
+/// The rustup proxy for `cargo` or `rustup` in `$CARGO_HOME/bin`, if `CARGO_HOME` is set and the proxy exists there.
+///
+/// For other tools, or without the proxy, this is `None`, so that the tool is searched for in `PATH`.
+#[must_use]
+pub fn rust_program(tool: &str) -> Option<Utf8PathBuf> {
+    if !["cargo", "rustup"].contains(&tool) {
+        return None;
+    }
+    let cargo_home = std::env::var("CARGO_HOME").ok().filter(|home| !home.is_empty())?;
+    let proxy = Utf8Path::new(&cargo_home)
+        .join("bin")
+        .join(format!("{tool}{}", std::env::consts::EXE_SUFFIX));
+    proxy.is_file().then_some(proxy)
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskHooks {
+    /// Commands run before the subcommand, each a program followed by its arguments (e.g. `["python3", "gen.py"]`)
+    #[serde(default)]
+    pub pre: Vec<Vec<String>>,
+    /// Commands run after the subcommand succeeds
+    #[serde(default)]
+    pub post: Vec<Vec<String>>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskProfile {
+    /// `CMake` cache entries passed as `-D<NAME>=<VALUE>` when configuring with `xtask cmake build`
+    #[serde(default)]
+    pub cmake_defines: BTreeMap<String, String>,
+    /// Environment variables set for every tool, on top of (and overriding) `xtask.env`
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Toolchains used in place of the pinned or configured ones
+    #[serde(default)]
+    pub rust: XtaskRust,
+    /// Per-command settings, whose `args` follow those of `xtask.commands` (e.g. `--release` for `build`)
+    #[serde(default)]
+    pub commands: BTreeMap<String, XtaskCommand>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskTools {
+    /// Absolute paths of tools, keyed by program name (e.g. `clang-tidy = "/opt/llvm/bin/clang-tidy"`), used in place
+    /// of the tools found by `CMake` or in `PATH`
+    #[serde(default)]
+    pub paths: BTreeMap<String, Utf8PathBuf>,
+    /// The settings of each tool, keyed by program name
+    #[serde(flatten)]
+    pub settings: BTreeMap<String, XtaskTool>,
+}
+
+impl XtaskTools {
+    /// The settings of the tool named `name`, if any.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&XtaskTool> {
+        self.settings.get(name)
+    }
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskTool {
+    /// Argument used to probe the tool for its version in place of `--version` (e.g. `-version` or `version`)
+    #[serde(default)]
+    pub probe: Option<String>,
+    /// The minimum required version, checked before the xtask commands run the tool (e.g. `"3.20"` for `cmake`)
+    #[serde(default)]
+    pub version: Option<String>,
+    /// A regex matching the version in the output of the probe, as its first capture group (e.g. `^(\d+\.\d+)`)
+    ///
+    /// By default, the version is the number following the word `version`, or else the first dotted number.
+    #[serde(default)]
+    pub matcher: Option<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskCommand {
+    /// Extra arguments inserted before the `-- '...'` arguments given on the command line
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Lint levels passed to `clippy` in place of the default `-D warnings` (only used by `xtask clippy`)
+    #[serde(default)]
+    pub lints: Option<XtaskLints>,
+}
+
+/// Lint levels, passed on as `-D`, then `-W`, then `-A` flags so that `allow` can carve exceptions out of `deny`.
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskLints {
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default)]
+    pub warn: Vec<String>,
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskBench {
+    /// The default regression threshold for `xtask bench --baseline` (e.g. `"5%"`)
+    #[serde(default)]
+    pub fail_on_regress: Option<String>,
+    /// Google Benchmark JSON output files to collect results from, relative to the workspace root
+    #[serde(default)]
+    pub google_benchmark_results: Vec<Utf8PathBuf>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskBridge {
+    /// Directory of committed `cxxbridge` headers, laid out as `<package>/<path within package>.h`
+    #[serde(default)]
+    pub gen_dir: Option<Utf8PathBuf>,
+    /// Vendored copies of the `cxx.h` and `cxx.cc` of the `cxx` crate compiled on the C++ side, relative to the
+    /// workspace root
+    #[serde(default)]
+    pub cxx_sources: Vec<Utf8PathBuf>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskCache {
+    /// The remote cache of `xtask cache push` and `xtask cache pull`: an `s3://` or `gs://` bucket prefix (accessed
+    /// with the `aws` or `gsutil` CLI and its credentials), an `http://` or `https://` base URL taking `PUT` and `GET`
+    /// requests, or a `file://` directory
+    #[serde(default)]
+    pub url: Option<String>,
+    /// The directories cached, relative to the workspace root (e.g. `build` and a `.ccache` directory)
+    ///
+    /// The `CMake` build directory `build` is cached if this is empty.
+    #[serde(default)]
+    pub paths: Vec<Utf8PathBuf>,
+    /// Globs of the git-tracked files whose contents key the cache entries, relative to the workspace root (e.g.
+    /// `CMakeLists.txt` and `cpp/**`)
+    ///
+    /// All tracked files key the entries if this is empty.
+    #[serde(default)]
+    pub key_files: Vec<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskCheckAll {
+    /// The steps of `xtask check-all`, replacing the default ones, each an xtask command line (e.g.
+    /// `"clippy --all-targets -- --all-features"`) or a table with the command line as `run` and the platforms it is
+    /// limited to
+    #[serde(default, deserialize_with = "check_all_steps")]
+    pub steps: Vec<XtaskCheckAllStep>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskCheckAllStep {
+    /// The xtask command line of the step
+    pub run: String,
+    /// The platforms to run the step on (all platforms if empty)
+    ///
+    /// A platform is an OS (e.g. `linux`, `macos`, `windows`), an OS family (`unix` or `windows`), or an OS and
+    /// architecture (e.g. `macos-aarch64`), as named by Rust's [`std::env::consts`].
+    #[serde(default)]
+    pub only: Vec<String>,
+    /// The platforms to skip the step on, named as for `only`
+    #[serde(default)]
+    pub skip: Vec<String>,
+}
+
+impl XtaskCheckAllStep {
+    /// Whether the step runs on the host platform.
+    #[must_use]
+    pub fn runs_on_host(&self) -> bool {
+        let host = |platform: &String| {
+            use std::env::consts::{ARCH, FAMILY, OS};
+            *platform == OS || *platform == FAMILY || *platform == format!("{OS}-{ARCH}")
+        };
+        (self.only.is_empty() || self.only.iter().any(host)) && !self.skip.iter().any(host)
+    }
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskClang {
+    /// The required clang version, or `"auto"` to read it from the workspace `.clang-version` file; any version is
+    /// accepted if unset
+    #[serde(default)]
+    pub version: String,
+    /// Accept Apple clang, whose version numbers do not follow LLVM releases, for `version`
+    #[serde(default)]
+    pub allow_apple_clang: bool,
+    /// Fail, rather than warn, when a clang tool is ambiguous: when its alternative name (e.g. `clang-format-17` for
+    /// `clang-format`, or the reverse) resolves to a different version also matching `version` (see
+    /// [`crate::validation::check_clang_ambiguity`])
+    #[serde(default)]
+    pub strict_tools: bool,
+    /// Regexes matching the version in the version output of the clang tools, as their first capture group, keyed by
+    /// tool name (e.g. `clang-tidy`)
+    ///
+    /// A tool may have a single regex or a list of alternatives (e.g. for the banners of different LLVM releases),
+    /// which are tried in order until one matches. These override the built-in matchers for `clang`, `clang++`,
+    /// `clangd`, `clang-format`, and `clang-tidy` (see [`crate::validation::CLANG_MATCHERS`]), which are used for the
+    /// tools not configured here.
+    #[serde(default, deserialize_with = "one_or_many_values")]
+    pub matchers: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub platform: XtaskPlatform,
+    /// Extra arguments for the clang subcommands, inserted before the `-- '...'` arguments given on the command line
+    #[serde(default)]
+    pub extra_args: XtaskClangExtraArgs,
+    #[serde(default)]
+    pub analyze: XtaskClangAnalyze,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskClangAnalyze {
+    /// The directory the HTML reports of `xtask clang analyze` are written to, relative to the workspace root
+    /// [default: `target/xtask/analyze`]
+    #[serde(default)]
+    pub output_dir: Option<Utf8PathBuf>,
+    /// The number of new findings (those not in `baseline`) `xtask clang analyze` tolerates before failing, overridden
+    /// by `--max-findings`
+    /// [default: 0]
+    #[serde(default)]
+    pub max_findings: Option<usize>,
+    /// The file of accepted findings, relative to the workspace root (e.g. `.clang-analyze-baseline`), which do not
+    /// count toward `max_findings`; it is written by `xtask clang analyze --update-baseline`
+    #[serde(default)]
+    pub baseline: Option<Utf8PathBuf>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskClangExtraArgs {
+    /// Arguments for run-clang-format.py (e.g. `--extensions=cpp,h,cc`); with `--staged`, its options are applied
+    /// to clang-format, which is run directly
+    #[serde(default)]
+    pub format: Vec<String>,
+    /// Arguments for run-clang-tidy (e.g. `-config-file=.clang-tidy`)
+    #[serde(default)]
+    pub tidy: Vec<String>,
+    /// Arguments for the clang static analyzer (e.g. `-Xanalyzer -analyzer-disable-checker=deadcode`)
+    #[serde(default)]
+    pub analyze: Vec<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskCmake {
+    /// The minimum required cmake version
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub speed: XtaskCmakeSpeed,
+    #[serde(default)]
+    pub install: XtaskCmakeInstall,
+    /// Build the CMake project before `xtask build` and `xtask check`, for crates linking against its libraries
+    #[serde(default)]
+    pub prerequisite: Option<XtaskCmakePrerequisite>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskCmakePrerequisite {
+    /// The targets built with `cmake --build` (e.g. `core`), or all targets if empty
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// Paths within the `CMake` build directory set as environment variables for cargo, keyed by variable name (e.g.
+    /// `CXXLIB_DIR = "lib"`), so that build scripts find the built libraries
+    #[serde(default)]
+    pub env: BTreeMap<String, Utf8PathBuf>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskCmakeInstall {
+    /// The headers `xtask cmake install` expects, relative to the `include` directory of the prefix (e.g.
+    /// `foo/foo.h`)
+    #[serde(default)]
+    pub headers: Vec<String>,
+    /// The libraries `xtask cmake install` expects, by name (e.g. `foo` for `lib/libfoo.a` or `lib/foo.lib`)
+    #[serde(default)]
+    pub libraries: Vec<String>,
+    /// The pkg-config packages `xtask cmake install` expects, by name (e.g. `foo` for `lib/pkgconfig/foo.pc`)
+    #[serde(default)]
+    pub pkg_config: Vec<String>,
+}
+
+impl XtaskCmakeInstall {
+    /// Whether no installed files are expected.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty() && self.libraries.is_empty() && self.pkg_config.is_empty()
+    }
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskCmakeSpeed {
+    /// Whether the precompiled headers declared with `target_precompile_headers` are used (via
+    /// `CMAKE_DISABLE_PRECOMPILE_HEADERS`), or `None` to leave it to the project
+    #[serde(default)]
+    pub pch: Option<bool>,
+    /// Unity builds combining up to N sources per batch (via `CMAKE_UNITY_BUILD` and `CMAKE_UNITY_BUILD_BATCH_SIZE`),
+    /// `0` to turn them off, or `None` to leave it to the project
+    #[serde(default)]
+    pub unity: Option<u32>,
+}
+
+impl XtaskCmakeSpeed {
+    /// The `-D` arguments for `cmake` applying the settings.
+    #[must_use]
+    pub fn cmake_defines(&self) -> Vec<String> {
+        let mut defines = vec![];
+        if let Some(pch) = self.pch {
+            let disable = if pch { "OFF" } else { "ON" };
+            defines.push(format!("-DCMAKE_DISABLE_PRECOMPILE_HEADERS={disable}"));
+        }
+        match self.unity {
+            Some(0) => defines.push("-DCMAKE_UNITY_BUILD=OFF".into()),
+            Some(batch_size) => {
+                defines.push("-DCMAKE_UNITY_BUILD=ON".into());
+                defines.push(format!("-DCMAKE_UNITY_BUILD_BATCH_SIZE={batch_size}"));
+            },
+            None => {},
+        }
+        defines
+    }
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskPlatform {
+    #[serde(default)]
+    pub macos: XtaskPlatformMacos,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskPlatformMacos {
+    /// Locations searched for clang tools, in priority order, in place of those found by `CMake` (but not of those
+    /// in `xtask.tools.paths`)
+    #[serde(default)]
+    pub search_paths: Vec<XtaskPlatformMacosSearchPath>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum XtaskPlatformMacosSearchPath {
+    /// `$(brew --prefix llvm@<major>)/bin`
+    Homebrew,
+    /// `/opt/local/libexec/llvm-<major>/bin`
+    MacPorts,
+    /// The directory containing `$(xcrun --find clang)`, i.e., of the toolchain chosen with `xcode-select`
+    #[serde(alias = "xcodeSelect")]
+    Xcode,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskCxx {
+    /// Globs of the C/C++ sources of the project, relative to the workspace root (e.g. `cxx/**/*.{h,cc}`), in place of
+    /// all the git-tracked files with a C or C++ extension
+    ///
+    /// The sources are those formatted, tidied, and analyzed by `xtask clang`, and checked for the license header by
+    /// `xtask headers` (see [`crate::cxx_sources`]).
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Globs of the files to leave out of the C/C++ sources, relative to the workspace root (e.g. `third_party/**`)
+    ///
+    /// These are also left out of the coverage measured by `xtask tarpaulin`.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskDist {
+    /// The name of the distribution archives, in place of the name of the first selected package
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Extra files to ship at the top of the archives (e.g. `README.md`), relative to the workspace root
+    #[serde(default)]
+    pub include: Vec<Utf8PathBuf>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::struct_excessive_bools)]
+pub struct XtaskDoc {
+    /// Always pass `--no-deps` to `cargo doc`, as with `xtask doc --no-deps`
+    #[serde(default)]
+    pub no_deps: bool,
+    /// Always pass `--document-private-items` to `cargo doc`, as with `xtask doc --document-private-items`
+    #[serde(default)]
+    pub document_private_items: bool,
+    /// Always deny rustdoc warnings, as with `xtask doc --deny-warnings`
+    #[serde(default)]
+    pub deny_warnings: bool,
+    /// Always check the links of the generated documentation, as with `xtask doc --check-links`
+    #[serde(default)]
+    pub check_links: bool,
+    /// The link checker for `--check-links`: `builtin` (the default), which checks the links between the generated
+    /// pages, or `lychee`, which also checks external links
+    #[serde(default)]
+    pub link_checker: Option<String>,
+    /// Also generate the documentation of the C++ code with doxygen, after the rustdoc documentation
+    #[serde(default)]
+    pub doxygen: Option<XtaskDocDoxygen>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskDocDoxygen {
+    /// The `Doxyfile` passed to doxygen, relative to the workspace root
+    pub doxyfile: Utf8PathBuf,
+    /// The HTML output directory of the `Doxyfile`, relative to the workspace root (e.g. `target/doxygen/html`)
+    pub html_dir: Utf8PathBuf,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskHeaders {
+    /// The license header every source file must start with (e.g. `SPDX-License-Identifier: MIT OR Apache-2.0`),
+    /// without comment markers, which may span several lines
+    #[serde(default)]
+    pub header: Option<String>,
+    /// Comment styles by file extension (without the dot), in place of the built-in `//` line comments for `rs` and
+    /// the C and C++ extensions; files with other extensions are not checked
+    #[serde(default)]
+    pub styles: BTreeMap<String, XtaskHeadersStyle>,
+    /// Globs of files not to check, relative to the workspace root (e.g. `third_party/**`, or `*.pb.h` for a file
+    /// name in any directory)
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskHeadersStyle {
+    /// The line opening the header comment, if any (e.g. `/*`)
+    #[serde(default)]
+    pub start: Option<String>,
+    /// The prefix of each line of the header (e.g. `//`, or ` *` between `start` and `end`)
+    pub line: String,
+    /// The line closing the header comment, if any (e.g. ` */`)
+    #[serde(default)]
+    pub end: Option<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskHermetic {
+    /// Environment variables kept with `--hermetic`, in addition to the built-in allowlist (e.g. `VCPKG_ROOT`)
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskLicenses {
+    /// C++ dependency manifests (`vcpkg.json` or `conanfile.txt`), relative to the workspace root
+    #[serde(default)]
+    pub manifests: Vec<Utf8PathBuf>,
+    /// Vendored C++ dependencies, which also provide the licenses of the dependencies listed in `manifests`
+    #[serde(default)]
+    pub vendored: Vec<XtaskLicensesDependency>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskLink {
+    /// The linker for the C++ and Rust code of the workspace (`lld` or `mold`), instead of the platform default
+    ///
+    /// mold is only supported on Linux, so lld is used in its place on macOS and Windows. Target presets keep the
+    /// linker of their toolchain.
+    #[serde(default)]
+    pub linker: Option<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskLint {
+    #[serde(default)]
+    pub cmake: XtaskLintCmake,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskLintCmake {
+    /// The formatter of `xtask lint cmake`: `cmake-format` (the default), which comes with the `cmake-lint` linter, or
+    /// `gersemi`, which only formats
+    #[serde(default)]
+    pub backend: Option<String>,
+    /// Globs of the `CMake` files to leave out, relative to the workspace root (e.g. `third_party/**`)
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskOutdated {
+    /// The upstream git repositories of the conan packages locked in `conan.lock`, by package name (e.g.
+    /// `fmt = "https://github.com/fmtlib/fmt"`), whose release tags `xtask outdated --cxx` compares against
+    #[serde(default)]
+    pub repositories: BTreeMap<String, String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskLicensesDependency {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// An SPDX license expression (e.g. `MIT OR Apache-2.0`)
+    pub license: String,
+    #[serde(default)]
+    pub repository: Option<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskPython {
+    /// The Python interpreter, in place of the active virtualenv's or the platform default
+    #[serde(default)]
+    pub interpreter: Option<String>,
+    /// The minimum required Python version
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskAndroid {
+    /// The minimum Android API level for the Android target presets (default 24)
+    #[serde(default)]
+    pub api_level: Option<u32>,
+    /// The Android NDK directory, in place of `ANDROID_NDK_HOME` or `ANDROID_NDK_ROOT`
+    #[serde(default)]
+    pub ndk: Option<Utf8PathBuf>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskIos {
+    /// The minimum iOS version for the iOS target presets (default `"13.0"`)
+    #[serde(default)]
+    pub deployment_target: Option<String>,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskStatus {
+    /// The directory the status of `xtask check` and `xtask tarpaulin` is written to as JSON, relative to the workspace
+    /// root (e.g. `public/status`)
+    #[serde(default)]
+    pub dir: Option<Utf8PathBuf>,
+    /// Also write SVG badges of the status (and of the coverage, for `xtask tarpaulin`)
+    #[serde(default)]
+    pub badges: bool,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskRust {
+    /// A program run in place of `cargo` for every cargo invocation (e.g. `cross`, or a shim that sets up
+    /// credentials), which must accept the arguments of cargo, including `+<toolchain>`
+    #[serde(default)]
+    pub cargo: Option<String>,
+    #[serde(default)]
+    pub toolchain: XtaskRustToolchain,
+}
+
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XtaskRustToolchain {
+    /// The nightly toolchain, or a list of acceptable ones in order of preference, of which the first installed one is
+    /// used (e.g. to fall back to an older nightly while a newer one is yanked or lacks miri)
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub nightly: Vec<String>,
+    #[serde(default)]
+    pub stable: Option<String>,
+}
+
+// Deserializes either a single string or a list of strings.
+fn one_or_many<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(one) => vec![one],
+        OneOrMany::Many(many) => many,
+    })
+}
+
+// Deserializes a map whose values are either a single string or a list of strings.
+fn one_or_many_values<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Vec<String>>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(transparent)]
+    struct Values(#[serde(deserialize_with = "one_or_many")] Vec<String>);
+    Ok(BTreeMap::<String, Values>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, Values(values))| (key, values))
+        .collect())
+}
+
+// Deserializes a list of `check-all` steps, each either a command line or a table.
+fn check_all_steps<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<XtaskCheckAllStep>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Step {
+        Run(String),
+        Table(XtaskCheckAllStep),
+    }
+    Ok(Vec::<Step>::deserialize(deserializer)?
+        .into_iter()
+        .map(|step| match step {
+            Step::Run(run) => XtaskCheckAllStep {
+                run,
+                only: vec![],
+                skip: vec![],
+            },
+            Step::Table(step) => step,
+        })
+        .collect())
+}
+
 pub struct Config {
     pub cmake_context: CMakeContext,
     pub cargo_metadata: cargo_metadata::Metadata,
-    pub rust_toolchain: RustToolchain,
+    pub rust_toolchain: Option<RustToolchain>,
+    pub xtask: Xtask,
 }
 
 impl Config {
     /// # Errors
     ///
     /// Will return `Err` under the following circumstances:
-    /// - `cargo metadata` fails
-    /// - Reading the `rust-toolchain.toml` file as text fails
+    /// - `cargo metadata` fails (see [`crate::workspace::metadata`])
+    /// - Reading the `rust-toolchain.toml` file as text fails (other than it not existing)
+    /// - Loading the xtask config fails (see [`Xtask::load`])
+    /// - Probing a configured macOS clang search path fails (see [`crate::detection::detect_macos_clang_paths`])
     pub fn load() -> BoxResult<Self> {
-        let cargo_metadata = cargo_metadata::MetadataCommand::new().exec()?;
-        let cmake_context = {
+        let cargo_metadata = crate::workspace::metadata(&crate::runner::SystemRunner, offline_requested())?;
+        let mut cmake_context: CMakeContext = {
             let path = cargo_metadata.workspace_root.join("build/cxx-auto-context.json");
             let data = std::fs::read_to_string(&path).map_err(|err| {
                 if err.kind() == std::io::ErrorKind::NotFound {
@@ -58,37 +976,246 @@ impl Config {
             })?;
             serde_json::from_str(&data)?
         };
-        let rust_toolchain: RustToolchain = {
+        let rust_toolchain: Option<RustToolchain> = {
             let path = cargo_metadata.workspace_root.join("rust-toolchain.toml");
-            let data = std::fs::read_to_string(&path).map_err(|err| {
-                if err.kind() == std::io::ErrorKind::NotFound {
-                    format!("Path not found: {}", path.as_std_path().display()).into()
-                } else {
-                    BoxError::from(err)
-                }
-            })?;
-            toml::from_str(&data)?
+            match std::fs::read_to_string(path) {
+                Ok(data) => Some(toml::from_str(&data)?),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+                Err(err) => return Err(err.into()),
+            }
         };
+        let xtask = Xtask::load(&cargo_metadata)?;
+        // NOTE: the configured search paths take precedence over the tools found by `CMake`, and `xtask.tools.paths`
+        // over both
+        #[cfg(target_os = "macos")]
+        cmake_context.apply_search_paths(&crate::detection::detect_macos_clang_paths(
+            &xtask,
+            &crate::runner::SystemRunner,
+        )?);
+        cmake_context.apply_tool_paths(&xtask);
         Ok(Config {
             cmake_context,
             cargo_metadata,
             rust_toolchain,
+            xtask,
         })
     }
 }
 
+// Whether the xtask runs offline (see `crate::command::Context::offline`), as requested by `XTASK_OFFLINE=1` or an
+// `--offline` flag before the `--` separating the tool arguments.
+//
+// NOTE: the config is loaded before the flags are parsed into the context, so they are looked for here as well
+fn offline_requested() -> bool {
+    std::env::var("XTASK_OFFLINE").map_or(false, |val| val == "1")
+        || std::env::args_os()
+            .skip(1)
+            .take_while(|arg| arg != "--")
+            .any(|arg| arg == "--offline")
+}
+
+/// The xtask config file names looked for in the workspace root, in place of `[workspace.metadata.xtask]`.
+pub const XTASK_FILES: &[&str] = &["xtask.toml", "xtask.json", "xtask.yaml", "xtask.yml"];
+
+/// The raw xtask config and a description of where it came from, or `None` if there is none.
+///
+/// The config is read from whichever of the [`XTASK_FILES`] exists in the workspace root, or else from the
+/// `[workspace.metadata.xtask]` table.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - More than one of the config files exists, or one exists alongside `[workspace.metadata.xtask]`
+/// - The config file cannot be read or parsed
+pub fn xtask_value(cargo_metadata: &cargo_metadata::Metadata) -> BoxResult<Option<(String, serde_json::Value)>> {
+    let files = XTASK_FILES
+        .iter()
+        .map(|file| cargo_metadata.workspace_root.join(file))
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    let metadata = cargo_metadata.workspace_metadata.get("xtask");
+    match (files.as_slice(), metadata) {
+        ([], None) => Ok(None),
+        ([], Some(value)) => Ok(Some(("[workspace.metadata.xtask]".into(), value.clone()))),
+        ([path], None) => Ok(Some((path.to_string(), read_xtask_file(path)?))),
+        _ => {
+            let mut sources = files.iter().map(ToString::to_string).collect::<Vec<_>>();
+            if metadata.is_some() {
+                sources.push("[workspace.metadata.xtask]".into());
+            }
+            Err(format!(
+                "the xtask config must be given in only one place, found: {}",
+                sources.join(", ")
+            )
+            .into())
+        },
+    }
+}
+
+/// Reads an xtask config file, in the format given by its extension (`toml`, `json`, `yaml`, or `yml`).
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The extension is not one of the supported formats
+/// - The file cannot be read or parsed
+pub fn read_xtask_file(path: &Utf8Path) -> BoxResult<serde_json::Value> {
+    let data = std::fs::read_to_string(path).map_err(|err| format!("failed to read `{path}`: {err}"))?;
+    let value = match path.extension() {
+        Some("toml") => toml::from_str(&data).map_err(|err| format!("failed to parse `{path}`: {err}"))?,
+        Some("json") => serde_json::from_str(&data).map_err(|err| format!("failed to parse `{path}`: {err}"))?,
+        Some("yaml" | "yml") => {
+            serde_yaml::from_str(&data).map_err(|err| format!("failed to parse `{path}`: {err}"))?
+        },
+        _ => return Err(format!("unsupported xtask config format `{path}`: expected toml, json, or yaml").into()),
+    };
+    Ok(value)
+}
+
+/// Writes an xtask config file, in the format given by its extension (`toml`, `json`, `yaml`, or `yml`).
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The extension is not one of the supported formats
+/// - `value` cannot be represented in the format (e.g. `null` in TOML)
+/// - The file cannot be written
+pub fn write_xtask_file(path: &Utf8Path, value: &serde_json::Value) -> BoxResult<()> {
+    let data = match path.extension() {
+        Some("toml") => toml::to_string_pretty(value)?,
+        Some("json") => serde_json::to_string_pretty(value)? + "\n",
+        Some("yaml" | "yml") => serde_yaml::to_string(value)?,
+        _ => return Err(format!("unsupported xtask config format `{path}`: expected toml, json, or yaml").into()),
+    };
+    std::fs::write(path, data).map_err(|err| format!("failed to write `{path}`: {err}"))?;
+    Ok(())
+}
+
+/// The configured default arguments for the xtask subcommand `name`.
+#[must_use]
+pub fn command_default_args<'a>(config: &'a Config, name: &str) -> &'a [String] {
+    config
+        .xtask
+        .commands
+        .get(name)
+        .map_or(&[], |command| command.args.as_slice())
+}
+
+/// The argument used to probe `tool` (a program name or path) for its version (see [`Xtask::tool_probe`]).
+#[must_use]
+pub fn tool_probe<'a>(config: &'a Config, tool: &str) -> &'a str {
+    config.xtask.tool_probe(tool)
+}
+
 pub mod rust {
+    /// Toolchain names passed to `cargo +<toolchain>`.
+    ///
+    /// Resolution order, first match wins:
+    /// 1. The `channel` pinned by the workspace `rust-toolchain.toml` (what rustup would auto-select), for the kind of
+    ///    toolchain it is: a `nightly*` channel pins the nightly toolchain, and any other the stable one
+    /// 2. The `[workspace.metadata.xtask.rust.toolchain]` `nightly` or `stable` name (or names, for `nightly`)
+    /// 3. The plain `nightly` or `stable` channel
     pub mod toolchain {
         use crate::config::Config;
 
+        // The channel pinned by `rust-toolchain.toml`, if it is a nightly one (`nightly`) or not (`!nightly`).
+        fn pinned(config: &Config, nightly: bool) -> Option<&str> {
+            config
+                .rust_toolchain
+                .as_ref()
+                .map(|rust_toolchain| rust_toolchain.toolchain.channel.as_str())
+                .filter(|channel| channel.starts_with("nightly") == nightly)
+        }
+
         #[must_use]
-        pub fn stable(_config: &Config) -> &str {
-            "stable"
+        pub fn stable(config: &Config) -> &str {
+            pinned(config, false)
+                .or(config.xtask.rust.toolchain.stable.as_deref())
+                .unwrap_or("stable")
         }
 
+        /// The preferred nightly toolchain: the first of [`nightly_candidates`].
         #[must_use]
         pub fn nightly(config: &Config) -> &str {
-            &config.rust_toolchain.toolchain.channel
+            nightly_candidates(config)[0]
+        }
+
+        /// The acceptable nightly toolchains, in order of preference, which is never empty: the pinned nightly (if any),
+        /// followed by the configured `nightly` names, or else the plain `nightly` channel.
+        ///
+        /// The first installed one is used (see [`crate::validation::first_installed_toolchain`]), so the configured
+        /// names are fallbacks for a pinned nightly that is not installed.
+        #[must_use]
+        pub fn nightly_candidates(config: &Config) -> Vec<&str> {
+            let mut candidates = pinned(config, true).into_iter().collect::<Vec<_>>();
+            for name in &config.xtask.rust.toolchain.nightly {
+                if !candidates.contains(&name.as_str()) {
+                    candidates.push(name);
+                }
+            }
+            if candidates.is_empty() {
+                candidates.push("nightly");
+            }
+            candidates
+        }
+
+        #[cfg(test)]
+        mod tests {
+            use crate::config::RustToolchain;
+
+            fn rust_toolchain(channel: &str) -> RustToolchain {
+                let data = format!(
+                    "[toolchain]\nchannel = \"{channel}\"\ncomponents = [\"rustfmt\", \"clippy\"]\nprofile = \"minimal\"\n"
+                );
+                toml::from_str(&data).unwrap()
+            }
+
+            #[test]
+            fn parses_a_sample_rust_toolchain_toml() {
+                let rust_toolchain = rust_toolchain("nightly-2023-08-08");
+                assert_eq!(rust_toolchain.toolchain.channel, "nightly-2023-08-08");
+                assert_eq!(rust_toolchain.toolchain.components, ["rustfmt", "clippy"]);
+                assert_eq!(rust_toolchain.toolchain.profile.as_deref(), Some("minimal"));
+            }
+
+            #[test]
+            fn a_nightly_pin_overrides_only_the_nightly_toolchain() {
+                let mut config = crate::testing::config(serde_json::json!({
+                    "rust": { "toolchain": { "stable": "1.72.0", "nightly": "nightly-2023-01-01" } }
+                }));
+                config.rust_toolchain = Some(rust_toolchain("nightly-2023-08-08"));
+                assert_eq!(super::nightly(&config), "nightly-2023-08-08");
+                assert_eq!(super::stable(&config), "1.72.0");
+            }
+
+            #[test]
+            fn the_configured_nightlies_follow_the_nightly_pin() {
+                let mut config = crate::testing::config(serde_json::json!({
+                    "rust": { "toolchain": { "nightly": ["nightly-2023-01-01", "nightly-2023-08-08"] } }
+                }));
+                config.rust_toolchain = Some(rust_toolchain("nightly-2023-08-08"));
+                assert_eq!(super::nightly_candidates(&config), [
+                    "nightly-2023-08-08",
+                    "nightly-2023-01-01"
+                ]);
+            }
+
+            #[test]
+            fn a_stable_pin_overrides_only_the_stable_toolchain() {
+                let mut config = crate::testing::config(serde_json::json!({}));
+                config.rust_toolchain = Some(rust_toolchain("1.72.0"));
+                assert_eq!(super::stable(&config), "1.72.0");
+                assert_eq!(super::nightly(&config), "nightly");
+            }
+
+            #[test]
+            fn without_a_pin_the_config_names_are_used() {
+                let config = crate::testing::config(serde_json::json!({
+                    "rust": { "toolchain": { "stable": "1.72.0" } }
+                }));
+                assert_eq!(super::stable(&config), "1.72.0");
+                assert_eq!(super::nightly(&config), "nightly");
+            }
         }
     }
 }
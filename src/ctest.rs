@@ -0,0 +1,206 @@
+use crate::BoxResult;
+use camino::Utf8Path;
+use std::fmt::Write;
+
+/// The result of one test in a ctest run.
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct TestResult {
+    pub name: String,
+    /// The status reported by ctest (e.g. `Passed`, `Failed`, `Timeout`, `Not Run`)
+    pub status: String,
+    /// What ctest reported after the status, if anything (e.g. `Required regular expression not found. Regex=[ok]`
+    /// for a failed pass regex, or `SegFault` for an `Exception`)
+    pub reason: Option<String>,
+    pub seconds: f64,
+}
+
+impl TestResult {
+    #[must_use]
+    pub fn passed(&self) -> bool {
+        self.status == "Passed"
+    }
+
+    /// Whether the test was not run (e.g. because it is disabled or a required file is missing).
+    #[must_use]
+    pub fn skipped(&self) -> bool {
+        self.status == "Not Run"
+    }
+}
+
+/// Parses the per-test result lines of ctest output (e.g. from `ctest --output-log <FILE>`), such as
+/// `1/3 Test #1: parse ........................***Failed    0.02 sec`, where the status may be followed by a reason
+/// (see [`TestResult::reason`]).
+///
+/// # Errors
+///
+/// Will return `Err` if the pattern for result lines fails to compile.
+pub fn parse_results(output: &str) -> BoxResult<Vec<TestResult>> {
+    let pattern = regex::Regex::new(
+        r"^\s*\d+/\d+\s+Test\s+#\d+:\s+(\S+)\s+\.*\s*(?:\*\*\*)?(Not Run|\w+)(?::?\s+(.*?))?\s+([\d.]+) sec\s*$",
+    )?;
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let captures = pattern.captures(line)?;
+            Some(TestResult {
+                name: captures[1].to_owned(),
+                status: captures[2].to_owned(),
+                reason: captures
+                    .get(3)
+                    .map(|reason| reason.as_str().trim().to_owned())
+                    .filter(|reason| !reason.is_empty()),
+                seconds: captures[4].parse().ok()?,
+            })
+        })
+        .collect())
+}
+
+/// Prints one line per test with its status and duration, followed by the totals.
+pub fn print_summary(results: &[TestResult]) {
+    let width = results.iter().map(|result| result.name.len()).max().unwrap_or_default();
+    for result in results {
+        println!(
+            "{:<width$}  {:<10} {:>8.2}s",
+            result.name, result.status, result.seconds
+        );
+    }
+    let failed = results.iter().filter(|result| !result.passed()).count();
+    let seconds = results.iter().map(|result| result.seconds).sum::<f64>();
+    println!(
+        "{} tests, {} passed, {failed} failed, {seconds:.2}s total",
+        results.len(),
+        results.len() - failed
+    );
+}
+
+/// Writes `results` to `path` as a `JUnit` XML report of a single `ctest` test suite, for CI test reporting.
+///
+/// Tests which were not run are reported as skipped, and those which neither passed nor were skipped as failures,
+/// with their status and reason as the message.
+///
+/// # Errors
+///
+/// Will return `Err` if the report cannot be written.
+pub fn write_junit(results: &[TestResult], path: &Utf8Path) -> BoxResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, junit(results)?).map_err(|err| format!("failed to write `{path}`: {err}"))?;
+    Ok(())
+}
+
+// The `JUnit` XML report of `results`, as described for `write_junit`.
+fn junit(results: &[TestResult]) -> BoxResult<String> {
+    let failures = results
+        .iter()
+        .filter(|result| !result.passed() && !result.skipped())
+        .count();
+    let skipped = results.iter().filter(|result| result.skipped()).count();
+    let seconds = results.iter().map(|result| result.seconds).sum::<f64>();
+    let mut report = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    writeln!(
+        report,
+        "  <testsuite name=\"ctest\" tests=\"{}\" failures=\"{failures}\" skipped=\"{skipped}\" time=\"{seconds:.3}\">",
+        results.len()
+    )?;
+    for result in results {
+        let name = escape(&result.name);
+        let testcase = format!(
+            "<testcase name=\"{name}\" classname=\"ctest\" time=\"{:.3}\"",
+            result.seconds
+        );
+        if result.passed() {
+            writeln!(report, "    {testcase}/>")?;
+        } else if result.skipped() {
+            writeln!(report, "    {testcase}>\n      <skipped/>\n    </testcase>")?;
+        } else {
+            let message = match &result.reason {
+                Some(reason) => format!("{}: {reason}", result.status),
+                None => result.status.clone(),
+            };
+            let message = escape(&message);
+            writeln!(
+                report,
+                "    {testcase}>\n      <failure message=\"{message}\"/>\n    </testcase>"
+            )?;
+        }
+    }
+    writeln!(report, "  </testsuite>\n</testsuites>")?;
+    Ok(report)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_results;
+
+    #[test]
+    fn result_lines_are_parsed_with_the_status_word() {
+        let output = "\
+Test project /work/build
+    Start 1: parse
+1/4 Test #1: parse ............................   Passed    0.02 sec
+2/4 Test #2: render ...........................***Failed    1.50 sec
+3/4 Test #3: disabled .........................***Not Run (Disabled)   0.00 sec
+4/4 Test #4: matches ..........................***Failed  Required regular expression not found. Regex=[ok]  0.03 sec
+
+50% tests passed, 2 tests failed out of 4
+";
+        let results = parse_results(output).unwrap();
+        let summary = results
+            .iter()
+            .map(|result| {
+                (
+                    result.name.as_str(),
+                    result.status.as_str(),
+                    result.reason.as_deref(),
+                    result.seconds,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(summary, [
+            ("parse", "Passed", None, 0.02),
+            ("render", "Failed", None, 1.5),
+            ("disabled", "Not Run", Some("(Disabled)"), 0.0),
+            (
+                "matches",
+                "Failed",
+                Some("Required regular expression not found. Regex=[ok]"),
+                0.03
+            ),
+        ]);
+        assert!(results[0].passed() && !results[1].passed() && results[2].skipped());
+    }
+
+    #[test]
+    fn junit_reports_failures_and_skipped_tests() {
+        let output = "\
+1/3 Test #1: parse ............................   Passed    0.02 sec
+2/3 Test #2: disabled .........................***Not Run (Disabled)   0.00 sec
+3/3 Test #3: matches ..........................***Failed  Required regular expression not found. Regex=[<ok>]  0.03 sec
+";
+        let report = super::junit(&parse_results(output).unwrap()).unwrap();
+        assert_eq!(
+            report,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="ctest" tests="3" failures="1" skipped="1" time="0.050">
+    <testcase name="parse" classname="ctest" time="0.020"/>
+    <testcase name="disabled" classname="ctest" time="0.000">
+      <skipped/>
+    </testcase>
+    <testcase name="matches" classname="ctest" time="0.030">
+      <failure message="Failed: Required regular expression not found. Regex=[&lt;ok&gt;]"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
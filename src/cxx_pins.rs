@@ -0,0 +1,200 @@
+use crate::BoxResult;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The git repository of the vcpkg ports, which a `builtin-baseline` is a commit of.
+pub const VCPKG_REPOSITORY: &str = "https://github.com/microsoft/vcpkg";
+
+// Directories not searched for `FetchContent_Declare` calls: build trees (including the `_deps` FetchContent
+// populates) and hidden directories such as `.git`.
+const SKIPPED_DIRS: &[&str] = &["_deps", "build", "target"];
+
+/// Where a C++ dependency is pinned.
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PinKind {
+    /// The `builtin-baseline` of `vcpkg.json`, or the baseline of the default registry in `vcpkg-configuration.json`
+    VcpkgBaseline,
+    /// A requirement locked in `conan.lock`
+    Conan,
+    /// A `FetchContent_Declare` with a `GIT_TAG`
+    FetchContent,
+}
+
+impl PinKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PinKind::VcpkgBaseline => "vcpkg",
+            PinKind::Conan => "conan",
+            PinKind::FetchContent => "FetchContent",
+        }
+    }
+}
+
+/// A pinned C++ dependency.
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct Pin {
+    pub kind: PinKind,
+    pub name: String,
+    /// The pinned version, tag, or commit
+    pub pinned: String,
+    /// The upstream git repository, if known
+    pub repository: Option<String>,
+    /// The file the dependency is pinned in, relative to the workspace root
+    pub source: Utf8PathBuf,
+}
+
+/// The pinned C++ dependencies of the project at `root`: the vcpkg baseline (from `vcpkg.json` or
+/// `vcpkg-configuration.json`), the requirements of `conan.lock`, and the `FetchContent_Declare` calls with a
+/// `GIT_TAG` in the `CMakeLists.txt` and `.cmake` files.
+///
+/// A conan lock does not record where a package comes from, so the upstream repository of a conan requirement is that
+/// of `repositories` with the same name, if any.
+///
+/// # Errors
+///
+/// Will return `Err` if a manifest, lock, or `CMake` file cannot be read or is malformed.
+pub fn find(root: &Utf8Path, repositories: &BTreeMap<String, String>) -> BoxResult<Vec<Pin>> {
+    let mut pins = vec![];
+    pins.extend(vcpkg_baseline(root)?);
+    pins.extend(conan_requirements(root, repositories)?);
+    let mut files = vec![];
+    cmake_files(root, &mut files)?;
+    files.sort();
+    for file in files {
+        pins.extend(fetch_content_declarations(root, &file)?);
+    }
+    Ok(pins)
+}
+
+/// The version number of the release tag `tag` (e.g. `1.2.3` for `v1.2.3`, `release-1.2.3`, or `curl-8_4_0`).
+///
+/// Returns `None` for tags of pre-releases (e.g. `v2.0.0-rc1`) and tags that are not versions.
+#[must_use]
+pub fn tag_version(tag: &str) -> Option<String> {
+    let version = regex::Regex::new(r"^[^\d]*(\d+(?:[._]\d+)*)$").ok()?;
+    let captures = version.captures(tag)?;
+    Some(captures[1].replace('_', "."))
+}
+
+/// The tag and version of the latest release among `tags` (as listed by [`crate::git::remote_tags`]).
+#[must_use]
+pub fn latest_release(tags: &[(String, String)]) -> Option<(&str, String)> {
+    tags.iter()
+        .filter_map(|(tag, _)| Some((tag.as_str(), tag_version(tag)?)))
+        .fold(None, |latest, (tag, version)| match latest {
+            Some((_, ref latest_version)) if crate::detection::version_at_least(&version, latest_version) => latest,
+            _ => Some((tag, version)),
+        })
+}
+
+// The vcpkg baseline: the `builtin-baseline` of `vcpkg.json`, or else the baseline of the default registry in its
+// embedded `vcpkg-configuration` or in `vcpkg-configuration.json`.
+fn vcpkg_baseline(root: &Utf8Path) -> BoxResult<Option<Pin>> {
+    let manifest = read_json(&root.join("vcpkg.json"))?;
+    let configuration = read_json(&root.join("vcpkg-configuration.json"))?;
+    let default_registry = |json: &Value| {
+        let registry = json.get("default-registry")?;
+        let baseline = registry.get("baseline")?.as_str()?;
+        let repository = registry.get("repository").and_then(Value::as_str);
+        Some((baseline.to_owned(), repository.map(String::from)))
+    };
+    let found = manifest
+        .as_ref()
+        .and_then(|json| json.get("builtin-baseline")?.as_str())
+        .map(|baseline| (baseline.to_owned(), None, "vcpkg.json"))
+        .or_else(|| {
+            let embedded = manifest.as_ref()?.get("vcpkg-configuration")?;
+            let (baseline, repository) = default_registry(embedded)?;
+            Some((baseline, repository, "vcpkg.json"))
+        })
+        .or_else(|| {
+            let (baseline, repository) = default_registry(configuration.as_ref()?)?;
+            Some((baseline, repository, "vcpkg-configuration.json"))
+        });
+    Ok(found.map(|(baseline, repository, source)| Pin {
+        kind: PinKind::VcpkgBaseline,
+        name: "baseline".into(),
+        pinned: baseline,
+        repository: Some(repository.unwrap_or_else(|| VCPKG_REPOSITORY.into())),
+        source: source.into(),
+    }))
+}
+
+// The requirements of `conan.lock`, which are references like `fmt/10.1.0#<revision>%<timestamp>`.
+fn conan_requirements(root: &Utf8Path, repositories: &BTreeMap<String, String>) -> BoxResult<Vec<Pin>> {
+    let Some(lock) = read_json(&root.join("conan.lock"))? else {
+        return Ok(vec![]);
+    };
+    let mut pins = vec![];
+    for key in ["requires", "build_requires", "python_requires"] {
+        let references = lock.get(key).and_then(Value::as_array).into_iter().flatten();
+        for reference in references.filter_map(Value::as_str) {
+            let reference = reference.split(['@', '#']).next().unwrap_or(reference);
+            let Some((name, version)) = reference.split_once('/') else {
+                continue;
+            };
+            pins.push(Pin {
+                kind: PinKind::Conan,
+                name: name.to_owned(),
+                pinned: version.to_owned(),
+                repository: repositories.get(name).cloned(),
+                source: "conan.lock".into(),
+            });
+        }
+    }
+    Ok(pins)
+}
+
+// The `FetchContent_Declare` calls of the `CMake` file `file` that fetch a git repository at a `GIT_TAG`.
+fn fetch_content_declarations(root: &Utf8Path, file: &Utf8Path) -> BoxResult<Vec<Pin>> {
+    let data = std::fs::read_to_string(file).map_err(|err| format!("failed to read `{file}`: {err}"))?;
+    let declare = regex::Regex::new(r"(?i)FetchContent_Declare\s*\(\s*([^\s)]+)([^)]*)\)")?;
+    let option = |name: &str| regex::Regex::new(&format!(r#"\b{name}\s+"?([^\s")]+)"#));
+    let (repository, tag) = (option("GIT_REPOSITORY")?, option("GIT_TAG")?);
+    let source = file.strip_prefix(root).unwrap_or(file);
+    let mut pins = vec![];
+    for captures in declare.captures_iter(&data) {
+        let options = &captures[2];
+        let (Some(repository), Some(tag)) = (repository.captures(options), tag.captures(options)) else {
+            continue;
+        };
+        pins.push(Pin {
+            kind: PinKind::FetchContent,
+            name: captures[1].to_owned(),
+            pinned: tag[1].to_owned(),
+            repository: Some(repository[1].to_owned()),
+            source: source.to_owned(),
+        });
+    }
+    Ok(pins)
+}
+
+// The `CMakeLists.txt` and `.cmake` files under `dir`.
+fn cmake_files(dir: &Utf8Path, files: &mut Vec<Utf8PathBuf>) -> BoxResult<()> {
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        if path.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name) {
+                cmake_files(path, files)?;
+            }
+        } else if name == "CMakeLists.txt" || path.extension() == Some("cmake") {
+            files.push(path.to_owned());
+        }
+    }
+    Ok(())
+}
+
+// The JSON contents of `path`, if it exists.
+fn read_json(path: &Utf8Path) -> BoxResult<Option<Value>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let data = std::fs::read_to_string(path).map_err(|err| format!("failed to read `{path}`: {err}"))?;
+    let json = serde_json::from_str(&data).map_err(|err| format!("failed to parse `{path}`: {err}"))?;
+    Ok(Some(json))
+}
@@ -0,0 +1,75 @@
+use crate::{config::Config, runner::Runner, BoxResult};
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// The C/C++ sources of the project, as described by `xtask.cxx`: the files matching one of the `include` globs (or,
+/// without any, the files with a C or C++ extension) and none of the `exclude` globs, relative to the workspace root.
+///
+/// The tools working on the C/C++ code (`xtask clang` and `xtask headers`) select their files with this, rather than
+/// by their own defaults.
+pub struct CxxSources {
+    root: Utf8PathBuf,
+    include: Vec<regex::Regex>,
+    exclude: Vec<regex::Regex>,
+}
+
+impl CxxSources {
+    /// The C/C++ sources described by the `xtask.cxx` of `config`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if a glob of `xtask.cxx.include` or `xtask.cxx.exclude` is malformed.
+    pub fn new(config: &Config) -> BoxResult<Self> {
+        let regexes = |globs: &[String]| {
+            globs
+                .iter()
+                .map(|glob| crate::glob::regex(glob))
+                .collect::<BoxResult<Vec<_>>>()
+        };
+        Ok(CxxSources {
+            root: config.cargo_metadata.workspace_root.clone(),
+            include: regexes(&config.xtask.cxx.include)?,
+            exclude: regexes(&config.xtask.cxx.exclude)?,
+        })
+    }
+
+    /// Whether `file` (absolute, or relative to the workspace root) is one of the sources.
+    #[must_use]
+    pub fn contains(&self, file: &Utf8Path) -> bool {
+        let relative = file
+            .strip_prefix(&self.root)
+            .unwrap_or(file)
+            .as_str()
+            .replace('\\', "/");
+        let included = if self.include.is_empty() {
+            file.extension()
+                .map_or(false, |ext| crate::git::CXX_EXTENSIONS.contains(&ext))
+        } else {
+            self.include.iter().any(|glob| glob.is_match(&relative))
+        };
+        included && !self.exclude.iter().any(|glob| glob.is_match(&relative))
+    }
+
+    /// The sources among `files`.
+    #[must_use]
+    pub fn filter(&self, files: Vec<Utf8PathBuf>) -> Vec<Utf8PathBuf> {
+        files.into_iter().filter(|file| self.contains(file)).collect()
+    }
+
+    /// The sources tracked by git, as absolute paths.
+    ///
+    /// Returns `None` if the workspace is not inside a git work tree.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if listing the files tracked by git fails.
+    pub fn tracked(&self, runner: &dyn Runner) -> BoxResult<Option<Vec<Utf8PathBuf>>> {
+        let files = crate::git::tracked_files(runner, &self.root)?;
+        Ok(files.map(|files| self.filter(files)))
+    }
+
+    /// Whether `xtask.cxx` describes the sources, rather than leaving them to the defaults.
+    #[must_use]
+    pub fn is_configured(&self) -> bool {
+        !self.include.is_empty() || !self.exclude.is_empty()
+    }
+}
@@ -1,4 +1,11 @@
-use crate::BoxResult;
+use crate::{
+    command::Context,
+    config::{Xtask, XtaskPlatformMacosSearchPath},
+    error::{Error, Kind},
+    runner::Runner,
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
 use std::process::Command;
 
 fn split_editor_command(editor: &str) -> BoxResult<(String, Vec<String>)> {
@@ -45,41 +52,332 @@ fn detect_git_core_editor() -> Option<String> {
     None
 }
 
-#[cfg(target_os = "macos")]
-use crate::config::{Config, XtaskPlatformMacosSearchPath};
-#[cfg(target_os = "macos")]
-use std::path::PathBuf;
+/// The Python interpreter for running Python scripts, as a program and its leading arguments (e.g. `py -3`).
+///
+/// In order of precedence, this is the configured `xtask.python.interpreter`, the interpreter of the active virtualenv
+/// (`$VIRTUAL_ENV`), or `py -3` on Windows and `python3` elsewhere.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - `xtask.python.version` is set and the interpreter fails to run
+/// - `xtask.python.version` is set and the interpreter is older than it
+pub fn detect_python(context: &Context<'_>) -> BoxResult<(String, Vec<String>)> {
+    let (python, python_args) = if let Some(interpreter) = &context.config.xtask.python.interpreter {
+        (interpreter.clone(), vec![])
+    } else if let Some(virtual_env) = std::env::var_os("VIRTUAL_ENV") {
+        let path = if cfg!(windows) {
+            std::path::Path::new(&virtual_env).join("Scripts").join("python.exe")
+        } else {
+            std::path::Path::new(&virtual_env).join("bin").join("python")
+        };
+        (path.to_string_lossy().into_owned(), vec![])
+    } else if cfg!(windows) {
+        ("py".into(), vec!["-3".into()])
+    } else {
+        ("python3".into(), vec![])
+    };
 
-#[cfg(target_os = "macos")]
-pub fn detect_macos_clang_paths(config: &Config) -> BoxResult<impl Iterator<Item = PathBuf>> {
-    let version = config.xtask.clang.version.as_str();
+    if let Some(minimum) = &context.config.xtask.python.version {
+        let mut cmd = context.command(&python);
+        context.config.xtask.c_locale(&mut cmd);
+        cmd.args(&python_args);
+        cmd.arg("--version");
+        let output = context
+            .runner
+            .output(&mut cmd)
+            .map_err(|err| Error::new(crate::error::kind(&err), format!("failed to run `{python}`: {err}")))?;
+        if !output.status.success() {
+            return Err(format!("`{python} --version` failed with non-zero exit code").into());
+        }
+        // NOTE: Python 2 prints its version to stderr
+        let banner = String::from_utf8([output.stdout, output.stderr].concat())?;
+        let found = regex::Regex::new(r"Python (\d+(?:\.\d+)*)")?
+            .captures(&banner)
+            .and_then(|captures| captures.get(1))
+            .map(|found| found.as_str())
+            .ok_or_else(|| {
+                let message = format!("could not determine the version of `{python}` from its version output");
+                Error::new(Kind::Validation, message)
+            })?;
+        if !version_at_least(minimum, found) {
+            let message = format!("`{python}` version mismatch: expected at least `{minimum}`, found `{found}`");
+            return Err(Error::new(Kind::Validation, message).into());
+        }
+    }
+
+    Ok((python, python_args))
+}
+
+/// Runs `tool --version` (or the configured probe argument for `tool`), returning its banner.
+///
+/// The banner includes stderr after stdout, since some builds and wrappers print it to stderr instead.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The command process fails to start
+/// - The command invocation fails with non-zero exit status
+/// - The command invocation fails to produce valid UTF-8 output
+pub fn detect_version_banner(context: &Context<'_>, tool: &str) -> BoxResult<String> {
+    context.validation().banner(tool)
+}
+
+/// The clang driver matching `clang_tidy`: `clang++` with the same version suffix (e.g. `clang++-17` for
+/// `clang-tidy-17`), from the same directory if it is there, or else from `PATH`.
+#[must_use]
+pub fn clang_driver(clang_tidy: &Utf8Path) -> Utf8PathBuf {
+    let suffix = clang_tidy
+        .file_name()
+        .and_then(|name| name.strip_prefix("clang-tidy"))
+        .unwrap_or_default();
+    let name = format!("clang++{suffix}");
+    match clang_tidy.parent().map(|dir| dir.join(&name)) {
+        Some(path) if path.is_file() => path,
+        _ => name.into(),
+    }
+}
+
+/// The version number following the word `version` in a `--version` banner (e.g. `cmake version 3.27.4`).
+#[must_use]
+pub fn parse_version(banner: &str) -> Option<&str> {
+    regex::Regex::new(r"version (\d+(?:\.\d+)*)")
+        .ok()?
+        .captures(banner)
+        .and_then(|captures| captures.get(1))
+        .map(|found| found.as_str())
+}
+
+/// Whether `found` is the `expected` version or a more specific one (e.g. `17.0.6` for `17`).
+#[must_use]
+pub fn version_matches(expected: &str, found: &str) -> bool {
+    expected
+        .split('.')
+        .zip(found.split('.').map(Some).chain(std::iter::repeat(None)))
+        .all(|(expected, found)| Some(expected) == found)
+}
+
+/// Whether `found` is at least the `minimum` version, comparing numeric components with missing ones as `0`.
+#[must_use]
+pub fn version_at_least(minimum: &str, found: &str) -> bool {
+    let components = |version: &str| {
+        version
+            .split('.')
+            .map(|component| component.parse::<u64>().unwrap_or_default())
+            .collect::<Vec<_>>()
+    };
+    let (mut minimum, mut found) = (components(minimum), components(found));
+    let len = minimum.len().max(found.len());
+    minimum.resize(len, 0);
+    found.resize(len, 0);
+    found >= minimum
+}
+
+/// Whether a `--version` banner comes from Apple clang (e.g. Xcode's `/usr/bin/clang`) rather than LLVM clang.
+#[must_use]
+pub fn is_apple_clang_banner(banner: &str) -> bool {
+    banner
+        .lines()
+        .any(|line| line.trim_start().starts_with("Apple clang version"))
+}
+
+/// The directories of the clang tools at the configured `xtask.clang.platform.macos.searchPaths`, in priority order
+/// (see [`macos_search_dir`]).
+///
+/// Search paths whose tooling or installation is missing are skipped.
+///
+/// # Errors
+///
+/// Will return `Err` if probing one of the configured search paths fails unexpectedly.
+pub fn detect_macos_clang_paths(xtask: &Xtask, runner: &dyn Runner) -> BoxResult<Vec<Utf8PathBuf>> {
+    let version = xtask.clang.version.as_str();
     let major_version = version.split('.').next().unwrap_or(version);
     let mut paths = vec![];
-    for entry in &config.xtask.clang.platform.macos.search_paths {
-        match entry {
-            XtaskPlatformMacosSearchPath::Homebrew => {
-                let formula = format!("llvm@{major_version}");
-                if let Some(prefix) = detect_homebrew_prefix(&formula)? {
-                    if let Some(parent) = prefix.parent() {
-                        let path = parent.join(formula).join("bin");
-                        paths.push(path);
-                    }
-                }
-            },
+    for entry in &xtask.clang.platform.macos.search_paths {
+        if let Some(path) = macos_search_dir(runner, entry, major_version)? {
+            if path.is_dir() {
+                paths.push(path);
+            }
         }
     }
-    Ok(paths.into_iter())
+    Ok(paths)
+}
+
+/// The directory of the clang tools that `entry` names for the clang `major_version` (any version if empty):
+/// - `homebrew`: `$(brew --prefix --installed llvm@<major>)/bin` (or of `llvm`, without a version)
+/// - `macPorts`: `/opt/local/libexec/llvm-<major>/bin` (only for a version)
+/// - `xcode`: the directory containing `$(xcrun --find clang)`
+///
+/// Returns `None` if the probe (run with `runner`) is missing or fails, i.e., the install is missing.
+///
+/// # Errors
+///
+/// Will return `Err` if a probe fails to start for a reason other than not being found.
+pub fn macos_search_dir(
+    runner: &dyn Runner,
+    entry: &XtaskPlatformMacosSearchPath,
+    major_version: &str,
+) -> BoxResult<Option<Utf8PathBuf>> {
+    let dir = match entry {
+        XtaskPlatformMacosSearchPath::Homebrew => {
+            let formula = if major_version.is_empty() {
+                "llvm".into()
+            } else {
+                format!("llvm@{major_version}")
+            };
+            let mut cmd = Command::new("brew");
+            cmd.args(["--prefix", "--installed", &formula]);
+            probe_path(runner, cmd)?.map(|prefix| prefix.join("bin"))
+        },
+        XtaskPlatformMacosSearchPath::MacPorts => (!major_version.is_empty())
+            .then(|| Utf8PathBuf::from(format!("/opt/local/libexec/llvm-{major_version}/bin"))),
+        XtaskPlatformMacosSearchPath::Xcode => {
+            let mut cmd = Command::new("xcrun");
+            cmd.args(["--find", "clang"]);
+            probe_path(runner, cmd)?.and_then(|clang| clang.parent().map(Utf8Path::to_owned))
+        },
+    };
+    Ok(dir)
+}
+
+// The path printed by `cmd`, or `None` if it is not found or fails.
+fn probe_path(runner: &dyn Runner, mut cmd: Command) -> BoxResult<Option<Utf8PathBuf>> {
+    let output = match runner.output(&mut cmd) {
+        Ok(output) => output,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let path = String::from_utf8(output.stdout)?;
+    let path = path.trim();
+    Ok((!path.is_empty()).then(|| path.into()))
+}
+
+#[cfg(target_os = "macos")]
+use std::path::PathBuf;
+
+/// The path of the macOS SDK selected with `xcode-select`, from `xcrun --show-sdk-path`.
+///
+/// The result is detected once and cached. Returns `None` if Xcode and the Command Line Tools are both missing.
+#[cfg(target_os = "macos")]
+#[must_use]
+pub fn detect_macos_sdk_root() -> Option<&'static std::path::Path> {
+    static SDK_ROOT: std::sync::OnceLock<Option<PathBuf>> = std::sync::OnceLock::new();
+    SDK_ROOT
+        .get_or_init(|| {
+            let mut cmd = Command::new("xcrun");
+            cmd.args(["--show-sdk-path"]);
+            detect_path_from_output(cmd).ok().flatten()
+        })
+        .as_deref()
 }
 
 #[cfg(target_os = "macos")]
-fn detect_homebrew_prefix(formula: &str) -> BoxResult<Option<PathBuf>> {
-    let mut cmd = Command::new("brew");
-    cmd.args(["--prefix", formula]);
-    let output = cmd.output()?;
+fn detect_path_from_output(mut cmd: Command) -> BoxResult<Option<PathBuf>> {
+    let output = match cmd.output() {
+        Ok(output) => output,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
     if output.status.success() {
-        if let Ok(prefix) = String::from_utf8(output.stdout) {
-            return Ok(Some(PathBuf::from(prefix.trim())));
+        if let Ok(path) = String::from_utf8(output.stdout) {
+            return Ok(Some(PathBuf::from(path.trim())));
         }
     }
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{config::XtaskPlatformMacosSearchPath, runner::MockRunner, testing};
+    use camino::Utf8Path;
+
+    #[test]
+    fn homebrew_is_probed_for_the_versioned_formula() {
+        let runner = MockRunner::new(|cmd| {
+            assert_eq!(testing::command_line(cmd), "brew --prefix --installed llvm@17");
+            Ok(testing::output(0, "/opt/homebrew/opt/llvm@17\n"))
+        });
+        let dir = super::macos_search_dir(&runner, &XtaskPlatformMacosSearchPath::Homebrew, "17").unwrap();
+        assert_eq!(dir.as_deref(), Some(Utf8Path::new("/opt/homebrew/opt/llvm@17/bin")));
+
+        let runner = MockRunner::new(|_| Ok(testing::output(1, "")));
+        let dir = super::macos_search_dir(&runner, &XtaskPlatformMacosSearchPath::Homebrew, "17").unwrap();
+        assert!(dir.is_none());
+    }
+
+    #[test]
+    fn macports_is_found_at_its_libexec_dir() {
+        let runner = MockRunner::new(|cmd| panic!("unexpected command `{}`", testing::command_line(cmd)));
+        let dir = super::macos_search_dir(&runner, &XtaskPlatformMacosSearchPath::MacPorts, "17").unwrap();
+        assert_eq!(dir.as_deref(), Some(Utf8Path::new("/opt/local/libexec/llvm-17/bin")));
+    }
+
+    #[test]
+    fn xcode_is_the_dir_of_the_selected_clang() {
+        let runner = MockRunner::new(|cmd| {
+            assert_eq!(testing::command_line(cmd), "xcrun --find clang");
+            Ok(testing::output(
+                0,
+                "/Applications/Xcode.app/Contents/Developer/Toolchains/XcodeDefault.xctoolchain/usr/bin/clang\n",
+            ))
+        });
+        let dir = super::macos_search_dir(&runner, &XtaskPlatformMacosSearchPath::Xcode, "17").unwrap();
+        assert_eq!(
+            dir.as_deref(),
+            Some(Utf8Path::new(
+                "/Applications/Xcode.app/Contents/Developer/Toolchains/XcodeDefault.xctoolchain/usr/bin"
+            ))
+        );
+
+        let runner = MockRunner::new(|_| Err(std::io::ErrorKind::NotFound.into()));
+        let dir = super::macos_search_dir(&runner, &XtaskPlatformMacosSearchPath::Xcode, "17").unwrap();
+        assert!(dir.is_none());
+    }
+
+    #[test]
+    fn missing_installs_are_skipped_in_priority_order() {
+        let temp = camino::Utf8PathBuf::try_from(std::env::temp_dir()).unwrap();
+        let config = testing::config(serde_json::json!({
+            "clang": {
+                "version": "17",
+                "platform": { "macos": { "searchPaths": [{ "kind": "homebrew" }, { "kind": "macPorts" }, { "kind": "xcode" }] } }
+            }
+        }));
+        let clang = temp.join("clang");
+        let runner = MockRunner::new(|cmd| {
+            if testing::command_line(cmd).starts_with("brew") {
+                Ok(testing::output(1, "Error: No such keg: /opt/homebrew/Cellar/llvm@17\n"))
+            } else {
+                Ok(testing::output(0, clang.as_str()))
+            }
+        });
+        let mut dirs = super::detect_macos_clang_paths(&config.xtask, &runner).unwrap();
+        // NOTE: MacPorts is only found where it is installed
+        dirs.retain(|dir| !dir.starts_with("/opt/local"));
+        assert_eq!(dirs, [temp]);
+    }
+
+    #[test]
+    fn the_first_search_dir_containing_a_tool_is_used() {
+        let temp = camino::Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join("xtask-test-search-paths");
+        let (first, second) = (temp.join("first"), temp.join("second"));
+        for dir in [&first, &second] {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join("clang-tidy"), "").unwrap();
+        }
+        std::fs::write(second.join("clang-format"), "").unwrap();
+        let mut config = testing::config(serde_json::json!({}));
+        config
+            .cmake_context
+            .apply_search_paths(&[first.clone(), second.clone()]);
+        std::fs::remove_dir_all(&temp).ok();
+        assert_eq!(config.cmake_context.bin_clang_tidy, first.join("clang-tidy"));
+        assert_eq!(config.cmake_context.bin_clang_format, second.join("clang-format"));
+        assert_eq!(config.cmake_context.bin_run_clang_tidy, "run-clang-tidy");
+    }
+}
@@ -0,0 +1,109 @@
+use crate::BoxResult;
+use camino::{Utf8Path, Utf8PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A link in the generated documentation whose target does not exist.
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct BrokenLink {
+    /// The HTML file containing the link
+    pub file: Utf8PathBuf,
+    /// The `href` of the link
+    pub href: String,
+    /// Why the link is broken (e.g. `file not found`)
+    pub reason: &'static str,
+}
+
+/// The crate directories of `doc_dir` (e.g. `target/doc`) for the crates named `crates`, as documented by rustdoc
+/// (with `-` replaced by `_`), leaving out those that were not documented.
+#[must_use]
+pub fn crate_dirs<'a>(doc_dir: &Utf8Path, crates: impl IntoIterator<Item = &'a str>) -> Vec<Utf8PathBuf> {
+    let dirs = crates
+        .into_iter()
+        .map(|name| doc_dir.join(name.replace('-', "_")))
+        .filter(|dir| dir.is_dir())
+        .collect::<BTreeSet<_>>();
+    dirs.into_iter().collect()
+}
+
+/// Checks the local links of the HTML files under `dirs`: each must name an existing file and, when it has a
+/// fragment, an element of that file with the fragment as its `id` (or `name`).
+///
+/// External links (e.g. `https://...`) are left to an external link checker. Fragments naming source lines (e.g.
+/// `#12-20` in the source pages) are not checked, since rustdoc resolves those with JavaScript.
+///
+/// # Errors
+///
+/// Will return `Err` if a directory or HTML file cannot be read.
+pub fn broken_links(dirs: &[Utf8PathBuf]) -> BoxResult<Vec<BrokenLink>> {
+    let href = regex::Regex::new(r#"\shref="([^"]*)""#)?;
+    let mut files = vec![];
+    for dir in dirs {
+        html_files(dir, &mut files)?;
+    }
+    let mut anchors = BTreeMap::<Utf8PathBuf, BTreeSet<String>>::new();
+    let mut broken = vec![];
+    for file in &files {
+        let html = std::fs::read_to_string(file).map_err(|err| format!("failed to read `{file}`: {err}"))?;
+        for captures in href.captures_iter(&html) {
+            let link = captures[1].replace("&amp;", "&");
+            if link.contains(':') || link.starts_with("//") {
+                continue;
+            }
+            let (path, fragment) = link.split_once('#').unwrap_or((&link, ""));
+            let path = path.split_once('?').map_or(path, |(path, _)| path);
+            let target = if path.is_empty() {
+                file.clone()
+            } else {
+                file.parent().unwrap_or(file).join(path)
+            };
+            let target = if target.is_dir() {
+                target.join("index.html")
+            } else {
+                target
+            };
+            if !target.is_file() {
+                broken.push(BrokenLink {
+                    file: file.clone(),
+                    href: link.clone(),
+                    reason: "file not found",
+                });
+                continue;
+            }
+            if fragment.is_empty() || fragment.chars().all(|char| char.is_ascii_digit() || char == '-') {
+                continue;
+            }
+            if !anchors.contains_key(&target) {
+                anchors.insert(target.clone(), ids(&target)?);
+            }
+            if !anchors[&target].contains(fragment) {
+                broken.push(BrokenLink {
+                    file: file.clone(),
+                    href: link.clone(),
+                    reason: "anchor not found",
+                });
+            }
+        }
+    }
+    Ok(broken)
+}
+
+// The `.html` files under `dir`.
+fn html_files(dir: &Utf8Path, files: &mut Vec<Utf8PathBuf>) -> BoxResult<()> {
+    for entry in dir.read_dir_utf8()? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            html_files(path, files)?;
+        } else if path.extension() == Some("html") {
+            files.push(path.to_owned());
+        }
+    }
+    Ok(())
+}
+
+// The `id` and `name` attributes of the elements of the HTML file `file`.
+fn ids(file: &Utf8Path) -> BoxResult<BTreeSet<String>> {
+    let html = std::fs::read_to_string(file).map_err(|err| format!("failed to read `{file}`: {err}"))?;
+    let id = regex::Regex::new(r#"\s(?:id|name)="([^"]*)""#)?;
+    Ok(id.captures_iter(&html).map(|captures| captures[1].to_owned()).collect())
+}
@@ -0,0 +1,299 @@
+use crate::BoxResult;
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+// The path of the current generation of the documentation, which the reload script of the served pages polls.
+const GENERATION_PATH: &str = "/__xtask/generation";
+
+// The path prefix of the doxygen output.
+const DOXYGEN_PREFIX: &str = "/cpp/";
+
+/// The generated documentation served by [`start`].
+pub struct Site {
+    /// The rustdoc output directory (e.g. `target/doc`)
+    pub rustdoc_dir: Utf8PathBuf,
+    /// The documented crates, as named in `rustdoc_dir` (see [`crate::doc_links::crate_dirs`])
+    pub crates: Vec<String>,
+    /// The doxygen HTML output directory, if any
+    pub doxygen_dir: Option<Utf8PathBuf>,
+}
+
+/// Serves `site` at `http://127.0.0.1:<port>/` on a background thread, returning the generation of the documentation
+/// for the caller to increment after each rebuild.
+///
+/// The index page links to the documented crates and the C++ API (the doxygen output, under `/cpp/`). The HTML pages
+/// are served with a script polling the generation, which reloads the page once it changes.
+///
+/// # Errors
+///
+/// Will return `Err` if `port` cannot be bound (e.g. it is in use).
+pub fn start(site: Site, port: u16) -> BoxResult<Arc<AtomicU64>> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|err| format!("failed to serve on port {port}: {err}\nPass another port to `--serve`"))?;
+    let site = Arc::new(site);
+    let generation = Arc::new(AtomicU64::new(0));
+    let serving = generation.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let site = site.clone();
+            let generation = serving.load(Ordering::SeqCst);
+            // NOTE: a failed response only concerns that request (e.g. the browser closed the connection)
+            std::thread::spawn(move || respond(&site, generation, stream).ok());
+        }
+    });
+    Ok(generation)
+}
+
+/// The latest modification time of the files `paths` and of the files under the directories `paths`, and their number
+/// (so that deletions also count as changes), leaving out hidden directories, the directories `excluded` (e.g. the
+/// target directory), and the paths that do not exist.
+///
+/// # Errors
+///
+/// Will return `Err` if a directory of `paths` cannot be read.
+pub fn sources_stamp(paths: &[Utf8PathBuf], excluded: &[Utf8PathBuf]) -> BoxResult<(usize, Option<SystemTime>)> {
+    let mut stamp = (0, None);
+    let mut dirs = vec![];
+    for path in paths {
+        match path.metadata() {
+            Ok(metadata) if metadata.is_dir() => dirs.push(path.clone()),
+            Ok(metadata) => {
+                stamp.0 += 1;
+                stamp.1 = stamp.1.max(metadata.modified().ok());
+            },
+            Err(_) => {},
+        }
+    }
+    while let Some(dir) = dirs.pop() {
+        for entry in dir.read_dir_utf8()? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_name().starts_with('.') || excluded.iter().any(|excluded| path == excluded) {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                dirs.push(path.to_owned());
+            } else {
+                stamp.0 += 1;
+                stamp.1 = stamp.1.max(metadata.modified().ok());
+            }
+        }
+    }
+    Ok(stamp)
+}
+
+/// The paths of the `INPUT` tag of the `Doxyfile` with the contents `doxyfile` (including those appended with
+/// `INPUT +=`), relative to the directory doxygen runs in. Doxygen reads the directory it runs in if there are none.
+#[must_use]
+pub fn doxygen_inputs(doxyfile: &str) -> Vec<String> {
+    let mut inputs = vec![];
+    let mut lines = doxyfile.lines();
+    while let Some(line) = lines.next() {
+        let mut line = line.trim().to_owned();
+        while let Some(continued) = line.strip_suffix('\\') {
+            line = format!("{continued} {}", lines.next().unwrap_or_default().trim());
+        }
+        let Some(rest) = line.strip_prefix("INPUT") else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let values = if let Some(values) = rest.strip_prefix("+=") {
+            values
+        } else if let Some(values) = rest.strip_prefix('=') {
+            inputs.clear();
+            values
+        } else {
+            continue;
+        };
+        // NOTE: paths containing spaces are quoted
+        for (i, part) in values.split('"').enumerate() {
+            if i % 2 == 1 {
+                inputs.push(part.to_owned());
+            } else {
+                inputs.extend(part.split_whitespace().map(ToOwned::to_owned));
+            }
+        }
+    }
+    inputs
+}
+
+// Responds to the request on `stream` for a file of `site`, at the documentation generation `generation`.
+fn respond(site: &Site, generation: u64, mut stream: TcpStream) -> BoxResult<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // NOTE: the headers are not needed, so they are skipped up to the blank line ending them
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+
+    let mut words = request.split_whitespace();
+    let (method, target) = (words.next().unwrap_or_default(), words.next().unwrap_or_default());
+    if method != "GET" && method != "HEAD" {
+        return send(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"method not allowed\n",
+            false,
+        );
+    }
+    let path = percent_decode(target.split(['?', '#']).next().unwrap_or_default());
+    let head = method == "HEAD";
+
+    if path == GENERATION_PATH {
+        return send(
+            &mut stream,
+            "200 OK",
+            "text/plain",
+            generation.to_string().as_bytes(),
+            head,
+        );
+    }
+    if path == "/" {
+        return send(
+            &mut stream,
+            "200 OK",
+            "text/html",
+            index(site, generation).as_bytes(),
+            head,
+        );
+    }
+    let Some(file) = resolve(site, &path) else {
+        return send(&mut stream, "404 Not Found", "text/plain", b"not found\n", head);
+    };
+    let Ok(data) = std::fs::read(&file) else {
+        return send(&mut stream, "404 Not Found", "text/plain", b"not found\n", head);
+    };
+    let content_type = content_type(&file);
+    if content_type == "text/html" {
+        let mut page = String::from_utf8_lossy(&data).into_owned();
+        page.push_str(&reload_script(generation));
+        return send(&mut stream, "200 OK", content_type, page.as_bytes(), head);
+    }
+    send(&mut stream, "200 OK", content_type, &data, head)
+}
+
+// Writes a response with `body` (leaving it out for a `HEAD` request) to `stream`.
+fn send(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8], head: bool) -> BoxResult<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\n\
+         Connection: close\r\n\r\n",
+        body.len()
+    )?;
+    if !head {
+        stream.write_all(body)?;
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+// The file of `site` for the request path `path` (an `index.html` for a directory), or `None` if it is outside the
+// served directories.
+fn resolve(site: &Site, path: &str) -> Option<Utf8PathBuf> {
+    let (dir, relative) = match path.strip_prefix(DOXYGEN_PREFIX) {
+        Some(relative) => (site.doxygen_dir.as_ref()?, relative),
+        None => (&site.rustdoc_dir, path.trim_start_matches('/')),
+    };
+    if relative
+        .split('/')
+        .any(|segment| segment == ".." || segment.contains('\\'))
+    {
+        return None;
+    }
+    let file = dir.join(relative);
+    if file.is_dir() {
+        Some(file.join("index.html"))
+    } else {
+        Some(file)
+    }
+}
+
+// The index page of `site`, linking to the documented crates and the doxygen output.
+fn index(site: &Site, generation: u64) -> String {
+    let mut page =
+        String::from("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Documentation</title></head>");
+    page.push_str("<body><h1>Documentation</h1><ul>");
+    for name in &site.crates {
+        let _ = write!(page, "<li><a href=\"/{name}/index.html\">{name}</a></li>");
+    }
+    if site.doxygen_dir.is_some() {
+        let _ = write!(page, "<li><a href=\"{DOXYGEN_PREFIX}index.html\">C++ API</a></li>");
+    }
+    page.push_str("</ul></body></html>\n");
+    page + reload_script(generation).as_str()
+}
+
+// The script reloading a page served at the documentation generation `generation` once the generation changes.
+fn reload_script(generation: u64) -> String {
+    format!(
+        "<script>setInterval(function () {{ fetch(\"{GENERATION_PATH}\").then(function (response) {{ return \
+         response.text(); }}).then(function (text) {{ if (text !== \"{generation}\") {{ location.reload(); }} }}).catch(\
+         function () {{}}); }}, 1000);</script>\n"
+    )
+}
+
+// The `Content-Type` of `file`, by its extension.
+fn content_type(file: &Utf8Path) -> &'static str {
+    match file.extension().unwrap_or_default() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "txt" | "md" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+// The request path `path` with its `%XX` escapes decoded.
+fn percent_decode(path: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| tail.get(.. 2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        if let Some(escaped) = escaped {
+            bytes.push(escaped);
+            rest = &tail[2 ..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn doxygen_inputs_are_read_from_the_input_tag() {
+        let doxyfile = "# INPUT = commented\nINPUT_ENCODING = UTF-8\nINPUT = include \\\n        src\nINPUT += \"doc pages\" README.md\n";
+        assert_eq!(super::doxygen_inputs(doxyfile), [
+            "include",
+            "src",
+            "doc pages",
+            "README.md"
+        ]);
+        assert!(super::doxygen_inputs("PROJECT_NAME = cxx-auto\n").is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{
+    error::{Error, Kind},
+    BoxResult,
+};
+use camino::Utf8Path;
+use std::collections::BTreeMap;
+
+/// The environment variables of the `.env` file at `path`, or none if it does not exist.
+///
+/// Each line is a `NAME=VALUE` assignment (optionally preceded by `export`), a blank line, or a `#` comment. A value
+/// may be quoted: single-quoted values are taken literally, and double-quoted values may use the escapes `\n`, `\t`,
+/// `\"`, and `\\`. An unquoted value is trimmed, and ends at a ` #` comment. Variables are not expanded.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The file cannot be read
+/// - A line is not an assignment, or its value is malformed (e.g. an unterminated quote)
+pub fn load(path: &Utf8Path) -> BoxResult<BTreeMap<String, String>> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+        Err(err) => return Err(format!("failed to read `{path}`: {err}").into()),
+    };
+    let mut vars = BTreeMap::new();
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let malformed = |problem: &str| {
+            let message = format!("malformed `{path}` line {}: {problem}", number + 1);
+            Error::new(Kind::Validation, message)
+        };
+        let assignment = line.strip_prefix("export ").map_or(line, str::trim_start);
+        let (name, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| malformed("expected `NAME=VALUE`"))?;
+        let name = name.trim();
+        if name.is_empty() || !name.chars().all(|char| char.is_ascii_alphanumeric() || char == '_') {
+            return Err(malformed(&format!("invalid variable name `{name}`")).into());
+        }
+        let value = parse_value(value.trim()).ok_or_else(|| malformed("unterminated quote"))?;
+        vars.insert(name.to_owned(), value);
+    }
+    Ok(vars)
+}
+
+// The value of an assignment (see `load`), or `None` if its quote is unterminated.
+fn parse_value(value: &str) -> Option<String> {
+    if let Some(rest) = value.strip_prefix('\'') {
+        let (value, _) = rest.split_once('\'')?;
+        return Some(value.to_owned());
+    }
+    if let Some(rest) = value.strip_prefix('"') {
+        let mut parsed = String::new();
+        let mut chars = rest.chars();
+        while let Some(char) = chars.next() {
+            match char {
+                '"' => return Some(parsed),
+                '\\' => match chars.next()? {
+                    'n' => parsed.push('\n'),
+                    't' => parsed.push('\t'),
+                    escaped => parsed.push(escaped),
+                },
+                char => parsed.push(char),
+            }
+        }
+        return None;
+    }
+    let value = value.find(" #").map_or(value, |comment| &value[.. comment]);
+    Some(value.trim_end().to_owned())
+}
@@ -0,0 +1,143 @@
+use std::{fmt, io, str::FromStr};
+
+/// The kind of an `xtask` failure, which determines the exit code of the xtask (see [`Kind::code`]), so that scripts
+/// and CI can tell failures apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// A subcommand, or a tool it ran, failed (exit code 1)
+    Failure,
+    /// Invalid arguments or configuration, or a tool of an unsupported version (exit code 2)
+    Validation,
+    /// A required tool was not found (exit code 3)
+    ToolMissing,
+    /// A linter reported failures (exit code 4)
+    Lint,
+    /// A formatter found files needing formatting (exit code 5)
+    Format,
+    /// A bug in the xtask itself (exit code 101, as for a panic)
+    Internal,
+}
+
+impl Kind {
+    /// The exit code of the xtask for a failure of this kind.
+    #[must_use]
+    pub fn code(self) -> i32 {
+        match self {
+            Kind::Failure => 1,
+            Kind::Validation => 2,
+            Kind::ToolMissing => 3,
+            Kind::Lint => 4,
+            Kind::Format => 5,
+            Kind::Internal => 101,
+        }
+    }
+
+    /// The name of the kind, as printed by `--error-format short`.
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::Failure => "failure",
+            Kind::Validation => "validation",
+            Kind::ToolMissing => "tool-missing",
+            Kind::Lint => "lint",
+            Kind::Format => "format",
+            Kind::Internal => "internal",
+        }
+    }
+}
+
+/// An error of a known [`Kind`].
+#[derive(Debug)]
+pub struct Error {
+    kind: Kind,
+    message: String,
+}
+
+impl Error {
+    #[must_use]
+    pub fn new(kind: Kind, message: impl Into<String>) -> Self {
+        Error {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {
+}
+
+/// The kind of `err`: that of an [`Error`] (including one carried by an [`io::Error`], as for a tool that is not
+/// found), [`Kind::Validation`] for invalid arguments, or else [`Kind::Failure`].
+#[must_use]
+pub fn kind(err: &(dyn std::error::Error + 'static)) -> Kind {
+    if let Some(err) = err.downcast_ref::<Error>() {
+        return err.kind;
+    }
+    if err.is::<pico_args::Error>() {
+        return Kind::Validation;
+    }
+    if let Some(inner) = err.downcast_ref::<io::Error>().and_then(io::Error::get_ref) {
+        return kind(inner);
+    }
+    Kind::Failure
+}
+
+/// How errors are printed before the xtask exits.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `error: <message>`, with the message spanning as many lines as it needs
+    Human,
+    /// `error[<kind>]: <message>` on a single line, for scripting
+    Short,
+}
+
+impl Format {
+    /// The error format named by `XTASK_ERROR_FORMAT`, or else [`Format::Human`].
+    #[must_use]
+    pub fn detect() -> Self {
+        std::env::var("XTASK_ERROR_FORMAT")
+            .ok()
+            .and_then(|val| val.parse().ok())
+            .unwrap_or(Format::Human)
+    }
+
+    /// Renders the error message `message` of kind `kind` in this format.
+    #[must_use]
+    pub fn render(self, kind: Kind, message: &str) -> String {
+        match self {
+            Format::Human => format!("error: {message}"),
+            Format::Short => {
+                let message = message
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                format!("error[{}]: {message}", kind.as_str())
+            },
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "human" => Ok(Format::Human),
+            "short" => Ok(Format::Short),
+            _ => Err(format!("expected `human` or `short`, found `{str}`")),
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use crate::{runner::Runner, BoxResult};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::process::Command;
+
+/// File extensions considered C or C++ sources or headers.
+pub const CXX_EXTENSIONS: &[&str] = &["c", "cc", "cpp", "cxx", "h", "hh", "hpp", "hxx"];
+
+/// The full commit hash that `reference` names in the git repository containing `dir`.
+///
+/// Returns `None` if `dir` is not inside a git work tree.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `reference` does not name a commit
+pub fn rev_parse(runner: &dyn Runner, dir: &Utf8Path, reference: &str) -> BoxResult<Option<String>> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["rev-parse", "--is-inside-work-tree"]);
+    if !runner.output(&mut cmd)?.status.success() {
+        return Ok(None);
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["rev-parse", "--verify", "--quiet"]);
+    cmd.arg(format!("{reference}^{{commit}}"));
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        return Err(format!("git ref `{reference}` does not name a commit").into());
+    }
+    Ok(Some(String::from_utf8(output.stdout)?.trim().to_owned()))
+}
+
+/// The C and C++ files under `dir` that differ from the git ref `reference`, as absolute paths.
+///
+/// Returns `None` if `dir` is not inside a git work tree. Deleted files are not included.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `reference` does not name a commit
+/// - `git diff` fails with non-zero exit status or produces non-UTF-8 output
+pub fn changed_cxx_files(runner: &dyn Runner, dir: &Utf8Path, reference: &str) -> BoxResult<Option<Vec<Utf8PathBuf>>> {
+    let files = changed_files(runner, dir, reference)?;
+    Ok(files.map(|files| {
+        files
+            .into_iter()
+            .filter(|path| path.extension().map_or(false, |ext| CXX_EXTENSIONS.contains(&ext)))
+            .collect()
+    }))
+}
+
+/// The files under `dir` that differ from the git ref `reference`, as absolute paths.
+///
+/// Returns `None` if `dir` is not inside a git work tree. Deleted files are not included.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `reference` does not name a commit
+/// - `git diff` fails with non-zero exit status or produces non-UTF-8 output
+pub fn changed_files(runner: &dyn Runner, dir: &Utf8Path, reference: &str) -> BoxResult<Option<Vec<Utf8PathBuf>>> {
+    if rev_parse(runner, dir, reference)?.is_none() {
+        return Ok(None);
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["diff", "--name-only", "--relative", "--diff-filter=d", reference]);
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`git diff` failed: \"{err}\"").into());
+    }
+    let files = String::from_utf8(output.stdout)?
+        .lines()
+        .map(|line| dir.join(line))
+        .collect();
+    Ok(Some(files))
+}
+
+/// The files under `dir` staged in the git index, as absolute paths.
+///
+/// Returns `None` if `dir` is not inside a git work tree. Staged deletions are not included.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `git diff --cached` fails with non-zero exit status or produces non-UTF-8 output
+pub fn staged_files(runner: &dyn Runner, dir: &Utf8Path) -> BoxResult<Option<Vec<Utf8PathBuf>>> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["rev-parse", "--is-inside-work-tree"]);
+    if !runner.output(&mut cmd)?.status.success() {
+        return Ok(None);
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["diff", "--cached", "--name-only", "--relative", "--diff-filter=d"]);
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`git diff --cached` failed: \"{err}\"").into());
+    }
+    let files = String::from_utf8(output.stdout)?
+        .lines()
+        .map(|line| dir.join(line))
+        .collect();
+    Ok(Some(files))
+}
+
+/// The files under `dir` tracked by git (as of the index), as absolute paths.
+///
+/// Returns `None` if `dir` is not inside a git work tree. Files deleted from the work tree are not included.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `git ls-files` fails with non-zero exit status or produces non-UTF-8 output
+pub fn tracked_files(runner: &dyn Runner, dir: &Utf8Path) -> BoxResult<Option<Vec<Utf8PathBuf>>> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["rev-parse", "--is-inside-work-tree"]);
+    if !runner.output(&mut cmd)?.status.success() {
+        return Ok(None);
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["ls-files", "--cached", "--exclude-standard"]);
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`git ls-files` failed: \"{err}\"").into());
+    }
+    let files = String::from_utf8(output.stdout)?
+        .lines()
+        .map(|line| dir.join(line))
+        .filter(|file| file.is_file())
+        .collect();
+    Ok(Some(files))
+}
+
+/// The contents of `file` (a path under `dir`) as staged in the git index, which may differ from the work tree.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `file` is not staged (`git show` fails with non-zero exit status)
+pub fn staged_contents(runner: &dyn Runner, dir: &Utf8Path, file: &Utf8Path) -> BoxResult<Vec<u8>> {
+    let relative = file.strip_prefix(dir).unwrap_or(file);
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.arg("show");
+    // NOTE: `:./<path>` names the index entry of a path relative to the current directory
+    cmd.arg(format!(":./{relative}"));
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`git show` of the staged `{relative}` failed: \"{err}\"").into());
+    }
+    Ok(output.stdout)
+}
+
+/// Stages `files` (paths under `dir`) in the git index.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `git add` fails with non-zero exit status
+pub fn add(runner: &dyn Runner, dir: &Utf8Path, files: &[Utf8PathBuf]) -> BoxResult<()> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["add", "--"]);
+    cmd.args(files);
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`git add` failed: \"{err}\"").into());
+    }
+    Ok(())
+}
+
+/// The best common ancestor of `reference` and `HEAD` in the git repository containing `dir`, as a full commit hash.
+///
+/// Returns `None` if `dir` is not inside a git work tree.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `reference` does not name a commit
+/// - `reference` and `HEAD` have no common ancestor
+pub fn merge_base(runner: &dyn Runner, dir: &Utf8Path, reference: &str) -> BoxResult<Option<String>> {
+    if rev_parse(runner, dir, reference)?.is_none() {
+        return Ok(None);
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["merge-base", reference, "HEAD"]);
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        return Err(format!("git ref `{reference}` has no common ancestor with `HEAD`").into());
+    }
+    Ok(Some(String::from_utf8(output.stdout)?.trim().to_owned()))
+}
+
+/// The path of `dir` relative to the top level of the git work tree containing it (empty at the top level).
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `dir` is not inside a git work tree
+pub fn prefix(runner: &dyn Runner, dir: &Utf8Path) -> BoxResult<Utf8PathBuf> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["rev-parse", "--show-prefix"]);
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        return Err(format!("`{dir}` is not inside a git work tree").into());
+    }
+    Ok(String::from_utf8(output.stdout)?.trim().into())
+}
+
+/// Checks out `commit` into a new detached work tree at `path`, for the git repository containing `dir`.
+///
+/// A work tree previously left at `path` is removed first.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `git worktree add` fails with non-zero exit status
+pub fn add_worktree(runner: &dyn Runner, dir: &Utf8Path, path: &Utf8Path, commit: &str) -> BoxResult<()> {
+    if path.exists() {
+        remove_worktree(runner, dir, path)?;
+    }
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["worktree", "add", "--detach", "--quiet"]);
+    cmd.arg(path);
+    cmd.arg(commit);
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`git worktree add` failed: \"{err}\"").into());
+    }
+    Ok(())
+}
+
+/// Removes the work tree at `path` (see [`add_worktree`]), discarding any changes in it.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `git worktree remove` fails with non-zero exit status
+pub fn remove_worktree(runner: &dyn Runner, dir: &Utf8Path, path: &Utf8Path) -> BoxResult<()> {
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["worktree", "remove", "--force"]);
+    cmd.arg(path);
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`git worktree remove` failed: \"{err}\"").into());
+    }
+    Ok(())
+}
+
+/// The `git status --porcelain` lines for `path` in the git repository containing `dir` (e.g. `?? Cargo.lock` if it is
+/// untracked, `!! Cargo.lock` if it is ignored, or ` M Cargo.lock` if it is modified), which are empty if it is committed
+/// and unchanged.
+///
+/// Returns `None` if `dir` is not inside a git work tree.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `git status` fails with non-zero exit status or produces non-UTF-8 output
+pub fn status(runner: &dyn Runner, dir: &Utf8Path, path: &Utf8Path) -> BoxResult<Option<Vec<String>>> {
+    if rev_parse(runner, dir, "HEAD")?.is_none() {
+        return Ok(None);
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.current_dir(dir);
+    cmd.args(["status", "--porcelain", "--ignored", "--"]);
+    cmd.arg(path);
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("`git status` failed: \"{err}\"").into());
+    }
+    Ok(Some(
+        String::from_utf8(output.stdout)?.lines().map(String::from).collect(),
+    ))
+}
+
+/// The tags of the remote git repository `url`, each with the commit it points to (peeling annotated tags).
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `git ls-remote` fails with non-zero exit status (e.g. the repository cannot be reached)
+pub fn remote_tags(runner: &dyn Runner, url: &str) -> BoxResult<Vec<(String, String)>> {
+    let mut tags = Vec::<(String, String)>::new();
+    for (commit, reference) in ls_remote(runner, url, &["--tags"], &[])? {
+        let Some(tag) = reference.strip_prefix("refs/tags/") else {
+            continue;
+        };
+        // NOTE: an annotated tag is listed as the tag object, followed by the commit it points to as `<tag>^{}`
+        match tag.strip_suffix("^{}") {
+            Some(tag) => {
+                if let Some(entry) = tags.iter_mut().find(|(name, _)| name == tag) {
+                    entry.1 = commit;
+                }
+            },
+            None => tags.push((tag.to_owned(), commit)),
+        }
+    }
+    Ok(tags)
+}
+
+/// The commit that `HEAD` of the remote git repository `url` points to.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `git` command process fails to start
+/// - `git ls-remote` fails with non-zero exit status (e.g. the repository cannot be reached)
+/// - The repository has no `HEAD`
+pub fn remote_head(runner: &dyn Runner, url: &str) -> BoxResult<String> {
+    ls_remote(runner, url, &[], &["HEAD"])?
+        .into_iter()
+        .find(|(_, reference)| reference == "HEAD")
+        .map(|(commit, _)| commit)
+        .ok_or_else(|| format!("git repository `{url}` has no `HEAD`").into())
+}
+
+// The commits and references listed by `git ls-remote <flags> <url> <patterns>`.
+fn ls_remote(runner: &dyn Runner, url: &str, flags: &[&str], patterns: &[&str]) -> BoxResult<Vec<(String, String)>> {
+    let mut cmd = Command::new("git");
+    cmd.arg("ls-remote");
+    cmd.args(flags);
+    cmd.arg(url);
+    cmd.args(patterns);
+    let output = runner.output(&mut cmd)?;
+    if !output.status.success() {
+        let err = String::from_utf8_lossy(&output.stderr);
+        // NOTE: only the first line, since git follows it with generic advice (e.g. about access rights)
+        let err = err.lines().next().unwrap_or_default();
+        return Err(format!("`git ls-remote {url}` failed: \"{err}\"").into());
+    }
+    Ok(String::from_utf8(output.stdout)?
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(commit, reference)| (commit.to_owned(), reference.to_owned()))
+        .collect())
+}
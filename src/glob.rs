@@ -0,0 +1,91 @@
+use crate::{
+    error::{Error, Kind},
+    BoxResult,
+};
+
+/// The regex matching the paths (relative to some root, with `/` separators) that match `glob`, where `*` matches
+/// within a path component, `**` matches across components, `?` matches a single character, and `{a,b}` matches either
+/// alternative (e.g. `*.{h,cc}`). A glob without `/` matches the file name in any directory.
+///
+/// # Errors
+///
+/// Will return `Err` if `glob` does not translate to a valid regex.
+pub fn regex(glob: &str) -> BoxResult<regex::Regex> {
+    let mut pattern = String::from(if glob.contains('/') { "^" } else { "(?:^|/)" });
+    let mut chars = glob.trim_start_matches('/').chars().peekable();
+    let mut alternatives = false;
+    while let Some(char) = chars.next() {
+        match char {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            },
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '{' if !alternatives => {
+                alternatives = true;
+                pattern.push_str("(?:");
+            },
+            ',' if alternatives => pattern.push('|'),
+            '}' if alternatives => {
+                alternatives = false;
+                pattern.push(')');
+            },
+            char => pattern.push_str(&regex::escape(&char.to_string())),
+        }
+    }
+    pattern.push('$');
+    regex::Regex::new(&pattern)
+        .map_err(|err| Error::new(Kind::Validation, format!("invalid glob `{glob}`: {err}")).into())
+}
+
+/// The globs without `{a,b}` alternatives that together match the paths matching `glob` (see [`regex`]), for tools whose
+/// globs lack them (e.g. `src/*.h` and `src/*.cc` for `src/*.{h,cc}`). A glob without `/` is prefixed with `**/`, so
+/// that it still matches the file name in any directory.
+#[must_use]
+pub fn expand(glob: &str) -> Vec<String> {
+    fn alternatives(glob: &str) -> Vec<String> {
+        let Some((prefix, rest)) = glob.split_once('{') else {
+            return vec![glob.into()];
+        };
+        let Some((choices, suffix)) = rest.split_once('}') else {
+            return vec![glob.into()];
+        };
+        choices
+            .split(',')
+            .flat_map(|choice| alternatives(&format!("{prefix}{choice}{suffix}")))
+            .collect()
+    }
+    let anchored = glob.contains('/');
+    alternatives(glob.trim_start_matches('/'))
+        .into_iter()
+        .map(|glob| if anchored { glob } else { format!("**/{glob}") })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn expand_splits_the_alternatives() {
+        assert_eq!(super::expand("cxx/**/*.{h,cc}"), ["cxx/**/*.h", "cxx/**/*.cc"]);
+        assert_eq!(super::expand("/third_party/**"), ["third_party/**"]);
+        assert_eq!(super::expand("*_generated.{h,rs}"), [
+            "**/*_generated.h",
+            "**/*_generated.rs"
+        ]);
+    }
+
+    #[test]
+    fn regex_matches_the_alternatives() {
+        let regex = super::regex("cxx/**/*.{h,cc}").unwrap();
+        assert!(regex.is_match("cxx/include/a.h"));
+        assert!(regex.is_match("cxx/a.cc"));
+        assert!(!regex.is_match("cxx/a.cpp"));
+        assert!(!regex.is_match("src/cxx/a.h"));
+    }
+}
@@ -1,5 +1,48 @@
-use crate::BoxResult;
-use std::process::ExitStatus;
+use crate::{
+    error::{Error, Format, Kind},
+    BoxResult,
+};
+use std::{
+    ffi::OsStr,
+    fmt,
+    process::{Command, ExitStatus},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+// The error format selected by `--error-format`, if any (see `set_error_format`).
+static ERROR_FORMAT: Mutex<Option<Format>> = Mutex::new(None);
+
+// Whether `--quiet` silences the status lines printed by `status` (see `set_quiet`).
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+// The format selected by `--env-format` for `print_env` (see `set_env_format`).
+static ENV_FORMAT: Mutex<EnvFormat> = Mutex::new(EnvFormat::Shell);
+
+/// How [`print_env`] prints commands.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EnvFormat {
+    /// A shell command line, e.g. `cd dir && env KEY=value program args`
+    Shell,
+    /// A JSON object on a single line, with the working directory, program, arguments, and the environment variables
+    /// set or removed for the command along with their inherited values
+    Json,
+}
+
+impl FromStr for EnvFormat {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "shell" => Ok(EnvFormat::Shell),
+            "json" => Ok(EnvFormat::Json),
+            _ => Err(format!("expected `shell` or `json`, found `{str}`")),
+        }
+    }
+}
 
 /// # Errors
 ///
@@ -13,26 +56,67 @@ pub fn help(args: &mut pico_args::Arguments, help: &str) -> BoxResult<bool> {
     Ok(false)
 }
 
+/// Selects the format errors are printed in by [`result`] and [`subcommand_result`], overriding
+/// `XTASK_ERROR_FORMAT`.
+pub fn set_error_format(format: Format) {
+    if let Ok(mut selected) = ERROR_FORMAT.lock() {
+        *selected = Some(format);
+    }
+}
+
+/// Silences the status lines printed by [`status`] for the whole process, as selected by `--quiet`.
+pub fn set_quiet() {
+    QUIET.store(true, Ordering::SeqCst);
+}
+
+/// Prints the status line `message` (e.g. what the xtask is doing, or a summary of its outcome), unless `--quiet` is
+/// given (see [`set_quiet`]).
+///
+/// The lines reporting what failed (e.g. the files needing formatting) and the output of the tools are printed
+/// regardless.
+pub fn status(message: impl fmt::Display) {
+    if !QUIET.load(Ordering::SeqCst) {
+        println!("{message}");
+    }
+}
+
+/// Prints the error of `result` (if any) and exits with the code of its kind (see [`crate::error::kind`]).
 pub fn result<T>(result: BoxResult<T>) {
     if let Err(err) = result {
-        println!("error: {err}");
-        let code = 1;
-        std::process::exit(code);
+        exit(crate::error::kind(&*err), &err.to_string());
     }
 }
 
+/// Like [`result`], but for the result of running `subcommand`, which also fails (as a [`Kind::Failure`]) if the
+/// subcommand reports a non-zero exit status, such as that of a tool it ran. The timings recorded with `--timings` are
+/// reported before the result (see [`crate::timings::finish`]).
 pub fn subcommand_result(subcommand: &str, result: BoxResult<Option<ExitStatus>>) {
+    // NOTE: the timings are reported first, since a failure exits
+    let timings = crate::timings::finish();
     match result {
         Ok(None) => {},
         Ok(Some(status)) => {
             if !status.success() {
-                println!("error: subcommand `{subcommand}` failed with non-zero exit code");
-                let code = status.code().unwrap_or(1);
-                std::process::exit(code);
+                exit(
+                    Kind::Failure,
+                    &format!("subcommand `{subcommand}` failed with non-zero exit code"),
+                );
             }
         },
         result => crate::handler::result(result),
     }
+    crate::handler::result(timings);
+}
+
+// Print the error `message` in the selected error format and exit with the code of `kind`.
+fn exit(kind: Kind, message: &str) -> ! {
+    let format = ERROR_FORMAT
+        .lock()
+        .ok()
+        .and_then(|selected| *selected)
+        .unwrap_or_else(Format::detect);
+    println!("{}", format.render(kind, message));
+    std::process::exit(kind.code());
 }
 
 /// # Errors
@@ -49,5 +133,82 @@ pub fn unused(args: &pico_args::Arguments) -> BoxResult<()> {
         message.push(' ');
         message.push_str(str.to_string_lossy().borrow());
     }
-    Err(format!("unrecognized arguments `{message}`").into())
+    Err(Error::new(Kind::Validation, format!("unrecognized arguments `{message}`")).into())
+}
+
+/// Selects the format commands are printed in by [`print_env`].
+pub fn set_env_format(format: EnvFormat) {
+    if let Ok(mut selected) = ENV_FORMAT.lock() {
+        *selected = format;
+    }
+}
+
+/// Prints `cmd` as a shell command line, including its working directory and sorted environment variables, so that
+/// the invocation can be reproduced by hand.
+///
+/// With `--env-format json` (see [`set_env_format`]), `cmd` is instead printed as a JSON object on a single line, such
+/// as `{"args":["build"],"cwd":"/ws","env":[{"inherited":"/usr/bin","name":"PATH","value":"/opt/llvm/bin:/usr/bin"}],
+/// "program":"cargo"}`. Only the variables set (or removed, with a `null` value) for `cmd` are listed, each
+/// with the value it would otherwise inherit (or `null`), which shows how the xtask changed the environment.
+pub fn print_env(cmd: &Command) {
+    fn quote(word: &OsStr) -> String {
+        let word = word.to_string_lossy();
+        let safe = |char: char| char.is_ascii_alphanumeric() || "%+,-./:=@_".contains(char);
+        if !word.is_empty() && word.chars().all(safe) {
+            word.into_owned()
+        } else {
+            format!("'{}'", word.replace('\'', r"'\''"))
+        }
+    }
+    if ENV_FORMAT.lock().map_or(false, |format| *format == EnvFormat::Json) {
+        print_env_json(cmd);
+        return;
+    }
+    let mut line = String::new();
+    if let Some(dir) = cmd.get_current_dir() {
+        line.push_str(&format!("cd {} && ", quote(dir.as_os_str())));
+    }
+    line.push_str("env");
+    let mut envs = cmd.get_envs().collect::<Vec<_>>();
+    envs.sort_unstable();
+    for (key, val) in envs {
+        match val {
+            Some(val) => {
+                let mut assignment = key.to_os_string();
+                assignment.push("=");
+                assignment.push(val);
+                line.push_str(&format!(" {}", quote(&assignment)));
+            },
+            None => line.push_str(&format!(" -u {}", quote(key))),
+        }
+    }
+    line.push_str(&format!(" {}", quote(cmd.get_program())));
+    for arg in cmd.get_args() {
+        line.push_str(&format!(" {}", quote(arg)));
+    }
+    println!("{line}");
+}
+
+// Prints `cmd` as a JSON object on a single line (see `print_env`).
+fn print_env_json(cmd: &Command) {
+    let lossy = |str: &OsStr| str.to_string_lossy().into_owned();
+    let mut envs = cmd.get_envs().collect::<Vec<_>>();
+    envs.sort_unstable();
+    let env = envs
+        .into_iter()
+        .map(|(key, val)| {
+            serde_json::json!({
+                "name": lossy(key),
+                "value": val.map(lossy),
+                "inherited": std::env::var_os(key).as_deref().map(lossy),
+            })
+        })
+        .collect::<Vec<_>>();
+    let json = serde_json::json!({
+        "cwd": cmd.get_current_dir().map(|dir| lossy(dir.as_os_str())),
+        "program": lossy(cmd.get_program()),
+        "args": cmd.get_args().map(lossy).collect::<Vec<_>>(),
+        "env": env,
+    });
+    println!("{json}");
 }
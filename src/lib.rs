@@ -2,12 +2,38 @@
 #![deny(clippy::pedantic)]
 #![deny(unsafe_code)]
 
+pub mod benchmark;
+pub mod cache;
+pub mod cmake_cache;
+pub mod cmake_install;
 pub mod command;
+pub mod compile_commands;
 pub mod config;
-// pub mod detection;
+pub mod ctest;
+pub mod cxx_pins;
+pub mod cxx_sources;
+pub mod detection;
+pub mod doc_links;
+pub mod doc_server;
+pub mod env_file;
+pub mod error;
+pub mod git;
+pub mod glob;
 pub mod handler;
 // pub mod install;
-// pub mod validation;
+pub mod linker;
+pub mod migration;
+pub mod ninja_log;
+pub mod runner;
+pub mod size;
+pub mod status;
+pub mod target_preset;
+#[cfg(test)]
+mod testing;
+pub mod timings;
+pub mod tool_cache;
+pub mod validation;
+pub mod version;
 pub mod workspace;
 
 pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
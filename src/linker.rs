@@ -0,0 +1,133 @@
+use crate::{
+    config::Xtask,
+    error::{Error, Kind},
+    runner::Runner,
+    BoxResult,
+};
+
+/// The xtask subcommands that link, for which the configured linker is checked before they run.
+pub const LINKING_SUBCOMMANDS: &[&str] = &[
+    "bench",
+    "build",
+    "cmake",
+    "dist",
+    "miri",
+    "size",
+    "tarpaulin",
+    "test",
+    "valgrind",
+];
+
+/// A linker that can be selected with `xtask.link.linker`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Linker {
+    Lld,
+    Mold,
+}
+
+impl Linker {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Linker::Lld => "lld",
+            Linker::Mold => "mold",
+        }
+    }
+}
+
+/// The linker used on this platform for the configured `xtask.link.linker`.
+pub struct Selection {
+    pub linker: Linker,
+    /// The program the compiler drivers run for the linker (e.g. `ld.lld` on Linux, `ld64.lld` on macOS)
+    pub program: &'static str,
+    /// The configured linker, if it is not available on this platform and `linker` is used in its place
+    pub fallback_from: Option<Linker>,
+}
+
+/// The linker for the configured `xtask.link.linker`, if any.
+///
+/// mold only supports Linux (and other ELF platforms), so lld is used in its place on macOS and Windows.
+///
+/// # Errors
+///
+/// Will return `Err` if `xtask.link.linker` is neither `lld` nor `mold`.
+pub fn selected(xtask: &Xtask) -> BoxResult<Option<Selection>> {
+    let configured = match xtask.link.linker.as_deref() {
+        None => return Ok(None),
+        Some("lld") => Linker::Lld,
+        Some("mold") => Linker::Mold,
+        Some(linker) => {
+            let message = format!("unrecognized `xtask.link.linker` `{linker}`: expected `lld` or `mold`");
+            return Err(Error::new(Kind::Validation, message).into());
+        },
+    };
+    let (linker, program) = if cfg!(target_os = "macos") {
+        (Linker::Lld, "ld64.lld")
+    } else if cfg!(windows) {
+        (Linker::Lld, "lld-link")
+    } else if configured == Linker::Mold {
+        (Linker::Mold, "mold")
+    } else {
+        (Linker::Lld, "ld.lld")
+    };
+    Ok(Some(Selection {
+        linker,
+        program,
+        fallback_from: (linker != configured).then_some(configured),
+    }))
+}
+
+/// Checks that the linker for the configured `xtask.link.linker` (if any) can be run, printing a note if it falls
+/// back to another linker on this platform.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - `xtask.link.linker` is not a known linker
+/// - The linker is not found or fails to report its version
+pub fn validate(xtask: &Xtask, runner: &dyn Runner) -> BoxResult<()> {
+    let Some(selection) = selected(xtask)? else {
+        return Ok(());
+    };
+    if let Some(configured) = selection.fallback_from {
+        crate::handler::status(format_args!(
+            "note: `{}` is not supported on this platform, so `{}` is used as the linker",
+            configured.as_str(),
+            selection.linker.as_str()
+        ));
+    }
+    crate::validation::version_banner(xtask, runner, selection.program).map_err(|err| {
+        let message = format!(
+            "the linker `{}` (from `xtask.link.linker`) cannot be run: {err}",
+            selection.program
+        );
+        Error::new(crate::error::kind(&*err), message)
+    })?;
+    Ok(())
+}
+
+/// The `-D` arguments for `cmake` linking the C++ targets with `selection`.
+///
+/// The flags are given as the `CMAKE_<TYPE>_LINKER_FLAGS_INIT` defaults, so that linker flags set by the project are
+/// kept. These only apply to a new cache, which is fine since a change of the configure arguments reconfigures from
+/// scratch (see [`crate::cmake_cache::stale_reasons`]).
+#[must_use]
+pub fn cmake_defines(selection: &Selection) -> Vec<String> {
+    if cfg!(windows) {
+        return vec![format!("-DCMAKE_LINKER={}", selection.program)];
+    }
+    let flag = format!("-fuse-ld={}", selection.linker.as_str());
+    ["EXE", "SHARED", "MODULE"]
+        .iter()
+        .map(|kind| format!("-DCMAKE_{kind}_LINKER_FLAGS_INIT={flag}"))
+        .collect()
+}
+
+/// The rustc flags linking the Rust crates with `selection`.
+#[must_use]
+pub fn rustflags(selection: &Selection) -> Vec<String> {
+    if cfg!(windows) {
+        return vec![format!("-Clinker={}", selection.program)];
+    }
+    vec![format!("-Clink-arg=-fuse-ld={}", selection.linker.as_str())]
+}
@@ -0,0 +1,112 @@
+use crate::{
+    error::{Error, Kind},
+    BoxResult,
+};
+use serde_json::Value;
+use std::fmt;
+
+/// The current version of the xtask config schema, which `xtask config migrate` writes to `schemaVersion`.
+///
+/// A config without `schemaVersion` predates versioning and has version 0.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A change made to the xtask config by a migration, with keys given as dotted paths (e.g. `clang.platform`).
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub enum Change {
+    /// The key `from` was renamed to `to`
+    Renamed { from: String, to: String },
+    /// The value of `key` was replaced (e.g. a renamed enum variant)
+    Replaced { key: String, from: Value, to: Value },
+    /// The key `key` was removed, since it no longer has any effect
+    Removed { key: String },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Renamed { from, to } => write!(f, "renamed `{from}` to `{to}`"),
+            Change::Replaced { key, from, to } => write!(f, "replaced `{key} = {from}` with `{key} = {to}`"),
+            Change::Removed { key } => write!(f, "removed `{key}`"),
+        }
+    }
+}
+
+// A migration from the previous schema version to `version`.
+struct Migration {
+    version: u32,
+    migrate: fn(&mut Value, &mut Vec<Change>),
+}
+
+// The migrations, in order of version.
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    migrate: migrate_v1,
+}];
+
+/// The schema version of the raw xtask config `value`: its `schemaVersion`, or 0 if it has none.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - `schemaVersion` is not a number
+/// - `schemaVersion` is newer than [`SCHEMA_VERSION`], i.e., the config is for a newer xtask
+pub fn schema_version(value: &Value) -> BoxResult<u32> {
+    let version = match value.get("schemaVersion") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| Error::new(Kind::Validation, format!("invalid `schemaVersion` {version}")))?,
+    };
+    if version > SCHEMA_VERSION {
+        let message = format!(
+            "the xtask config has schema version {version}, but this xtask only supports up to {SCHEMA_VERSION}\n\
+             Update the xtask dependency"
+        );
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+    Ok(version)
+}
+
+/// Upgrades the raw xtask config `value` to [`SCHEMA_VERSION`], applying the migrations for each version after its
+/// own (see [`schema_version`]) and setting its `schemaVersion`, and returns the changes made.
+///
+/// A config that is already up to date is left as is.
+///
+/// # Errors
+///
+/// Will return `Err` if the schema version of `value` is invalid or newer than [`SCHEMA_VERSION`].
+pub fn migrate(value: &mut Value) -> BoxResult<Vec<Change>> {
+    let version = schema_version(value)?;
+    let mut changes = vec![];
+    if version == SCHEMA_VERSION {
+        return Ok(changes);
+    }
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > version) {
+        (migration.migrate)(value, &mut changes);
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.insert("schemaVersion".into(), SCHEMA_VERSION.into());
+    }
+    Ok(changes)
+}
+
+// Version 1 renamed the `xcodeSelect` kind of `clang.platform.macos.searchPaths` to `xcode`.
+fn migrate_v1(value: &mut Value, changes: &mut Vec<Change>) {
+    let Some(search_paths) = value
+        .pointer_mut("/clang/platform/macos/searchPaths")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+    for (i, search_path) in search_paths.iter_mut().enumerate() {
+        let key = format!("clang.platform.macos.searchPaths[{i}].kind");
+        if let Some(kind) = search_path.get_mut("kind") {
+            if kind == "xcodeSelect" {
+                let to = Value::from("xcode");
+                let from = std::mem::replace(kind, to.clone());
+                changes.push(Change::Replaced { key, from, to });
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::BoxResult;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The time spent building one `CMake` target in the last ninja build.
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct TargetTime {
+    /// The target (from the `CMakeFiles/<target>.dir/` of its objects), or the file name of an output outside any
+    /// target directory (e.g. a linked library)
+    pub target: String,
+    /// The summed durations of the target's build steps
+    pub seconds: f64,
+    /// The number of build steps
+    pub steps: usize,
+}
+
+/// The per-target times of the last build recorded in a ninja log (`.ninja_log`), slowest first, along with the
+/// wall-clock seconds of that build.
+///
+/// Each line of the log records the start and end (in milliseconds since the start of its build) of a step, and its
+/// output, such as `120\t5310\t1700000000\tCMakeFiles/core.dir/src/parse.cc.o\t1f2e3d4c`. A build ends where the end
+/// times start over, since ninja appends the steps of each build in the order they finish.
+///
+/// # Errors
+///
+/// Will return `Err` if the pattern for target directories fails to compile.
+pub fn parse_target_times(log: &str) -> BoxResult<(Vec<TargetTime>, f64)> {
+    let target_dir = regex::Regex::new(r"CMakeFiles/([^/]+)\.dir/")?;
+    let mut builds = vec![vec![]];
+    let mut last_end = 0;
+    for line in log.lines().filter(|line| !line.starts_with('#')) {
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let [start, end, _, output, hash] = fields.as_slice() else {
+            continue;
+        };
+        let (Ok(start), Ok(end)) = (start.parse::<u64>(), end.parse::<u64>()) else {
+            continue;
+        };
+        if end < last_end {
+            builds.push(vec![]);
+        }
+        last_end = end;
+        if let Some(build) = builds.last_mut() {
+            build.push((start, end, *output, *hash));
+        }
+    }
+    let build = builds.pop().unwrap_or_default();
+
+    // NOTE: a step with several outputs is logged once per output, so steps are told apart by their times and hash
+    let mut steps = BTreeMap::<String, BTreeSet<(u64, u64, &str)>>::new();
+    for (start, end, output, hash) in &build {
+        let target = match target_dir.captures(output) {
+            Some(captures) => captures[1].to_owned(),
+            None => output.rsplit('/').next().unwrap_or(output).to_owned(),
+        };
+        steps.entry(target).or_default().insert((*start, *end, hash));
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let mut times = steps
+        .into_iter()
+        .map(|(target, steps)| TargetTime {
+            target,
+            seconds: steps.iter().map(|(start, end, _)| end - start).sum::<u64>() as f64 / 1000.0,
+            steps: steps.len(),
+        })
+        .collect::<Vec<_>>();
+    times.sort_by(|lhs, rhs| rhs.seconds.total_cmp(&lhs.seconds));
+    let start = build.iter().map(|(start, ..)| *start).min().unwrap_or_default();
+    let end = build.iter().map(|(_, end, ..)| *end).max().unwrap_or_default();
+    #[allow(clippy::cast_precision_loss)]
+    let wall = (end - start) as f64 / 1000.0;
+    Ok((times, wall))
+}
+
+/// Prints one line per target with its build time and number of steps, followed by the totals.
+pub fn print_summary(times: &[TargetTime], wall: f64) {
+    let width = times.iter().map(|time| time.target.len()).max().unwrap_or_default();
+    for time in times {
+        println!("{:<width$}  {:>8.2}s  {} steps", time.target, time.seconds, time.steps);
+    }
+    let seconds = times.iter().map(|time| time.seconds).sum::<f64>();
+    println!("{} targets, {seconds:.2}s of build steps in {wall:.2}s", times.len());
+}
@@ -0,0 +1,414 @@
+use crate::{
+    error::{Error, Kind},
+    timings::Category,
+    BoxResult,
+};
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+        Mutex,
+        Once,
+    },
+};
+
+// Process ids of the children being waited on by `SystemRunner`, to which interrupts are forwarded.
+static CHILDREN: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+
+/// Spawns the processes for commands, so that tests can substitute a [`MockRunner`].
+///
+/// A runner may be shared between threads by commands that run several processes in parallel.
+pub trait Runner: Sync {
+    /// Runs `cmd` with inherited stdio and waits for it to finish.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the process fails to start.
+    fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus>;
+
+    /// Runs `cmd` with captured stdout and stderr and waits for it to finish.
+    ///
+    /// Unlike [`Command::output`], stdin is inherited unless it is set for `cmd`, as for [`Runner::status`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the process fails to start.
+    fn output(&self, cmd: &mut Command) -> io::Result<Output>;
+
+    /// Runs `cmd` with captured stdout and stderr, passing each line (without its newline) to `on_line` as it is
+    /// written, and waits for it to finish.
+    ///
+    /// The default implementation passes the lines of [`Runner::output`] once the process has finished.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the process fails to start.
+    fn stream(&self, cmd: &mut Command, on_line: &mut dyn FnMut(Stream, &str)) -> io::Result<ExitStatus> {
+        let output = self.output(cmd)?;
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            on_line(Stream::Stdout, line);
+        }
+        for line in String::from_utf8_lossy(&output.stderr).lines() {
+            on_line(Stream::Stderr, line);
+        }
+        Ok(output.status)
+    }
+}
+
+/// The output stream of a process a line was written to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Runs commands as real processes.
+#[allow(clippy::module_name_repetitions)]
+pub struct SystemRunner;
+
+// NOTE: the processes are timed with `--timings` (see `crate::timings`)
+impl Runner for SystemRunner {
+    fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus> {
+        let name = crate::timings::command_name(cmd);
+        crate::timings::record(
+            Category::Command,
+            || name,
+            || {
+                let mut child = spawn(cmd)?;
+                let _running = Running::new(&child);
+                child.wait()
+            },
+        )
+    }
+
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        let name = crate::timings::command_name(cmd);
+        crate::timings::record(
+            Category::Command,
+            || name,
+            || {
+                cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+                let child = spawn(cmd)?;
+                let _running = Running::new(&child);
+                child.wait_with_output()
+            },
+        )
+    }
+
+    fn stream(&self, cmd: &mut Command, on_line: &mut dyn FnMut(Stream, &str)) -> io::Result<ExitStatus> {
+        let name = crate::timings::command_name(cmd);
+        crate::timings::record(Category::Command, || name, || stream(cmd, on_line))
+    }
+}
+
+// Runs `cmd` for `SystemRunner::stream`.
+fn stream(cmd: &mut Command, on_line: &mut dyn FnMut(Stream, &str)) -> io::Result<ExitStatus> {
+    cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    let mut child = spawn(cmd)?;
+    let _running = Running::new(&child);
+    let (sender, receiver) = mpsc::channel();
+    // NOTE: each pipe is read on a thread of its own, so that a process filling one pipe cannot block on it while
+    // the other is being read
+    let readers = [
+        child
+            .stdout
+            .take()
+            .map(|pipe| read_lines(pipe, Stream::Stdout, sender.clone())),
+        child.stderr.take().map(|pipe| read_lines(pipe, Stream::Stderr, sender)),
+    ];
+    for (stream, line) in receiver {
+        on_line(stream, &line);
+    }
+    for reader in readers.into_iter().flatten() {
+        let _ = reader.join();
+    }
+    child.wait()
+}
+
+// Spawns `cmd`, with the interrupt handler installed (see `install_interrupt_handler`).
+fn spawn(cmd: &mut Command) -> io::Result<Child> {
+    install_interrupt_handler();
+    cmd.spawn().map_err(|err| spawn_error(cmd, err))
+}
+
+// A child registered in `CHILDREN` while it is being waited on, until this is dropped.
+struct Running(u32);
+
+impl Running {
+    fn new(child: &Child) -> Self {
+        if let Ok(mut children) = CHILDREN.lock() {
+            children.insert(child.id());
+        }
+        Running(child.id())
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        if let Ok(mut children) = CHILDREN.lock() {
+            children.remove(&self.0);
+        }
+    }
+}
+
+// Report a program of `cmd` that is not found as a missing tool (see `crate::error::Kind::ToolMissing`).
+fn spawn_error(cmd: &Command, err: io::Error) -> io::Error {
+    if err.kind() != io::ErrorKind::NotFound {
+        return err;
+    }
+    let program = cmd.get_program().to_string_lossy();
+    let message = format!("`{program}` was not found (is it installed and on `PATH`?)");
+    io::Error::new(io::ErrorKind::NotFound, Error::new(Kind::ToolMissing, message))
+}
+
+// Send the lines read from `pipe` to `sender` until the pipe is closed.
+fn read_lines(
+    pipe: impl Read + Send + 'static,
+    stream: Stream,
+    sender: mpsc::Sender<(Stream, String)>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        for line in BufReader::new(pipe).split(b'\n') {
+            let Ok(mut line) = line else {
+                break;
+            };
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            if sender
+                .send((stream, String::from_utf8_lossy(&line).into_owned()))
+                .is_err()
+            {
+                break;
+            }
+        }
+    })
+}
+
+// Install (once) the handler for Ctrl-C and termination signals.
+//
+// While children are running (on any thread) the signal is forwarded to each of them and the xtask keeps waiting, so
+// the children can clean up and their (failed) statuses are reported as usual. Otherwise the xtask exits as it would
+// without the handler.
+fn install_interrupt_handler() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        // NOTE: if a handler cannot be installed the default signal behavior is kept
+        let _ = ctrlc::set_handler(|| {
+            let children = CHILDREN.lock().map(|children| children.clone()).unwrap_or_default();
+            if children.is_empty() {
+                std::process::exit(130);
+            }
+            for pid in children {
+                forward_interrupt(pid);
+            }
+        });
+    });
+}
+
+#[cfg(unix)]
+fn forward_interrupt(pid: u32) {
+    use nix::{sys::signal, unistd::Pid};
+    // NOTE: a Ctrl-C from the terminal also reaches the child directly, and a second `SIGINT` is harmless
+    if let Ok(pid) = i32::try_from(pid) {
+        let _ = signal::kill(Pid::from_raw(pid), signal::Signal::SIGINT);
+    }
+}
+
+// NOTE: on Windows, Ctrl-C is delivered to every process attached to the console, including the child
+#[cfg(not(unix))]
+fn forward_interrupt(_pid: u32) {
+}
+
+/// Runs commands by passing them to a handler instead of spawning processes.
+///
+/// The handler can inspect the command (e.g. with [`Command::get_args`]) and returns the output the process would
+/// have produced. For [`Runner::status`] only the output's status is used.
+#[allow(clippy::module_name_repetitions)]
+pub struct MockRunner<F> {
+    handler: F,
+}
+
+impl<F> MockRunner<F>
+where
+    F: Fn(&Command) -> io::Result<Output> + Sync,
+{
+    pub fn new(handler: F) -> Self {
+        MockRunner { handler }
+    }
+}
+
+impl<F> Runner for MockRunner<F>
+where
+    F: Fn(&Command) -> io::Result<Output> + Sync,
+{
+    fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus> {
+        (self.handler)(cmd).map(|output| output.status)
+    }
+
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        (self.handler)(cmd)
+    }
+}
+
+/// An exit status with the exit code `code`, for commands that report their own outcome rather than a process's.
+#[cfg(unix)]
+#[must_use]
+pub fn exit_status(code: i32) -> ExitStatus {
+    std::os::unix::process::ExitStatusExt::from_raw(code << 8)
+}
+
+/// An exit status with the exit code `code`, for commands that report their own outcome rather than a process's.
+#[cfg(windows)]
+#[must_use]
+pub fn exit_status(code: i32) -> ExitStatus {
+    #[allow(clippy::cast_sign_loss)]
+    std::os::windows::process::ExitStatusExt::from_raw(code as u32)
+}
+
+/// How [`run_parallel`] shows the output of the commands.
+#[derive(Clone, Copy)]
+pub enum ParallelOutput {
+    /// Each line is written as soon as it is produced, prefixed with the label of its command (colored if `color` is
+    /// set), so that the output of different commands can be told apart
+    Prefixed { color: bool },
+    /// The commands inherit stdout and stderr, so their output is written as is and may interleave mid-line
+    Passthrough,
+}
+
+// The colors of the prefixes, cycled through in the order of the commands (ANSI cyan, yellow, green, magenta, blue,
+// and red).
+const PREFIX_COLORS: &[u8] = &[36, 33, 32, 35, 34, 31];
+
+// The widest prefix column, beyond which longer labels are not padded to.
+const PREFIX_WIDTH: usize = 32;
+
+/// Runs `commands` (each with a label naming it, e.g. the file it processes) with `runner` across `jobs` threads,
+/// returning the number of commands that failed.
+///
+/// The output is shown as selected by `output`. With `print_env`, each command is printed (see
+/// [`crate::handler::print_env`]) before it starts.
+///
+/// # Errors
+///
+/// Will return `Err` if a command process fails to start, after the commands already started have finished.
+pub fn run_parallel(
+    runner: &dyn Runner,
+    commands: Vec<(String, Command)>,
+    jobs: usize,
+    output: ParallelOutput,
+    print_env: bool,
+) -> BoxResult<usize> {
+    let threads = jobs.max(1).min(commands.len());
+    let width = commands
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or_default()
+        .min(PREFIX_WIDTH);
+    let queue = Mutex::new(commands.into_iter().enumerate());
+    let failures = AtomicUsize::new(0);
+    let error = Mutex::new(None);
+    std::thread::scope(|scope| {
+        for _ in 0 .. threads {
+            scope.spawn(|| {
+                while let Some((index, (label, mut cmd))) = queue.lock().map(|mut queue| queue.next()).ok().flatten() {
+                    if print_env {
+                        let _stdout = io::stdout().lock();
+                        crate::handler::print_env(&cmd);
+                    }
+                    let status = match output {
+                        ParallelOutput::Prefixed { color } => {
+                            let prefix = if color {
+                                let color = PREFIX_COLORS[index % PREFIX_COLORS.len()];
+                                format!("\x1b[{color}m{label:<width$} |\x1b[0m ")
+                            } else {
+                                format!("{label:<width$} | ")
+                            };
+                            runner.stream(&mut cmd, &mut |stream, line| match stream {
+                                Stream::Stdout => {
+                                    let _ = writeln!(io::stdout().lock(), "{prefix}{line}");
+                                },
+                                Stream::Stderr => {
+                                    let _ = writeln!(io::stderr().lock(), "{prefix}{line}");
+                                },
+                            })
+                        },
+                        ParallelOutput::Passthrough => runner.status(&mut cmd),
+                    };
+                    match status {
+                        Ok(status) => {
+                            if !status.success() {
+                                failures.fetch_add(1, Ordering::SeqCst);
+                            }
+                        },
+                        Err(err) => {
+                            let program = cmd.get_program().to_string_lossy().into_owned();
+                            if let Ok(mut error) = error.lock() {
+                                error.get_or_insert_with(|| {
+                                    Error::new(crate::error::kind(&err), format!("failed to run `{program}`: {err}"))
+                                });
+                            }
+                            break;
+                        },
+                    }
+                }
+            });
+        }
+    });
+    if let Some(error) = error.into_inner().ok().flatten() {
+        return Err(error.into());
+    }
+    Ok(failures.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockRunner, ParallelOutput, Runner, Running, SystemRunner, CHILDREN};
+    use crate::error::Kind;
+    use std::{io, process::Command};
+
+    fn registered(pid: u32) -> bool {
+        CHILDREN.lock().unwrap().contains(&pid)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn children_are_registered_while_running() {
+        let mut first = Command::new("sleep").arg("1").spawn().unwrap();
+        let mut second = Command::new("sleep").arg("1").spawn().unwrap();
+        {
+            let _first = Running::new(&first);
+            drop(Running::new(&second));
+            assert!(registered(first.id()));
+            assert!(!registered(second.id()));
+        }
+        assert!(!registered(first.id()));
+        first.kill().unwrap();
+        second.kill().unwrap();
+        first.wait().unwrap();
+        second.wait().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_captures_stdout_and_stderr() {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let output = SystemRunner.output(&mut cmd).unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b"out\n");
+        assert_eq!(output.stderr, b"err\n");
+    }
+
+    #[test]
+    fn run_parallel_keeps_the_kind_of_a_spawn_error() {
+        let runner = MockRunner::new(|cmd| Err(super::spawn_error(cmd, io::ErrorKind::NotFound.into())));
+        let commands = vec![("missing".to_owned(), Command::new("missing-tool"))];
+        let err = super::run_parallel(&runner, commands, 1, ParallelOutput::Passthrough, false).unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::ToolMissing);
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{runner::Runner, BoxResult};
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+/// The sizes of the build artifacts of one profile at some point in time.
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Deserialize, Serialize)]
+pub struct Record {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// The commit checked out at the time, if the workspace is a git checkout
+    pub rev: Option<String>,
+    /// The cargo profile directory (e.g. `debug`)
+    pub profile: String,
+    /// Artifact sizes in bytes, keyed by `cargo:<file name>` or `cmake:<path within the build directory>`
+    pub sizes: BTreeMap<String, u64>,
+}
+
+/// The cargo profile directory for a build with the given cargo arguments: `release` with `--release`, the named
+/// profile with `--profile <NAME>` (`debug` for `dev`), and `debug` otherwise.
+#[must_use]
+pub fn profile_dir(args: &[OsString]) -> String {
+    let mut profile = "debug".to_owned();
+    let mut args = args.iter().map(|arg| arg.to_string_lossy());
+    while let Some(arg) = args.next() {
+        let name = if arg == "--release" {
+            Some("release".into())
+        } else if arg == "--profile" {
+            args.next().map(std::borrow::Cow::into_owned)
+        } else {
+            arg.strip_prefix("--profile=").map(Into::into)
+        };
+        if let Some(name) = name {
+            profile = if name == "dev" { "debug".into() } else { name };
+        }
+    }
+    profile
+}
+
+/// Measures the artifacts in `<target_dir>/<profile>` and under the `CMake` build directory `cmake_dir`.
+///
+/// Artifacts are libraries (`.rlib`, `.a`, `.lib`, `.so`, `.dylib`, `.dll`) and executables.
+///
+/// # Errors
+///
+/// Will return `Err` if a directory cannot be read.
+pub fn measure(target_dir: &Path, profile: &str, cmake_dir: &Path) -> BoxResult<BTreeMap<String, u64>> {
+    let mut sizes = BTreeMap::new();
+    if let Ok(entries) = std::fs::read_dir(target_dir.join(profile)) {
+        for entry in entries {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() && is_artifact(&entry.path(), &metadata) {
+                let key = format!("cargo:{}", entry.file_name().to_string_lossy());
+                sizes.insert(key, metadata.len());
+            }
+        }
+    }
+    if cmake_dir.is_dir() {
+        measure_cmake_dir(cmake_dir, cmake_dir, &mut sizes)?;
+    }
+    Ok(sizes)
+}
+
+fn measure_cmake_dir(root: &Path, dir: &Path, sizes: &mut BTreeMap<String, u64>) -> BoxResult<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            // NOTE: `CMakeFiles` holds object files and the compiler checks run during configuration
+            if entry.file_name() != "CMakeFiles" {
+                measure_cmake_dir(root, &path, sizes)?;
+            }
+        } else if is_artifact(&path, &metadata) {
+            let key = format!(
+                "cmake:{}",
+                path.strip_prefix(root)?.to_string_lossy().replace('\\', "/")
+            );
+            sizes.insert(key, metadata.len());
+        }
+    }
+    Ok(())
+}
+
+fn is_artifact(path: &Path, metadata: &std::fs::Metadata) -> bool {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("rlib" | "a" | "lib" | "so" | "dylib" | "dll" | "exe") => true,
+        Some(_) => false,
+        None => is_executable(metadata),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Measures the current artifacts of `profile` (see [`measure`]), along with the time and the checked out commit.
+///
+/// The `CMake` build directory is `<root>/build`, where `xtask cmake build` places it.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - A directory cannot be read
+/// - Determining the checked out commit fails
+pub fn record(runner: &dyn Runner, root: &Utf8Path, target_dir: &Path, profile: &str) -> BoxResult<Record> {
+    let sizes = measure(target_dir, profile, root.join("build").as_std_path())?;
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs();
+    let rev = crate::git::rev_parse(runner, root, "HEAD")?;
+    Ok(Record {
+        timestamp,
+        rev,
+        profile: profile.to_owned(),
+        sizes,
+    })
+}
+
+/// The file the size history is stored in.
+#[must_use]
+pub fn history_path(target_dir: &Path) -> PathBuf {
+    target_dir.join("xtask").join("size").join("history.json")
+}
+
+/// The file the named baseline `name` is stored in.
+#[must_use]
+pub fn baseline_path(target_dir: &Path, name: &str) -> PathBuf {
+    target_dir
+        .join("xtask")
+        .join("size")
+        .join("baselines")
+        .join(format!("{name}.json"))
+}
+
+/// Loads the size history, oldest record first.
+///
+/// # Errors
+///
+/// Will return `Err` if the history file exists but cannot be read or parsed.
+pub fn load_history(target_dir: &Path) -> BoxResult<Vec<Record>> {
+    let path = history_path(target_dir);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// Appends `record` to the size history.
+///
+/// # Errors
+///
+/// Will return `Err` if the history file cannot be read, parsed, or written.
+pub fn append_history(target_dir: &Path, record: Record) -> BoxResult<()> {
+    let mut history = load_history(target_dir)?;
+    history.push(record);
+    write(&history_path(target_dir), &history)
+}
+
+/// Loads the named baseline `name`.
+///
+/// # Errors
+///
+/// Will return `Err` if the baseline does not exist or cannot be read or parsed.
+pub fn load_baseline(target_dir: &Path, name: &str) -> BoxResult<Record> {
+    let path = baseline_path(target_dir, name);
+    let data = std::fs::read_to_string(&path).map_err(|err| {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            format!("no size baseline named `{name}`\nSave one with `xtask size --save-baseline {name}`")
+        } else {
+            format!("failed to read `{}`: {err}", path.display())
+        }
+    })?;
+    Ok(serde_json::from_str(&data)?)
+}
+
+/// Saves `record` as the named baseline `name`, replacing any previous one.
+///
+/// # Errors
+///
+/// Will return `Err` if the baseline file cannot be written.
+pub fn save_baseline(target_dir: &Path, name: &str, record: &Record) -> BoxResult<()> {
+    write(&baseline_path(target_dir, name), record)
+}
+
+fn write(path: &Path, value: &impl Serialize) -> BoxResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+/// Prints the artifact sizes of `current` with their change relative to `reference`, if any.
+pub fn print_report(current: &BTreeMap<String, u64>, reference: Option<&BTreeMap<String, u64>>) {
+    for (artifact, &size) in current {
+        match reference.and_then(|reference| reference.get(artifact)) {
+            #[allow(clippy::cast_possible_wrap)]
+            Some(&previous) => println!("{artifact}: {size} bytes ({:+} bytes)", size as i64 - previous as i64),
+            None => println!("{artifact}: {size} bytes (new)"),
+        }
+    }
+    if let Some(reference) = reference {
+        for artifact in reference.keys().filter(|artifact| !current.contains_key(*artifact)) {
+            println!("{artifact}: removed");
+        }
+    }
+}
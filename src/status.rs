@@ -0,0 +1,132 @@
+use crate::{config::Config, runner::Runner, BoxResult};
+use camino::Utf8Path;
+use serde::Serialize;
+use std::fmt::Write;
+
+/// The outcome of an xtask command, as written to the configured `xtask.status.dir`.
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Serialize)]
+pub struct Status {
+    /// The xtask subcommand (e.g. `check`)
+    pub command: String,
+    pub passing: bool,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// The commit checked out at the time, if the workspace is a git checkout
+    pub rev: Option<String>,
+    /// The percentage of lines covered, for coverage commands
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub coverage: Option<f64>,
+}
+
+/// Writes the status of `command` to `<dir>/<command>.json` if `xtask.status.dir` is configured, along with badges
+/// (`<dir>/<command>.svg`, and `<dir>/coverage.svg` given `coverage`) if `xtask.status.badges` is set.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Determining the checked out commit fails
+/// - A status or badge file cannot be written
+pub fn record(
+    config: &Config,
+    runner: &dyn Runner,
+    command: &str,
+    passing: bool,
+    coverage: Option<f64>,
+) -> BoxResult<()> {
+    let settings = &config.xtask.status;
+    let Some(dir) = &settings.dir else {
+        return Ok(());
+    };
+    let root = &config.cargo_metadata.workspace_root;
+    let dir = root.join(dir);
+    std::fs::create_dir_all(&dir).map_err(|err| format!("failed to create `{dir}`: {err}"))?;
+
+    let status = Status {
+        command: command.to_owned(),
+        passing,
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs(),
+        rev: crate::git::rev_parse(runner, root, "HEAD")?,
+        coverage,
+    };
+    write(
+        &dir.join(format!("{command}.json")),
+        &serde_json::to_string_pretty(&status)?,
+    )?;
+
+    if settings.badges {
+        let (message, color) = if passing {
+            ("passing", "#4c1")
+        } else {
+            ("failing", "#e05d44")
+        };
+        write(&dir.join(format!("{command}.svg")), &badge(command, message, color)?)?;
+        if let Some(coverage) = coverage {
+            let color = match coverage {
+                coverage if coverage >= 90.0 => "#4c1",
+                coverage if coverage >= 75.0 => "#a3c51c",
+                coverage if coverage >= 50.0 => "#dfb317",
+                _ => "#e05d44",
+            };
+            write(
+                &dir.join("coverage.svg"),
+                &badge("coverage", &format!("{coverage:.0}%"), color)?,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn write(path: &Utf8Path, contents: &str) -> BoxResult<()> {
+    std::fs::write(path, contents).map_err(|err| format!("failed to write `{path}`: {err}"))?;
+    Ok(())
+}
+
+/// A flat badge in the style of shields.io, reading `label` on grey and `message` on `color`.
+///
+/// # Errors
+///
+/// Will return `Err` if formatting the SVG fails.
+pub fn badge(label: &str, message: &str, color: &str) -> BoxResult<String> {
+    // NOTE: an approximation of the text width of 11px Verdana, which is what the shields.io badges use
+    let width = |text: &str| 10 + 7 * text.chars().count();
+    let (label_width, message_width) = (width(label), width(message));
+    let total_width = label_width + message_width;
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{total_width}" height="20" role="img" aria-label="{label}: {message}">"#
+    )?;
+    writeln!(svg, r##"<rect width="{label_width}" height="20" fill="#555"/>"##)?;
+    writeln!(
+        svg,
+        r#"<rect x="{label_width}" width="{message_width}" height="20" fill="{color}"/>"#
+    )?;
+    writeln!(
+        svg,
+        r##"<g fill="#fff" text-anchor="middle" font-family="Verdana,Geneva,DejaVu Sans,sans-serif" font-size="11">"##
+    )?;
+    writeln!(svg, r#"<text x="{}" y="14">{label}</text>"#, label_width / 2)?;
+    writeln!(
+        svg,
+        r#"<text x="{}" y="14">{message}</text>"#,
+        label_width + message_width / 2
+    )?;
+    writeln!(svg, "</g>\n</svg>")?;
+    Ok(svg)
+}
+
+/// The percentage of lines covered according to a Cobertura XML report (e.g. `cobertura.xml` from tarpaulin).
+#[must_use]
+pub fn cobertura_coverage(xml: &str) -> Option<f64> {
+    let line_rate = regex::Regex::new(r#"<coverage\b[^>]*\sline-rate="([0-9.]+)""#)
+        .ok()?
+        .captures(xml)?
+        .get(1)?
+        .as_str()
+        .parse::<f64>()
+        .ok()?;
+    Some(line_rate * 100.0)
+}
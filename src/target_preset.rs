@@ -0,0 +1,271 @@
+use crate::{
+    config::Xtask,
+    error::{Error, Kind},
+    runner::Runner,
+    BoxResult,
+};
+use camino::Utf8PathBuf;
+use std::process::Command;
+
+/// The platform of a [`TargetPreset`].
+pub enum Platform {
+    /// Android, built with the clang of the NDK
+    Android {
+        /// The `ANDROID_ABI` of the NDK's `CMake` toolchain file (e.g. `arm64-v8a`)
+        abi: &'static str,
+        /// The target prefix of the NDK's clang wrappers (e.g. `aarch64-linux-android` for
+        /// `aarch64-linux-android24-clang`)
+        clang_target: &'static str,
+    },
+    /// iOS, built with the SDK of Xcode
+    Ios {
+        /// The SDK name for `xcrun --sdk` and `CMAKE_OSX_SYSROOT` (`iphoneos` or `iphonesimulator`)
+        sdk: &'static str,
+        /// The `CMAKE_OSX_ARCHITECTURES` value
+        arch: &'static str,
+    },
+}
+
+/// A cross compilation target, selected with `--target-preset <NAME>`, which is applied to both the cargo `--target`
+/// and the `CMake` toolchain configuration.
+pub struct TargetPreset {
+    pub name: &'static str,
+    /// The Rust target triple passed to cargo as `--target`
+    pub triple: &'static str,
+    pub platform: Platform,
+}
+
+/// The built-in target presets.
+pub const TARGET_PRESETS: &[TargetPreset] = &[
+    TargetPreset {
+        name: "android-arm64",
+        triple: "aarch64-linux-android",
+        platform: Platform::Android {
+            abi: "arm64-v8a",
+            clang_target: "aarch64-linux-android",
+        },
+    },
+    TargetPreset {
+        name: "android-armv7",
+        triple: "armv7-linux-androideabi",
+        platform: Platform::Android {
+            abi: "armeabi-v7a",
+            clang_target: "armv7a-linux-androideabi",
+        },
+    },
+    TargetPreset {
+        name: "android-x86",
+        triple: "i686-linux-android",
+        platform: Platform::Android {
+            abi: "x86",
+            clang_target: "i686-linux-android",
+        },
+    },
+    TargetPreset {
+        name: "android-x86_64",
+        triple: "x86_64-linux-android",
+        platform: Platform::Android {
+            abi: "x86_64",
+            clang_target: "x86_64-linux-android",
+        },
+    },
+    TargetPreset {
+        name: "ios",
+        triple: "aarch64-apple-ios",
+        platform: Platform::Ios {
+            sdk: "iphoneos",
+            arch: "arm64",
+        },
+    },
+    TargetPreset {
+        name: "ios-sim",
+        triple: "aarch64-apple-ios-sim",
+        platform: Platform::Ios {
+            sdk: "iphonesimulator",
+            arch: "arm64",
+        },
+    },
+    TargetPreset {
+        name: "ios-sim-x86_64",
+        triple: "x86_64-apple-ios",
+        platform: Platform::Ios {
+            sdk: "iphonesimulator",
+            arch: "x86_64",
+        },
+    },
+];
+
+const DEFAULT_ANDROID_API_LEVEL: u32 = 24;
+const DEFAULT_IOS_DEPLOYMENT_TARGET: &str = "13.0";
+
+/// Looks up the built-in target preset `name`.
+///
+/// # Errors
+///
+/// Will return `Err` if there is no target preset named `name`.
+pub fn find(name: &str) -> BoxResult<&'static TargetPreset> {
+    TARGET_PRESETS.iter().find(|preset| preset.name == name).ok_or_else(|| {
+        let available = TARGET_PRESETS
+            .iter()
+            .map(|preset| preset.name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let message = format!("unknown target preset `{name}`: expected one of {available}");
+        Error::new(Kind::Validation, message).into()
+    })
+}
+
+/// The Android NDK: the configured `xtask.android.ndk`, or else `ANDROID_NDK_HOME` or `ANDROID_NDK_ROOT`.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - None of them is set
+/// - The directory does not contain the NDK's `CMake` toolchain file
+pub fn android_ndk(xtask: &Xtask) -> BoxResult<Utf8PathBuf> {
+    let ndk = xtask
+        .android
+        .ndk
+        .clone()
+        .or_else(|| std::env::var("ANDROID_NDK_HOME").ok().map(Utf8PathBuf::from))
+        .or_else(|| std::env::var("ANDROID_NDK_ROOT").ok().map(Utf8PathBuf::from))
+        .ok_or_else(|| {
+            let message =
+                "the Android NDK was not found\nSet `ANDROID_NDK_HOME` (or `xtask.android.ndk`) to its directory";
+            Error::new(Kind::ToolMissing, message)
+        })?;
+    if !ndk.join("build/cmake/android.toolchain.cmake").is_file() {
+        let message = format!(
+            "`{ndk}` is not an Android NDK (it has no `build/cmake/android.toolchain.cmake`)\nSet `ANDROID_NDK_HOME` \
+             (or `xtask.android.ndk`) to the directory of an NDK r19 or later"
+        );
+        return Err(Error::new(Kind::ToolMissing, message).into());
+    }
+    Ok(ndk)
+}
+
+/// Checks that the SDK for `preset` is available: the Android NDK (see [`android_ndk`]) and its clang, or the iOS SDK
+/// of Xcode.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The Android NDK is not found or has no clang for the configured API level
+/// - The iOS SDK is not found by `xcrun` (e.g. when not on macOS or without Xcode)
+pub fn validate(preset: &TargetPreset, xtask: &Xtask, runner: &dyn Runner) -> BoxResult<()> {
+    match preset.platform {
+        Platform::Android { .. } => {
+            let clang = android_clang(preset, xtask)?.ok_or("expected an Android target preset")?;
+            if !clang.is_file() {
+                let message = format!(
+                    "the Android NDK has no clang for API level {} of `{}`: `{clang}` does not exist\nSet \
+                     `xtask.android.apiLevel` to a level the NDK supports",
+                    android_api_level(xtask),
+                    preset.name
+                );
+                return Err(Error::new(Kind::ToolMissing, message).into());
+            }
+        },
+        Platform::Ios { sdk, .. } => {
+            let mut cmd = Command::new("xcrun");
+            cmd.args(["--sdk", sdk, "--show-sdk-path"]);
+            let found = runner.output(&mut cmd).map_or(false, |output| output.status.success());
+            if !found {
+                let message = format!(
+                    "the `{sdk}` SDK was not found by `xcrun`\nTarget preset `{}` requires macOS with Xcode installed \
+                     (select it with `xcode-select`)",
+                    preset.name
+                );
+                return Err(Error::new(Kind::ToolMissing, message).into());
+            }
+        },
+    }
+    Ok(())
+}
+
+/// The `CMake` defines configuring the build for `preset`.
+///
+/// # Errors
+///
+/// Will return `Err` if the Android NDK is not found (see [`android_ndk`]).
+pub fn cmake_defines(preset: &TargetPreset, xtask: &Xtask) -> BoxResult<Vec<String>> {
+    let defines = match preset.platform {
+        Platform::Android { abi, .. } => {
+            let ndk = android_ndk(xtask)?;
+            vec![
+                format!("-DCMAKE_TOOLCHAIN_FILE={ndk}/build/cmake/android.toolchain.cmake"),
+                format!("-DANDROID_ABI={abi}"),
+                format!("-DANDROID_PLATFORM=android-{}", android_api_level(xtask)),
+            ]
+        },
+        Platform::Ios { sdk, arch } => vec![
+            "-DCMAKE_SYSTEM_NAME=iOS".to_owned(),
+            format!("-DCMAKE_OSX_SYSROOT={sdk}"),
+            format!("-DCMAKE_OSX_ARCHITECTURES={arch}"),
+            format!("-DCMAKE_OSX_DEPLOYMENT_TARGET={}", ios_deployment_target(xtask)),
+        ],
+    };
+    Ok(defines)
+}
+
+/// The environment variables pointing cargo and the `cc` crate (and so `cxx-build`) at the compilers for `preset`.
+///
+/// For Android, these are the linker and the C and C++ compilers of the NDK for the configured API level. For iOS,
+/// only the deployment target is set, since the compilers are found with `xcrun`.
+///
+/// # Errors
+///
+/// Will return `Err` if the Android NDK is not found (see [`android_ndk`]).
+pub fn cargo_env(preset: &TargetPreset, xtask: &Xtask) -> BoxResult<Vec<(String, String)>> {
+    let Some(clang) = android_clang(preset, xtask)? else {
+        return Ok(vec![(
+            "IPHONEOS_DEPLOYMENT_TARGET".to_owned(),
+            ios_deployment_target(xtask).to_owned(),
+        )]);
+    };
+    let bin = clang.parent().map(ToOwned::to_owned).unwrap_or_default();
+    let clangxx = Utf8PathBuf::from(clang.as_str().replacen("-clang", "-clang++", 1));
+    let triple = preset.triple.replace('-', "_");
+    Ok(vec![
+        (
+            format!("CARGO_TARGET_{}_LINKER", triple.to_uppercase()),
+            clang.to_string(),
+        ),
+        (format!("CC_{triple}"), clang.to_string()),
+        (format!("CXX_{triple}"), clangxx.to_string()),
+        (format!("AR_{triple}"), bin.join("llvm-ar").to_string()),
+    ])
+}
+
+// The clang wrapper of the NDK for `preset` at the configured API level, or `None` if `preset` is not for Android.
+fn android_clang(preset: &TargetPreset, xtask: &Xtask) -> BoxResult<Option<Utf8PathBuf>> {
+    let Platform::Android { clang_target, .. } = preset.platform else {
+        return Ok(None);
+    };
+    let ndk = android_ndk(xtask)?;
+    let host = if cfg!(target_os = "macos") {
+        // NOTE: the NDK ships universal binaries for macOS under the `darwin-x86_64` name
+        "darwin-x86_64"
+    } else if cfg!(windows) {
+        "windows-x86_64"
+    } else {
+        "linux-x86_64"
+    };
+    let extension = if cfg!(windows) { ".cmd" } else { "" };
+    let api_level = android_api_level(xtask);
+    Ok(Some(ndk.join(format!(
+        "toolchains/llvm/prebuilt/{host}/bin/{clang_target}{api_level}-clang{extension}"
+    ))))
+}
+
+fn android_api_level(xtask: &Xtask) -> u32 {
+    xtask.android.api_level.unwrap_or(DEFAULT_ANDROID_API_LEVEL)
+}
+
+fn ios_deployment_target(xtask: &Xtask) -> &str {
+    xtask
+        .ios
+        .deployment_target
+        .as_deref()
+        .unwrap_or(DEFAULT_IOS_DEPLOYMENT_TARGET)
+}
@@ -0,0 +1,65 @@
+use crate::config::{CMakeContext, Config};
+
+/// The workspace root of the configs of [`config`].
+pub const ROOT: &str = "/work";
+
+/// A config for a workspace at [`ROOT`] without packages, with the xtask config `xtask` (as in `xtask.json`).
+pub fn config(xtask: serde_json::Value) -> Config {
+    let cargo_metadata = serde_json::from_value(serde_json::json!({
+        "packages": [],
+        "workspace_members": [],
+        "resolve": null,
+        "workspace_root": ROOT,
+        "target_directory": format!("{ROOT}/target"),
+        "version": 1,
+    }))
+    .unwrap();
+    Config {
+        cmake_context: CMakeContext {
+            bin_clang_format: "clang-format".into(),
+            bin_clang_tidy: "clang-tidy".into(),
+            bin_run_clang_format: "run-clang-format".into(),
+            bin_run_clang_tidy: "run-clang-tidy".into(),
+        },
+        cargo_metadata,
+        rust_toolchain: None,
+        xtask: serde_json::from_value(xtask).unwrap(),
+    }
+}
+
+/// Adds workspace members named `names` to `config`, each with its manifest at `<ROOT>/<name>/Cargo.toml`.
+pub fn add_members(config: &mut Config, names: &[&str]) {
+    for name in names {
+        let package = serde_json::from_value::<cargo_metadata::Package>(serde_json::json!({
+            "name": name,
+            "version": "0.1.0",
+            "id": format!("{name} 0.1.0 (path+file://{ROOT}/{name})"),
+            "dependencies": [],
+            "targets": [],
+            "features": {},
+            "manifest_path": format!("{ROOT}/{name}/Cargo.toml"),
+        }))
+        .unwrap();
+        config.cargo_metadata.workspace_members.push(package.id.clone());
+        config.cargo_metadata.packages.push(package);
+    }
+}
+
+/// The output of a process exiting with `code` after printing `stdout`, for the handler of a
+/// [`crate::runner::MockRunner`].
+pub fn output(code: i32, stdout: &str) -> std::process::Output {
+    std::process::Output {
+        status: crate::runner::exit_status(code),
+        stdout: stdout.into(),
+        stderr: vec![],
+    }
+}
+
+/// The program of `cmd` followed by its arguments, separated by spaces.
+pub fn command_line(cmd: &std::process::Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,222 @@
+use crate::BoxResult;
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{
+    fmt::Write,
+    process::Command,
+    sync::Mutex,
+    thread::ThreadId,
+    time::{Duration, Instant},
+};
+
+// The recorder enabled by `--timings`, if any (see `enable`).
+static RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+
+// The width of the bars of the summary, for a span as long as the longest top-level one.
+const BAR_WIDTH: usize = 20;
+
+// The longest command line shown in the summary and trace before it is cut short.
+const LABEL_WIDTH: usize = 100;
+
+/// The kind of a recorded span of time.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    /// A run of an `xtask` subcommand, including those run by aggregate commands (e.g. `check-all`)
+    Subcommand,
+    /// A tool probed for its version (or installed toolchains) during validation
+    Probe,
+    /// An external command run by the xtask
+    Command,
+    /// A `pre` or `post` hook of a subcommand (see `xtask.hooks`)
+    Hook,
+}
+
+impl Category {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Subcommand => "subcommand",
+            Category::Probe => "probe",
+            Category::Command => "command",
+            Category::Hook => "hook",
+        }
+    }
+}
+
+// A span of time spent on something, relative to when recording started.
+struct Span {
+    category: Category,
+    name: String,
+    thread: usize,
+    start: Duration,
+    duration: Duration,
+}
+
+struct Recorder {
+    started: Instant,
+    trace: Option<Utf8PathBuf>,
+    threads: Vec<ThreadId>,
+    spans: Vec<Span>,
+}
+
+/// Starts recording timings (unless already started), to be reported by [`finish`], and written as a Chrome trace to
+/// `trace` if given.
+pub fn enable(trace: Option<Utf8PathBuf>) {
+    if let Ok(mut recorder) = RECORDER.lock() {
+        let recorder = recorder.get_or_insert_with(|| Recorder {
+            started: Instant::now(),
+            trace: None,
+            threads: vec![],
+            spans: vec![],
+        });
+        recorder.trace = recorder.trace.take().or(trace);
+    }
+}
+
+/// Runs `f`, recording the time it takes as a span named `name` if timings are enabled.
+pub fn record<T>(category: Category, name: impl FnOnce() -> String, f: impl FnOnce() -> T) -> T {
+    let enabled = RECORDER.lock().map_or(false, |recorder| recorder.is_some());
+    if !enabled {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    let duration = start.elapsed();
+    if let Ok(mut recorder) = RECORDER.lock() {
+        if let Some(recorder) = recorder.as_mut() {
+            let id = std::thread::current().id();
+            let thread = recorder
+                .threads
+                .iter()
+                .position(|thread| *thread == id)
+                .unwrap_or_else(|| {
+                    recorder.threads.push(id);
+                    recorder.threads.len() - 1
+                });
+            recorder.spans.push(Span {
+                category,
+                name: name(),
+                thread,
+                start: start.saturating_duration_since(recorder.started),
+                duration,
+            });
+        }
+    }
+    result
+}
+
+/// The name of the span of `cmd`: its command line, cut short if it is long.
+#[must_use]
+pub fn command_name(cmd: &Command) -> String {
+    let program = cmd.get_program().to_string_lossy();
+    let program = Utf8Path::new(&program).file_name().unwrap_or(&program).to_owned();
+    let mut name = std::iter::once(program)
+        .chain(cmd.get_args().map(|arg| arg.to_string_lossy().into_owned()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if name.chars().count() > LABEL_WIDTH {
+        name = name.chars().take(LABEL_WIDTH - 3).collect::<String>() + "...";
+    }
+    name
+}
+
+/// Stops recording timings and, if they were enabled, prints a summary of the recorded spans and writes the Chrome
+/// trace (if requested).
+///
+/// The summary lists the spans in the order they started, each indented below the span it ran within (on the same
+/// thread) with a bar for its share of the longest span, like a flame graph turned on its side, and ends with the
+/// total time of each category.
+///
+/// # Errors
+///
+/// Will return `Err` if the trace cannot be written.
+pub fn finish() -> BoxResult<()> {
+    let Some(mut recorder) = RECORDER.lock().ok().and_then(|mut recorder| recorder.take()) else {
+        return Ok(());
+    };
+    // NOTE: a span is recorded when it ends, so an enclosing span comes after the spans within it
+    recorder
+        .spans
+        .sort_by(|lhs, rhs| (lhs.start, rhs.duration).cmp(&(rhs.start, lhs.duration)));
+    println!("{}", summary(&recorder.spans)?);
+    if let Some(path) = &recorder.trace {
+        let data = serde_json::to_string(&trace(&recorder.spans))?;
+        std::fs::write(path, data).map_err(|err| format!("failed to write `{path}`: {err}"))?;
+        println!("wrote the Chrome trace of the timings to `{path}` (open it in `chrome://tracing` or Perfetto)");
+    }
+    Ok(())
+}
+
+// The summary of `spans` (sorted by start), as described for `finish`.
+fn summary(spans: &[Span]) -> BoxResult<String> {
+    let longest = spans.iter().map(|span| span.duration).max().unwrap_or_default();
+    let mut summary = String::from("\nTimings:\n");
+    let mut open = Vec::<&Span>::new();
+    for span in spans {
+        let end = span.start + span.duration;
+        open.retain(|outer| outer.thread != span.thread || outer.start + outer.duration >= end);
+        let mut enclosing = open.iter().filter(|outer| outer.thread == span.thread);
+        // NOTE: the command of a probe is left out, since the probe is named after it
+        if span.category == Category::Command
+            && enclosing
+                .clone()
+                .last()
+                .map_or(false, |outer| outer.category == Category::Probe)
+        {
+            continue;
+        }
+        let depth = enclosing.by_ref().count();
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_precision_loss,
+            clippy::cast_sign_loss
+        )]
+        let bar = if longest.is_zero() {
+            0
+        } else {
+            ((span.duration.as_secs_f64() / longest.as_secs_f64()) * BAR_WIDTH as f64).ceil() as usize
+        };
+        writeln!(
+            summary,
+            "{:>9.3}s  {:<BAR_WIDTH$}  {}{} {}",
+            span.duration.as_secs_f64(),
+            "#".repeat(bar),
+            "  ".repeat(depth),
+            span.category.as_str(),
+            span.name
+        )?;
+        open.push(span);
+    }
+    writeln!(summary)?;
+    for category in [Category::Subcommand, Category::Hook, Category::Probe, Category::Command] {
+        let spans = spans.iter().filter(|span| span.category == category);
+        let (count, total) = spans.fold((0, Duration::ZERO), |(count, total), span| {
+            (count + 1, total + span.duration)
+        });
+        writeln!(
+            summary,
+            "{:>9.3}s  in {count} {}s",
+            total.as_secs_f64(),
+            category.as_str()
+        )?;
+    }
+    Ok(summary)
+}
+
+// The Chrome trace (in the Trace Event Format) of `spans`, with a complete event per span.
+fn trace(spans: &[Span]) -> serde_json::Value {
+    let events = spans
+        .iter()
+        .map(|span| {
+            serde_json::json!({
+                "name": span.name,
+                "cat": span.category.as_str(),
+                "ph": "X",
+                "ts": u64::try_from(span.start.as_micros()).unwrap_or(u64::MAX),
+                "dur": u64::try_from(span.duration.as_micros()).unwrap_or(u64::MAX),
+                "pid": std::process::id(),
+                "tid": span.thread,
+            })
+        })
+        .collect::<Vec<_>>();
+    serde_json::json!({ "traceEvents": events, "displayTimeUnit": "ms" })
+}
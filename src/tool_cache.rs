@@ -0,0 +1,160 @@
+use crate::BoxResult;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::UNIX_EPOCH};
+
+/// The validated version of a tool, as stored in the [`ToolCache`].
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Entry {
+    /// The binary the program resolved to (see [`locate`])
+    pub path: Utf8PathBuf,
+    /// The version the tool reported
+    pub version: String,
+    /// The modification time of the binary, in nanoseconds since the Unix epoch
+    pub mtime: u64,
+    /// The version requirement the version was validated against
+    pub requirement: String,
+}
+
+/// The versions of the tools validated by previous xtask invocations, keyed by the program run for each tool (see
+/// [`crate::validation::Validation::with_cache`]).
+///
+/// An entry is only used while its binary is unchanged (by path and modification time) and its requirement is still
+/// the configured one, so that repeated invocations skip re-probing the tools without missing an upgrade or a config
+/// change.
+#[cfg_attr(feature = "debug", derive(Debug))]
+#[derive(Default, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct ToolCache {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl ToolCache {
+    /// The path of the cache file under the target directory `target_dir`.
+    #[must_use]
+    pub fn path(target_dir: &Utf8Path) -> Utf8PathBuf {
+        target_dir.join("xtask").join("tools.json")
+    }
+
+    /// Loads the cache file at `path`, or an empty cache if it is missing or malformed (e.g. written by another
+    /// version of the xtask).
+    #[must_use]
+    pub fn load(path: &Utf8Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// Saves the cache to the file at `path`, creating its directory if needed.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the file cannot be written.
+    pub fn save(&self, path: &Utf8Path) -> BoxResult<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The cached version of `program`, if it was validated against `requirement` and its binary is unchanged since.
+    #[must_use]
+    pub fn get(&self, program: &str, requirement: &str) -> Option<&str> {
+        let entry = self.entries.get(program)?;
+        if entry.requirement != requirement {
+            return None;
+        }
+        let path = locate(program)?;
+        (path == entry.path && mtime(&path)? == entry.mtime).then_some(entry.version.as_str())
+    }
+
+    /// Records that `program` reported `version`, which was validated against `requirement`. Nothing is recorded if
+    /// the binary of `program` cannot be located.
+    pub fn insert(&mut self, program: &str, version: &str, requirement: &str) {
+        let Some(path) = locate(program) else {
+            return;
+        };
+        let Some(mtime) = mtime(&path) else {
+            return;
+        };
+        let entry = Entry {
+            path,
+            version: version.to_owned(),
+            mtime,
+            requirement: requirement.to_owned(),
+        };
+        self.entries.insert(program.to_owned(), entry);
+    }
+}
+
+/// The binary `program` resolves to: `program` itself if it is a path, or else the first match in `PATH` (with an
+/// `.exe` extension on Windows).
+#[must_use]
+pub fn locate(program: &str) -> Option<Utf8PathBuf> {
+    let path = Utf8Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_owned());
+    }
+    let name = if cfg!(windows) && path.extension().is_none() {
+        format!("{program}.exe")
+    } else {
+        program.to_owned()
+    };
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .filter_map(|dir| Utf8PathBuf::from_path_buf(dir).ok())
+        .map(|dir| dir.join(&name))
+        .find(|path| path.is_file())
+}
+
+// The modification time of `path`, in nanoseconds since the Unix epoch.
+fn mtime(path: &Utf8Path) -> Option<u64> {
+    let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+    modified.duration_since(UNIX_EPOCH).ok()?.as_nanos().try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ToolCache;
+
+    #[test]
+    fn entries_are_invalidated_by_a_new_binary_or_requirement() {
+        let dir = camino::Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join("xtask-test-tool-cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        let tool = dir.join("clang-format");
+        std::fs::write(&tool, "").unwrap();
+        let path = ToolCache::path(&dir);
+
+        let mut cache = ToolCache::load(&path);
+        assert_eq!(cache.get(tool.as_str(), "17"), None);
+        cache.insert(tool.as_str(), "17.0.6", "17");
+        cache.save(&path).unwrap();
+
+        let mut cache = ToolCache::load(&path);
+        assert_eq!(cache.get(tool.as_str(), "17"), Some("17.0.6"));
+        assert_eq!(cache.get(tool.as_str(), "18"), None);
+
+        // As if the binary was replaced since.
+        cache.entries.get_mut(tool.as_str()).unwrap().mtime -= 1;
+        let found = cache.get(tool.as_str(), "17").map(str::to_owned);
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn a_malformed_cache_file_is_empty() {
+        let dir = camino::Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join("xtask-test-malformed-tool-cache");
+        let path = ToolCache::path(&dir);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "{").unwrap();
+        let cache = ToolCache::load(&path);
+        std::fs::remove_dir_all(&dir).ok();
+        assert!(cache.entries.is_empty());
+    }
+}
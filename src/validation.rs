@@ -0,0 +1,1152 @@
+use crate::{
+    config::{Config, Xtask, XtaskClang, XtaskTool},
+    error::{Error, Kind},
+    runner::Runner,
+    timings::Category,
+    tool_cache::ToolCache,
+    BoxError,
+    BoxResult,
+};
+use camino::Utf8PathBuf;
+use std::{cell::RefCell, collections::BTreeMap, fmt, process::Command};
+
+/// The built-in matchers of the versions of the clang tools, keyed by tool name, which `xtask.clang.matchers` can
+/// override (see [`clang_matchers`]).
+///
+/// These match the banners of LLVM releases and of vendor builds (e.g. `Ubuntu clang version 14.0.0-1ubuntu1`,
+/// `Homebrew clang version 17.0.6`), where clang-tidy reports the version of LLVM (e.g. `LLVM version 17.0.6`).
+pub const CLANG_MATCHERS: &[(&str, &str)] = &[
+    ("clang", r"\bclang version (\d+(?:\.\d+)*)"),
+    ("clang++", r"\bclang version (\d+(?:\.\d+)*)"),
+    ("clangd", r"\bclangd version (\d+(?:\.\d+)*)"),
+    ("clang-format", r"\bclang-format version (\d+(?:\.\d+)*)"),
+    ("clang-tidy", r"\bLLVM version (\d+(?:\.\d+)*)"),
+];
+
+/// A tool the xtask commands run, for referring to it by a checked name rather than by its program name.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    ClangFormat,
+    ClangTidy,
+    /// The clang driver (`clang++`) matching `clang-tidy` (see [`crate::detection::clang_driver`])
+    ClangDriver,
+    RunClangFormat,
+    RunClangTidy,
+    Cmake,
+    Ninja,
+    CmakeFormat,
+    CmakeLint,
+    Gersemi,
+}
+
+impl Tool {
+    /// All the tools.
+    pub const ALL: &'static [Tool] = &[
+        Tool::ClangFormat,
+        Tool::ClangTidy,
+        Tool::ClangDriver,
+        Tool::RunClangFormat,
+        Tool::RunClangTidy,
+        Tool::Cmake,
+        Tool::Ninja,
+        Tool::CmakeFormat,
+        Tool::CmakeLint,
+        Tool::Gersemi,
+    ];
+
+    /// The program name of the tool, which is also its key in `xtask.tools`.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::ClangFormat => "clang-format",
+            Tool::ClangTidy => "clang-tidy",
+            Tool::ClangDriver => "clang++",
+            Tool::RunClangFormat => "run-clang-format",
+            Tool::RunClangTidy => "run-clang-tidy",
+            Tool::Cmake => "cmake",
+            Tool::Ninja => "ninja",
+            Tool::CmakeFormat => "cmake-format",
+            Tool::CmakeLint => "cmake-lint",
+            Tool::Gersemi => "gersemi",
+        }
+    }
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A tool resolved (and validated) by [`Validation::tool`].
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct ResolvedTool {
+    pub tool: Tool,
+    /// The program to run: the path found by `CMake` or configured in `xtask.tools.paths`, or else a program name to be
+    /// searched for in `PATH`
+    pub path: Utf8PathBuf,
+    /// The version the tool reported, if it was probed for a configured requirement (see [`Validation::tool`])
+    pub version: Option<String>,
+    /// The environment variables the xtask sets for the tool (e.g. those of `xtask.env`)
+    pub env: BTreeMap<String, String>,
+}
+
+/// Resolves the tools of a config, validating their versions against the configured requirements.
+pub struct Validation<'a> {
+    config: &'a Config,
+    runner: &'a dyn Runner,
+    command: Box<dyn Fn(&str) -> Command + 'a>,
+    cache: Option<(Utf8PathBuf, RefCell<ToolCache>)>,
+}
+
+impl<'a> Validation<'a> {
+    /// Resolves the tools of `config`, probing them with `runner` in the environment of `xtask.env`.
+    ///
+    /// As for [`validate_clang_tool`], this does not depend on an xtask command context, so it can be used from the
+    /// build script of a downstream crate. The commands use [`crate::command::Context::validation`] instead.
+    #[must_use]
+    pub fn new(config: &'a Config, runner: &'a dyn Runner) -> Self {
+        let xtask = &config.xtask;
+        Self::with_command(config, runner, move |program| {
+            let mut cmd = Command::new(&*xtask.tool_path(program));
+            cmd.envs(&xtask.env);
+            cmd
+        })
+    }
+
+    /// Resolves the tools of `config`, probing them with `runner` in the environment of the commands `command` creates
+    /// for a program.
+    #[must_use]
+    pub fn with_command(config: &'a Config, runner: &'a dyn Runner, command: impl Fn(&str) -> Command + 'a) -> Self {
+        Validation {
+            config,
+            runner,
+            command: Box::new(command),
+            cache: None,
+        }
+    }
+
+    /// Keeps the versions of the tools validated in the [`ToolCache`] file at `path`, to skip probing them again while
+    /// they and their requirements are unchanged (see [`Validation::tool`]).
+    ///
+    /// The cache is loaded now and saved whenever a tool is probed. A failure to save it is reported as a warning.
+    #[must_use]
+    pub fn with_cache(mut self, path: Utf8PathBuf) -> Self {
+        let cache = ToolCache::load(&path);
+        self.cache = Some((path, RefCell::new(cache)));
+        self
+    }
+
+    /// The program to run for `tool`, without validating it (see [`ResolvedTool::path`]).
+    #[must_use]
+    pub fn path(&self, tool: Tool) -> Utf8PathBuf {
+        let cmake = &self.config.cmake_context;
+        match tool {
+            Tool::ClangFormat => cmake.bin_clang_format.clone(),
+            Tool::ClangTidy => cmake.bin_clang_tidy.clone(),
+            Tool::ClangDriver => crate::detection::clang_driver(&cmake.bin_clang_tidy),
+            Tool::RunClangFormat => cmake.bin_run_clang_format.clone(),
+            Tool::RunClangTidy => cmake.bin_run_clang_tidy.clone(),
+            _ => self.config.xtask.tool_path(tool.name()).as_ref().into(),
+        }
+    }
+
+    /// Resolves `tool`, checking its version against the configured requirement, if any: `xtask.clang.version` for
+    /// the clang tools, and `xtask.tools.<name>.version` (and `xtask.cmake.version` for cmake) for the others. The
+    /// tool is only probed for its version if there is a requirement, and the `run-clang-*` scripts, which do not
+    /// report one, never are. With a tool cache (see [`Validation::with_cache`]), the version of an unchanged tool is
+    /// not probed again.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the following circumstances:
+    /// - Probing the tool for its version fails (see [`version_banner`])
+    /// - The version does not match the requirement or cannot be determined
+    /// - A clang tool is ambiguous and `xtask.clang.strictTools` is set (see [`check_clang_ambiguity`])
+    pub fn tool(&self, tool: Tool) -> BoxResult<ResolvedTool> {
+        let path = self.path(tool);
+        let program = path.as_str();
+        let version = match self.requirement(tool, program) {
+            Some(requirement) => Some(self.cached_version(tool, program, &requirement)?),
+            None => None,
+        };
+        let env = (self.command)(program)
+            .get_envs()
+            .filter_map(|(name, value)| Some((name.to_str()?.to_owned(), value?.to_str()?.to_owned())))
+            .collect();
+        Ok(ResolvedTool {
+            tool,
+            path,
+            version,
+            env,
+        })
+    }
+
+    // The version requirement of `tool` (run as `program`), if any, as described for `Validation::tool`. This also
+    // keys the entries of the tool cache, so that they are invalidated when the requirement changes.
+    fn requirement(&self, tool: Tool, program: &str) -> Option<String> {
+        let xtask = &self.config.xtask;
+        match tool {
+            Tool::RunClangFormat | Tool::RunClangTidy => None,
+            Tool::ClangFormat | Tool::ClangTidy | Tool::ClangDriver => {
+                let clang = &xtask.clang;
+                (!clang.version.is_empty()).then(|| {
+                    let apple = if clang.allow_apple_clang {
+                        " (or Apple clang)"
+                    } else {
+                        ""
+                    };
+                    format!("clang {}{apple}", clang.version)
+                })
+            },
+            _ => {
+                let minimum = (tool == Tool::Cmake)
+                    .then_some(xtask.cmake.version.as_deref())
+                    .flatten();
+                let configured = configured_tool(xtask, program).and_then(|config| config.version.as_deref());
+                let requirement = [minimum, configured]
+                    .into_iter()
+                    .flatten()
+                    .map(|version| format!(">={version}"))
+                    .collect::<Vec<_>>();
+                (!requirement.is_empty()).then(|| requirement.join(", "))
+            },
+        }
+    }
+
+    // The version of `tool` (run as `program`) validated against `requirement`, from the tool cache if it has a valid
+    // entry, or else by probing the tool, recording the result in the tool cache (if any).
+    fn cached_version(&self, tool: Tool, program: &str, requirement: &str) -> BoxResult<String> {
+        let Some((path, cache)) = &self.cache else {
+            return self.version(tool, program);
+        };
+        if let Some(version) = cache.borrow().get(program, requirement) {
+            return Ok(version.to_owned());
+        }
+        let version = self.version(tool, program)?;
+        let mut cache = cache.borrow_mut();
+        cache.insert(program, &version, requirement);
+        if let Err(err) = cache.save(path) {
+            crate::handler::status(format_args!("warning: failed to write the tool cache `{path}`: {err}"));
+        }
+        Ok(version)
+    }
+
+    // Probes `tool` (run as `program`) for its version and checks it against its requirement.
+    fn version(&self, tool: Tool, program: &str) -> BoxResult<String> {
+        let xtask = &self.config.xtask;
+        let banner = self.banner(program)?;
+        if let Tool::ClangFormat | Tool::ClangTidy | Tool::ClangDriver = tool {
+            check_clang_version(&xtask.clang, program, &banner)?;
+            let found = parse_clang_version(&xtask.clang, program, &banner)?;
+            check_clang_ambiguity(xtask, program, found, |alternative| self.banner(alternative))?;
+            return Ok(found.to_owned());
+        }
+        if tool == Tool::Cmake {
+            if let Some(minimum) = &xtask.cmake.version {
+                check_minimum_version(xtask, program, minimum, &banner)?;
+            }
+        }
+        check_configured_version(xtask, program, &banner)?;
+        Ok(parse_tool_version(xtask, program, &banner)?.to_owned())
+    }
+
+    /// Runs `program` with its probe argument (see [`Xtask::tool_probe`]), returning its version banner as described
+    /// for [`version_banner`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` under the same circumstances as [`version_banner`].
+    pub fn banner(&self, program: &str) -> BoxResult<String> {
+        probe_banner(&self.config.xtask, self.runner, program, (self.command)(program))
+    }
+}
+
+/// A tool that failed validation with [`validate_tools`].
+#[cfg_attr(feature = "debug", derive(Debug))]
+pub struct ToolError {
+    pub tool: Tool,
+    pub error: BoxError,
+}
+
+/// Resolves each of `tools` with `validation` (see [`Validation::tool`]), carrying on past the failures so that a
+/// command needing several tools can report them all at once (see [`tool_errors`]).
+///
+/// Returns the tools resolved, in the order of `tools`, and those that failed.
+#[must_use]
+pub fn validate_tools(validation: &Validation<'_>, tools: &[Tool]) -> (Vec<ResolvedTool>, Vec<ToolError>) {
+    let mut resolved = vec![];
+    let mut errors = vec![];
+    for &tool in tools {
+        match validation.tool(tool) {
+            Ok(found) => resolved.push(found),
+            Err(error) => errors.push(ToolError { tool, error }),
+        }
+    }
+    (resolved, errors)
+}
+
+/// Checks that `errors` (from [`validate_tools`]) is empty.
+///
+/// # Errors
+///
+/// Will return `Err` listing every one of `errors`, of the kind of the first (e.g. `Kind::ToolMissing`).
+pub fn tool_errors(mut errors: Vec<ToolError>) -> BoxResult<()> {
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0).error),
+        count => {
+            let kind = crate::error::kind(&*errors[0].error);
+            let message = errors
+                .iter()
+                .map(|error| format!("- {}: {}", error.tool, error.error.to_string().replace('\n', "\n  ")))
+                .collect::<Vec<_>>()
+                .join("\n");
+            Err(Error::new(kind, format!("{count} tools failed validation:\n{message}")).into())
+        },
+    }
+}
+
+/// Runs `tool` (or its path in `xtask.tools.paths`, see [`Xtask::tool_path`]) with its probe argument (see
+/// [`Xtask::tool_probe`]) and the configured `xtask.env`, returning its version banner.
+///
+/// The banner includes stderr after stdout, since some builds and wrappers print it to stderr instead.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The command process fails to start
+/// - The command invocation fails with non-zero exit status
+/// - The command invocation fails to produce valid UTF-8 output
+pub fn version_banner(xtask: &Xtask, runner: &dyn Runner, tool: &str) -> BoxResult<String> {
+    let mut cmd = Command::new(&*xtask.tool_path(tool));
+    cmd.envs(&xtask.env);
+    probe_banner(xtask, runner, tool, cmd)
+}
+
+// Runs `cmd` (the command for the program `tool`) with the probe argument of `tool`, returning its version banner as
+// described for `version_banner`.
+fn probe_banner(xtask: &Xtask, runner: &dyn Runner, tool: &str, mut cmd: Command) -> BoxResult<String> {
+    let probe = xtask.tool_probe(tool);
+    xtask.c_locale(&mut cmd);
+    cmd.arg(probe);
+    let name = || format!("{tool} {probe}");
+    let output = crate::timings::record(Category::Probe, name, || runner.output(&mut cmd)).map_err(|err| {
+        Error::new(
+            crate::error::kind(&err),
+            format!("failed to run `{tool} {probe}`: {err}"),
+        )
+    })?;
+    if !output.status.success() {
+        return Err(format!("`{tool} {probe}` failed with non-zero exit code").into());
+    }
+    Ok(String::from_utf8([output.stdout, output.stderr].concat())?)
+}
+
+/// The message of the error for running `rustup` when it is not installed.
+pub const RUSTUP_MISSING: &str =
+    "`rustup` was not found\nInstall it from https://rustup.rs, or set the toolchain manually";
+
+/// The error for failing to run the `rustup` command `command_line` with `err`: one of `Kind::ToolMissing` with
+/// [`RUSTUP_MISSING`] if rustup is not installed.
+#[must_use]
+pub fn rustup_error(command_line: &str, err: &std::io::Error) -> Error {
+    if err.kind() == std::io::ErrorKind::NotFound {
+        Error::new(Kind::ToolMissing, RUSTUP_MISSING)
+    } else {
+        Error::new(
+            crate::error::kind(err),
+            format!("failed to run `{command_line}`: {err}"),
+        )
+    }
+}
+
+/// The first of the acceptable toolchains `candidates` (in order of preference) that is installed with rustup.
+///
+/// A single candidate is returned as is, without checking, since running it reports a missing toolchain anyway.
+/// Toolchains are matched by their full name (e.g. `nightly-2023-08-08-x86_64-unknown-linux-gnu`) or the name without
+/// the host triple (e.g. `nightly-2023-08-08`).
+///
+/// With `pinned` (a `rust-toolchain.toml` selecting the toolchain), a missing rustup only skips the check with a
+/// warning, returning the first candidate.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The `rustup toolchain list` command process fails to start (including rustup not being installed, unless
+///   `pinned`)
+/// - The command invocation fails with non-zero exit status
+/// - None of `candidates` is installed
+pub fn first_installed_toolchain<'a>(
+    xtask: &Xtask,
+    runner: &dyn Runner,
+    candidates: &[&'a str],
+    pinned: bool,
+) -> BoxResult<&'a str> {
+    let [first, ..] = candidates else {
+        return Ok("nightly");
+    };
+    if candidates.len() == 1 {
+        return Ok(first);
+    }
+    let mut cmd = Command::new(&*xtask.tool_path("rustup"));
+    cmd.envs(&xtask.env);
+    cmd.args(["toolchain", "list"]);
+    let name = || "rustup toolchain list".into();
+    let output = match crate::timings::record(Category::Probe, name, || runner.output(&mut cmd)) {
+        Ok(output) => output,
+        Err(err) if pinned && err.kind() == std::io::ErrorKind::NotFound => {
+            crate::handler::status(format_args!(
+                "warning: `rustup` was not found, so the installed toolchains are not checked; using `{first}`"
+            ));
+            return Ok(first);
+        },
+        Err(err) => return Err(rustup_error("rustup toolchain list", &err).into()),
+    };
+    if !output.status.success() {
+        return Err("`rustup toolchain list` failed with non-zero exit code".into());
+    }
+    let stdout = String::from_utf8(output.stdout)?;
+    let installed = stdout
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .collect::<Vec<_>>();
+    let host = crate::version::TARGET;
+    candidates
+        .iter()
+        .find(|candidate| {
+            installed
+                .iter()
+                .any(|toolchain| toolchain == *candidate || *toolchain == format!("{candidate}-{host}"))
+        })
+        .copied()
+        .ok_or_else(|| {
+            let message = format!(
+                "none of the acceptable toolchains is installed: {}\nInstall one with `rustup toolchain install {first}`",
+                candidates.join(", ")
+            );
+            Error::new(Kind::ToolMissing, message).into()
+        })
+}
+
+/// Checks that the cargo subcommand of the rustup component `component` (e.g. `clippy`) runs with `toolchain`, by
+/// running `probe` (e.g. `cargo +<toolchain> clippy --version`).
+///
+/// A failing probe is usually a component missing from that toolchain, which `rustup component list` tells apart from
+/// other failures to report it with the command adding the component.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The probe process fails to start
+/// - The probe fails with non-zero exit status, where `Kind::ToolMissing` means the component is not installed for
+///   `toolchain`
+pub fn validate_cargo_component(
+    xtask: &Xtask,
+    runner: &dyn Runner,
+    component: &str,
+    toolchain: &str,
+    mut probe: Command,
+) -> BoxResult<()> {
+    let command_line = crate::timings::command_name(&probe);
+    let name = || command_line.clone();
+    let output = crate::timings::record(Category::Probe, name, || runner.output(&mut probe)).map_err(|err| {
+        Error::new(
+            crate::error::kind(&err),
+            format!("failed to run `{command_line}`: {err}"),
+        )
+    })?;
+    if output.status.success() {
+        return Ok(());
+    }
+    if !component_installed(xtask, runner, component, toolchain) {
+        let message = format!(
+            "the `{component}` component is not installed for the `{toolchain}` toolchain\nAdd it with `rustup \
+             component add {component} --toolchain {toolchain}`"
+        );
+        return Err(Error::new(Kind::ToolMissing, message).into());
+    }
+    Err(format!("`{command_line}` failed with non-zero exit code").into())
+}
+
+// Whether `rustup component list` lists `component` as installed for `toolchain`. This is assumed if rustup fails, to
+// fall back to the generic failure of the probe.
+fn component_installed(xtask: &Xtask, runner: &dyn Runner, component: &str, toolchain: &str) -> bool {
+    let mut cmd = Command::new(&*xtask.tool_path("rustup"));
+    cmd.envs(&xtask.env);
+    cmd.args(["component", "list", "--installed", "--toolchain", toolchain]);
+    let name = || format!("rustup component list --installed --toolchain {toolchain}");
+    let Ok(output) = crate::timings::record(Category::Probe, name, || runner.output(&mut cmd)) else {
+        return true;
+    };
+    if !output.status.success() {
+        return true;
+    }
+    let host = crate::version::TARGET;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .any(|line| line == component || line == format!("{component}-{host}"))
+}
+
+/// Checks that `tool` reports a version of at least `minimum`.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Probing the tool for its version fails (see [`version_banner`])
+/// - The version is older than `minimum` or cannot be determined
+pub fn validate_tool(xtask: &Xtask, runner: &dyn Runner, tool: &str, minimum: &str) -> BoxResult<()> {
+    let banner = version_banner(xtask, runner, tool)?;
+    check_minimum_version(xtask, tool, minimum, &banner)
+}
+
+/// Checks that the clang tool `tool` (e.g. `clang-format`) reports a version within the configured
+/// `xtask.clang.version` requirement. Any version is accepted if none is configured.
+///
+/// This does not depend on an xtask command context or the current directory, so it can be called from the build
+/// script of a downstream crate, with the config from [`Xtask::load`] (given the metadata for `CARGO_MANIFEST_DIR`) or
+/// one constructed directly.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Probing the tool for its version fails (see [`version_banner`])
+/// - The version does not match the requirement or cannot be determined (see [`check_clang_version`])
+/// - The tool is ambiguous and `xtask.clang.strictTools` is set (see [`check_clang_ambiguity`])
+pub fn validate_clang_tool(xtask: &Xtask, runner: &dyn Runner, tool: &str) -> BoxResult<()> {
+    if xtask.clang.version.is_empty() {
+        return Ok(());
+    }
+    let banner = version_banner(xtask, runner, tool)?;
+    check_clang_version(&xtask.clang, tool, &banner)?;
+    let found = parse_clang_version(&xtask.clang, tool, &banner)?;
+    check_clang_ambiguity(xtask, tool, found, |alternative| {
+        version_banner(xtask, runner, alternative)
+    })
+}
+
+/// Checks that `tool` reports a version of at least the configured `xtask.tools.<name>.version`, where `<name>` is
+/// the file name of `tool`. Any version is accepted if none is configured.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Probing the tool for its version fails (see [`version_banner`])
+/// - The version is older than the configured one or cannot be determined (see [`check_configured_version`])
+pub fn validate_configured_tool(xtask: &Xtask, runner: &dyn Runner, tool: &str) -> BoxResult<()> {
+    if configured_tool(xtask, tool)
+        .and_then(|config| config.version.as_ref())
+        .is_none()
+    {
+        return Ok(());
+    }
+    let banner = version_banner(xtask, runner, tool)?;
+    check_configured_version(xtask, tool, &banner)
+}
+
+/// Checks that the version banner `banner` of `tool` names a version of at least the configured
+/// `xtask.tools.<name>.version`, if any.
+///
+/// # Errors
+///
+/// Will return `Err` if the version is older than the configured one or cannot be determined.
+pub fn check_configured_version(xtask: &Xtask, tool: &str, banner: &str) -> BoxResult<()> {
+    match configured_tool(xtask, tool).and_then(|config| config.version.as_deref()) {
+        Some(minimum) => check_minimum_version(xtask, tool, minimum, banner),
+        None => Ok(()),
+    }
+}
+
+/// The version in the version banner `banner` of `tool`, as matched by the configured `xtask.tools.<name>.matcher`.
+///
+/// Without a matcher, this is the number following the word `version` (see [`crate::detection::parse_version`]), or
+/// else the first dotted number (e.g. `1.11.1` for `ninja --version`).
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The configured matcher is not a valid regex
+/// - The version cannot be determined
+pub fn parse_tool_version<'b>(xtask: &Xtask, tool: &str, banner: &'b str) -> BoxResult<&'b str> {
+    let found = match configured_tool(xtask, tool).and_then(|config| config.matcher.as_deref()) {
+        Some(matcher) => {
+            let matcher = regex::Regex::new(matcher).map_err(|err| {
+                Error::new(
+                    Kind::Validation,
+                    format!("invalid `xtask.tools` matcher for `{tool}`: {err}"),
+                )
+            })?;
+            matcher
+                .captures(banner)
+                .and_then(|captures| captures.get(1))
+                .map(|found| found.as_str())
+        },
+        None => crate::detection::parse_version(banner).or_else(|| {
+            regex::Regex::new(r"\d+(?:\.\d+)+")
+                .ok()?
+                .find(banner)
+                .map(|found| found.as_str())
+        }),
+    };
+    found.ok_or_else(|| {
+        let message = format!("could not determine the version of `{tool}` from its version output");
+        Error::new(Kind::Validation, message).into()
+    })
+}
+
+/// Checks that the version banner `banner` of `tool` names a version of at least `minimum` (see
+/// [`parse_tool_version`]).
+///
+/// # Errors
+///
+/// Will return `Err` if the version is older than `minimum` or cannot be determined.
+pub fn check_minimum_version(xtask: &Xtask, tool: &str, minimum: &str, banner: &str) -> BoxResult<()> {
+    let found = parse_tool_version(xtask, tool, banner)?;
+    if !crate::detection::version_at_least(minimum, found) {
+        let message = format!("`{tool}` version mismatch: expected at least `{minimum}`, found `{found}`");
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+    Ok(())
+}
+
+/// The version in the version banner `banner` of the clang tool `tool`, as matched by the first of its matchers that
+/// matches (see [`clang_matchers`]), or else the number following the word `version` (see
+/// [`crate::detection::parse_version`]).
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - A configured matcher is not a valid regex
+/// - The version cannot be determined
+pub fn parse_clang_version<'b>(clang: &XtaskClang, tool: &str, banner: &'b str) -> BoxResult<&'b str> {
+    let matchers = clang_matchers(clang, tool);
+    let mut found = None;
+    for matcher in &matchers {
+        let matcher = regex::Regex::new(matcher).map_err(|err| {
+            Error::new(
+                Kind::Validation,
+                format!("invalid `xtask.clang.matchers` matcher for `{tool}`: {err}"),
+            )
+        })?;
+        found = matcher
+            .captures(banner)
+            .and_then(|captures| captures.get(1))
+            .map(|found| found.as_str());
+        if found.is_some() {
+            break;
+        }
+    }
+    if matchers.is_empty() {
+        found = crate::detection::parse_version(banner);
+    }
+    found.ok_or_else(|| {
+        let message = format!("could not determine the version of `{tool}` from its version output");
+        Error::new(Kind::Validation, message).into()
+    })
+}
+
+/// The matchers of the version of the clang tool `tool` (a program name or path), in order of preference: those
+/// configured in `xtask.clang.matchers`, or else the built-in one of [`CLANG_MATCHERS`]. This is empty for the tools
+/// with neither.
+///
+/// Tools are looked up by their file name, and then by the name without an `.exe` extension or a version suffix (e.g.
+/// `clang-format` for `/usr/bin/clang-format-17`).
+#[must_use]
+pub fn clang_matchers<'a>(clang: &'a XtaskClang, tool: &str) -> Vec<&'a str> {
+    let name = camino::Utf8Path::new(tool).file_name().unwrap_or(tool);
+    let base = name.strip_suffix(".exe").unwrap_or(name);
+    let base = regex::Regex::new(r"-\d+(?:\.\d+)*$").map_or(base, |suffix| {
+        suffix.find(base).map_or(base, |found| &base[.. found.start()])
+    });
+    [name, base]
+        .into_iter()
+        .find_map(|key| clang.matchers.get(key))
+        .map(|matchers| matchers.iter().map(String::as_str).collect())
+        .or_else(|| {
+            CLANG_MATCHERS
+                .iter()
+                .find(|(builtin, _)| *builtin == base)
+                .map(|(_, matcher)| vec![*matcher])
+        })
+        .unwrap_or_default()
+}
+
+/// Checks that the version banner `banner` of the clang tool `tool` names a version within the `version` requirement
+/// of `clang` (see [`parse_clang_version`]).
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - The banner is from Apple clang and `xtask.clang.allowAppleClang` is not set
+/// - The version does not match the requirement or cannot be determined
+pub fn check_clang_version(clang: &XtaskClang, tool: &str, banner: &str) -> BoxResult<()> {
+    let expected = clang.version.as_str();
+    if expected.is_empty() {
+        return Ok(());
+    }
+    if !clang.allow_apple_clang && crate::detection::is_apple_clang_banner(banner) {
+        let message = format!(
+            "`{tool}` is Apple clang, whose versions do not correspond to LLVM releases\nInstall LLVM clang \
+             `{expected}` (e.g. from Homebrew or MacPorts) or set `xtask.clang.allowAppleClang`"
+        );
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+    let found = parse_clang_version(clang, tool, banner)?;
+    if !crate::detection::version_matches(expected, found) {
+        let message = format!("`{tool}` version mismatch: expected `{expected}`, found `{found}`");
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+    Ok(())
+}
+
+/// The alternative name of the clang tool `tool` (a program name or path) which reported the version `found`: the
+/// name without its version suffix if it has one (e.g. `/usr/bin/clang-format` for `/usr/bin/clang-format-17`), or
+/// else the name with the major version of `found` as a suffix (e.g. `clang++-17` for `clang++`).
+#[must_use]
+pub fn clang_alternative(tool: &str, found: &str) -> Option<Utf8PathBuf> {
+    let path = camino::Utf8Path::new(tool);
+    let name = path.file_name()?;
+    let (stem, exe) = name.strip_suffix(".exe").map_or((name, ""), |stem| (stem, ".exe"));
+    let is_version = |suffix: &str| {
+        suffix
+            .split('.')
+            .all(|part| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit()))
+    };
+    let alternative = match stem.rsplit_once('-') {
+        Some((base, suffix)) if is_version(suffix) => base.to_owned(),
+        _ => format!("{stem}-{}", found.split('.').next()?),
+    };
+    Some(path.with_file_name(format!("{alternative}{exe}")))
+}
+
+/// Checks that the clang tool `tool`, which reported the version `found`, is not ambiguous: that its alternative name
+/// (see [`clang_alternative`]), probed for its version banner with `banner`, does not resolve to a different version
+/// also matching `xtask.clang.version`. The first match (`tool`) is used regardless, with a warning, unless
+/// `xtask.clang.strictTools` is set. Tools configured in `xtask.tools.paths` are never ambiguous.
+///
+/// # Errors
+///
+/// Will return `Err` if the tool is ambiguous and `xtask.clang.strictTools` is set.
+pub fn check_clang_ambiguity(
+    xtask: &Xtask,
+    tool: &str,
+    found: &str,
+    banner: impl FnOnce(&str) -> BoxResult<String>,
+) -> BoxResult<()> {
+    let name = camino::Utf8Path::new(tool).file_name().unwrap_or(tool);
+    if xtask.tools.paths.contains_key(name) {
+        return Ok(());
+    }
+    let Some(alternative) = clang_alternative(tool, found) else {
+        return Ok(());
+    };
+    // An alternative which is not installed, or which does not match the requirement, is not a candidate.
+    let Ok(other) = banner(alternative.as_str()) else {
+        return Ok(());
+    };
+    if check_clang_version(&xtask.clang, alternative.as_str(), &other).is_err() {
+        return Ok(());
+    }
+    let version = parse_clang_version(&xtask.clang, alternative.as_str(), &other)?;
+    if version == found {
+        return Ok(());
+    }
+    let message = format!(
+        "`{tool}` is ambiguous: both `{tool}` (version `{found}`) and `{alternative}` (version `{version}`) match \
+         clang version `{}`",
+        xtask.clang.version
+    );
+    if xtask.clang.strict_tools {
+        let message = format!("{message}\nConfigure the one to use in `xtask.tools.paths.{name}`");
+        return Err(Error::new(Kind::Validation, message).into());
+    }
+    crate::handler::status(format_args!("warning: {message}; using `{tool}`"));
+    Ok(())
+}
+
+// The `xtask.tools` settings for `tool` (a program name or path), keyed by its file name.
+fn configured_tool<'a>(xtask: &'a Xtask, tool: &str) -> Option<&'a XtaskTool> {
+    let name = camino::Utf8Path::new(tool).file_name().unwrap_or(tool);
+    xtask.tools.get(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tool;
+    use crate::{command::Context, error::Kind, runner::MockRunner, testing};
+
+    #[test]
+    fn validate_clang_tool_rejects_a_version_mismatch() {
+        let config = testing::config(serde_json::json!({ "clang": { "version": "17" } }));
+        let runner = MockRunner::new(|cmd| {
+            assert_eq!(testing::command_line(cmd), "clang-format --version");
+            Ok(testing::output(0, "Ubuntu clang-format version 14.0.0-1ubuntu1\n"))
+        });
+        let err = super::validate_clang_tool(&config.xtask, &runner, "clang-format").unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::Validation);
+        assert_eq!(
+            err.to_string(),
+            "`clang-format` version mismatch: expected `17`, found `14.0.0`"
+        );
+
+        let runner = MockRunner::new(|_| Ok(testing::output(0, "clang-format version 17.0.6\n")));
+        super::validate_clang_tool(&config.xtask, &runner, "clang-format").unwrap();
+    }
+
+    #[test]
+    fn validate_clang_tool_reads_a_banner_on_stderr() {
+        let config = testing::config(serde_json::json!({ "clang": { "version": "17" } }));
+        let runner = MockRunner::new(|_| {
+            Ok(std::process::Output {
+                stderr: b"clang-format version 17.0.6\n".to_vec(),
+                ..testing::output(0, "")
+            })
+        });
+        super::validate_clang_tool(&config.xtask, &runner, "clang-format").unwrap();
+    }
+
+    #[test]
+    fn clang_alternative_adds_or_strips_the_version_suffix() {
+        let alternative = |tool| super::clang_alternative(tool, "17.0.6").unwrap();
+        assert_eq!(alternative("/usr/bin/clang-format-17"), "/usr/bin/clang-format");
+        assert_eq!(alternative("/usr/bin/clang-format"), "/usr/bin/clang-format-17");
+        assert_eq!(alternative("clang++"), "clang++-17");
+        assert_eq!(alternative("clang-tidy-17.0.exe"), "clang-tidy.exe");
+    }
+
+    #[test]
+    fn ambiguous_clang_tools_warn_or_fail_in_strict_mode() {
+        let runner = MockRunner::new(|cmd| match testing::command_line(cmd).as_str() {
+            "clang-format --version" => Ok(testing::output(0, "clang-format version 17.0.1\n")),
+            "clang-format-17 --version" => Ok(testing::output(0, "clang-format version 17.0.6\n")),
+            line => panic!("unexpected command `{line}`"),
+        });
+        let config = testing::config(serde_json::json!({ "clang": { "version": "17" } }));
+        let resolved = super::Validation::new(&config, &runner)
+            .tool(Tool::ClangFormat)
+            .unwrap();
+        assert_eq!(resolved.path, "clang-format");
+        assert_eq!(resolved.version.as_deref(), Some("17.0.1"));
+
+        let config = testing::config(serde_json::json!({ "clang": { "version": "17", "strictTools": true } }));
+        let Err(err) = super::Validation::new(&config, &runner).tool(Tool::ClangFormat) else {
+            panic!("expected an ambiguity error");
+        };
+        assert_eq!(crate::error::kind(&*err), Kind::Validation);
+        assert_eq!(
+            err.to_string(),
+            "`clang-format` is ambiguous: both `clang-format` (version `17.0.1`) and `clang-format-17` (version \
+             `17.0.6`) match clang version `17`\nConfigure the one to use in `xtask.tools.paths.clang-format`"
+        );
+        let err = super::validate_clang_tool(&config.xtask, &runner, "clang-format").unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::Validation);
+    }
+
+    #[test]
+    fn missing_or_mismatched_clang_alternatives_are_not_ambiguous() {
+        let config = testing::config(serde_json::json!({ "clang": { "version": "17", "strictTools": true } }));
+        let runner = MockRunner::new(|cmd| match testing::command_line(cmd).as_str() {
+            "clang-format-17 --version" => Ok(testing::output(0, "clang-format version 17.0.6\n")),
+            "clang-format --version" => Ok(testing::output(0, "clang-format version 14.0.0\n")),
+            line => panic!("unexpected command `{line}`"),
+        });
+        super::validate_clang_tool(&config.xtask, &runner, "clang-format-17").unwrap();
+
+        let runner = MockRunner::new(|cmd| match testing::command_line(cmd).as_str() {
+            "clang-format --version" => Ok(testing::output(0, "clang-format version 17.0.6\n")),
+            _ => Err(std::io::ErrorKind::NotFound.into()),
+        });
+        super::validate_clang_tool(&config.xtask, &runner, "clang-format").unwrap();
+    }
+
+    #[test]
+    fn cached_versions_are_not_probed_again() {
+        let dir = camino::Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join("xtask-test-validation-cache");
+        std::fs::create_dir_all(&dir).unwrap();
+        let cmake = dir.join("cmake");
+        std::fs::write(&cmake, "").unwrap();
+        let cache = crate::tool_cache::ToolCache::path(&dir);
+        let config = testing::config(serde_json::json!({
+            "cmake": { "version": "3.20" },
+            "tools": { "paths": { "cmake": cmake } }
+        }));
+        let runner = MockRunner::new(|_| Ok(testing::output(0, "cmake version 3.27.4\n")));
+        let probed = super::Validation::new(&config, &runner)
+            .with_cache(cache.clone())
+            .tool(Tool::Cmake);
+        let runner = MockRunner::new(|cmd| panic!("unexpected command `{}`", testing::command_line(cmd)));
+        let cached = super::Validation::new(&config, &runner)
+            .with_cache(cache.clone())
+            .tool(Tool::Cmake);
+
+        let config = testing::config(serde_json::json!({
+            "cmake": { "version": "3.28" },
+            "tools": { "paths": { "cmake": cmake } }
+        }));
+        let runner = MockRunner::new(|_| Ok(testing::output(0, "cmake version 3.27.4\n")));
+        let changed = super::Validation::new(&config, &runner)
+            .with_cache(cache)
+            .tool(Tool::Cmake);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(probed.unwrap().version.as_deref(), Some("3.27.4"));
+        assert_eq!(cached.unwrap().version.as_deref(), Some("3.27.4"));
+        assert_eq!(crate::error::kind(&*changed.err().unwrap()), Kind::Validation);
+    }
+
+    #[test]
+    fn check_clang_version_rejects_apple_clang_unless_allowed() {
+        let apple = "Apple clang version 15.0.0 (clang-1500.0.40.1)\nTarget: arm64-apple-darwin23.0.0\n";
+        let llvm = "Homebrew clang version 15.0.7\nTarget: arm64-apple-darwin23.0.0\n";
+        let config = testing::config(serde_json::json!({ "clang": { "version": "15" } }));
+        let err = super::check_clang_version(&config.xtask.clang, "clang", apple).unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::Validation);
+        assert!(err.to_string().starts_with("`clang` is Apple clang"));
+        super::check_clang_version(&config.xtask.clang, "clang", llvm).unwrap();
+
+        let config = testing::config(serde_json::json!({ "clang": { "version": "15", "allowAppleClang": true } }));
+        super::check_clang_version(&config.xtask.clang, "clang", apple).unwrap();
+    }
+
+    #[test]
+    fn version_probes_see_the_configured_env() {
+        let config = testing::config(serde_json::json!({ "clang": { "version": "17" }, "env": { "FOO": "bar" } }));
+        let runner = MockRunner::new(|cmd| {
+            assert!(
+                cmd.get_envs()
+                    .any(|(name, value)| name == "FOO" && value == Some("bar".as_ref()))
+            );
+            Ok(testing::output(0, "clang-format version 17.0.6\n"))
+        });
+        super::validate_clang_tool(&config.xtask, &runner, "clang-format").unwrap();
+    }
+
+    #[test]
+    fn validate_clang_tool_accepts_any_version_if_none_is_configured() {
+        let config = testing::config(serde_json::json!({ "clang": { "allowAppleClang": true } }));
+        let runner = MockRunner::new(|cmd| panic!("unexpected command `{}`", testing::command_line(cmd)));
+        super::validate_clang_tool(&config.xtask, &runner, "clang-format").unwrap();
+    }
+
+    #[test]
+    fn parse_clang_version_tries_each_matcher() {
+        let config = testing::config(serde_json::json!({
+            "clang": {
+                "matchers": { "clang-tidy": [r"\bLLVM version (\d+(?:\.\d+)*)", r"^clang-tidy (\d+(?:\.\d+)*)"] }
+            }
+        }));
+        let clang = &config.xtask.clang;
+        let old = "LLVM (http://llvm.org/):\n  LLVM version 14.0.6\n  Optimized build.\n";
+        let new = "clang-tidy 18.1.8\nTarget: x86_64-pc-linux-gnu\n";
+        assert_eq!(super::parse_clang_version(clang, "clang-tidy", old).unwrap(), "14.0.6");
+        assert_eq!(
+            super::parse_clang_version(clang, "clang-tidy-18", new).unwrap(),
+            "18.1.8"
+        );
+        assert!(super::parse_clang_version(clang, "clang-tidy", "clang-tidy\n").is_err());
+    }
+
+    #[test]
+    fn a_single_matcher_is_a_list_of_one() {
+        let config = testing::config(serde_json::json!({
+            "clang": { "matchers": { "clang-format": r"^cf (\d+)" } }
+        }));
+        assert_eq!(super::clang_matchers(&config.xtask.clang, "clang-format"), [
+            r"^cf (\d+)"
+        ]);
+        assert_eq!(super::clang_matchers(&config.xtask.clang, "clangd"), [
+            super::CLANG_MATCHERS[2].1
+        ]);
+    }
+
+    #[test]
+    fn validate_tool_rejects_an_older_version() {
+        let config = testing::config(serde_json::json!({}));
+        let runner = MockRunner::new(|_| Ok(testing::output(0, "1.10.0\n")));
+        let err = super::validate_tool(&config.xtask, &runner, "ninja", "1.11").unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::Validation);
+        assert_eq!(
+            err.to_string(),
+            "`ninja` version mismatch: expected at least `1.11`, found `1.10.0`"
+        );
+
+        let runner = MockRunner::new(|_| Ok(testing::output(0, "1.11.1\n")));
+        super::validate_tool(&config.xtask, &runner, "ninja", "1.11").unwrap();
+    }
+
+    #[test]
+    fn first_installed_toolchain_falls_back_to_a_later_candidate() {
+        let config = testing::config(serde_json::json!({}));
+        let host = crate::version::TARGET;
+        let installed = format!("stable-{host} (default)\nnightly-{host}\n");
+        let runner = MockRunner::new(|cmd| {
+            assert!(testing::command_line(cmd).ends_with("rustup toolchain list"));
+            Ok(testing::output(0, &installed))
+        });
+        let candidates = ["nightly-2023-08-08", "nightly"];
+        let toolchain = super::first_installed_toolchain(&config.xtask, &runner, &candidates, false).unwrap();
+        assert_eq!(toolchain, "nightly");
+    }
+
+    #[test]
+    fn first_installed_toolchain_fails_if_no_candidate_is_installed() {
+        let config = testing::config(serde_json::json!({}));
+        let runner = MockRunner::new(|_| Ok(testing::output(0, "stable-x86_64-unknown-linux-gnu (default)\n")));
+        let candidates = ["nightly-2023-08-08", "nightly"];
+        let err = super::first_installed_toolchain(&config.xtask, &runner, &candidates, false).unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::ToolMissing);
+    }
+
+    #[test]
+    fn a_component_missing_from_the_toolchain_is_reported_with_the_command_adding_it() {
+        let config = testing::config(serde_json::json!({}));
+        let host = crate::version::TARGET;
+        let probe = || {
+            let mut cmd = std::process::Command::new("cargo");
+            cmd.args(["+nightly", "clippy", "--version"]);
+            cmd
+        };
+        let installed = |components: String| {
+            MockRunner::new(move |cmd| match testing::command_line(cmd) {
+                line if line == "cargo +nightly clippy --version" => Ok(testing::output(1, "")),
+                line if line.ends_with("rustup component list --installed --toolchain nightly") => {
+                    Ok(testing::output(0, &components))
+                },
+                line => panic!("unexpected command `{line}`"),
+            })
+        };
+
+        let runner = installed(format!("cargo-{host}\nrust-src\n"));
+        let err = super::validate_cargo_component(&config.xtask, &runner, "clippy", "nightly", probe()).unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::ToolMissing);
+        assert_eq!(
+            err.to_string(),
+            "the `clippy` component is not installed for the `nightly` toolchain\nAdd it with `rustup component add \
+             clippy --toolchain nightly`"
+        );
+
+        let runner = installed(format!("clippy-{host}\nrust-src\n"));
+        let err = super::validate_cargo_component(&config.xtask, &runner, "clippy", "nightly", probe()).unwrap_err();
+        assert_ne!(crate::error::kind(&*err), Kind::ToolMissing);
+        assert_eq!(
+            err.to_string(),
+            "`cargo +nightly clippy --version` failed with non-zero exit code"
+        );
+
+        let runner = MockRunner::new(|_| Ok(testing::output(0, "clippy 0.1.73\n")));
+        super::validate_cargo_component(&config.xtask, &runner, "clippy", "nightly", probe()).unwrap();
+    }
+
+    #[test]
+    fn cmake_is_checked_against_the_configured_minimum_version() {
+        let banner =
+            |version: &str| format!("cmake version {version}\n\nCMake suite maintained and supported by Kitware.\n");
+        let config = testing::config(serde_json::json!({ "cmake": { "version": "3.25" } }));
+        let mut args = pico_args::Arguments::from_vec(vec![]);
+        let mut context = Context::new(&config, &mut args, vec![]);
+        let old = banner("3.22.1");
+        let runner = MockRunner::new(|cmd| {
+            assert!(testing::command_line(cmd).ends_with("cmake --version"));
+            Ok(testing::output(0, &old))
+        });
+        context.runner = &runner;
+        let Err(err) = context.validation().tool(Tool::Cmake) else {
+            panic!("an older cmake was accepted");
+        };
+        assert_eq!(crate::error::kind(&*err), Kind::Validation);
+        assert_eq!(
+            err.to_string(),
+            "`cmake` version mismatch: expected at least `3.25`, found `3.22.1`"
+        );
+
+        let new = banner("3.28.3");
+        let runner = MockRunner::new(|_| Ok(testing::output(0, &new)));
+        context.runner = &runner;
+        let tool = context.validation().tool(Tool::Cmake).unwrap();
+        assert_eq!(tool.version.as_deref(), Some("3.28.3"));
+    }
+
+    #[test]
+    fn cmake_is_not_probed_without_a_configured_version() {
+        let config = testing::config(serde_json::json!({}));
+        let mut args = pico_args::Arguments::from_vec(vec![]);
+        let mut context = Context::new(&config, &mut args, vec![]);
+        let runner = MockRunner::new(|cmd| panic!("unexpected command `{}`", testing::command_line(cmd)));
+        context.runner = &runner;
+        assert!(context.validation().tool(Tool::Cmake).unwrap().version.is_none());
+    }
+
+    #[test]
+    fn tools_are_probed_with_the_configured_argument() {
+        let config = testing::config(serde_json::json!({ "tools": { "scons": { "probe": "-v" } } }));
+        let runner = MockRunner::new(|cmd| {
+            assert_eq!(testing::command_line(cmd), "scons -v");
+            Ok(testing::output(0, "SCons by Steven Knight et al.:\n\tSCons: v4.5.2\n"))
+        });
+        super::validate_tool(&config.xtask, &runner, "scons", "4.5").unwrap();
+
+        let runner = MockRunner::new(|cmd| {
+            assert_eq!(testing::command_line(cmd), "ninja --version");
+            Ok(testing::output(0, "1.11.1\n"))
+        });
+        super::validate_tool(&config.xtask, &runner, "ninja", "1.11").unwrap();
+    }
+
+    #[test]
+    fn a_missing_rustup_is_reported_with_guidance() {
+        let config = testing::config(serde_json::json!({}));
+        let runner = MockRunner::new(|_| Err(std::io::ErrorKind::NotFound.into()));
+        let candidates = ["nightly-2023-08-08", "nightly"];
+        let err = super::first_installed_toolchain(&config.xtask, &runner, &candidates, false).unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::ToolMissing);
+        assert!(err.to_string().contains("https://rustup.rs"));
+    }
+
+    #[test]
+    fn a_missing_rustup_is_skipped_for_a_pinned_toolchain() {
+        let config = testing::config(serde_json::json!({}));
+        let runner = MockRunner::new(|_| Err(std::io::ErrorKind::NotFound.into()));
+        let candidates = ["nightly-2023-08-08", "nightly"];
+        let toolchain = super::first_installed_toolchain(&config.xtask, &runner, &candidates, true).unwrap();
+        assert_eq!(toolchain, "nightly-2023-08-08");
+    }
+
+    #[test]
+    fn validate_tools_collects_every_failure() {
+        let config = testing::config(serde_json::json!({
+            "cmake": { "version": "3.25" },
+            "tools": { "ninja": { "version": "1.11" } }
+        }));
+        let mut args = pico_args::Arguments::from_vec(vec![]);
+        let mut context = Context::new(&config, &mut args, vec![]);
+        let runner = MockRunner::new(|cmd| {
+            if testing::command_line(cmd).starts_with("cmake") {
+                Ok(testing::output(0, "cmake version 3.22.1\n"))
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    crate::error::Error::new(Kind::ToolMissing, "`ninja` was not found"),
+                ))
+            }
+        });
+        context.runner = &runner;
+        let validation = context.validation();
+        let (resolved, errors) = super::validate_tools(&validation, &[Tool::Cmake, Tool::Ninja, Tool::CmakeLint]);
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].tool.name(), "cmake-lint");
+        assert_eq!(errors.iter().map(|error| error.tool.name()).collect::<Vec<_>>(), [
+            "cmake", "ninja"
+        ]);
+        assert_eq!(crate::error::kind(&*errors[1].error), Kind::ToolMissing);
+        let err = super::tool_errors(errors).unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::Validation);
+        assert_eq!(
+            err.to_string(),
+            "2 tools failed validation:\n- cmake: `cmake` version mismatch: expected at least `3.25`, found \
+             `3.22.1`\n- ninja: failed to run `ninja --version`: `ninja` was not found"
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use crate::BoxResult;
+use std::sync::OnceLock;
+
+/// The version of this crate.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The git commit this crate was built from, if it was built from a git checkout.
+///
+/// This is the commit of the cxx-auto-xtask checkout the crate was compiled from (and `None` for a crates.io build),
+/// not that of the project whose xtask binary depends on it; the binary can report its own with [`set_git_commit`].
+pub const GIT_COMMIT: Option<&str> = option_env!("CXX_AUTO_XTASK_GIT_COMMIT");
+
+/// The target triple this crate was built for.
+pub const TARGET: &str = env!("CXX_AUTO_XTASK_TARGET");
+
+// The git commit of the xtask binary, if it supplied one with `set_git_commit`.
+static BINARY_GIT_COMMIT: OnceLock<&'static str> = OnceLock::new();
+
+/// Sets the git commit reported by `xtask --version` to `commit`, in place of [`GIT_COMMIT`], for an xtask binary
+/// that captures its own (e.g. from its build script, as `set_git_commit(env!("XTASK_GIT_COMMIT"))`).
+///
+/// Only the first call has an effect.
+pub fn set_git_commit(commit: &'static str) {
+    let _ = BINARY_GIT_COMMIT.set(commit);
+}
+
+/// The git commit reported by `xtask --version`: the one set with [`set_git_commit`], or else [`GIT_COMMIT`].
+#[must_use]
+pub fn git_commit() -> Option<&'static str> {
+    BINARY_GIT_COMMIT.get().copied().or(GIT_COMMIT)
+}
+
+/// Prints the version information if `--version` was given, as JSON if `--json` was also given.
+///
+/// Returns `true` if the version was printed.
+///
+/// # Errors
+///
+/// Will return `Err` if argument processing fails.
+pub fn version(args: &mut pico_args::Arguments) -> BoxResult<bool> {
+    if !args.contains(["-V", "--version"]) {
+        return Ok(false);
+    }
+    if args.contains("--json") {
+        let json = serde_json::json!({
+            "version": VERSION,
+            "gitCommit": git_commit(),
+            "target": TARGET,
+        });
+        println!("{}", serde_json::to_string_pretty(&json)?);
+    } else {
+        let commit = git_commit().unwrap_or("unknown commit");
+        println!("cxx-auto-xtask {VERSION} ({commit} {TARGET})");
+    }
+    Ok(true)
+}
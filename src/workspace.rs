@@ -1,27 +1,176 @@
-use crate::BoxResult;
-use std::{path::PathBuf, process::Command};
+use crate::{
+    config::Config,
+    error::{Error, Kind},
+    runner::Runner,
+    BoxResult,
+};
+use camino::{Utf8Path, Utf8PathBuf};
 
+/// The `cargo metadata` of the workspace containing the current directory, run with `runner` (with `--offline` if
+/// `offline` is set).
+///
+/// This runs the cargo that invoked the xtask (see [`crate::config::running_cargo`]), or else the rustup proxy or the
+/// `cargo` on `PATH`.
+///
 /// # Errors
 ///
 /// Will return `Err` under the following circumstances:
-/// - The command process for `cargo metadata --format-version=1` fails to start
+/// - The `cargo metadata` command process fails to start
 /// - The command invocation fails with non-zero exit status
-/// - The command invocation fails to produce valid UTF-8 output
-/// - The command invocation fails to produce valid JSON output
-/// - `workspace_root` is not found in the JSON output
-pub fn project_root() -> BoxResult<PathBuf> {
-    let data = Command::new("cargo")
-        .args(["metadata", "--format-version=1"])
-        .output()?;
-    if !data.status.success() {
-        let err = String::from_utf8(data.stderr)?;
-        return Err(format!("`cargo metadata` failed: \"{err}\"").into());
-    }
-    let json = serde_json::from_str::<serde_json::Value>(&String::from_utf8(data.stdout)?)?;
-    let path = json
-        .get("workspace_root")
+/// - The command invocation fails to produce valid metadata
+pub fn metadata(runner: &dyn Runner, offline: bool) -> BoxResult<cargo_metadata::Metadata> {
+    let mut metadata_command = cargo_metadata::MetadataCommand::new();
+    if let Some(cargo) = crate::config::running_cargo()
+        .map(Utf8PathBuf::from)
+        .or_else(|| crate::config::rust_program("cargo"))
+    {
+        metadata_command.cargo_path(cargo);
+    }
+    if offline {
+        metadata_command.other_options(vec!["--offline".into()]);
+    }
+    let mut cmd = metadata_command.cargo_command();
+    let output = runner.output(&mut cmd).map_err(|err| {
+        Error::new(
+            crate::error::kind(&err),
+            format!("failed to run `cargo metadata`: {err}"),
+        )
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = format!("`cargo metadata` failed with non-zero exit code\n{}", stderr.trim_end());
+        return Err(Error::new(Kind::Failure, message).into());
+    }
+    // NOTE: as with `MetadataCommand::exec`, lines other than the metadata (e.g. printed by a cargo wrapper) are skipped
+    let stdout = String::from_utf8(output.stdout)?;
+    let json = stdout
+        .lines()
+        .find(|line| line.starts_with('{'))
+        .ok_or("`cargo metadata` did not print the metadata")?;
+    Ok(cargo_metadata::MetadataCommand::parse(json)?)
+}
+
+/// The root directory of the workspace, as reported by `cargo metadata` when the config was loaded.
+#[must_use]
+pub fn project_root(config: &Config) -> &Utf8Path {
+    &config.cargo_metadata.workspace_root
+}
+
+/// The directory cargo places build output in, as reported by `cargo metadata` when the config was loaded.
+///
+/// This honors `CARGO_TARGET_DIR` and `build.target-dir` in `.cargo/config.toml`, falling back to
+/// `<project_root>/target`.
+#[must_use]
+pub fn target_dir(config: &Config) -> &Utf8Path {
+    &config.cargo_metadata.target_directory
+}
+
+/// The names of the workspace member packages, as reported by `cargo metadata`.
+pub fn members(config: &Config) -> impl Iterator<Item = &str> {
+    config
+        .cargo_metadata
+        .workspace_packages()
+        .into_iter()
+        .map(|package| package.name.as_str())
+}
+
+/// Consumes the `-p`/`--package` flags from `args`, returning the selected packages, or `defaults` if none are given.
+///
+/// # Errors
+///
+/// Will return `Err` under the following circumstances:
+/// - Argument processing fails (e.g. invalid arguments)
+/// - A selected package is not a member of the workspace
+pub fn packages(config: &Config, args: &mut pico_args::Arguments, defaults: &[&str]) -> BoxResult<Vec<String>> {
+    let packages = args.values_from_str::<_, String>(["-p", "--package"])?;
+    if packages.is_empty() {
+        return Ok(defaults.iter().map(|&package| package.into()).collect());
+    }
+    for package in &packages {
+        if !members(config).any(|member| member == package) {
+            return Err(format!("package `{package}` is not a member of the workspace").into());
+        }
+    }
+    Ok(packages)
+}
+
+/// The workspace member package owning each of `files`: the one whose manifest directory is the innermost containing
+/// the file. Files outside every package are left out.
+#[must_use]
+pub fn owning_packages<'a>(
+    config: &'a Config,
+    files: &[Utf8PathBuf],
+) -> Vec<(&'a cargo_metadata::Package, Utf8PathBuf)> {
+    let packages = config.cargo_metadata.workspace_packages();
+    files
         .iter()
-        .find_map(|val| val.as_str().map(PathBuf::from))
-        .ok_or("`workspace_root` not found in `cargo metadata` output")?;
-    Ok(path)
+        .filter_map(|file| {
+            let package = packages
+                .iter()
+                .filter(|package| {
+                    package
+                        .manifest_path
+                        .parent()
+                        .map_or(false, |dir| file.starts_with(dir))
+                })
+                .max_by_key(|package| package.manifest_path.as_str().len())?;
+            Some((*package, file.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Kind, runner::MockRunner, testing};
+
+    #[test]
+    fn metadata_runs_cargo_metadata_offline_with_the_runner() {
+        let json = serde_json::json!({
+            "packages": [],
+            "workspace_members": [],
+            "resolve": null,
+            "workspace_root": "/work",
+            "target_directory": "/work/target",
+            "version": 1,
+        });
+        let stdout = format!("warning: from a cargo wrapper\n{json}\n");
+        let runner = MockRunner::new(|cmd| {
+            assert!(testing::command_line(cmd).ends_with("cargo metadata --format-version 1 --offline"));
+            Ok(testing::output(0, &stdout))
+        });
+        let metadata = super::metadata(&runner, true).unwrap();
+        assert_eq!(metadata.workspace_root, "/work");
+        assert_eq!(metadata.target_directory, "/work/target");
+    }
+
+    #[test]
+    fn metadata_fails_with_the_cargo_metadata_failure() {
+        let runner = MockRunner::new(|_| Ok(testing::output(101, "")));
+        let err = super::metadata(&runner, false).unwrap_err();
+        assert_eq!(crate::error::kind(&*err), Kind::Failure);
+        assert!(err.to_string().starts_with("`cargo metadata` failed"));
+    }
+
+    #[test]
+    fn cargo_target_dir_overrides_the_default_target_dir() {
+        let manifest_dir = env!("CARGO_MANIFEST_DIR");
+        let mut config = crate::testing::config(serde_json::json!({}));
+        config.cargo_metadata = cargo_metadata::MetadataCommand::new()
+            .manifest_path(format!("{manifest_dir}/Cargo.toml"))
+            .no_deps()
+            .env("CARGO_TARGET_DIR", "/shared/target")
+            .exec()
+            .unwrap();
+        assert_eq!(super::target_dir(&config), "/shared/target");
+
+        // NOTE: the default can only be checked if the tests themselves do not run with a `CARGO_TARGET_DIR`
+        if std::env::var_os("CARGO_TARGET_DIR").is_none() {
+            config.cargo_metadata = cargo_metadata::MetadataCommand::new()
+                .manifest_path(format!("{manifest_dir}/Cargo.toml"))
+                .no_deps()
+                .exec()
+                .unwrap();
+            assert_eq!(super::target_dir(&config), format!("{manifest_dir}/target"));
+        }
+    }
 }